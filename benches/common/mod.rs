@@ -0,0 +1,28 @@
+// Shared setup for the DB-backed benchmarks. They need a disposable MySQL database
+// (`BENCH_DATABASE_URL`) and skip with a note when it isn't set, so `cargo bench`
+// stays runnable anywhere.
+
+use std::process::Command;
+
+pub fn env(key: &str) -> Option<String> {
+    std::env::var(key).ok().filter(|v| !v.trim().is_empty())
+}
+
+/// `BENCH_SIZES` (comma-separated row counts) or the given defaults
+pub fn sizes(default: &[usize]) -> Vec<usize> {
+    match env("BENCH_SIZES") {
+        Some(v) => v.split(',').filter_map(|s| s.trim().parse().ok()).collect(),
+        None => default.to_vec(),
+    }
+}
+
+/// Runs `country-currency-api --bench-seed <rows>` against the bench database.
+pub fn seed(database_url: &str, rows: usize) {
+    let status = Command::new(env!("CARGO_BIN_EXE_country-currency-api"))
+        .arg("--bench-seed")
+        .arg(rows.to_string())
+        .env("DATABASE_URL", database_url)
+        .status()
+        .expect("run --bench-seed");
+    assert!(status.success(), "--bench-seed {} failed", rows);
+}
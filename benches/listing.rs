@@ -0,0 +1,49 @@
+// `GET /countries` latency by table size, measured over HTTP against a running instance.
+//
+//   BENCH_DATABASE_URL=mysql://... BENCH_BASE_URL=http://localhost:8080 cargo bench --bench listing
+//
+// The instance must use the same database and keep `AUTO_REFRESH_ON_STALE` off (the
+// default), so a background refresh doesn't rewrite the table mid-run. Seeded rows are
+// removed at the end.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::time::Duration;
+
+mod common;
+
+const QUERIES: [(&str, &str); 3] = [
+    ("first_page", "/countries"),
+    ("sorted", "/countries?sort=gdp_desc&limit=200"),
+    ("filtered", "/countries?region=Europe&currency=EUR&sort=name_asc"),
+];
+
+fn listing(c: &mut Criterion) {
+    let (Some(database_url), Some(base_url)) = (common::env("BENCH_DATABASE_URL"), common::env("BENCH_BASE_URL"))
+    else {
+        eprintln!("listing: BENCH_DATABASE_URL and BENCH_BASE_URL not set, skipping");
+        return;
+    };
+    let rt = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let client = reqwest::Client::new();
+
+    let mut group = c.benchmark_group("countries_list");
+    group.measurement_time(Duration::from_secs(10));
+    for rows in common::sizes(&[250, 2_500, 25_000]) {
+        common::seed(&database_url, rows);
+        for (name, path) in QUERIES {
+            let url = format!("{}{}", base_url.trim_end_matches('/'), path);
+            group.bench_with_input(BenchmarkId::new(name, rows), &url, |b, url| {
+                b.to_async(&rt).iter(|| async {
+                    let res = client.get(url).send().await.expect("request");
+                    assert!(res.status().is_success(), "{} -> {}", url, res.status());
+                    res.bytes().await.expect("body")
+                })
+            });
+        }
+    }
+    group.finish();
+    common::seed(&database_url, 0);
+}
+
+criterion_group!(benches, listing);
+criterion_main!(benches);
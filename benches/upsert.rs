@@ -0,0 +1,106 @@
+// Refresh write throughput: the refresh's row-by-row `INSERT ... ON DUPLICATE KEY UPDATE`
+// against one multi-row statement per 500 rows, in a transaction like the refresh.
+//
+//   BENCH_DATABASE_URL=mysql://... cargo bench --bench upsert
+//
+// Rows come from `--bench-seed` and are written to a scratch `bench_countries` table
+// (same schema as `countries`), dropped at the end. After the first iteration every
+// row exists, which is the steady state of a daily refresh: mostly updates.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sqlx::{mysql::MySqlPoolOptions, MySql, Pool, QueryBuilder};
+
+mod common;
+
+type Row = (String, Option<String>, Option<String>, i64, Option<String>, Option<f64>, Option<f64>);
+
+const CHUNK: usize = 500;
+
+const UPDATE: &str = " ON DUPLICATE KEY UPDATE \
+    capital=VALUES(capital), region=VALUES(region), population=VALUES(population), \
+    currency_code=VALUES(currency_code), exchange_rate=VALUES(exchange_rate), \
+    estimated_gdp=VALUES(estimated_gdp), last_refreshed_at=NOW()";
+
+const INSERT: &str = "INSERT INTO bench_countries \
+    (name, capital, region, population, currency_code, exchange_rate, estimated_gdp, last_refreshed_at) ";
+
+async fn row_by_row(pool: &Pool<MySql>, rows: &[Row]) {
+    let sql = format!("{}VALUES (?, ?, ?, ?, ?, ?, ?, NOW()){}", INSERT, UPDATE);
+    let mut tx = pool.begin().await.expect("begin");
+    for r in rows {
+        sqlx::query(&sql)
+            .bind(&r.0)
+            .bind(&r.1)
+            .bind(&r.2)
+            .bind(r.3)
+            .bind(&r.4)
+            .bind(r.5)
+            .bind(r.6)
+            .execute(&mut *tx)
+            .await
+            .expect("upsert");
+    }
+    tx.commit().await.expect("commit");
+}
+
+async fn batched(pool: &Pool<MySql>, rows: &[Row]) {
+    let mut tx = pool.begin().await.expect("begin");
+    for chunk in rows.chunks(CHUNK) {
+        let mut qb = QueryBuilder::<MySql>::new(INSERT);
+        qb.push_values(chunk, |mut b, r| {
+            b.push_bind(&r.0)
+                .push_bind(&r.1)
+                .push_bind(&r.2)
+                .push_bind(r.3)
+                .push_bind(&r.4)
+                .push_bind(r.5)
+                .push_bind(r.6)
+                .push("NOW()");
+        });
+        qb.push(UPDATE);
+        qb.build().execute(&mut *tx).await.expect("batched upsert");
+    }
+    tx.commit().await.expect("commit");
+}
+
+fn upsert(c: &mut Criterion) {
+    let Some(database_url) = common::env("BENCH_DATABASE_URL") else {
+        eprintln!("upsert: BENCH_DATABASE_URL not set, skipping");
+        return;
+    };
+    let rt = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let pool = rt.block_on(async {
+        let pool = MySqlPoolOptions::new().max_connections(2).connect(&database_url).await.expect("connect");
+        sqlx::query("DROP TABLE IF EXISTS bench_countries").execute(&pool).await.expect("drop");
+        sqlx::query("CREATE TABLE bench_countries LIKE countries").execute(&pool).await.expect("create");
+        pool
+    });
+
+    let mut group = c.benchmark_group("refresh_upsert");
+    group.sample_size(10);
+    for size in common::sizes(&[250, 2_500, 25_000]) {
+        common::seed(&database_url, size);
+        let rows: Vec<Row> = rt.block_on(
+            sqlx::query_as(
+                "SELECT name, capital, region, population, currency_code, exchange_rate, estimated_gdp \
+                 FROM countries WHERE data_source = 'bench' ORDER BY id",
+            )
+            .fetch_all(&pool),
+        )
+        .expect("read seeded rows");
+        group.throughput(Throughput::Elements(rows.len() as u64));
+        group.bench_with_input(BenchmarkId::new("row_by_row", size), &rows, |b, rows| {
+            b.to_async(&rt).iter(|| row_by_row(&pool, rows))
+        });
+        group.bench_with_input(BenchmarkId::new("batched", size), &rows, |b, rows| {
+            b.to_async(&rt).iter(|| batched(&pool, rows))
+        });
+    }
+    group.finish();
+
+    rt.block_on(sqlx::query("DROP TABLE IF EXISTS bench_countries").execute(&pool)).expect("drop");
+    common::seed(&database_url, 0);
+}
+
+criterion_group!(benches, upsert);
+criterion_main!(benches);
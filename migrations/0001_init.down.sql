@@ -0,0 +1,3 @@
+-- Reverts 0001_init: drops all cached data
+DROP TABLE IF EXISTS app_meta;
+DROP TABLE IF EXISTS countries;
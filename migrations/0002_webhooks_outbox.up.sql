@@ -0,0 +1,22 @@
+-- Webhook subscriptions + transactional outbox (one row per event per subscription).
+CREATE TABLE IF NOT EXISTS webhooks (
+  id         INT AUTO_INCREMENT PRIMARY KEY,
+  url        VARCHAR(512) NOT NULL,
+  active     BOOLEAN      NOT NULL DEFAULT TRUE,
+  created_at DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS outbox (
+  id              BIGINT AUTO_INCREMENT PRIMARY KEY,
+  webhook_id      INT          NOT NULL,
+  event_type      VARCHAR(64)  NOT NULL,
+  payload         TEXT         NOT NULL,
+  status          VARCHAR(16)  NOT NULL DEFAULT 'pending', -- pending | delivered | failed
+  attempts        INT          NOT NULL DEFAULT 0,
+  next_attempt_at DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  last_error      VARCHAR(512) NULL,
+  created_at      DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  delivered_at    DATETIME     NULL,
+  KEY idx_outbox_due (status, next_attempt_at),
+  KEY idx_outbox_webhook (webhook_id)
+);
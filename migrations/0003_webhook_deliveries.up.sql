@@ -0,0 +1,13 @@
+-- One row per delivery attempt, for self-serve debugging (GET /webhooks/:id/deliveries).
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+  id           BIGINT AUTO_INCREMENT PRIMARY KEY,
+  outbox_id    BIGINT       NOT NULL,
+  webhook_id   INT          NOT NULL,
+  attempt      INT          NOT NULL,
+  status_code  INT          NULL,     -- NULL when no HTTP response (timeout, DNS, ...)
+  latency_ms   BIGINT       NOT NULL,
+  error        VARCHAR(512) NULL,
+  attempted_at DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  KEY idx_deliveries_outbox (outbox_id),
+  KEY idx_deliveries_webhook (webhook_id, attempted_at)
+);
@@ -0,0 +1,3 @@
+-- Per-subscription signing secret. Empty for subscriptions created before signing
+-- existed: those stay unsigned until rotated via POST /webhooks/:id/secret.
+ALTER TABLE webhooks ADD COLUMN secret VARCHAR(128) NOT NULL DEFAULT '';
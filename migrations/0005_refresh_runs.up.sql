@@ -0,0 +1,12 @@
+-- One row per POST /countries/refresh (or scheduled) run.
+CREATE TABLE IF NOT EXISTS refresh_runs (
+  id          BIGINT AUTO_INCREMENT PRIMARY KEY,
+  status      VARCHAR(16)  NOT NULL DEFAULT 'running', -- running | succeeded | failed
+  started_at  DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  finished_at DATETIME     NULL,
+  inserted    INT          NOT NULL DEFAULT 0,
+  updated     INT          NOT NULL DEFAULT 0,
+  skipped     INT          NOT NULL DEFAULT 0,
+  error       VARCHAR(512) NULL,
+  KEY idx_refresh_runs_started (started_at)
+);
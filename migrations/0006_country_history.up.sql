@@ -0,0 +1,19 @@
+-- Change log: one row per country per refresh run in which it was inserted or changed.
+-- Snapshot columns hold the values after the run; `changes` is {"field": {"from": .., "to": ..}}.
+CREATE TABLE IF NOT EXISTS country_history (
+  id            BIGINT AUTO_INCREMENT PRIMARY KEY,
+  run_id        BIGINT       NOT NULL,
+  name          VARCHAR(128) NOT NULL,
+  change_type   VARCHAR(16)  NOT NULL, -- inserted | updated
+  capital       VARCHAR(128) NULL,
+  region        VARCHAR(64)  NULL,
+  population    BIGINT       NOT NULL,
+  currency_code CHAR(3)      NULL,
+  exchange_rate DOUBLE       NULL,
+  estimated_gdp DOUBLE       NULL,
+  flag_url      VARCHAR(256) NULL,
+  changes       TEXT         NOT NULL,
+  recorded_at   DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  KEY idx_history_run (run_id),
+  KEY idx_history_name_run (name, run_id)
+);
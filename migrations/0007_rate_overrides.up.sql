@@ -0,0 +1,9 @@
+-- Operator-pinned exchange rates; the refresh uses these instead of the upstream value until they expire
+CREATE TABLE IF NOT EXISTS rate_overrides (
+  currency_code CHAR(3)      PRIMARY KEY,
+  rate          DOUBLE       NOT NULL,
+  reason        VARCHAR(255) NULL,
+  expires_at    DATETIME     NULL, -- NULL = until removed
+  created_at    DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at    DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+);
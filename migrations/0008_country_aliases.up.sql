@@ -0,0 +1,25 @@
+-- Alternate names users type for a country, resolved by GET /countries/:name
+CREATE TABLE IF NOT EXISTS country_aliases (
+  alias        VARCHAR(128) PRIMARY KEY,
+  country_name VARCHAR(128) NOT NULL, -- countries.name as published upstream
+  created_at   DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  KEY idx_aliases_country (country_name)
+);
+
+INSERT IGNORE INTO country_aliases (alias, country_name) VALUES
+  ('Ivory Coast', 'Côte d''Ivoire'),
+  ('UK', 'United Kingdom of Great Britain and Northern Ireland'),
+  ('United Kingdom', 'United Kingdom of Great Britain and Northern Ireland'),
+  ('Great Britain', 'United Kingdom of Great Britain and Northern Ireland'),
+  ('USA', 'United States of America'),
+  ('US', 'United States of America'),
+  ('United States', 'United States of America'),
+  ('Russia', 'Russian Federation'),
+  ('South Korea', 'Korea (Republic of)'),
+  ('North Korea', 'Korea (Democratic People''s Republic of)'),
+  ('Vietnam', 'Viet Nam'),
+  ('Iran', 'Iran (Islamic Republic of)'),
+  ('Syria', 'Syrian Arab Republic'),
+  ('Bolivia', 'Bolivia (Plurinational State of)'),
+  ('Tanzania', 'Tanzania, United Republic of'),
+  ('Venezuela', 'Venezuela (Bolivarian Republic of)');
@@ -0,0 +1,2 @@
+DROP INDEX idx_countries_iso ON countries;
+ALTER TABLE countries DROP COLUMN iso_code;
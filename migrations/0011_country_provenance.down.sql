@@ -0,0 +1,4 @@
+ALTER TABLE countries
+  DROP COLUMN rate_source,
+  DROP COLUMN source_fetched_at,
+  DROP COLUMN data_source;
@@ -0,0 +1,5 @@
+-- Where each row's values came from, shown in country responses
+ALTER TABLE countries
+  ADD COLUMN data_source       VARCHAR(32) NOT NULL DEFAULT 'restcountries',
+  ADD COLUMN source_fetched_at DATETIME    NULL, -- when the refresh fetched the country payload
+  ADD COLUMN rate_source       VARCHAR(32) NULL; -- open.er-api | override; NULL without a rate
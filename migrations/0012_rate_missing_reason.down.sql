@@ -0,0 +1 @@
+ALTER TABLE countries DROP COLUMN rate_missing_reason;
@@ -0,0 +1,2 @@
+-- Why a country has no exchange_rate after the last refresh (GET /countries/missing-rates)
+ALTER TABLE countries ADD COLUMN rate_missing_reason VARCHAR(32) NULL; -- no_currency | unknown_code | provider_omitted | non_positive_rate
@@ -0,0 +1,2 @@
+-- Dataset completeness (0-100) right after each successful run
+ALTER TABLE refresh_runs ADD COLUMN completeness DOUBLE NULL;
@@ -0,0 +1,18 @@
+-- Other capitals (seats of government, judicial/legislative capitals) than the one
+-- restcountries publishes in countries.capital, resolved by GET /capitals/:name
+CREATE TABLE IF NOT EXISTS capital_aliases (
+  capital      VARCHAR(128) PRIMARY KEY,
+  country_name VARCHAR(128) NOT NULL, -- countries.name as published upstream
+  created_at   DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  KEY idx_capital_aliases_country (country_name)
+);
+
+INSERT IGNORE INTO capital_aliases (capital, country_name) VALUES
+  ('Cape Town', 'South Africa'),
+  ('Bloemfontein', 'South Africa'),
+  ('La Paz', 'Bolivia (Plurinational State of)'),
+  ('The Hague', 'Netherlands'),
+  ('Abidjan', 'Côte d''Ivoire'),
+  ('Cotonou', 'Benin'),
+  ('Putrajaya', 'Malaysia'),
+  ('Colombo', 'Sri Lanka');
@@ -0,0 +1,29 @@
+-- Scheduled dataset exports (services::export_service) and their run history
+CREATE TABLE IF NOT EXISTS export_jobs (
+  id               INT AUTO_INCREMENT PRIMARY KEY,
+  name             VARCHAR(64)  NOT NULL,
+  schedule         VARCHAR(64)  NOT NULL, -- cron expression, UTC
+  format           VARCHAR(8)   NOT NULL, -- json | csv
+  destination_type VARCHAR(16)  NOT NULL, -- dir | webhook
+  destination      VARCHAR(512) NOT NULL, -- sub-directory of <cache dir>/exports, or URL
+  active           BOOLEAN      NOT NULL DEFAULT TRUE,
+  next_run_at      DATETIME     NULL,
+  last_run_at      DATETIME     NULL,
+  created_at       DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE KEY uq_export_jobs_name (name),
+  KEY idx_export_jobs_due (active, next_run_at)
+);
+
+CREATE TABLE IF NOT EXISTS export_job_runs (
+  id          BIGINT AUTO_INCREMENT PRIMARY KEY,
+  job_id      INT          NOT NULL,
+  trigger_by  VARCHAR(16)  NOT NULL, -- schedule | manual
+  status      VARCHAR(16)  NOT NULL, -- running | succeeded | failed
+  started_at  DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  finished_at DATETIME     NULL,
+  row_count   INT          NOT NULL DEFAULT 0,
+  bytes       BIGINT       NOT NULL DEFAULT 0,
+  location    VARCHAR(512) NULL,
+  error       TEXT         NULL,
+  KEY idx_export_runs_job (job_id, id)
+);
@@ -0,0 +1,4 @@
+ALTER TABLE refresh_runs
+  DROP COLUMN upstream_calls,
+  DROP COLUMN bytes_downloaded,
+  DROP COLUMN rows_written;
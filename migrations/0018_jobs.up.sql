@@ -0,0 +1,16 @@
+-- Persisted background jobs run by services::job_queue (refresh, export, render_images)
+CREATE TABLE IF NOT EXISTS jobs (
+  id           BIGINT AUTO_INCREMENT PRIMARY KEY,
+  kind         VARCHAR(32)  NOT NULL,
+  params       TEXT         NOT NULL, -- JSON
+  status       VARCHAR(16)  NOT NULL DEFAULT 'queued', -- queued | running | succeeded | failed | cancelled
+  progress     INT          NOT NULL DEFAULT 0, -- percent
+  message      VARCHAR(255) NULL,
+  result       MEDIUMTEXT   NULL, -- JSON
+  error        TEXT         NULL,
+  created_at   DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  started_at   DATETIME     NULL,
+  heartbeat_at DATETIME     NULL,
+  finished_at  DATETIME     NULL,
+  KEY idx_jobs_status (status, id)
+);
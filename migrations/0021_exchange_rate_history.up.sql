@@ -0,0 +1,11 @@
+-- Daily exchange rates: one point per currency per UTC day, written by every refresh.
+-- A later refresh on the same day replaces that day's point.
+CREATE TABLE IF NOT EXISTS exchange_rate_history (
+  currency_code CHAR(3)     NOT NULL,
+  day           DATE        NOT NULL,
+  rate          DOUBLE      NOT NULL,
+  source        VARCHAR(32) NOT NULL, -- open.er-api | override
+  run_id        BIGINT      NOT NULL,
+  recorded_at   DATETIME    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (currency_code, day)
+);
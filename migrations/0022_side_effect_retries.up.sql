@@ -0,0 +1,16 @@
+-- Post-commit refresh work (summary image, flag sprite) that failed, retried by
+-- services::side_effects with exponential backoff. At most one pending row per kind:
+-- a retry redoes the work from current data, so later failures fold into it.
+CREATE TABLE IF NOT EXISTS side_effect_retries (
+  id              BIGINT AUTO_INCREMENT PRIMARY KEY,
+  kind            VARCHAR(32)  NOT NULL, -- summary_image | flag_sprite
+  run_id          BIGINT       NOT NULL, -- latest refresh run that failed it
+  status          VARCHAR(16)  NOT NULL DEFAULT 'pending', -- pending | done | failed | superseded
+  attempts        INT          NOT NULL DEFAULT 0,
+  next_attempt_at DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  last_error      VARCHAR(512) NULL,
+  created_at      DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  finished_at     DATETIME     NULL,
+  KEY idx_side_effect_retries_due (status, next_attempt_at),
+  KEY idx_side_effect_retries_kind (kind, status)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE refresh_runs
+  DROP COLUMN quarantined;
@@ -0,0 +1,3 @@
+-- Upstream records each refresh run dropped as unstorable (see GET /admin/overview)
+ALTER TABLE refresh_runs
+  ADD COLUMN quarantined INT NOT NULL DEFAULT 0;
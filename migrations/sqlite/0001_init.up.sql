@@ -0,0 +1,24 @@
+-- SQLite version of ../0001_init. Names compare case-insensitively (NOCASE), like
+-- the MySQL default collation.
+CREATE TABLE IF NOT EXISTS countries (
+  id                INTEGER PRIMARY KEY AUTOINCREMENT,
+  name              VARCHAR(128) NOT NULL COLLATE NOCASE,
+  capital           VARCHAR(128) NULL COLLATE NOCASE,
+  region            VARCHAR(64)  NULL COLLATE NOCASE,
+  population        BIGINT       NOT NULL,
+  currency_code     CHAR(3)      NULL,
+  exchange_rate     DOUBLE       NULL,
+  estimated_gdp     DOUBLE       NULL,
+  flag_url          VARCHAR(256) NULL,
+  last_refreshed_at DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT ux_countries_name UNIQUE (name)
+);
+
+CREATE INDEX idx_countries_region ON countries (region);
+CREATE INDEX idx_countries_currency ON countries (currency_code);
+CREATE INDEX idx_countries_gdp ON countries (estimated_gdp);
+
+CREATE TABLE IF NOT EXISTS app_meta (
+  k VARCHAR(64) PRIMARY KEY,
+  v VARCHAR(512) NOT NULL
+);
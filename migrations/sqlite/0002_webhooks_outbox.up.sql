@@ -0,0 +1,23 @@
+-- Webhook subscriptions + transactional outbox (one row per event per subscription).
+CREATE TABLE IF NOT EXISTS webhooks (
+  id         INTEGER PRIMARY KEY AUTOINCREMENT,
+  url        VARCHAR(512) NOT NULL,
+  active     BOOLEAN      NOT NULL DEFAULT TRUE,
+  created_at DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS outbox (
+  id              INTEGER PRIMARY KEY AUTOINCREMENT,
+  webhook_id      INT          NOT NULL,
+  event_type      VARCHAR(64)  NOT NULL,
+  payload         TEXT         NOT NULL,
+  status          VARCHAR(16)  NOT NULL DEFAULT 'pending', -- pending | delivered | failed
+  attempts        INT          NOT NULL DEFAULT 0,
+  next_attempt_at DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  last_error      VARCHAR(512) NULL,
+  created_at      DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  delivered_at    DATETIME     NULL
+);
+
+CREATE INDEX idx_outbox_due ON outbox (status, next_attempt_at);
+CREATE INDEX idx_outbox_webhook ON outbox (webhook_id);
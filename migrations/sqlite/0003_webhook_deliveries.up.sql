@@ -0,0 +1,14 @@
+-- One row per delivery attempt, for self-serve debugging (GET /webhooks/:id/deliveries).
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+  id           INTEGER PRIMARY KEY AUTOINCREMENT,
+  outbox_id    BIGINT       NOT NULL,
+  webhook_id   INT          NOT NULL,
+  attempt      INT          NOT NULL,
+  status_code  INT          NULL,     -- NULL when no HTTP response (timeout, DNS, ...)
+  latency_ms   BIGINT       NOT NULL,
+  error        VARCHAR(512) NULL,
+  attempted_at DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_deliveries_outbox ON webhook_deliveries (outbox_id);
+CREATE INDEX idx_deliveries_webhook ON webhook_deliveries (webhook_id, attempted_at);
@@ -0,0 +1,13 @@
+-- One row per POST /countries/refresh (or scheduled) run.
+CREATE TABLE IF NOT EXISTS refresh_runs (
+  id          INTEGER PRIMARY KEY AUTOINCREMENT,
+  status      VARCHAR(16)  NOT NULL DEFAULT 'running', -- running | succeeded | failed
+  started_at  DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  finished_at DATETIME     NULL,
+  inserted    INT          NOT NULL DEFAULT 0,
+  updated     INT          NOT NULL DEFAULT 0,
+  skipped     INT          NOT NULL DEFAULT 0,
+  error       VARCHAR(512) NULL
+);
+
+CREATE INDEX idx_refresh_runs_started ON refresh_runs (started_at);
@@ -0,0 +1,20 @@
+-- Change log: one row per country per refresh run in which it was inserted or changed.
+-- Snapshot columns hold the values after the run; `changes` is {"field": {"from": .., "to": ..}}.
+CREATE TABLE IF NOT EXISTS country_history (
+  id            INTEGER PRIMARY KEY AUTOINCREMENT,
+  run_id        BIGINT       NOT NULL,
+  name          VARCHAR(128) NOT NULL COLLATE NOCASE,
+  change_type   VARCHAR(16)  NOT NULL, -- inserted | updated
+  capital       VARCHAR(128) NULL,
+  region        VARCHAR(64)  NULL,
+  population    BIGINT       NOT NULL,
+  currency_code CHAR(3)      NULL,
+  exchange_rate DOUBLE       NULL,
+  estimated_gdp DOUBLE       NULL,
+  flag_url      VARCHAR(256) NULL,
+  changes       TEXT         NOT NULL,
+  recorded_at   DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_history_run ON country_history (run_id);
+CREATE INDEX idx_history_name_run ON country_history (name, run_id);
@@ -0,0 +1,10 @@
+-- Operator-pinned exchange rates; the refresh uses these instead of the upstream value until they expire.
+-- No ON UPDATE clause in SQLite: the upsert in PUT /rates/:code sets updated_at itself.
+CREATE TABLE IF NOT EXISTS rate_overrides (
+  currency_code CHAR(3)      PRIMARY KEY,
+  rate          DOUBLE       NOT NULL,
+  reason        VARCHAR(255) NULL,
+  expires_at    DATETIME     NULL, -- NULL = until removed
+  created_at    DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at    DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,3 @@
+-- ISO 3166-1 alpha-2 code from upstream (restcountries `alpha2Code`), shown by autocomplete
+ALTER TABLE countries ADD COLUMN iso_code CHAR(2) NULL;
+CREATE INDEX idx_countries_iso ON countries (iso_code);
@@ -0,0 +1,10 @@
+-- Destructive operations, with the affected row as it was, so they can be undone by hand.
+CREATE TABLE IF NOT EXISTS audit_log (
+  id          INTEGER PRIMARY KEY AUTOINCREMENT,
+  action      VARCHAR(64)  NOT NULL, -- e.g. country.deleted
+  subject     VARCHAR(128) NOT NULL,
+  snapshot    TEXT         NOT NULL, -- JSON object of the row before the change
+  created_at  DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_audit_created ON audit_log (created_at);
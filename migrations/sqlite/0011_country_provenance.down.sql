@@ -0,0 +1,3 @@
+ALTER TABLE countries DROP COLUMN rate_source;
+ALTER TABLE countries DROP COLUMN source_fetched_at;
+ALTER TABLE countries DROP COLUMN data_source;
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS capital_aliases;
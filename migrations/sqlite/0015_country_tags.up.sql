@@ -0,0 +1,10 @@
+-- Curated groupings ("sahel", "opec", ...) maintained by operators on top of the
+-- upstream data; keyed by name like country_aliases so they survive re-inserts
+CREATE TABLE IF NOT EXISTS country_tags (
+  tag          VARCHAR(64)  NOT NULL COLLATE NOCASE,
+  country_name VARCHAR(128) NOT NULL COLLATE NOCASE, -- countries.name as published upstream
+  created_at   DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (tag, country_name)
+);
+
+CREATE INDEX idx_country_tags_country ON country_tags (country_name);
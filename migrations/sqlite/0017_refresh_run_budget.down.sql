@@ -0,0 +1,3 @@
+ALTER TABLE refresh_runs DROP COLUMN upstream_calls;
+ALTER TABLE refresh_runs DROP COLUMN bytes_downloaded;
+ALTER TABLE refresh_runs DROP COLUMN rows_written;
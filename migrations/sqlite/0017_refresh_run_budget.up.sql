@@ -0,0 +1,4 @@
+-- What each refresh run cost (see GET /refresh/history)
+ALTER TABLE refresh_runs ADD COLUMN upstream_calls INT NOT NULL DEFAULT 0;
+ALTER TABLE refresh_runs ADD COLUMN bytes_downloaded BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE refresh_runs ADD COLUMN rows_written INT NOT NULL DEFAULT 0;
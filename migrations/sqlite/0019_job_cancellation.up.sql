@@ -0,0 +1,2 @@
+-- DELETE /jobs/:id on a running job asks it to stop at its next checkpoint
+ALTER TABLE jobs ADD COLUMN cancel_requested BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Client address (see TRUSTED_PROXIES) of the request behind each audited operation
+ALTER TABLE audit_log ADD COLUMN client_ip VARCHAR(45) NULL;
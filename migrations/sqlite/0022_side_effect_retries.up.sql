@@ -0,0 +1,17 @@
+-- Post-commit refresh work (summary image, flag sprite) that failed, retried by
+-- services::side_effects with exponential backoff. At most one pending row per kind:
+-- a retry redoes the work from current data, so later failures fold into it.
+CREATE TABLE IF NOT EXISTS side_effect_retries (
+  id              INTEGER PRIMARY KEY AUTOINCREMENT,
+  kind            VARCHAR(32)  NOT NULL, -- summary_image | flag_sprite
+  run_id          BIGINT       NOT NULL, -- latest refresh run that failed it
+  status          VARCHAR(16)  NOT NULL DEFAULT 'pending', -- pending | done | failed | superseded
+  attempts        INT          NOT NULL DEFAULT 0,
+  next_attempt_at DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  last_error      VARCHAR(512) NULL,
+  created_at      DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  finished_at     DATETIME     NULL
+);
+
+CREATE INDEX idx_side_effect_retries_due ON side_effect_retries (status, next_attempt_at);
+CREATE INDEX idx_side_effect_retries_kind ON side_effect_retries (kind, status);
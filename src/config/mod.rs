@@ -1,74 +1,612 @@
+use arc_swap::ArcSwap;
 use reqwest::Client;
-use sqlx::{mysql::MySqlPoolOptions, MySql, Pool};
+use serde_json::Value;
 use sqlx::migrate::Migrator;
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, OnceLock};
+use std::net::SocketAddr;
 use std::{env, path::PathBuf};
 use tokio::fs;
-use tracing::info;
+use tracing::{info, warn};
 
-// Embed migrations at compile time from ./migrations (next to Cargo.toml)
-static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+use crate::db::{self, DbPool, DbPoolOptions};
+use crate::services::auto_refresh::{AutoRefresh, RefreshThrottle};
+use crate::services::db_monitor::DbHealth;
+use crate::services::hooks::RefreshHooks;
+use crate::services::providers::{CountriesProvider, HttpCountries, HttpRates, RatesProvider};
+use crate::services::refresh_feed::RefreshFeed;
+use crate::services::refresh_scheduler::RefreshSchedule;
+use crate::services::refresh_window::RefreshWindow;
+use crate::services::migration_service;
+use crate::utils::auth::ApiKeys;
+use crate::utils::case::KeyCase;
+use crate::utils::chaos::Chaos;
+use crate::utils::server_timing;
+use crate::utils::client_ip::TrustedProxies;
+use crate::utils::lockout::{AuthLockout, LockoutPolicy};
+use crate::utils::image::{Branding, BrandingConfig, ImageHealth, RankMetric, Ranking};
+use crate::utils::image_cache::ImageCache;
+use crate::utils::server::ServerTuning;
+use crate::utils::signed_url::UrlSigner;
+use crate::utils::single_flight::AggregateFlights;
+
+// Embed migrations at compile time from ./migrations (next to Cargo.toml), or their
+// SQLite twins in ./migrations/sqlite
+#[cfg(not(feature = "sqlite"))]
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+#[cfg(feature = "sqlite")]
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
 
 #[derive(Clone)]
 pub struct AppState {
-    pub pool: Pool<MySql>,
+    pub pool: DbPool,
     pub http: Client,
+    /// Settings `reload` can swap at runtime (SIGHUP / `POST /admin/reload-config`)
+    pub runtime: Arc<ArcSwap<RuntimeConfig>>,
     pub summary_image_path: PathBuf,
+    /// Directory holding generated artifacts (summary image, variants, flag sprite)
+    pub cache_dir: PathBuf,
+    pub branding: Branding,
+    /// Top list of the saved summary image (`SUMMARY_TOP_N`, `SUMMARY_RANK_BY`)
+    pub summary_ranking: Ranking,
+    pub image_cache: ImageCache,
+    /// Coalesces concurrent `/stats` and `/regions` computations
+    pub aggregates: AggregateFlights,
+    pub image_health: ImageHealth,
+    /// When set, `/static/*` serves this directory (the image/export cache dir)
+    pub static_dir: Option<PathBuf>,
+    pub static_max_age_secs: u64,
+    /// Deployment-specific refresh hooks, registered in `main`
+    pub hooks: RefreshHooks,
+    /// Where refreshes get countries and rates; HTTP (`COUNTRIES_URL` / `RATES_URL`) unless `main` swaps them
+    pub countries_provider: Arc<dyn CountriesProvider>,
+    pub rates_provider: Arc<dyn RatesProvider>,
+    /// Throttles read-triggered refreshes (used when `auto_refresh_on_stale` is on)
+    pub auto_refresh: AutoRefresh,
+    pub refresh_throttle: RefreshThrottle,
+    /// How each refresh run ended, for `GET /refresh/wait`
+    pub refresh_feed: RefreshFeed,
+    /// Hours/days automatic refreshes may run
+    pub refresh_window: RefreshWindow,
+    /// Keep raw upstream payloads of this many recent runs; 0 = don't archive
+    pub raw_archive_runs: usize,
+    /// Bearer token for operator endpoints; `None` disables them
+    pub admin_token: Option<String>,
+    /// Scoped keys; empty = only admin endpoints need a token
+    pub api_keys: Arc<ApiKeys>,
+    /// Failed-authentication counts and lockouts
+    pub auth_lockout: AuthLockout,
+    /// Signs and checks temporary links; `None` = `URL_SIGNING_KEY` unset, no signed URLs
+    pub url_signer: Option<UrlSigner>,
+    /// Set once migrations ran and the DB answered (`/health/started`)
+    pub startup: Startup,
+    pub db_health: DbHealth,
+    /// Fault injection for resilience tests; always `None` in release builds
+    pub chaos: Option<Arc<Chaos>>,
+    /// Peers whose `Forwarded` / `X-Forwarded-For` name the client
+    pub trusted_proxies: Arc<TrustedProxies>,
+    /// Set when `/admin/*` is served on its own listener instead of the public one
+    pub admin_addr: Option<SocketAddr>,
+    /// Send `Server-Timing` headers (`utils::server_timing`)
+    pub server_timing: bool,
+}
+
+/// Startup progress, shared with the health probes.
+#[derive(Clone, Default)]
+pub struct Startup {
+    started: Arc<OnceLock<DateTime<Utc>>>,
+    warmed: Arc<OnceLock<DateTime<Utc>>>,
+    /// Readiness waits for the warm-up too (`WARMUP=true`)
+    warmup: bool,
+}
+
+impl Startup {
+    fn new(warmup: bool) -> Self {
+        Self { warmup, ..Self::default() }
+    }
+
+    pub fn started_at(&self) -> Option<DateTime<Utc>> {
+        self.started.get().copied()
+    }
+
+    fn mark_started(&self) {
+        self.started.set(Utc::now()).ok();
+    }
+
+    pub fn mark_warmed(&self) {
+        self.warmed.set(Utc::now()).ok();
+    }
+
+    /// Started, and warmed up when warm-up is enabled.
+    pub fn is_ready(&self) -> bool {
+        self.started_at().is_some() && (!self.warmup || self.warmed.get().is_some())
+    }
+}
+
+/// Reloadable settings. Everything else in `AppConfig` needs a restart.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct RuntimeConfig {
+    /// Upstream timeout for refresh fetches; per-request deadlines can only shorten it
+    pub external_timeout_ms: u64,
+    pub countries_url: String,
+    pub rates_url: String,
+    /// Data older than this is reported as stale
+    pub stale_after_secs: u64,
+    /// Default JSON key casing; `?case=` overrides per request
+    pub response_case: KeyCase,
+    /// Wrap JSON responses in `{data, meta, errors}`; `X-Envelope` overrides per request
+    pub response_envelope: bool,
+    /// Add `_links` to country responses
+    pub hateoas_links: bool,
+    /// `GET /countries` answers the pre-pagination bare array instead of `{data, page, ...}`
+    pub list_bare_array: bool,
+    /// Reads of stale data may trigger a background refresh
+    pub auto_refresh_on_stale: bool,
+    /// Log a warning when a list query's EXPLAIN shows a full table scan
+    pub explain_queries: bool,
+    /// `false` keeps DB checks out of the readiness probe
+    pub health_check_db: bool,
+    /// Reject unrecognized query parameters on endpoints that declare theirs
+    pub strict_query_params: bool,
+    /// `DELETE /countries/:name` needs the name echoed in `?confirm=` or `X-Confirm-Delete`
+    pub delete_require_confirm: bool,
+    /// `GET /countries/image` renders a missing summary image instead of answering 404
+    pub image_render_on_missing: bool,
+    /// `PUBLIC_BASE_URL`: origin of the public site, without a trailing `/`; unset = no sitemap
+    pub public_base_url: Option<String>,
+    /// Route template (`:name`) of a country's page in the sitemap
+    pub sitemap_country_path: String,
+    /// Path prefixes `robots.txt` disallows
+    pub robots_disallow: Vec<String>,
+}
+
+fn env_flag(key: &str, default: bool) -> bool {
+    match env::var(key) {
+        Ok(v) if matches!(v.as_str(), "1" | "true" | "yes") => true,
+        Ok(v) if matches!(v.as_str(), "0" | "false" | "no") => false,
+        _ => default,
+    }
+}
+
+impl RuntimeConfig {
+    pub fn from_env() -> Self {
+        let external_timeout_ms: u64 = env::var("EXTERNAL_TIMEOUT_MS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(12_000);
+        // Allow tests / env to override the external endpoints
+        let countries_url = env::var("COUNTRIES_URL").unwrap_or_else(|_| {
+            "https://restcountries.com/v2/all?fields=name,alpha2Code,capital,region,population,flag,currencies".into()
+        });
+        let base = env::var("BASE_CURRENCY").unwrap_or_else(|_| "USD".into());
+        let rates_url = env::var("RATES_URL")
+            .unwrap_or_else(|_| format!("https://open.er-api.com/v6/latest/{}", base));
+        let stale_after_secs: u64 = env::var("STALE_AFTER_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(86_400);
+        let response_case = env::var("RESPONSE_CASE")
+            .ok()
+            .and_then(|s| KeyCase::parse(&s))
+            .unwrap_or_default();
+        Self {
+            external_timeout_ms,
+            countries_url,
+            rates_url,
+            stale_after_secs,
+            response_case,
+            response_envelope: env_flag("RESPONSE_ENVELOPE", false),
+            hateoas_links: env_flag("HATEOAS_LINKS", false),
+            list_bare_array: env_flag("LIST_BARE_ARRAY", false),
+            auto_refresh_on_stale: env_flag("AUTO_REFRESH_ON_STALE", false),
+            explain_queries: env_flag("EXPLAIN_QUERIES", false),
+            // Probes check the DB unless HEALTH_CHECK_DB=false
+            health_check_db: env_flag("HEALTH_CHECK_DB", true),
+            strict_query_params: env_flag("STRICT_QUERY_PARAMS", false),
+            delete_require_confirm: env_flag("DELETE_REQUIRE_CONFIRM", true),
+            image_render_on_missing: env_flag("IMAGE_RENDER_ON_MISSING", true),
+            public_base_url: env::var("PUBLIC_BASE_URL")
+                .ok()
+                .map(|s| s.trim().trim_end_matches('/').to_string())
+                .filter(|s| s.starts_with("https://") || s.starts_with("http://")),
+            sitemap_country_path: env::var("SITEMAP_COUNTRY_PATH")
+                .ok()
+                .filter(|s| s.starts_with('/'))
+                .unwrap_or_else(|| crate::routes::paths::COUNTRY.into()),
+            robots_disallow: env::var("ROBOTS_DISALLOW")
+                .unwrap_or_else(|_| "/admin/".into())
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .collect(),
+        }
+    }
+
+    pub fn external_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.external_timeout_ms)
+    }
+}
+
+/// Re-reads `.env` (overriding the process env) and swaps in the new runtime settings.
+/// In-flight requests keep the snapshot they loaded; no connection is dropped.
+/// Returns the names of the settings that changed.
+pub fn reload(state: &AppState) -> Vec<String> {
+    dotenvy::dotenv_override().ok();
+    crate::services::mock_upstreams::apply_env();
+    let next = RuntimeConfig::from_env();
+    let prev = state.runtime.load_full();
+    let changed = changed_fields(&prev, &next);
+
+    state.runtime.store(Arc::new(next));
+    info!("config reloaded; changed: {:?}", changed);
+    changed
+}
+
+/// Names of the `RuntimeConfig` fields that differ, sorted. Compared through
+/// their serialized form, so a new setting is covered without listing it here.
+fn changed_fields(prev: &RuntimeConfig, next: &RuntimeConfig) -> Vec<String> {
+    let (Ok(Value::Object(prev)), Ok(Value::Object(next))) = (serde_json::to_value(prev), serde_json::to_value(next))
+    else {
+        return Vec::new();
+    };
+    prev.into_iter().filter(|(k, v)| next.get(k) != Some(v)).map(|(k, _)| k).collect()
+}
+
+/// Runs embedded migrations and a first DB ping, then marks the app started.
+pub async fn run_startup(state: &AppState) -> Result<(), anyhow::Error> {
+    // run embedded migrations (creates/uses `sqlx_migrations` table; idempotent)
+    MIGRATOR.run(&state.pool)
+        .await
+        .map_err(|e| anyhow::anyhow!("migrations failed: {}", e))?;
+    info!("✅ Migrations up to date");
+
+    // Catch hand-edited schemas here rather than as silent `try_get` defaults later
+    let drift = migration_service::schema_drift(&state.pool)
+        .await
+        .map_err(|e| anyhow::anyhow!("schema check failed: {}", e))?;
+    if !drift.is_empty() {
+        anyhow::bail!("schema drift detected:\n  {}", drift.join("\n  "));
+    }
+
+    // ping
+    sqlx::query_scalar::<_, i32>("SELECT 1")
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| anyhow::anyhow!("DB connectivity check failed: {}", e))?;
+    info!("✅ Database connected");
+
+    state.startup.mark_started();
+    Ok(())
 }
 
 pub struct AppConfig {
     pub port: u16,
+    /// `ADMIN_ADDR`: internal listener for `/admin/*`; unset = served on `port`
+    pub admin_addr: Option<SocketAddr>,
     pub database_url: String,
-    pub external_timeout_ms: u64,
+    /// How long a request waits for a pool connection before a 503
+    pub db_acquire_timeout_ms: u64,
+    /// Background DB ping interval
+    pub db_probe_secs: u64,
+    /// An outage this long marks the service degraded
+    pub db_degraded_after_secs: u64,
     pub summary_image_path: PathBuf,
+    pub branding: BrandingConfig,
+    pub summary_ranking: Ranking,
+    pub serve_static: bool,
+    pub static_max_age_secs: u64,
+    pub refresh_exclude_countries: Vec<String>,
+    pub webhook_poll_secs: u64,
+    pub webhook_max_attempts: i32,
+    /// Retries of a failed post-refresh summary image / flag sprite before giving up
+    pub side_effect_max_attempts: i32,
+    /// A background job is asked to stop after this long, and abandoned shortly after
+    pub job_max_runtime_secs: u64,
+    /// Refreshes allowed back to back before `refresh_min_interval_secs` applies
+    pub refresh_burst: u32,
+    pub refresh_min_interval_secs: u64,
+    pub raw_archive_runs: usize,
+    pub refresh_window: RefreshWindow,
+    /// `REFRESH_CRON` / `REFRESH_INTERVAL_SECS`; `None` = refresh only on demand
+    pub refresh_schedule: Option<RefreshSchedule>,
+    pub admin_token: Option<String>,
+    /// `API_KEYS`: named keys limited to scopes
+    pub api_keys: ApiKeys,
+    /// `AUTH_*`: lockout after repeated failed authentications
+    pub auth_lockout: LockoutPolicy,
+    /// `URL_SIGNING_KEY`
+    pub url_signer: Option<UrlSigner>,
+    /// Run `services::warmup` after startup and hold readiness until it's done
+    pub warmup: bool,
+    /// Sentry-compatible DSN; unset disables error reporting
+    pub sentry_dsn: Option<String>,
+    pub sentry_environment: String,
+    /// Defaults to the crate version
+    pub sentry_release: String,
+    pub chaos: Option<Chaos>,
+    /// `TRUSTED_PROXIES`: load balancers allowed to report the client address
+    pub trusted_proxies: TrustedProxies,
+    /// Inbound keep-alive and HTTP/2 (`HTTP_*`)
+    pub server: ServerTuning,
+    /// `SERVER_TIMING`: DB, upstream and serialization time per response
+    pub server_timing: bool,
+    pub runtime: RuntimeConfig,
 }
 
 impl AppConfig {
     pub fn from_env() -> Result<Self, anyhow::Error> {
         let port: u16 = env::var("PORT").unwrap_or_else(|_| "8080".into()).parse()?;
         let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is required");
-        let external_timeout_ms: u64 = env::var("EXTERNAL_TIMEOUT_MS")
+        db::check_url(&database_url).map_err(anyhow::Error::msg)?;
+        let admin_addr: Option<SocketAddr> = match env::var("ADMIN_ADDR") {
+            Ok(v) if !v.trim().is_empty() => Some(v.trim().parse().map_err(|_| {
+                anyhow::anyhow!("ADMIN_ADDR must be an address like 127.0.0.1:9090, got {:?}", v)
+            })?),
+            _ => None,
+        };
+        let db_acquire_timeout_ms: u64 = env::var("DB_ACQUIRE_TIMEOUT_MS")
             .ok()
             .and_then(|s| s.parse().ok())
-            .unwrap_or(12_000);
+            .unwrap_or(30_000);
+        let db_probe_secs: u64 = env::var("DB_PROBE_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(5);
+        let db_degraded_after_secs: u64 = env::var("DB_DEGRADED_AFTER_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(15);
         let summary_image_path =
             PathBuf::from(env::var("SUMMARY_IMAGE_PATH").unwrap_or_else(|_| "cache/summary.png".into()));
-        Ok(Self { port, database_url, external_timeout_ms, summary_image_path })
+        // Optional image branding; unset vars keep the embedded font + default palette
+        let branding = BrandingConfig {
+            font_path: env::var("IMAGE_FONT_PATH").ok().map(PathBuf::from),
+            logo_path: env::var("IMAGE_LOGO_PATH").ok().map(PathBuf::from),
+            background: env::var("IMAGE_BACKGROUND_COLOR").ok(),
+            text: env::var("IMAGE_TEXT_COLOR").ok(),
+            accent: env::var("IMAGE_ACCENT_COLOR").ok(),
+            muted: env::var("IMAGE_MUTED_COLOR").ok(),
+            title: env::var("IMAGE_TITLE").ok(),
+            map_geojson_path: env::var("MAP_GEOJSON_PATH").ok().filter(|s| !s.trim().is_empty()).map(PathBuf::from),
+        };
+        let summary_ranking = Ranking::new(
+            RankMetric::parse(&env::var("SUMMARY_RANK_BY").unwrap_or_else(|_| "gdp".into()))
+                .map_err(|e| anyhow::anyhow!("SUMMARY_RANK_BY: {}", e))?,
+            env::var("SUMMARY_TOP_N").ok().and_then(|s| s.parse().ok()).unwrap_or(5),
+        )
+        .map_err(|e| anyhow::anyhow!("SUMMARY_TOP_N: {}", e))?;
+        // Optional /static/* exposure of the cache dir (e.g. behind a CDN)
+        let serve_static = env::var("SERVE_STATIC")
+            .map(|v| matches!(v.as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+        let static_max_age_secs: u64 = env::var("STATIC_MAX_AGE_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(300);
+        // Comma-separated country names the refresh should never upsert
+        let refresh_exclude_countries: Vec<String> = env::var("REFRESH_EXCLUDE_COUNTRIES")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        let webhook_poll_secs: u64 = env::var("WEBHOOK_POLL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(5);
+        let webhook_max_attempts: i32 = env::var("WEBHOOK_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(8);
+        let side_effect_max_attempts: i32 = env::var("SIDE_EFFECT_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(8);
+        let refresh_burst: u32 = env::var("REFRESH_BURST")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(2);
+        let refresh_min_interval_secs: u64 = env::var("REFRESH_MIN_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(60);
+        let job_max_runtime_secs: u64 = env::var("JOB_MAX_RUNTIME_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(900);
+        let raw_archive_runs: usize = env::var("RAW_ARCHIVE_RUNS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+        let refresh_window = RefreshWindow::parse(
+            &env::var("REFRESH_WINDOW").unwrap_or_default(),
+            &env::var("REFRESH_TIMEZONE").unwrap_or_else(|_| "UTC".into()),
+            env_flag("REFRESH_SKIP_WEEKENDS", false),
+        )
+        .map_err(anyhow::Error::msg)?;
+        let refresh_schedule = RefreshSchedule::parse(
+            &env::var("REFRESH_CRON").unwrap_or_default(),
+            &env::var("REFRESH_INTERVAL_SECS").unwrap_or_default(),
+        )
+        .map_err(anyhow::Error::msg)?;
+        let admin_token = env::var("ADMIN_TOKEN").ok().filter(|s| !s.trim().is_empty());
+        let var = |k: &str| env::var(k).unwrap_or_default();
+        let api_keys = ApiKeys::parse(&var("API_KEYS")).map_err(anyhow::Error::msg)?;
+        let url_signer = match env::var("URL_SIGNING_KEY") {
+            Ok(k) if !k.trim().is_empty() => Some(UrlSigner::new(k.trim()).map_err(anyhow::Error::msg)?),
+            _ => None,
+        };
+        let auth_lockout = LockoutPolicy {
+            max_failures: env::var("AUTH_MAX_FAILURES").ok().and_then(|s| s.parse().ok()).unwrap_or(10),
+            window: std::time::Duration::from_secs(
+                env::var("AUTH_FAILURE_WINDOW_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(300),
+            ),
+            base: std::time::Duration::from_secs(
+                env::var("AUTH_LOCKOUT_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(60),
+            ),
+            max: std::time::Duration::from_secs(
+                env::var("AUTH_LOCKOUT_MAX_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(3_600),
+            ),
+        };
+        let mut chaos = Chaos::parse(
+            &var("CHAOS_LATENCY_PERCENT"),
+            &var("CHAOS_LATENCY_MS"),
+            &var("CHAOS_ERROR_PERCENT"),
+            &var("CHAOS_DB_ERROR_PERCENT"),
+        )
+        .map_err(anyhow::Error::msg)?;
+        if chaos.is_some() && !cfg!(debug_assertions) {
+            warn!("CHAOS_* settings ignored: fault injection only runs in debug builds");
+            chaos = None;
+        }
+        let trusted_proxies = TrustedProxies::parse(&var("TRUSTED_PROXIES")).map_err(anyhow::Error::msg)?;
+        // Inbound connections; 0 turns a timeout/interval off
+        let secs = |k: &str, default: u64| {
+            env::var(k).ok().and_then(|s| s.parse::<u64>().ok()).unwrap_or(default)
+        };
+        let nonzero = |s: u64| (s > 0).then(|| std::time::Duration::from_secs(s));
+        let server = ServerTuning {
+            keep_alive: env_flag("HTTP_KEEP_ALIVE", true),
+            keep_alive_timeout: nonzero(secs("HTTP_KEEP_ALIVE_TIMEOUT_SECS", 30)),
+            http2: env_flag("HTTP2", false),
+            http2_max_concurrent_streams: env::var("HTTP2_MAX_CONCURRENT_STREAMS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(200),
+            http2_keep_alive_interval: nonzero(secs("HTTP2_KEEP_ALIVE_INTERVAL_SECS", 0)),
+            http2_keep_alive_timeout: std::time::Duration::from_secs(secs("HTTP2_KEEP_ALIVE_TIMEOUT_SECS", 20)),
+        };
+        Ok(Self {
+            port,
+            admin_addr,
+            database_url,
+            db_acquire_timeout_ms,
+            db_probe_secs,
+            db_degraded_after_secs,
+            summary_image_path,
+            branding,
+            summary_ranking,
+            serve_static,
+            static_max_age_secs,
+            refresh_exclude_countries,
+            webhook_poll_secs,
+            webhook_max_attempts,
+            side_effect_max_attempts,
+            job_max_runtime_secs,
+            refresh_burst,
+            refresh_min_interval_secs,
+            raw_archive_runs,
+            refresh_window,
+            refresh_schedule,
+            admin_token,
+            api_keys,
+            auth_lockout,
+            url_signer,
+            warmup: env_flag("WARMUP", false),
+            sentry_dsn: env::var("SENTRY_DSN").ok().filter(|s| !s.trim().is_empty()),
+            sentry_environment: env::var("SENTRY_ENVIRONMENT").unwrap_or_else(|_| "production".into()),
+            sentry_release: env::var("SENTRY_RELEASE")
+                .unwrap_or_else(|_| env!("CARGO_PKG_VERSION").to_string()),
+            chaos,
+            trusted_proxies,
+            server,
+            server_timing: server_timing::enabled_from_env(),
+            runtime: RuntimeConfig::from_env(),
+        })
     }
 
     pub async fn build_state(&self) -> Result<AppState, anyhow::Error> {
-        // connect
-        let pool = MySqlPoolOptions::new()
+        // connect lazily: the first connection is made by `run_startup`, after the
+        // listener is up, so probes answer while first-boot migrations run
+        let pool = DbPoolOptions::new()
             .max_connections(10)
-            .connect(&self.database_url)
-            .await?;
-
-        // run embedded migrations (creates/uses `sqlx_migrations` table; idempotent)
-        MIGRATOR.run(&pool)
-            .await
-            .map_err(|e| anyhow::anyhow!("migrations failed: {}", e))?;
-        info!("✅ Migrations up to date");
-
-        // ping
-        sqlx::query_scalar::<_, i32>("SELECT 1")
-            .fetch_one(&pool)
-            .await
-            .map_err(|e| anyhow::anyhow!("DB connectivity check failed: {}", e))?;
-        info!("✅ Database connected");
+            .acquire_timeout(std::time::Duration::from_millis(self.db_acquire_timeout_ms))
+            .connect_lazy_with(db::connect_options(&self.database_url)?);
 
         // ensure cache dir
         if let Some(parent) = self.summary_image_path.parent() {
             fs::create_dir_all(parent).await.ok();
         }
 
+        // image branding (fail fast on a bad font/logo/color rather than at first render)
+        let branding = Branding::load(&self.branding)
+            .map_err(|e| anyhow::anyhow!("image branding: {}", e))?;
+        if let Some(shapes) = &branding.map_shapes {
+            info!("Map geometry loaded for {} countries", shapes.len());
+        }
+
+        // everything generated (summary, image variants) lives next to the summary image
+        let cache_dir = match self.summary_image_path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let image_cache = ImageCache::new(cache_dir.join("variants"), &branding.fingerprint);
+
         // http client
         let http = Client::builder()
-            .timeout(std::time::Duration::from_millis(self.external_timeout_ms))
+            .timeout(self.runtime.external_timeout())
             .build()?;
 
         Ok(AppState {
             pool,
-            http,
+            http: http.clone(),
+            runtime: Arc::new(ArcSwap::from_pointee(self.runtime.clone())),
             summary_image_path: self.summary_image_path.clone(),
+            branding,
+            summary_ranking: self.summary_ranking,
+            image_cache,
+            aggregates: AggregateFlights::default(),
+            image_health: ImageHealth::default(),
+            static_dir: self.serve_static.then(|| cache_dir.clone()),
+            cache_dir,
+            static_max_age_secs: self.static_max_age_secs,
+            hooks: RefreshHooks::default(),
+            countries_provider: Arc::new(HttpCountries(http.clone())),
+            rates_provider: Arc::new(HttpRates(http.clone())),
+            // Window fixed at boot; a reloaded STALE_AFTER_SECS only changes what counts as stale
+            auto_refresh: AutoRefresh::new(std::time::Duration::from_secs(
+                self.runtime.stale_after_secs.max(1),
+            )),
+            refresh_throttle: RefreshThrottle::new(
+                self.refresh_burst,
+                std::time::Duration::from_secs(self.refresh_min_interval_secs),
+            ),
+            refresh_feed: RefreshFeed::default(),
+            raw_archive_runs: self.raw_archive_runs,
+            refresh_window: self.refresh_window.clone(),
+            admin_token: self.admin_token.clone(),
+            api_keys: Arc::new(self.api_keys.clone()),
+            auth_lockout: AuthLockout::new(self.auth_lockout.clone()),
+            url_signer: self.url_signer.clone(),
+            startup: Startup::new(self.warmup),
+            db_health: DbHealth::new(std::time::Duration::from_secs(self.db_degraded_after_secs)),
+            chaos: self.chaos.clone().map(Arc::new),
+            trusted_proxies: Arc::new(self.trusted_proxies.clone()),
+            admin_addr: self.admin_addr,
+            server_timing: self.server_timing,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_reports_every_changed_setting() {
+        let prev = RuntimeConfig::from_env();
+        assert_eq!(changed_fields(&prev, &prev.clone()), Vec::<String>::new());
+
+        let next = RuntimeConfig {
+            response_case: match prev.response_case {
+                KeyCase::Snake => KeyCase::Camel,
+                KeyCase::Camel => KeyCase::Snake,
+            },
+            hateoas_links: !prev.hateoas_links,
+            robots_disallow: vec!["/private/".into()],
+            ..prev.clone()
+        };
+        assert_eq!(changed_fields(&prev, &next), ["hateoas_links", "response_case", "robots_disallow"]);
+    }
+}
@@ -0,0 +1,390 @@
+// The database backend. MySQL by default; built with `--features sqlite` the app runs on
+// an embedded SQLite file instead (`DATABASE_URL=sqlite://...`, migrations from
+// `migrations/sqlite`), so a local run needs no database server. Code names the backend
+// only through the aliases below. SQL is written portably where both dialects agree
+// (`CURRENT_TIMESTAMP`, `CASE WHEN`, `REPLACE INTO`, `JSON_OBJECT`); the rest is built
+// with the `sql_*!` macros, which expand to string literals so they compose with
+// `concat!` in `const` column lists.
+
+use std::str::FromStr;
+
+use sqlx::Transaction;
+
+#[cfg(not(feature = "sqlite"))]
+pub use sqlx::mysql::{
+    MySql as Db, MySqlConnectOptions as DbConnectOptions, MySqlPoolOptions as DbPoolOptions,
+    MySqlQueryResult as DbQueryResult, MySqlRow as DbRow,
+};
+#[cfg(feature = "sqlite")]
+pub use sqlx::sqlite::{
+    Sqlite as Db, SqliteConnectOptions as DbConnectOptions, SqlitePoolOptions as DbPoolOptions,
+    SqliteQueryResult as DbQueryResult, SqliteRow as DbRow,
+};
+
+pub type DbPool = sqlx::Pool<Db>;
+
+/// `DATABASE_URL` schemes this build can connect to
+#[cfg(not(feature = "sqlite"))]
+const SCHEMES: &[&str] = &["mysql", "mariadb"];
+#[cfg(feature = "sqlite")]
+const SCHEMES: &[&str] = &["sqlite"];
+
+/// Rejects a `DATABASE_URL` for the other backend at startup, instead of with a driver
+/// error on the first query.
+pub fn check_url(url: &str) -> Result<(), String> {
+    let scheme = url.split_once(':').map(|(s, _)| s.to_ascii_lowercase()).unwrap_or_default();
+    if SCHEMES.contains(&scheme.as_str()) {
+        return Ok(());
+    }
+    Err(match scheme.as_str() {
+        "sqlite" => "DATABASE_URL is a sqlite:// URL but this binary was built without the `sqlite` feature \
+                     (cargo run --features sqlite)"
+            .to_string(),
+        "mysql" | "mariadb" => "DATABASE_URL is a mysql:// URL but this binary was built with the `sqlite` \
+                                feature; drop --features sqlite"
+            .to_string(),
+        _ => format!("DATABASE_URL must be a {}:// URL", SCHEMES[0]),
+    })
+}
+
+/// Connection settings for `url`. SQLite creates the file on first run, and writers wait
+/// for each other (WAL, busy timeout) instead of failing with "database is locked".
+pub fn connect_options(url: &str) -> Result<DbConnectOptions, sqlx::Error> {
+    let opts = DbConnectOptions::from_str(url)?;
+    #[cfg(feature = "sqlite")]
+    let opts = opts
+        .create_if_missing(true)
+        .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
+        .busy_timeout(std::time::Duration::from_secs(10));
+    Ok(opts)
+}
+
+/// One-connection pool for the CLI modes (`migrate`, `--bench-seed`)
+pub async fn connect(url: &str) -> Result<DbPool, sqlx::Error> {
+    DbPoolOptions::new().max_connections(1).connect_with(connect_options(url)?).await
+}
+
+/// Id of the row an `INSERT` created.
+#[cfg(not(feature = "sqlite"))]
+pub fn last_insert_id(res: &DbQueryResult) -> i64 {
+    res.last_insert_id() as i64
+}
+#[cfg(feature = "sqlite")]
+pub fn last_insert_id(res: &DbQueryResult) -> i64 {
+    res.last_insert_rowid()
+}
+
+/// Starts a transaction that may write. On SQLite, which has no row locks (`FOR UPDATE`
+/// expands to nothing there), it takes the database write lock up front like
+/// `BEGIN IMMEDIATE`: a deferred transaction that reads first fails with SQLITE_BUSY when
+/// another connection commits before its first write. sqlx only issues a plain `BEGIN`.
+pub async fn begin(pool: &DbPool) -> Result<Transaction<'static, Db>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    if cfg!(feature = "sqlite") {
+        sqlx::query("DELETE FROM app_meta WHERE 0").execute(&mut *tx).await?;
+    }
+    Ok(tx)
+}
+
+/// Prefix turning a `SELECT` into its query plan (see `utils::explain`)
+#[cfg(not(feature = "sqlite"))]
+pub const EXPLAIN: &str = "EXPLAIN ";
+#[cfg(feature = "sqlite")]
+pub const EXPLAIN: &str = "EXPLAIN QUERY PLAN ";
+
+/// `col` ordered by a MySQL UCA collation. SQLite has none of them: names keep the
+/// column's case-insensitive `NOCASE` order.
+#[cfg(not(feature = "sqlite"))]
+pub fn collate(col: &str, collation: &str) -> String {
+    format!("CONVERT({} USING utf8mb4) COLLATE {}", col, collation)
+}
+#[cfg(feature = "sqlite")]
+pub fn collate(col: &str, _collation: &str) -> String {
+    col.to_string()
+}
+
+/// A DATETIME column as RFC 3339 UTC, e.g. `2026-03-01T12:00:00Z`
+#[cfg(not(feature = "sqlite"))]
+#[macro_export]
+macro_rules! sql_iso {
+    ($col:literal) => {
+        concat!("DATE_FORMAT(", $col, ", '%Y-%m-%dT%H:%i:%sZ')")
+    };
+}
+#[cfg(feature = "sqlite")]
+#[macro_export]
+macro_rules! sql_iso {
+    ($col:literal) => {
+        concat!("strftime('%Y-%m-%dT%H:%M:%SZ', ", $col, ")")
+    };
+}
+
+/// A DATE or DATETIME column as `YYYY-MM-DD`
+#[cfg(not(feature = "sqlite"))]
+#[macro_export]
+macro_rules! sql_day {
+    ($col:literal) => {
+        concat!("DATE_FORMAT(", $col, ", '%Y-%m-%d')")
+    };
+}
+#[cfg(feature = "sqlite")]
+#[macro_export]
+macro_rules! sql_day {
+    ($col:literal) => {
+        concat!("strftime('%Y-%m-%d', ", $col, ")")
+    };
+}
+
+/// Today's UTC date
+#[cfg(not(feature = "sqlite"))]
+#[macro_export]
+macro_rules! sql_utc_date {
+    () => {
+        "UTC_DATE()"
+    };
+}
+#[cfg(feature = "sqlite")]
+#[macro_export]
+macro_rules! sql_utc_date {
+    () => {
+        "date('now')"
+    };
+}
+
+/// Now plus `n` seconds; `n` is SQL, usually a `?` placeholder
+#[cfg(not(feature = "sqlite"))]
+#[macro_export]
+macro_rules! sql_secs_from_now {
+    ($n:literal) => {
+        concat!("NOW() + INTERVAL ", $n, " SECOND")
+    };
+}
+#[cfg(feature = "sqlite")]
+#[macro_export]
+macro_rules! sql_secs_from_now {
+    ($n:literal) => {
+        concat!("datetime('now', '+' || ", $n, " || ' seconds')")
+    };
+}
+
+/// Now minus `n` seconds
+#[cfg(not(feature = "sqlite"))]
+#[macro_export]
+macro_rules! sql_secs_ago {
+    ($n:literal) => {
+        concat!("NOW() - INTERVAL ", $n, " SECOND")
+    };
+}
+#[cfg(feature = "sqlite")]
+#[macro_export]
+macro_rules! sql_secs_ago {
+    ($n:literal) => {
+        concat!("datetime('now', '-' || ", $n, " || ' seconds')")
+    };
+}
+
+/// Now minus `n` days
+#[cfg(not(feature = "sqlite"))]
+#[macro_export]
+macro_rules! sql_days_ago {
+    ($n:literal) => {
+        concat!("NOW() - INTERVAL ", $n, " DAY")
+    };
+}
+#[cfg(feature = "sqlite")]
+#[macro_export]
+macro_rules! sql_days_ago {
+    ($n:literal) => {
+        concat!("datetime('now', '-' || ", $n, " || ' days')")
+    };
+}
+
+/// JSON array of `col` over the group
+#[cfg(not(feature = "sqlite"))]
+#[macro_export]
+macro_rules! sql_json_arrayagg {
+    ($col:literal) => {
+        concat!("JSON_ARRAYAGG(", $col, ")")
+    };
+}
+#[cfg(feature = "sqlite")]
+#[macro_export]
+macro_rules! sql_json_arrayagg {
+    ($col:literal) => {
+        concat!("json_group_array(", $col, ")")
+    };
+}
+
+/// `INSERT` that skips rows colliding with a unique key
+#[cfg(not(feature = "sqlite"))]
+#[macro_export]
+macro_rules! sql_insert_ignore {
+    () => {
+        "INSERT IGNORE"
+    };
+}
+#[cfg(feature = "sqlite")]
+#[macro_export]
+macro_rules! sql_insert_ignore {
+    () => {
+        "INSERT OR IGNORE"
+    };
+}
+
+/// Upsert clause after `INSERT ... VALUES (...)`: on a collision with the unique key
+/// (the columns before `;`) the listed columns take the inserted values. More
+/// assignments can follow as `, col = expr`.
+#[cfg(not(feature = "sqlite"))]
+#[macro_export]
+macro_rules! sql_upsert {
+    ($($key:literal),+; $col:literal $(, $cols:literal)*) => {
+        concat!(" ON DUPLICATE KEY UPDATE ", $col, " = VALUES(", $col, ")" $(, ", ", $cols, " = VALUES(", $cols, ")")*)
+    };
+}
+#[cfg(feature = "sqlite")]
+#[macro_export]
+macro_rules! sql_upsert {
+    ($key:literal $(, $keys:literal)*; $col:literal $(, $cols:literal)*) => {
+        concat!(
+            " ON CONFLICT (", $key $(, ", ", $keys)*, ") DO UPDATE SET ",
+            $col, " = excluded.", $col $(, ", ", $cols, " = excluded.", $cols)*
+        )
+    };
+}
+
+/// Escape clause after a `LIKE ?` whose pattern went through `escape_like`. Backslash is
+/// MySQL's default escape character; SQLite has none unless told.
+#[cfg(not(feature = "sqlite"))]
+#[macro_export]
+macro_rules! sql_like_escape {
+    () => {
+        ""
+    };
+}
+#[cfg(feature = "sqlite")]
+#[macro_export]
+macro_rules! sql_like_escape {
+    () => {
+        " ESCAPE '\\'"
+    };
+}
+
+/// Row locks for a read-then-write transaction (see `begin`)
+#[cfg(not(feature = "sqlite"))]
+#[macro_export]
+macro_rules! sql_for_update {
+    () => {
+        " FOR UPDATE"
+    };
+}
+#[cfg(feature = "sqlite")]
+#[macro_export]
+macro_rules! sql_for_update {
+    () => {
+        ""
+    };
+}
+
+/// Row locks for a work claim: several instances share a table without double-claiming.
+/// SQLite is one process; `begin` serializes claims there.
+#[cfg(not(feature = "sqlite"))]
+#[macro_export]
+macro_rules! sql_skip_locked {
+    () => {
+        " FOR UPDATE SKIP LOCKED"
+    };
+}
+#[cfg(feature = "sqlite")]
+#[macro_export]
+macro_rules! sql_skip_locked {
+    () => {
+        ""
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls_for_the_other_backend_are_rejected_with_a_hint() {
+        let mysql = check_url("mysql://root@localhost:3306/countries");
+        let sqlite = check_url("sqlite://data/countries.db");
+        if cfg!(feature = "sqlite") {
+            assert!(sqlite.is_ok());
+            assert!(mysql.unwrap_err().contains("drop --features sqlite"));
+        } else {
+            assert!(mysql.is_ok());
+            assert!(check_url("MariaDB://db/countries").is_ok());
+            assert!(sqlite.unwrap_err().contains("--features sqlite"));
+        }
+        assert!(check_url("postgres://db/countries").unwrap_err().contains("must be a"));
+        assert!(check_url("countries.db").is_err());
+    }
+
+    #[test]
+    fn upsert_names_the_key_only_where_the_dialect_needs_it() {
+        let sql = sql_upsert!("currency_code", "day"; "rate", "source");
+        if cfg!(feature = "sqlite") {
+            assert_eq!(
+                sql,
+                " ON CONFLICT (currency_code, day) DO UPDATE SET rate = excluded.rate, source = excluded.source"
+            );
+        } else {
+            assert_eq!(sql, " ON DUPLICATE KEY UPDATE rate = VALUES(rate), source = VALUES(source)");
+        }
+    }
+
+    /// The SQLite dialect end to end on a throwaway database: the migrations, the listing
+    /// queries and the date macros all have to parse and mean the same as on MySQL.
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn sqlite_dialect_runs_against_a_migrated_database() {
+        use crate::services::country_repository::{self, CountryQuery};
+        use crate::services::migration_service::schema_drift;
+        use crate::types::query::SortOrder;
+
+        let pool = connect("sqlite::memory:").await.unwrap();
+        crate::config::MIGRATOR.run(&pool).await.unwrap();
+        assert_eq!(schema_drift(&pool).await.unwrap(), Vec::<String>::new());
+
+        let rows = [("Ghana", "Africa", 1.0), ("Nigeria", "Africa", 3.0), ("Peru", "Americas", 2.0)];
+        for (name, region, gdp) in rows {
+            sqlx::query(concat!(
+                "INSERT INTO countries (name, region, population, estimated_gdp, last_refreshed_at) ",
+                "VALUES (?, ?, 1, ?, ", sql_secs_from_now!("?"), ")"
+            ))
+            .bind(name)
+            .bind(region)
+            .bind(gdp)
+            .bind(3600)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        // NOCASE region match, GDP order
+        let q = CountryQuery {
+            region: Some("africa".into()),
+            sort: SortOrder::GdpDesc,
+            limit: 10,
+            ..Default::default()
+        };
+        let listed = country_repository::list(&pool, &q).await.unwrap();
+        let names: Vec<&str> = listed.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Nigeria", "Ghana"]);
+        assert_eq!(country_repository::count(&pool, &q).await.unwrap(), 2);
+        // `_` is literal after escape_like, so "G_ana" matches nothing
+        let q = CountryQuery { q: Some(format!("%{}%", country_repository::escape_like("G_ana"))), ..q };
+        assert_eq!(country_repository::count(&pool, &q).await.unwrap(), 0);
+
+        let (stamp,): (String,) =
+            sqlx::query_as(concat!("SELECT ", sql_iso!("last_refreshed_at"), " FROM countries WHERE name = 'Peru'"))
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let at = chrono::DateTime::parse_from_rfc3339(&stamp).unwrap();
+        let ahead = at.signed_duration_since(chrono::Utc::now()).num_seconds();
+        assert!((3590..=3600).contains(&ahead), "{stamp} is {ahead}s from now");
+    }
+}
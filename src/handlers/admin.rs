@@ -0,0 +1,388 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::config::{self, AppState};
+use crate::db::{self, DbRow};
+use crate::services::data_quality;
+use crate::utils::auth::{self, AdminAuth};
+use crate::utils::client_ip::ClientIp;
+use crate::routes::registry;
+use crate::utils::error::ApiError;
+use crate::utils::signed_url::MAX_TTL_SECS;
+use crate::{sql_days_ago, sql_iso};
+
+fn run_json(r: &DbRow) -> serde_json::Value {
+    serde_json::json!({
+        "id": r.try_get::<i64, _>("id").unwrap_or_default(),
+        "status": r.try_get::<String, _>("status").unwrap_or_default(),
+        "started_at": r.try_get::<Option<String>, _>("started_at").ok().flatten(),
+        "finished_at": r.try_get::<Option<String>, _>("finished_at").ok().flatten(),
+        "inserted": r.try_get::<i32, _>("inserted").unwrap_or_default(),
+        "updated": r.try_get::<i32, _>("updated").unwrap_or_default(),
+        "skipped": r.try_get::<i32, _>("skipped").unwrap_or_default(),
+        "quarantined": r.try_get::<i32, _>("quarantined").unwrap_or_default(),
+        "completeness": r.try_get::<Option<f64>, _>("completeness").ok().flatten(),
+        "upstream_calls": r.try_get::<i32, _>("upstream_calls").unwrap_or_default(),
+        "bytes_downloaded": r.try_get::<i64, _>("bytes_downloaded").unwrap_or_default(),
+        "rows_written": r.try_get::<i32, _>("rows_written").unwrap_or_default(),
+        "error": r.try_get::<Option<String>, _>("error").ok().flatten(),
+    })
+}
+
+/// One-call ops dashboard: data freshness, refresh history, quarantined upstream records,
+/// background jobs, webhook delivery health and summary image health.
+pub async fn overview(_: AdminAuth, State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+    let db = ApiError::db;
+
+    // --- data freshness ---
+    let (total,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM countries")
+        .fetch_one(&state.pool)
+        .await
+        .map_err(db)?;
+    let ts: Option<(String,)> =
+        sqlx::query_as("SELECT v FROM app_meta WHERE k='last_refreshed_at'")
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(db)?;
+    let last_refreshed_at = ts.map(|x| x.0);
+    let age_secs = last_refreshed_at
+        .as_deref()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|t| (Utc::now() - t.with_timezone(&Utc)).num_seconds());
+    let stale_after_secs = state.runtime.load().stale_after_secs;
+    let stale = age_secs.map(|a| a > stale_after_secs as i64).unwrap_or(true);
+
+    // --- refresh history ---
+    let runs = sqlx::query(concat!(
+        "SELECT id, status, inserted, updated, skipped, quarantined, completeness, error, \
+         upstream_calls, bytes_downloaded, rows_written, ",
+        sql_iso!("started_at"), " as started_at, ",
+        sql_iso!("finished_at"), " as finished_at ",
+        "FROM refresh_runs ORDER BY id DESC LIMIT 10"
+    ))
+    .fetch_all(&state.pool)
+    .await
+    .map_err(db)?;
+    let (failed_24h,): (i64,) = sqlx::query_as(concat!(
+        "SELECT COUNT(*) FROM refresh_runs WHERE status = 'failed' AND started_at >= ",
+        sql_days_ago!("1")
+    ))
+    .fetch_one(&state.pool)
+    .await
+    .map_err(db)?;
+
+    // --- quarantined upstream records (counted per run, the records aren't kept) ---
+    let (quarantined_24h,): (i64,) = sqlx::query_as(concat!(
+        "SELECT CAST(COALESCE(SUM(quarantined), 0) AS SIGNED) FROM refresh_runs WHERE started_at >= ",
+        sql_days_ago!("1")
+    ))
+    .fetch_one(&state.pool)
+    .await
+    .map_err(db)?;
+
+    // --- background jobs ---
+    let job_counts = sqlx::query("SELECT status, COUNT(*) as n FROM jobs GROUP BY status ORDER BY status")
+        .fetch_all(&state.pool)
+        .await
+        .map_err(db)?;
+    let active_jobs = sqlx::query(concat!(
+        "SELECT id, kind, status, progress, message, ",
+        sql_iso!("created_at"), " as created_at, ",
+        sql_iso!("started_at"), " as started_at ",
+        "FROM jobs WHERE status IN ('queued', 'running') ORDER BY id ASC LIMIT 10"
+    ))
+    .fetch_all(&state.pool)
+    .await
+    .map_err(db)?;
+    let recent_job_failures = sqlx::query(concat!(
+        "SELECT id, kind, status, error, ",
+        sql_iso!("finished_at"), " as finished_at ",
+        "FROM jobs WHERE status = 'failed' ORDER BY id DESC LIMIT 10"
+    ))
+    .fetch_all(&state.pool)
+    .await
+    .map_err(db)?;
+
+    // --- webhooks ---
+    let (active_hooks, pending, failed): (i64, i64, i64) = sqlx::query_as(
+        "SELECT (SELECT COUNT(*) FROM webhooks WHERE active = TRUE), \
+         (SELECT COUNT(*) FROM outbox WHERE status = 'pending'), \
+         (SELECT COUNT(*) FROM outbox WHERE status = 'failed')",
+    )
+    .fetch_one(&state.pool)
+    .await
+    .map_err(db)?;
+    let recent_failures = sqlx::query(concat!(
+        "SELECT o.id, o.webhook_id, w.url, o.event_type, o.status, o.attempts, o.last_error, ",
+        sql_iso!("o.next_attempt_at"), " as next_attempt_at ",
+        "FROM outbox o JOIN webhooks w ON w.id = o.webhook_id \
+         WHERE o.last_error IS NOT NULL AND o.status <> 'delivered' \
+         ORDER BY o.id DESC LIMIT 10"
+    ))
+    .fetch_all(&state.pool)
+    .await
+    .map_err(db)?;
+
+    let image = state.image_health.snapshot();
+
+    Ok((
+        axum::http::StatusCode::OK,
+        Json(serde_json::json!({
+            "data": {
+                "total_countries": total,
+                "last_refreshed_at": last_refreshed_at,
+                "age_secs": age_secs,
+                "stale": stale,
+                "stale_after_secs": stale_after_secs,
+            },
+            "refresh": {
+                "last_run": runs.first().map(run_json),
+                "recent_runs": runs.iter().map(run_json).collect::<Vec<_>>(),
+                "failed_last_24h": failed_24h,
+            },
+            "quarantined": {
+                "last_run": runs.first().map(|r| r.try_get::<i32, _>("quarantined").unwrap_or_default()),
+                "last_24h": quarantined_24h,
+            },
+            "jobs": {
+                "by_status": job_counts
+                    .iter()
+                    .map(|r| {
+                        let status = r.try_get::<String, _>("status").unwrap_or_default();
+                        (status, serde_json::json!(r.try_get::<i64, _>("n").unwrap_or_default()))
+                    })
+                    .collect::<serde_json::Map<_, _>>(),
+                "active": active_jobs.iter().map(|r| serde_json::json!({
+                    "id": r.try_get::<i64, _>("id").unwrap_or_default(),
+                    "kind": r.try_get::<String, _>("kind").unwrap_or_default(),
+                    "status": r.try_get::<String, _>("status").unwrap_or_default(),
+                    "progress": r.try_get::<i32, _>("progress").unwrap_or_default(),
+                    "message": r.try_get::<Option<String>, _>("message").ok().flatten(),
+                    "created_at": r.try_get::<Option<String>, _>("created_at").ok().flatten(),
+                    "started_at": r.try_get::<Option<String>, _>("started_at").ok().flatten(),
+                })).collect::<Vec<_>>(),
+                "recent_failures": recent_job_failures.iter().map(|r| serde_json::json!({
+                    "id": r.try_get::<i64, _>("id").unwrap_or_default(),
+                    "kind": r.try_get::<String, _>("kind").unwrap_or_default(),
+                    "status": r.try_get::<String, _>("status").unwrap_or_default(),
+                    "error": r.try_get::<Option<String>, _>("error").ok().flatten(),
+                    "finished_at": r.try_get::<Option<String>, _>("finished_at").ok().flatten(),
+                })).collect::<Vec<_>>(),
+            },
+            "webhooks": {
+                "active_subscriptions": active_hooks,
+                "pending_deliveries": pending,
+                "failed_deliveries": failed,
+                "recent_failures": recent_failures.iter().map(|r| serde_json::json!({
+                    "delivery_id": r.try_get::<i64, _>("id").unwrap_or_default(),
+                    "webhook_id": r.try_get::<i32, _>("webhook_id").unwrap_or_default(),
+                    "url": r.try_get::<String, _>("url").unwrap_or_default(),
+                    "event_type": r.try_get::<String, _>("event_type").unwrap_or_default(),
+                    "status": r.try_get::<String, _>("status").unwrap_or_default(),
+                    "attempts": r.try_get::<i32, _>("attempts").unwrap_or_default(),
+                    "last_error": r.try_get::<Option<String>, _>("last_error").ok().flatten(),
+                    "next_attempt_at": r.try_get::<Option<String>, _>("next_attempt_at").ok().flatten(),
+                })).collect::<Vec<_>>(),
+            },
+            "summary_image": {
+                "ok": !image.is_stale(),
+                "last_rendered_at": image.last_success_at,
+                "last_error": image.last_error,
+                "last_error_at": image.last_error_at,
+            },
+        })),
+    ))
+}
+
+/// Latest 100 `audit_log` entries, newest first, with the row snapshots they captured.
+pub async fn audit_log(
+    _: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let rows = sqlx::query(&format!("SELECT {} FROM audit_log ORDER BY id DESC LIMIT 100", AUDIT_COLUMNS))
+        .fetch_all(&state.pool)
+        .await
+        .map_err(ApiError::db)?;
+    Ok(Json(rows.iter().map(audit_entry).collect::<Vec<_>>()))
+}
+
+const AUDIT_COLUMNS: &str =
+    concat!("id, action, subject, client_ip, snapshot, ", sql_iso!("created_at"), " as created_at");
+
+fn audit_entry(r: &DbRow) -> serde_json::Value {
+    let snapshot = r.try_get::<String, _>("snapshot").unwrap_or_default();
+    serde_json::json!({
+        "id": r.try_get::<i64, _>("id").unwrap_or_default(),
+        "action": r.try_get::<String, _>("action").unwrap_or_default(),
+        "subject": r.try_get::<String, _>("subject").unwrap_or_default(),
+        "client_ip": r.try_get::<Option<String>, _>("client_ip").ok().flatten(),
+        "snapshot": serde_json::from_str::<serde_json::Value>(&snapshot)
+            .unwrap_or(serde_json::Value::String(snapshot)),
+        "created_at": r.try_get::<Option<String>, _>("created_at").ok().flatten(),
+    })
+}
+
+/// Client addresses are the only personal data the service keeps: `audit_log.client_ip`
+/// of deletes and the `ip:` subject of auth lockouts. Parsed so any spelling of an IPv6
+/// address (`2001:DB8:0::1`) matches the stored one.
+fn actor_ip(raw: &str) -> Result<String, ApiError> {
+    raw.trim()
+        .parse::<std::net::IpAddr>()
+        .map(|ip| ip.to_string())
+        .map_err(|_| ApiError::Validation("actor must be an IPv4 or IPv6 address".into()))
+}
+
+/// Every `audit_log` entry attributable to one client address, oldest first (a data
+/// subject access export).
+pub async fn export_actor(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Path(ip): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let ip = actor_ip(&ip)?;
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM audit_log WHERE client_ip = ? OR subject = ? ORDER BY id ASC",
+        AUDIT_COLUMNS
+    ))
+    .bind(&ip)
+    .bind(format!("ip:{}", ip))
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+    Ok(Json(serde_json::json!({
+        "actor": ip,
+        "total": rows.len(),
+        "entries": rows.iter().map(audit_entry).collect::<Vec<_>>(),
+    })))
+}
+
+/// Removes a client address from `audit_log`. Entries stay, with the row snapshots they
+/// hold, so the log still accounts for every delete and lockout; only who did it is gone.
+/// The erasure is itself audited, without the address.
+pub async fn erase_actor(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    ClientIp(operator_ip): ClientIp,
+    Path(ip): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let ip = actor_ip(&ip)?;
+    let mut tx = db::begin(&state.pool).await.map_err(ApiError::db)?;
+    let cleared = sqlx::query("UPDATE audit_log SET client_ip = NULL WHERE client_ip = ?")
+        .bind(&ip)
+        .execute(&mut *tx)
+        .await
+        .map_err(ApiError::db)?
+        .rows_affected();
+    let renamed = sqlx::query("UPDATE audit_log SET subject = 'ip:erased' WHERE subject = ?")
+        .bind(format!("ip:{}", ip))
+        .execute(&mut *tx)
+        .await
+        .map_err(ApiError::db)?
+        .rows_affected();
+    if cleared + renamed == 0 {
+        return Err(ApiError::NotFound("No audit entries for this address".into()));
+    }
+    sqlx::query(
+        "INSERT INTO audit_log (action, subject, client_ip, snapshot) VALUES ('actor.erased', 'ip:erased', ?, ?)",
+    )
+    .bind(operator_ip.map(|ip| ip.to_string()))
+    .bind(serde_json::json!({ "client_ip_cleared": cleared, "subjects_renamed": renamed }).to_string())
+    .execute(&mut *tx)
+    .await
+    .map_err(ApiError::db)?;
+    tx.commit().await.map_err(ApiError::db)?;
+    Ok(Json(serde_json::json!({ "client_ip_cleared": cleared, "subjects_renamed": renamed })))
+}
+
+/// Image variant cache contents and hit/miss counters since startup.
+pub async fn cache_stats(
+    _: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    Ok(Json(serde_json::json!({ "image_variants": state.image_cache.stats().await })))
+}
+
+/// Drops every cached image variant without a restart.
+pub async fn clear_cache(
+    _: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let removed = state.image_cache.clear().await;
+    Ok(Json(serde_json::json!({ "image_variants": { "removed": removed } })))
+}
+
+/// Re-reads `.env` and swaps in the reloadable settings (same as SIGHUP).
+pub async fn reload_config(
+    _: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let changed = config::reload(&state);
+    Ok(Json(serde_json::json!({
+        "changed": changed,
+        "config": &**state.runtime.load(),
+    })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DataQualityParams {
+    /// Also `HEAD` every flag URL (one outbound request per country)
+    #[serde(default)]
+    pub flags: bool,
+}
+
+/// Consistency checks over the cached countries. Always `200`; `ok` is false when any
+/// check found issues, so monitors alert on the body rather than the status.
+pub async fn data_quality(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Query(params): Query<DataQualityParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let countries = data_quality::load(&state.pool).await.map_err(ApiError::db)?;
+    Ok(Json(data_quality::run(&state.http, &countries, params.flags).await))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SignedUrlBody {
+    /// Path and optional query, e.g. `/countries/image?format=svg` or `/static/exports/nightly/countries.csv`
+    pub path: String,
+    /// Default one hour, at most a week
+    pub expires_in_secs: Option<i64>,
+}
+
+/// A link to a signable route (the images, the bundle) or a published export file that
+/// works without an API key until it expires. It is relative; prefix it with the public host.
+pub async fn create_signed_url(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Json(body): Json<SignedUrlBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Some(signer) = &state.url_signer else {
+        return Err(ApiError::Validation("signed URLs are disabled (URL_SIGNING_KEY unset)".into()));
+    };
+    let ttl = body.expires_in_secs.unwrap_or(3_600);
+    if !(1..=MAX_TTL_SECS).contains(&ttl) {
+        return Err(ApiError::Validation(format!("expires_in_secs must be between 1 and {}", MAX_TTL_SECS)));
+    }
+    let (path, query) = match body.path.trim().split_once('?') {
+        Some((p, q)) => (p, Some(q)),
+        None => (body.path.trim(), None),
+    };
+    if !auth::signable("GET", path) {
+        let mut signable: Vec<&str> = registry::ENDPOINTS.iter().filter(|e| e.signed).map(|e| e.path).collect();
+        signable.push("/static/exports/*");
+        return Err(ApiError::Validation(format!("only these routes can be signed: {}", signable.join(", "))));
+    }
+    let expires_at = Utc::now() + chrono::Duration::seconds(ttl);
+    Ok(Json(serde_json::json!({
+        "url": signer.sign(path, query, expires_at.timestamp()),
+        "expires_at": expires_at.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+    })))
+}
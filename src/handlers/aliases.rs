@@ -0,0 +1,99 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use sqlx::Row;
+use utoipa::ToSchema;
+
+use crate::config::AppState;
+use crate::utils::auth::AdminAuth;
+use crate::utils::error::ApiError;
+use crate::{sql_iso, sql_upsert};
+
+#[derive(Deserialize, ToSchema)]
+pub struct PutAlias {
+    /// Canonical country name, as stored in `countries`
+    pub country: String,
+}
+
+pub async fn list_aliases(
+    _: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let rows = sqlx::query(concat!(
+        "SELECT alias, country_name, ",
+        sql_iso!("created_at"), " as created_at ",
+        "FROM country_aliases ORDER BY country_name ASC, alias ASC"
+    ))
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+
+    let out: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            serde_json::json!({
+                "alias": r.try_get::<String, _>("alias").unwrap_or_default(),
+                "country": r.try_get::<String, _>("country_name").unwrap_or_default(),
+                "created_at": r.try_get::<Option<String>, _>("created_at").ok().flatten(),
+            })
+        })
+        .collect();
+    Ok(Json(out))
+}
+
+/// Creates or repoints an alias. The target must be a cached country.
+pub async fn put_alias(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Path(alias): Path<String>,
+    Json(body): Json<PutAlias>,
+) -> Result<impl IntoResponse, ApiError> {
+    let alias = alias.trim().to_string();
+    if alias.is_empty() || alias.chars().count() > 128 {
+        return Err(ApiError::Validation("alias must be 1-128 characters".into()));
+    }
+
+    let country: Option<(String,)> =
+        sqlx::query_as("SELECT name FROM countries WHERE LOWER(name)=LOWER(?) LIMIT 1")
+            .bind(body.country.trim())
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(ApiError::db)?;
+    let Some((country,)) = country else {
+        return Err(ApiError::NotFound("Country not found".into()));
+    };
+    if country.eq_ignore_ascii_case(&alias) {
+        return Err(ApiError::Validation("alias is already the country's name".into()));
+    }
+
+    sqlx::query(concat!(
+        "INSERT INTO country_aliases (alias, country_name) VALUES (?, ?)",
+        sql_upsert!("alias"; "country_name")
+    ))
+    .bind(&alias)
+    .bind(&country)
+    .execute(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+
+    Ok(Json(serde_json::json!({ "alias": alias, "country": country })))
+}
+
+pub async fn delete_alias(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Path(alias): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let res = sqlx::query("DELETE FROM country_aliases WHERE LOWER(alias)=LOWER(?)")
+        .bind(alias.trim())
+        .execute(&state.pool)
+        .await
+        .map_err(ApiError::db)?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Alias not found".into()));
+    }
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
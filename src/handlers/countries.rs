@@ -1,228 +1,1132 @@
 use axum::{
-    extract::{Path, Query, State},
-    http::header,
+    extract::{Query, State},
+    http::{header, HeaderMap, HeaderValue, Uri},
     response::{IntoResponse, Response},
-    Json,
 };
 use serde::Deserialize;
-use sqlx::{mysql::MySqlRow, MySql, Row};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::Ordering;
+use sqlx::Row;
+use utoipa::IntoParams;
 
 use crate::config::AppState;
+use crate::db;
+use crate::handlers::jobs;
 use crate::models::country::Country;
+use crate::routes::paths;
+use crate::services::auto_refresh;
+use crate::services::bundle::{self, Compression};
+use crate::services::checksum;
+use crate::services::country_repository::{self, country_from_row, CountryQuery};
+use crate::services::flag_service::{fetch_flag, SPRITE_JSON, SPRITE_PNG};
+use crate::services::job_queue::JobKind;
+use crate::services::migration_service;
 use crate::services::refresh_service::{refresh_cache, RefreshResult};
-use crate::utils::error::ApiError;
+use crate::types::path::CountryName;
+use crate::types::query::{AutocompleteParams, Cursor, ListParams, SortOrder, ValidQuery};
+use crate::utils::auth::KeyRestriction;
+use crate::utils::case::{requested_case, KeyCase};
+use crate::utils::client_ip::ClientIp;
+use crate::utils::envelope;
+use crate::utils::error::{ApiError, POOL_TIMEOUTS};
+use crate::utils::explain;
+use crate::utils::telemetry;
+use crate::utils::i18n::Lang;
+use crate::utils::image::{
+    build_country_card, build_country_card_svg, build_summary_image, build_summary_png, build_summary_svg,
+    summary_data, DataStamp, RankMetric, Ranking, CARD_SIZE, SUMMARY_SIZE,
+};
+use crate::utils::image_cache::VariantKey;
+use crate::utils::jsonapi;
+use crate::utils::map::{build_map_png, MapMetric, MAP_SIZE};
+use crate::utils::server_timing::TimedJson;
+use crate::utils::single_flight::AggregateKey;
+use crate::{sql_for_update, sql_iso, sql_like_escape};
 
-#[derive(Deserialize)]
-pub struct ListParams {
-    pub region: Option<String>,
-    pub currency: Option<String>,
-    /// Allowed: gdp_desc | gdp_asc | name_asc | population_desc
-    pub sort: Option<String>,
-    pub page: Option<usize>,
-    pub limit: Option<usize>,
-}
-
-pub async fn refresh(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
-    let res: RefreshResult = refresh_cache(&state).await?;
-    Ok((axum::http::StatusCode::OK, Json(res)))
-}
-
-// --- Basic validation using ApiError::Validation(String) ---
-fn validate_list_params(p: &ListParams) -> Result<(), ApiError> {
-    if let Some(s) = p.sort.as_deref() {
-        let ok = matches!(s, "gdp_desc" | "gdp_asc" | "name_asc" | "population_desc");
-        if !ok {
-            return Err(ApiError::Validation(
-                "sort must be one of gdp_desc, gdp_asc, name_asc, population_desc".into(),
-            ));
-        }
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImageParams {
+    /// Allowed: png (default) | svg
+    pub format: Option<String>,
+    /// Allowed: en (default) | fr | de | es | pt (region subtags like fr-FR are ignored)
+    pub lang: Option<String>,
+    /// Summary only: length of the top list, 1-10 (default `SUMMARY_TOP_N`)
+    pub top: Option<usize>,
+    /// Summary only: gdp | population | exchange_rate (default `SUMMARY_RANK_BY`)
+    pub rank_by: Option<String>,
+}
+
+fn image_lang(p: &ImageParams) -> Result<Lang, ApiError> {
+    match p.lang.as_deref() {
+        None => Ok(Lang::En),
+        Some(s) => Lang::parse(s).ok_or_else(|| {
+            ApiError::Validation(format!("lang must be one of {}", Lang::ALLOWED))
+        }),
     }
-    if let Some(page) = p.page {
-        if page < 1 {
-            return Err(ApiError::Validation("page must be >= 1".into()));
-        }
+}
+
+/// `?top=` / `?rank_by=` over the configured ranking of the summary image.
+fn image_ranking(p: &ImageParams, default: Ranking) -> Result<Ranking, ApiError> {
+    let metric = match p.rank_by.as_deref() {
+        Some(s) => RankMetric::parse(s).map_err(ApiError::Validation)?,
+        None => default.metric,
+    };
+    Ranking::new(metric, p.top.unwrap_or(default.top)).map_err(ApiError::Validation)
+}
+
+fn wants_svg(p: &ImageParams) -> Result<bool, ApiError> {
+    match p.format.as_deref() {
+        None | Some("png") => Ok(false),
+        Some("svg") => Ok(true),
+        Some(_) => Err(ApiError::Validation("format must be one of png, svg".into())),
     }
-    if let Some(limit) = p.limit {
-        if !(1..=200).contains(&limit) {
-            return Err(ApiError::Validation("limit must be between 1 and 200".into()));
-        }
+}
+
+fn image_response(svg: bool, bytes: Vec<u8>) -> Result<Response, ApiError> {
+    let content_type = if svg { "image/svg+xml" } else { "image/png" };
+    Response::builder()
+        .status(axum::http::StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .body(axum::body::Body::from(bytes))
+        .map_err(|e| ApiError::Internal(format!("response build failed: {}", e)))
+}
+
+/// Data version used to key cached image variants: the last refresh timestamp.
+async fn data_version(state: &AppState) -> Result<String, ApiError> {
+    let ts: Option<(String,)> =
+        sqlx::query_as("SELECT v FROM app_meta WHERE k='last_refreshed_at'")
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(ApiError::db)?;
+    Ok(ts.map(|x| x.0).unwrap_or_else(|| "never".into()))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RefreshParams {
+    /// Skip the conditional requests and download both payloads
+    pub force: Option<bool>,
+    /// Queue the refresh as a job and answer `202` right away
+    #[serde(rename = "async")]
+    pub background: Option<bool>,
+}
+
+pub async fn refresh(
+    State(state): State<AppState>,
+    Query(p): Query<RefreshParams>,
+) -> Result<Response, ApiError> {
+    let force = p.force.unwrap_or(false);
+    if p.background.unwrap_or(false) {
+        return jobs::accepted(&state, &JobKind::Refresh { force }).await;
+    }
+    let res: RefreshResult = refresh_cache(&state, force).await?;
+    Ok((axum::http::StatusCode::OK, TimedJson(res)).into_response())
+}
+
+/// `/countries?...` for another page of the same listing, with `paging` (`page=` or
+/// `cursor=`) in place of the current position.
+fn list_link_to(p: &ListParams, paging: String) -> String {
+    let mut q = Vec::new();
+    if let Some(r) = &p.region {
+        q.push(format!("region={}", jsonapi::encode(r.as_str())));
     }
-    if let Some(curr) = p.currency.as_deref() {
-        if curr.len() != 3 {
-            return Err(ApiError::Validation(
-                "currency must be a 3-letter ISO code (e.g., NGN)".into(),
-            ));
+    if let Some(c) = &p.currency {
+        q.push(format!("currency={}", c.as_str()));
+    }
+    if let Some(t) = &p.tag {
+        q.push(format!("tag={}", t.as_str()));
+    }
+    if let Some(text) = &p.q {
+        q.push(format!("q={}", jsonapi::encode(text)));
+    }
+    if p.sort != SortOrder::Id {
+        q.push(format!("sort={}", p.sort.as_str()));
+    }
+    if let Some(l) = p.locale {
+        q.push(format!("locale={}", l.as_str()));
+    }
+    q.push(paging);
+    q.push(format!("limit={}", p.limit));
+    format!("{}?{}", paths::COUNTRIES, q.join("&"))
+}
+
+fn list_link(p: &ListParams, page: usize) -> String {
+    list_link_to(p, format!("page={}", page))
+}
+
+fn cursor_link(p: &ListParams, cursor: &str) -> String {
+    list_link_to(p, format!("cursor={}", cursor))
+}
+
+/// RFC 8288 `Link` header value for page `p.page` of `last_page`: `first` and `last`
+/// always, `prev` / `next` where they exist. Keyset pages only link `first` and `next`.
+pub(crate) fn list_link_header(p: &ListParams, last_page: usize, next_cursor: Option<&str>) -> String {
+    let mut links = vec![format!("<{}>; rel=\"first\"", list_link(p, 1))];
+    if p.cursor.is_some() {
+        if let Some(cursor) = next_cursor {
+            links.push(format!("<{}>; rel=\"next\"", cursor_link(p, cursor)));
         }
+        return links.join(", ");
+    }
+    if p.page > 1 {
+        links.push(format!("<{}>; rel=\"prev\"", list_link(p, (p.page - 1).min(last_page))));
     }
-    Ok(())
+    if p.page < last_page {
+        links.push(format!("<{}>; rel=\"next\"", list_link(p, p.page + 1)));
+    }
+    links.push(format!("<{}>; rel=\"last\"", list_link(p, last_page)));
+    links.join(", ")
+}
+
+/// Plain JSON `GET /countries` body: one page of `data` with the paging totals. `page` is
+/// null on keyset pages; `next_cursor` is null on the last page.
+pub(crate) fn list_page(
+    data: Vec<serde_json::Value>,
+    p: &ListParams,
+    total: i64,
+    last_page: usize,
+    next_cursor: Option<String>,
+) -> serde_json::Value {
+    serde_json::json!({
+        "data": data,
+        "page": p.cursor.is_none().then_some(p.page),
+        "limit": p.limit,
+        "total": total,
+        "total_pages": last_page,
+        "next_cursor": next_cursor,
+    })
 }
 
+#[tracing::instrument(
+    name = "countries.list",
+    skip_all,
+    fields(
+        filter.region = tracing::field::Empty,
+        filter.currency = tracing::field::Empty,
+        sort = p.sort.as_str(),
+        page = p.page,
+        limit = p.limit,
+        result.count = tracing::field::Empty,
+    )
+)]
 pub async fn list_countries(
     State(state): State<AppState>,
-    Query(p): Query<ListParams>,
+    headers: HeaderMap,
+    restriction: KeyRestriction,
+    ValidQuery(p): ValidQuery<ListParams>,
+) -> Result<Response, ApiError> {
+    if let Some(r) = &p.region {
+        telemetry::record("filter.region", telemetry::hash_attr(r.as_str()).as_str());
+    }
+    if let Some(c) = &p.currency {
+        telemetry::record("filter.currency", telemetry::hash_attr(c.as_str()).as_str());
+    }
+    auto_refresh::maybe_refresh(&state).await;
+
+    // One row past the page tells whether there is a next one
+    let query = CountryQuery { restriction: restriction.0, limit: p.limit + 1, ..CountryQuery::from_params(&p) };
+    if state.runtime.load().explain_queries {
+        explain::warn_on_full_scan(&state.pool, query.select(db::EXPLAIN)).await;
+    }
+
+    let mut out: Vec<Country> = country_repository::list(&state.pool, &query)
+        .await
+        .map_err(ApiError::db)?;
+    let next_cursor = if out.len() > p.limit {
+        out.truncate(p.limit);
+        out.last().map(|c| Cursor::after(c, p.sort).encode(p.sort, p.locale))
+    } else {
+        None
+    };
+    telemetry::record("result.count", out.len());
+
+    let total = country_repository::count(&state.pool, &query)
+        .await
+        .map_err(ApiError::db)?;
+    let last_page = (total as usize).div_ceil(p.limit).max(1);
+
+    if !jsonapi::wants_jsonapi(&headers) {
+        let data: Vec<serde_json::Value> = out.iter().map(|c| country_body(&state, c)).collect();
+        let body = if state.runtime.load().list_bare_array {
+            serde_json::Value::Array(data)
+        } else {
+            list_page(data, &p, total, last_page, next_cursor.clone())
+        };
+        let mut res = TimedJson(body).into_response();
+        if let Ok(v) = HeaderValue::from_str(&list_link_header(&p, last_page, next_cursor.as_deref())) {
+            res.headers_mut().insert(header::LINK, v);
+        }
+        return Ok(res);
+    }
+
+    let data: Vec<serde_json::Value> = out.iter().map(jsonapi::country_resource).collect();
+    if p.cursor.is_some() {
+        return Ok(jsonapi::document(serde_json::json!({
+            "data": data,
+            "meta": { "total": total, "limit": p.limit, "pages": last_page, "next_cursor": next_cursor },
+            "links": {
+                "first": list_link(&p, 1),
+                "next": next_cursor.as_deref().map(|c| cursor_link(&p, c)),
+            },
+        })));
+    }
+    Ok(jsonapi::document(serde_json::json!({
+        "data": data,
+        "meta": {
+            "total": total,
+            "page": p.page,
+            "limit": p.limit,
+            "pages": last_page,
+            "next_cursor": next_cursor,
+        },
+        "links": {
+            "self": list_link(&p, p.page),
+            "first": list_link(&p, 1),
+            "last": list_link(&p, last_page),
+            "prev": (p.page > 1).then(|| list_link(&p, p.page - 1)),
+            "next": (p.page < last_page).then(|| list_link(&p, p.page + 1)),
+        },
+    })))
+}
+
+/// `_links` for a country; omitted relations (neighbors, rates) have no backing data yet.
+pub(crate) fn country_links(c: &Country) -> serde_json::Value {
+    let name = [("name", c.name.as_str())];
+    let mut links = serde_json::json!({
+        "self": { "href": paths::link(paths::COUNTRY, &name) },
+        "image": { "href": paths::link(paths::COUNTRY_IMAGE, &name) },
+        "history": { "href": paths::link(paths::COUNTRY_DIFF, &name) },
+    });
+    if let Some(flag) = &c.flag_url {
+        links["flag"] = serde_json::json!({ "href": flag });
+    }
+    if let Some(region) = &c.region {
+        links["region"] = serde_json::json!({
+            "href": format!("{}?region={}", paths::COUNTRIES, jsonapi::encode(region)),
+        });
+    }
+    links
+}
+
+/// Plain JSON body for a country, with `_links` when `HATEOAS_LINKS` is on.
+fn country_body(state: &AppState, c: &Country) -> serde_json::Value {
+    let mut v = serde_json::to_value(c).unwrap_or_default();
+    if state.runtime.load().hateoas_links {
+        v["_links"] = country_links(c);
+    }
+    v
+}
+
+fn like_prefix(q: &str) -> String {
+    format!("{}%", country_repository::escape_like(q))
+}
+
+/// Countries without an exchange rate after the last refresh, with the reason the
+/// refresh recorded, plus a count per reason for triage.
+pub async fn missing_rates(
+    State(state): State<AppState>,
+    restriction: KeyRestriction,
 ) -> Result<impl IntoResponse, ApiError> {
-    // Validate query params → 400 if invalid
-    validate_list_params(&p)?;
-
-    // Build query dynamically with safe bindings
-    let mut qb = sqlx::QueryBuilder::<MySql>::new(
-        "SELECT id,name,capital,region,population,currency_code,exchange_rate,estimated_gdp,flag_url,\
-         DATE_FORMAT(last_refreshed_at, '%Y-%m-%dT%H:%i:%sZ') as last_refreshed_at \
-         FROM countries WHERE 1=1",
+    let mut qb = sqlx::QueryBuilder::new(concat!(
+        "SELECT name, currency_code, rate_missing_reason, ",
+        sql_iso!("last_refreshed_at"), " as last_refreshed_at ",
+        "FROM countries WHERE exchange_rate IS NULL"
+    ));
+    if let Some(r) = restriction.get() {
+        r.push_sql(&mut qb);
+    }
+    qb.push(" ORDER BY name ASC");
+    let rows = qb.build().fetch_all(&state.pool).await.map_err(ApiError::db)?;
+
+    let mut by_reason = std::collections::BTreeMap::<String, u64>::new();
+    let countries: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            // Rows written before reasons were recorded
+            let reason = r
+                .try_get::<Option<String>, _>("rate_missing_reason")
+                .ok()
+                .flatten()
+                .unwrap_or_else(|| "unknown".into());
+            *by_reason.entry(reason.clone()).or_default() += 1;
+            serde_json::json!({
+                "name": r.try_get::<String, _>("name").unwrap_or_default(),
+                "currency_code": r.try_get::<Option<String>, _>("currency_code").ok().flatten(),
+                "reason": reason,
+                "last_refreshed_at": r.try_get::<Option<String>, _>("last_refreshed_at").ok().flatten(),
+            })
+        })
+        .collect();
+
+    Ok(TimedJson(serde_json::json!({
+        "total": countries.len(),
+        "by_reason": by_reason,
+        "countries": countries,
+    })))
+}
+
+/// `If-None-Match` lists `etag` (or `*`).
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|t| t.trim() == etag || t.trim() == "*"))
+}
+
+/// Everything besides the row that shapes a country body: JSON:API or plain JSON, the
+/// envelope, key casing and `_links`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Representation {
+    jsonapi: bool,
+    envelope: bool,
+    case: KeyCase,
+    links: bool,
+}
+
+/// Weak ETag of one country: a hash of the stored row (including `last_refreshed_at`, so
+/// every refresh that touches the row changes it) and of the representation asked for.
+fn country_etag(c: &Country, repr: Representation) -> String {
+    let mut h = Sha256::new();
+    h.update(serde_json::to_vec(c).unwrap_or_default());
+    h.update(format!("{repr:?}"));
+    format!("W/\"{}\"", hex::encode(&h.finalize()[..16]))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BundleParams {
+    /// gzip (default) | zstd
+    pub compression: Option<String>,
+}
+
+/// The latest refresh as one archive: countries.json, rates.json, stats.json, the summary
+/// image and a manifest with checksums. The DB reads share one snapshot, so the files
+/// never mix two refreshes. A key limited to some countries gets only those, and no
+/// summary image (it covers every country).
+pub async fn get_bundle(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    restriction: KeyRestriction,
+    Query(p): Query<BundleParams>,
+) -> Result<Response, ApiError> {
+    let compression = p
+        .compression
+        .as_deref()
+        .map(Compression::parse)
+        .transpose()
+        .map_err(ApiError::Validation)?
+        .unwrap_or_default();
+
+    let mut tx = db::begin(&state.pool).await.map_err(ApiError::db)?;
+    let ts: Option<(String,)> = sqlx::query_as("SELECT v FROM app_meta WHERE k='last_refreshed_at'")
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(ApiError::db)?;
+    let version = ts.map(|x| x.0);
+    // Each restriction is its own variant of the bundle
+    let variant = restriction
+        .get()
+        .map(|r| format!("-{}", hex::encode(&Sha256::digest(format!("{:?}", r))[..8])))
+        .unwrap_or_default();
+    let etag = format!(
+        "\"bundle-{}{}.{}\"",
+        version.as_deref().unwrap_or("never").replace(|c: char| !c.is_ascii_alphanumeric(), ""),
+        variant,
+        compression.extension()
     );
+    if etag_matches(&headers, &etag) {
+        return Ok((axum::http::StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+    let countries = country_repository::all(&mut *tx, restriction.get()).await.map_err(ApiError::db)?;
+    let completeness = country_repository::completeness(&mut *tx, restriction.get())
+        .await
+        .map_err(ApiError::db)?;
+    tx.commit().await.map_err(ApiError::db)?;
 
-    if let Some(r) = p.region.as_deref() {
-        qb.push(" AND region = ").push_bind(r);
+    let mut rates = std::collections::BTreeMap::new();
+    let mut regions = std::collections::BTreeMap::<&str, u64>::new();
+    for c in &countries {
+        if let (Some(code), Some(rate)) = (&c.currency_code, c.exchange_rate) {
+            rates.insert(code.as_str(), serde_json::json!({ "rate": rate, "source": c.rate_source }));
+        }
+        *regions.entry(c.region.as_deref().unwrap_or(checksum::NO_REGION)).or_default() += 1;
     }
-    if let Some(c) = p.currency.as_deref() {
-        qb.push(" AND currency_code = ").push_bind(c);
+    let stats = serde_json::json!({
+        "last_refreshed_at": version,
+        "total_countries": countries.len(),
+        "regions": regions,
+        "completeness": completeness,
+        "checksum": checksum::compute(&countries).sha256,
+    });
+
+    let json = |v: &serde_json::Value| serde_json::to_vec_pretty(v).unwrap_or_default();
+    let mut files = vec![
+        ("countries.json", serde_json::to_vec_pretty(&countries).unwrap_or_default()),
+        ("rates.json", json(&serde_json::json!({ "last_refreshed_at": version, "rates": rates }))),
+        ("stats.json", json(&stats)),
+    ];
+    // Rendered in the background after a refresh; a bundle without it is still useful
+    if restriction.get().is_none() {
+        if let Ok(png) = tokio::fs::read(&state.summary_image_path).await {
+            files.push(("summary.png", png));
+        }
     }
+    let manifest = serde_json::json!({
+        "last_refreshed_at": version,
+        "files": files.iter().map(|(n, b)| bundle::manifest_entry(n, b)).collect::<Vec<_>>(),
+    });
+    files.push(("manifest.json", json(&manifest)));
 
-    let order_clause = match p.sort.as_deref() {
-        Some("gdp_desc")        => " ORDER BY estimated_gdp DESC",
-        Some("gdp_asc")         => " ORDER BY estimated_gdp ASC",
-        Some("name_asc")        => " ORDER BY name ASC",
-        Some("population_desc") => " ORDER BY population DESC",
-        _                       => " ORDER BY id ASC",
-    };
-    qb.push(order_clause);
+    let mtime = version
+        .as_deref()
+        .and_then(|v| chrono::DateTime::parse_from_rfc3339(v).ok())
+        .map(|t| t.timestamp().max(0) as u64)
+        .unwrap_or(0);
+    let archive = tokio::task::spawn_blocking(move || bundle::build(&files, mtime, compression))
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?
+        .map_err(|e| ApiError::Internal(format!("could not build bundle: {}", e)))?;
 
-    let page = p.page.unwrap_or(1).max(1);
-    let limit = p.limit.unwrap_or(50).clamp(1, 200);
-    let offset = (page - 1) * limit;
+    Ok((
+        [
+            (header::CONTENT_TYPE, compression.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"countries-bundle.{}\"", compression.extension()),
+            ),
+            (header::ETAG, etag),
+        ],
+        archive,
+    )
+        .into_response())
+}
 
-    qb.push(" LIMIT ").push_bind(limit as i64);
-    qb.push(" OFFSET ").push_bind(offset as i64);
+/// Deterministic hash of the whole dataset plus one per region, so a mirror can check
+/// it's in sync before pulling everything. The hash doubles as a strong ETag.
+pub async fn dataset_checksum(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    restriction: KeyRestriction,
+) -> Result<Response, ApiError> {
+    let countries = country_repository::all(&state.pool, restriction.get()).await.map_err(ApiError::db)?;
+    let sum = checksum::compute(&countries);
+    let etag = format!("\"{}\"", sum.sha256);
+    if etag_matches(&headers, &etag) {
+        return Ok((axum::http::StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+    Ok(([(header::ETAG, etag.clone())], TimedJson(sum)).into_response())
+}
 
-    let rows: Vec<MySqlRow> = qb
-        .build()
-        .fetch_all(&state.pool)
-        .await
-        .map_err(|e| ApiError::Internal(e.to_string()))?;
+/// Prefix search over names and aliases, meant to be called on every keystroke:
+/// both `LIKE 'prefix%'` scans are served by the unique indexes on
+/// `countries.name` / `country_aliases.alias` (case-insensitive collation).
+/// Each limited branch is a derived table: SQLite rejects parenthesized `UNION` operands.
+pub async fn autocomplete(
+    State(state): State<AppState>,
+    restriction: KeyRestriction,
+    ValidQuery(p): ValidQuery<AutocompleteParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let pattern = like_prefix(&p.q);
+    let rows = sqlx::query(concat!(
+        "SELECT * FROM (SELECT name, iso_code, flag_url FROM countries WHERE name LIKE ?",
+        sql_like_escape!(),
+        " ORDER BY name ASC LIMIT ?) n \
+         UNION ALL \
+         SELECT * FROM (SELECT c.name, c.iso_code, c.flag_url FROM country_aliases a \
+          JOIN countries c ON c.name = a.country_name \
+          WHERE a.alias LIKE ?",
+        sql_like_escape!(),
+        " ORDER BY a.alias ASC LIMIT ?) a"
+    ))
+    .bind(&pattern)
+    .bind(p.limit as i64)
+    .bind(&pattern)
+    .bind(p.limit as i64)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+    let visible = match restriction.get() {
+        Some(r) => Some(country_repository::visible_names(&state.pool, r).await.map_err(ApiError::db)?),
+        None => None,
+    };
 
-    let out: Vec<Country> = rows
-        .into_iter()
-        .map(|r| Country {
-            id: r.try_get::<i64, _>("id").unwrap_or_default(),
-            name: r.try_get::<String, _>("name").unwrap_or_default(),
-            capital: r.try_get::<Option<String>, _>("capital").ok().flatten(),
-            region: r.try_get::<Option<String>, _>("region").ok().flatten(),
-            population: r.try_get::<i64, _>("population").unwrap_or_default(),
-            currency_code: r.try_get::<Option<String>, _>("currency_code").ok().flatten(),
-            exchange_rate: r.try_get::<Option<f64>, _>("exchange_rate").ok().flatten(),
-            estimated_gdp: r.try_get::<Option<f64>, _>("estimated_gdp").ok().flatten(),
-            flag_url: r.try_get::<Option<String>, _>("flag_url").ok().flatten(),
-            last_refreshed_at: r
-                .try_get::<Option<String>, _>("last_refreshed_at")
-                .ok()
-                .flatten(),
+    // Direct name matches come first; an alias hit for a country already listed is dropped
+    let mut seen = std::collections::HashSet::new();
+    let out: Vec<serde_json::Value> = rows
+        .iter()
+        .filter_map(|r| {
+            let name: String = r.try_get("name").unwrap_or_default();
+            if visible.as_ref().is_some_and(|v| !v.contains(&name)) {
+                return None;
+            }
+            seen.insert(name.to_lowercase()).then(|| {
+                serde_json::json!({
+                    "name": name,
+                    "iso": r.try_get::<Option<String>, _>("iso_code").ok().flatten(),
+                    "flag": r.try_get::<Option<String>, _>("flag_url").ok().flatten(),
+                })
+            })
         })
+        .take(p.limit)
         .collect();
 
-    Ok((axum::http::StatusCode::OK, Json(out)))
+    // A limited key's suggestions must not be served to anyone else from a shared cache
+    let cache = if visible.is_some() { "private, max-age=60" } else { "public, max-age=60" };
+    Ok(([(header::CACHE_CONTROL, cache)], TimedJson(out)))
 }
 
+#[tracing::instrument(
+    name = "countries.get",
+    skip_all,
+    fields(country = telemetry::hash_attr(name.as_str()), result.found = tracing::field::Empty)
+)]
 pub async fn get_country(
     State(state): State<AppState>,
-    Path(name): Path<String>,
-) -> Result<impl IntoResponse, ApiError> {
-    let row = sqlx::query(
-        "SELECT id,name,capital,region,population,currency_code,exchange_rate,estimated_gdp,flag_url,\
-         DATE_FORMAT(last_refreshed_at, '%Y-%m-%dT%H:%i:%sZ') as last_refreshed_at \
-         FROM countries WHERE LOWER(name)=LOWER(?) LIMIT 1",
-    )
+    headers: HeaderMap,
+    uri: Uri,
+    restriction: KeyRestriction,
+    name: CountryName,
+) -> Result<Response, ApiError> {
+    auto_refresh::maybe_refresh(&state).await;
+    let name = name.as_str();
+
+    let sql = format!(
+        "SELECT {} FROM countries WHERE LOWER(name)=LOWER(?) \
+         OR name = (SELECT country_name FROM country_aliases WHERE LOWER(alias)=LOWER(?)) \
+         ORDER BY LOWER(name)=LOWER(?) DESC LIMIT 1",
+        country_repository::LIST_COLUMNS
+    );
+    let row = sqlx::query(&sql)
+    .bind(name)
+    .bind(name)
     .bind(name)
     .fetch_optional(&state.pool)
     .await
-    .map_err(|e| ApiError::Internal(e.to_string()))?;
+    .map_err(ApiError::db)?;
 
-    let Some(r) = row else {
+    let mut c = row.as_ref().map(country_from_row);
+    if let Some(found) = &c {
+        // Possibly found by alias: the restriction is checked against the stored name
+        if !country_repository::visible(&state.pool, restriction.get(), &found.name)
+            .await
+            .map_err(ApiError::db)?
+        {
+            c = None;
+        }
+    }
+    telemetry::record("result.found", c.is_some());
+    let Some(c) = c else {
         return Err(ApiError::NotFound("Country not found".into()));
     };
 
-    let c = Country {
-        id: r.try_get::<i64, _>("id").unwrap_or_default(),
-        name: r.try_get::<String, _>("name").unwrap_or_default(),
-        capital: r.try_get::<Option<String>, _>("capital").ok().flatten(),
-        region: r.try_get::<Option<String>, _>("region").ok().flatten(),
-        population: r.try_get::<i64, _>("population").unwrap_or_default(),
-        currency_code: r.try_get::<Option<String>, _>("currency_code").ok().flatten(),
-        exchange_rate: r.try_get::<Option<f64>, _>("exchange_rate").ok().flatten(),
-        estimated_gdp: r.try_get::<Option<f64>, _>("estimated_gdp").ok().flatten(),
-        flag_url: r.try_get::<Option<String>, _>("flag_url").ok().flatten(),
-        last_refreshed_at: r
-            .try_get::<Option<String>, _>("last_refreshed_at")
-            .ok()
-            .flatten(),
+    let wants_jsonapi = jsonapi::wants_jsonapi(&headers);
+    let repr = Representation {
+        jsonapi: wants_jsonapi,
+        envelope: envelope::enabled(&state, &headers)?,
+        case: requested_case(&state, &uri)?,
+        links: state.runtime.load().hateoas_links,
     };
+    let etag = country_etag(&c, repr);
+    if etag_matches(&headers, &etag) {
+        return Ok((
+            axum::http::StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::VARY, "Accept".into())],
+        )
+            .into_response());
+    }
 
-    Ok((axum::http::StatusCode::OK, Json(c)))
+    let mut res = if wants_jsonapi {
+        jsonapi::document(serde_json::json!({ "data": jsonapi::country_resource(&c) }))
+    } else {
+        (axum::http::StatusCode::OK, TimedJson(country_body(&state, &c))).into_response()
+    };
+    if let Ok(v) = HeaderValue::from_str(&etag) {
+        res.headers_mut().insert(header::ETAG, v);
+    }
+    res.headers_mut().insert(header::VARY, HeaderValue::from_static("Accept"));
+    Ok(res)
 }
 
-pub async fn delete_country(
+/// Reverse lookup: the country (or countries: there are several Kingstons) whose capital
+/// is `:name`, falling back to `capital_aliases` for countries with more than one capital.
+pub async fn get_capital(
     State(state): State<AppState>,
-    Path(name): Path<String>,
+    restriction: KeyRestriction,
+    name: CountryName,
 ) -> Result<impl IntoResponse, ApiError> {
-    let res = sqlx::query("DELETE FROM countries WHERE LOWER(name)=LOWER(?)")
+    let name = name.as_str();
+    let sql = format!(
+        "SELECT {}, 'capital' as matched_by FROM countries WHERE LOWER(capital)=LOWER(?) \
+         UNION ALL \
+         SELECT {}, 'alias' as matched_by FROM countries \
+         WHERE name IN (SELECT country_name FROM capital_aliases WHERE LOWER(capital)=LOWER(?)) \
+         AND NOT LOWER(COALESCE(capital, ''))=LOWER(?) \
+         ORDER BY name ASC",
+        country_repository::LIST_COLUMNS,
+        country_repository::LIST_COLUMNS,
+    );
+    let rows = sqlx::query(&sql)
         .bind(name)
-        .execute(&state.pool)
+        .bind(name)
+        .bind(name)
+        .fetch_all(&state.pool)
         .await
-        .map_err(|e| ApiError::Internal(e.to_string()))?;
+        .map_err(ApiError::db)?;
+    let rows = match restriction.get() {
+        Some(r) => {
+            let visible = country_repository::visible_names(&state.pool, r).await.map_err(ApiError::db)?;
+            rows.into_iter()
+                .filter(|row| visible.contains(&row.try_get::<String, _>("name").unwrap_or_default()))
+                .collect()
+        }
+        None => rows,
+    };
+    if rows.is_empty() {
+        return Err(ApiError::NotFound("Capital not found".into()));
+    }
+
+    let countries: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            let mut v = country_body(&state, &country_from_row(r));
+            v["matched_by"] = r.try_get::<String, _>("matched_by").unwrap_or_default().into();
+            v
+        })
+        .collect();
+    Ok(TimedJson(serde_json::json!({ "capital": name, "countries": countries })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteParams {
+    /// Must repeat the country name (case-insensitive) unless `X-Confirm-Delete` does
+    pub confirm: Option<String>,
+}
+
+/// Deletes a country and records the row in `audit_log` in the same transaction.
+pub async fn delete_country(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    ClientIp(client_ip): ClientIp,
+    name: CountryName,
+    Query(p): Query<DeleteParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let name = name.as_str();
+    if state.runtime.load().delete_require_confirm {
+        let header = headers.get("x-confirm-delete").and_then(|v| v.to_str().ok());
+        let confirmed = [p.confirm.as_deref(), header]
+            .into_iter()
+            .flatten()
+            .any(|c| c.trim().to_lowercase() == name.to_lowercase());
+        if !confirmed {
+            return Err(ApiError::Validation(format!(
+                "deleting a country needs confirmation: repeat its name in ?confirm={} or the X-Confirm-Delete header",
+                jsonapi::encode(name)
+            )));
+        }
+    }
+
+    let mut tx = db::begin(&state.pool).await.map_err(ApiError::db)?;
+    let snap = sqlx::query(concat!(
+        "INSERT INTO audit_log (action, subject, client_ip, snapshot) \
+         SELECT 'country.deleted', name, ?, JSON_OBJECT(\
+           'id', id, 'name', name, 'iso_code', iso_code, 'capital', capital, 'region', region, \
+           'population', population, 'currency_code', currency_code, 'exchange_rate', exchange_rate, \
+           'estimated_gdp', estimated_gdp, 'flag_url', flag_url, \
+           'last_refreshed_at', ",
+        sql_iso!("last_refreshed_at"),
+        ") FROM countries WHERE LOWER(name)=LOWER(?)",
+        sql_for_update!()
+    ))
+    .bind(client_ip.map(|ip| ip.to_string()))
+    .bind(name)
+    .execute(&mut *tx)
+    .await
+    .map_err(ApiError::db)?;
 
-    if res.rows_affected() == 0 {
+    if snap.rows_affected() == 0 {
         return Err(ApiError::NotFound("Country not found".into()));
     }
 
-    Ok((axum::http::StatusCode::OK, Json(serde_json::json!({ "ok": true }))))
+    sqlx::query("DELETE FROM countries WHERE LOWER(name)=LOWER(?)")
+        .bind(name)
+        .execute(&mut *tx)
+        .await
+        .map_err(ApiError::db)?;
+    tx.commit().await.map_err(ApiError::db)?;
+
+    Ok((axum::http::StatusCode::OK, TimedJson(serde_json::json!({ "ok": true }))))
 }
 
 pub async fn status(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+    let image = state.image_health.snapshot();
+    let db = state.db_health.snapshot();
+    let db_state = state.db_health.state();
+
+    // During an outage report what's known without queueing behind the pool
+    if db_state == "degraded" {
+        return Ok((
+            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            TimedJson(serde_json::json!({
+                "status": "degraded",
+                "db": { "state": db_state, "health": db },
+                "summary_image": {
+                    "ok": !image.is_stale(),
+                    "last_rendered_at": image.last_success_at,
+                    "last_error": image.last_error,
+                    "last_error_at": image.last_error_at,
+                }
+            })),
+        ));
+    }
+
     let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM countries")
         .fetch_one(&state.pool)
         .await
-        .map_err(|e| ApiError::Internal(e.to_string()))?;
+        .map_err(ApiError::db)?;
 
     let ts: Option<(String,)> =
         sqlx::query_as("SELECT v FROM app_meta WHERE k='last_refreshed_at'")
             .fetch_optional(&state.pool)
             .await
-            .map_err(|e| ApiError::Internal(e.to_string()))?;
+            .map_err(ApiError::db)?;
+
+    let migrations = migration_service::status(&state.pool)
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?;
+
+    let completeness = country_repository::completeness(&state.pool, None)
+        .await
+        .map_err(ApiError::db)?;
 
     Ok((
         axum::http::StatusCode::OK,
-        Json(serde_json::json!({
+        TimedJson(serde_json::json!({
+            "status": "ok",
             "total_countries": count.0,
-            "last_refreshed_at": ts.map(|x| x.0)
+            "db": { "state": db_state, "health": db },
+            "db_pool": {
+                "size": state.pool.size(),
+                "idle": state.pool.num_idle(),
+                "timeouts_total": POOL_TIMEOUTS.load(Ordering::Relaxed),
+            },
+            "last_refreshed_at": ts.map(|x| x.0),
+            "completeness": completeness,
+            "migrations": {
+                "in_sync": migrations.in_sync(),
+                "applied": migrations.applied,
+                "pending": migrations.pending,
+                "unknown": migrations.unknown,
+                "dirty": migrations.dirty,
+                "embedded_latest": migrations.embedded_latest,
+                "db_latest": migrations.db_latest,
+            },
+            "summary_image": {
+                "ok": !image.is_stale(),
+                "last_rendered_at": image.last_success_at,
+                "last_error": image.last_error,
+                "last_error_at": image.last_error_at,
+            }
         })),
     ))
 }
 
-pub async fn get_image(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+/// `Accept: application/json` (and no image type): the summary as data instead of pixels.
+fn wants_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("application/json") && !v.contains("image/"))
+}
+
+pub async fn get_image(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(p): Query<ImageParams>,
+) -> Result<Response, ApiError> {
+    let mut res = summary_image(state, &headers, p).await?;
+    res.headers_mut().append(header::VARY, HeaderValue::from_static("Accept"));
+    Ok(res)
+}
+
+/// The summary image's data, stamped with the refresh it describes
+async fn summary_json(state: &AppState, ranking: Ranking) -> Result<serde_json::Value, ApiError> {
+    let data = summary_data(&state.pool, ranking)
+        .await
+        .map_err(|e| ApiError::Internal(format!("summary query failed: {}", e)))?;
+    let version = data_version(state).await?;
+    let mut body =
+        serde_json::to_value(&data).map_err(|e| ApiError::Internal(format!("summary encode failed: {}", e)))?;
+    body["last_refreshed_at"] = if version == "never" { serde_json::Value::Null } else { version.into() };
+    Ok(body)
+}
+
+async fn summary_image(state: AppState, headers: &HeaderMap, p: ImageParams) -> Result<Response, ApiError> {
+    let lang = image_lang(&p)?;
+    let svg = wants_svg(&p)?;
+    let ranking = image_ranking(&p, state.summary_ranking)?;
+
+    if wants_json(headers) {
+        let body = state.aggregates.run(AggregateKey::Summary { ranking }, || summary_json(&state, ranking)).await?;
+        return Ok(TimedJson(body).into_response());
+    }
+
+    // Variants other than the saved English PNG are rendered on demand and cached per refresh
+    if svg || lang != Lang::En || ranking != state.summary_ranking {
+        let version = data_version(&state).await?;
+        let subject = ranking.subject();
+        let key = VariantKey {
+            version: &version,
+            subject: &subject,
+            width: SUMMARY_SIZE.0,
+            height: SUMMARY_SIZE.1,
+            lang,
+            format: if svg { "svg" } else { "png" },
+        };
+        let bytes = state
+            .image_cache
+            .get_or_render(&key, || async {
+                if svg {
+                    build_summary_svg(&state.pool, lang, &state.branding, ranking).await.map(String::into_bytes)
+                } else {
+                    build_summary_png(&state.pool, lang, &state.branding, ranking).await
+                }
+                .map_err(|e| ApiError::Internal(format!("could not render image: {}", e)))
+            })
+            .await?;
+        return image_response(svg, bytes);
+    }
+
     let path = &state.summary_image_path;
     if !path.exists() {
-        return Err(ApiError::NotFound("Summary image not found".into()));
+        if !state.runtime.load().image_render_on_missing {
+            return Err(ApiError::NotFound("Summary image not found".into()));
+        }
+        // Fresh deployment (or a deleted file): render it now, once for concurrent callers
+        let version = data_version(&state).await?;
+        let subject = ranking.subject();
+        let key = VariantKey {
+            version: &version,
+            subject: &subject,
+            width: SUMMARY_SIZE.0,
+            height: SUMMARY_SIZE.1,
+            lang: Lang::En,
+            format: "png",
+        };
+        let bytes = state
+            .image_cache
+            .get_or_render(&key, || async {
+                let res = build_summary_image(&state.pool, path, &state.branding, ranking).await;
+                state.image_health.record(&res);
+                res.map_err(|e| ApiError::Internal(format!("could not render image: {}", e)))?;
+                tokio::fs::read(path)
+                    .await
+                    .map_err(|e| ApiError::Internal(format!("could not read image: {}", e)))
+            })
+            .await?;
+        return image_response(false, bytes);
     }
 
     let bytes = tokio::fs::read(path)
         .await
         .map_err(|e| ApiError::Internal(format!("could not read image: {}", e)))?;
 
-    let resp = Response::builder()
-        .status(axum::http::StatusCode::OK)
-        .header(header::CONTENT_TYPE, "image/png")
-        .body(axum::body::Body::from(bytes))
-        .map_err(|e| ApiError::Internal(format!("response build failed: {}", e)))?;
+    let mut resp = image_response(false, bytes)?;
+
+    // The last render failed: still serve the previous image, but say so
+    let health = state.image_health.snapshot();
+    if health.is_stale() {
+        let warning = format!(
+            "110 - \"summary image is stale; last render failed at {}\"",
+            health.last_error_at.map(|t| t.to_rfc3339()).unwrap_or_else(|| "unknown".into())
+        );
+        if let Ok(v) = header::HeaderValue::from_str(&warning) {
+            resp.headers_mut().insert(header::WARNING, v);
+        }
+    }
 
     Ok(resp)
 }
 
-// --- Health endpoint: verifies DB connectivity on demand ---
-pub async fn health(State(state): State<AppState>) -> impl IntoResponse {
-    match sqlx::query_scalar::<_, i32>("SELECT 1").fetch_one(&state.pool).await {
-        Ok(_) => (axum::http::StatusCode::OK, Json(serde_json::json!({ "ok": true }))),
-        Err(e) => (
-            axum::http::StatusCode::SERVICE_UNAVAILABLE,
-            Json(serde_json::json!({ "ok": false, "db": e.to_string() })),
-        ),
+/// `GET /countries/image/meta`: the refresh run and timestamp embedded in the summary
+/// image, next to those of the data now. `lagging` means the image shows older data,
+/// e.g. because the render after the last refresh failed.
+pub async fn get_image_meta(State(state): State<AppState>) -> Result<TimedJson<serde_json::Value>, ApiError> {
+    let bytes = match tokio::fs::read(&state.summary_image_path).await {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(ApiError::NotFound("Summary image not found".into()))
+        }
+        Err(e) => return Err(ApiError::Internal(format!("could not read image: {}", e))),
+    };
+    // Files rendered before the stamp was embedded have none
+    let image = DataStamp::from_png(&bytes);
+    let data = DataStamp::current(&state.pool)
+        .await
+        .map_err(|e| ApiError::Internal(format!("data stamp query failed: {}", e)))?;
+    Ok(TimedJson(serde_json::json!({
+        "refresh_run_id": image.as_ref().and_then(|s| s.refresh_run_id),
+        "last_refreshed_at": image.as_ref().and_then(|s| s.last_refreshed_at.clone()),
+        "data": data,
+        "lagging": image.as_ref() != Some(&data),
+        "render": state.image_health.snapshot(),
+    })))
+}
+
+pub async fn get_country_image(
+    State(state): State<AppState>,
+    restriction: KeyRestriction,
+    name: CountryName,
+    Query(p): Query<ImageParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let name = name.as_str();
+    let svg = wants_svg(&p)?;
+    let lang = image_lang(&p)?;
+    // Checked before the cache, which holds cards rendered for every caller
+    if !country_repository::visible(&state.pool, restriction.get(), name).await.map_err(ApiError::db)? {
+        return Err(ApiError::NotFound("Country not found".into()));
+    }
+
+    let version = data_version(&state).await?;
+    let key = VariantKey {
+        version: &version,
+        subject: name,
+        width: CARD_SIZE.0,
+        height: CARD_SIZE.1,
+        lang,
+        format: if svg { "svg" } else { "png" },
+    };
+    let bytes = state
+        .image_cache
+        .get_or_render(&key, || render_country_card(&state, name, lang, svg))
+        .await?;
+    image_response(svg, bytes)
+}
+
+/// Renders a country card from the current data (cache miss path of `get_country_image`).
+async fn render_country_card(
+    state: &AppState,
+    name: &str,
+    lang: Lang,
+    svg: bool,
+) -> Result<Vec<u8>, ApiError> {
+    let sql = format!(
+        "SELECT {}, \
+         (SELECT AVG(r.estimated_gdp) FROM countries r WHERE r.region = c.region) as region_avg_gdp \
+         FROM countries c WHERE LOWER(c.name)=LOWER(?) LIMIT 1",
+        country_repository::LIST_COLUMNS
+    );
+    let row = sqlx::query(&sql)
+    .bind(name)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+
+    let Some(r) = row else {
+        return Err(ApiError::NotFound("Country not found".into()));
+    };
+
+    let region_avg_gdp = r.try_get::<Option<f64>, _>("region_avg_gdp").ok().flatten();
+    let c = country_from_row(&r);
+
+    if svg {
+        return Ok(build_country_card_svg(&c, region_avg_gdp, lang, &state.branding).into_bytes());
     }
+
+    // Best effort: a missing/unreachable flag just leaves it off the card
+    let flag = match c.flag_url.as_deref() {
+        Some(url) => fetch_flag(&state.http, url).await,
+        None => None,
+    };
+
+    build_country_card(c, region_avg_gdp, flag, lang, &state.branding)
+        .await
+        .map_err(|e| ApiError::Internal(format!("could not render image: {}", e)))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MapParams {
+    /// Allowed: estimated_gdp (default) | population
+    pub metric: Option<String>,
+    /// One of the regions `GET /regions` lists; unknown regions are a 404
+    pub region: Option<String>,
+}
+
+pub async fn get_map(
+    State(state): State<AppState>,
+    Query(p): Query<MapParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let metric = match p.metric.as_deref() {
+        None => MapMetric::EstimatedGdp,
+        Some(m) => MapMetric::parse(m).ok_or_else(|| {
+            ApiError::Validation("metric must be one of estimated_gdp, population".into())
+        })?,
+    };
+
+    // Only stored regions are rendered, under their stored spelling, so arbitrary
+    // `?region=` values can't each fill the variant cache with a PNG
+    let region = match p.region.as_deref() {
+        None => None,
+        Some(r) => Some(
+            sqlx::query_scalar::<_, String>("SELECT region FROM countries WHERE region = ? LIMIT 1")
+                .bind(r)
+                .fetch_optional(&state.pool)
+                .await
+                .map_err(ApiError::db)?
+                .ok_or_else(|| ApiError::NotFound("Region not found".into()))?,
+        ),
+    };
+
+    let version = data_version(&state).await?;
+    let subject = match &region {
+        None => format!("map-{}", metric.as_str()),
+        Some(r) => format!("map-{}-r:{}", metric.as_str(), r),
+    };
+    let key = VariantKey {
+        version: &version,
+        subject: &subject,
+        width: MAP_SIZE.0,
+        height: MAP_SIZE.1,
+        lang: Lang::En,
+        format: "png",
+    };
+    let bytes = state
+        .image_cache
+        .get_or_render(&key, || async {
+            build_map_png(&state.pool, metric, region.as_deref(), &state.branding)
+                .await
+                .map_err(|e| ApiError::Internal(format!("could not render image: {}", e)))
+        })
+        .await?;
+    image_response(false, bytes)
+}
+
+pub async fn get_flag_sprite(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+    serve_cached_file(&state, SPRITE_PNG, "image/png").await
+}
+
+pub async fn get_flag_sprite_map(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    serve_cached_file(&state, SPRITE_JSON, "application/json").await
+}
+
+async fn serve_cached_file(
+    state: &AppState,
+    file: &str,
+    content_type: &'static str,
+) -> Result<Response, ApiError> {
+    let bytes = match tokio::fs::read(state.cache_dir.join(file)).await {
+        Ok(b) => b,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(ApiError::NotFound("Flag sprite not found".into()));
+        }
+        Err(e) => return Err(ApiError::Internal(format!("could not read {}: {}", file, e))),
+    };
+    Response::builder()
+        .status(axum::http::StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .body(axum::body::Body::from(bytes))
+        .map_err(|e| ApiError::Internal(format!("response build failed: {}", e)))
 }
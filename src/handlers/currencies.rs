@@ -0,0 +1,65 @@
+use axum::{extract::State, response::IntoResponse};
+use sqlx::Row;
+
+use crate::config::AppState;
+use crate::sql_json_arrayagg;
+use crate::utils::auth::KeyRestriction;
+use crate::utils::error::ApiError;
+use crate::utils::server_timing::TimedJson;
+
+/// Every currency code in the cache with its current exchange rate and the countries using
+/// it, for currency pickers. One `GROUP BY`; countries without a currency are left out.
+pub async fn list_currencies(
+    State(state): State<AppState>,
+    restriction: KeyRestriction,
+) -> Result<impl IntoResponse, ApiError> {
+    // Countries sharing a code share its rate (overrides are per code too); MAX just picks it
+    let mut qb = sqlx::QueryBuilder::new(concat!(
+        "SELECT currency_code, MAX(exchange_rate) as exchange_rate, MAX(rate_source) as rate_source, \
+         COUNT(*) as countries, CAST(",
+        sql_json_arrayagg!("name"),
+        " AS CHAR) as names FROM countries WHERE currency_code IS NOT NULL"
+    ));
+    if let Some(r) = restriction.get() {
+        r.push_sql(&mut qb);
+    }
+    qb.push(" GROUP BY currency_code ORDER BY currency_code ASC");
+    let rows = qb.build().fetch_all(&state.pool).await.map_err(ApiError::db)?;
+
+    let out: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            let names = country_names(r.try_get::<Option<String>, _>("names").ok().flatten().as_deref());
+            serde_json::json!({
+                "code": r.try_get::<String, _>("currency_code").unwrap_or_default(),
+                "exchange_rate": r.try_get::<Option<f64>, _>("exchange_rate").ok().flatten(),
+                "rate_source": r.try_get::<Option<String>, _>("rate_source").ok().flatten(),
+                "countries": r.try_get::<i64, _>("countries").unwrap_or_default(),
+                "country_names": names,
+            })
+        })
+        .collect();
+    Ok(TimedJson(out))
+}
+
+/// The aggregated `names` column as a sorted list (the aggregate's order is unspecified).
+/// NULL or unparsable text reads as no names.
+fn country_names(raw: Option<&str>) -> Vec<String> {
+    let mut names: Vec<String> = raw.and_then(|s| serde_json::from_str(s).ok()).unwrap_or_default();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn country_names_are_sorted_and_null_is_empty() {
+        assert_eq!(country_names(Some(r#"["Togo", "Benin", "Niger"]"#)), ["Benin", "Niger", "Togo"]);
+        assert_eq!(country_names(Some(r#"["Côte d'Ivoire"]"#)), ["Côte d'Ivoire"]);
+        assert!(country_names(None).is_empty());
+        assert!(country_names(Some("[null]")).is_empty());
+        assert!(country_names(Some("not json")).is_empty());
+    }
+}
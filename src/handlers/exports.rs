@@ -0,0 +1,188 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use sqlx::Row;
+use utoipa::ToSchema;
+
+use crate::config::AppState;
+use crate::db::{self, DbRow};
+use crate::services::export_service::{self, Destination, Format};
+use crate::services::job_queue::StopSignal;
+use crate::sql_iso;
+use crate::utils::auth::AdminAuth;
+use crate::utils::error::ApiError;
+
+#[derive(Deserialize, ToSchema)]
+pub struct DestinationBody {
+    /// "dir" | "webhook"
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Sub-directory of `<cache dir>/exports` for `dir`
+    pub path: Option<String>,
+    /// Target for `webhook`
+    pub url: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateExportJob {
+    pub name: String,
+    /// Cron expression in UTC, e.g. "30 2 * * *"
+    pub schedule: String,
+    pub format: String,
+    pub destination: DestinationBody,
+}
+
+const JOB_COLS: &str = concat!(
+    "id, name, schedule, format, destination_type, destination, active, ",
+    sql_iso!("next_run_at"), " as next_run_at, ",
+    sql_iso!("last_run_at"), " as last_run_at, ",
+    sql_iso!("created_at"), " as created_at"
+);
+
+fn job_json(r: &DbRow) -> serde_json::Value {
+    serde_json::json!({
+        "id": r.try_get::<i32, _>("id").unwrap_or_default(),
+        "name": r.try_get::<String, _>("name").unwrap_or_default(),
+        "schedule": r.try_get::<String, _>("schedule").unwrap_or_default(),
+        "format": r.try_get::<String, _>("format").unwrap_or_default(),
+        "destination": {
+            "type": r.try_get::<String, _>("destination_type").unwrap_or_default(),
+            "target": r.try_get::<String, _>("destination").unwrap_or_default(),
+        },
+        "active": r.try_get::<bool, _>("active").unwrap_or_default(),
+        "next_run_at": r.try_get::<Option<String>, _>("next_run_at").ok().flatten(),
+        "last_run_at": r.try_get::<Option<String>, _>("last_run_at").ok().flatten(),
+        "created_at": r.try_get::<Option<String>, _>("created_at").ok().flatten(),
+    })
+}
+
+pub async fn list_export_jobs(
+    _: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let rows = sqlx::query(&format!("SELECT {} FROM export_jobs ORDER BY id ASC", JOB_COLS))
+        .fetch_all(&state.pool)
+        .await
+        .map_err(ApiError::db)?;
+    Ok(Json(rows.iter().map(job_json).collect::<Vec<_>>()))
+}
+
+pub async fn create_export_job(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Json(body): Json<CreateExportJob>,
+) -> Result<impl IntoResponse, ApiError> {
+    let name = body.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(ApiError::Validation("name must be 1-64 characters".into()));
+    }
+    let schedule = export_service::parse_schedule(&body.schedule).map_err(ApiError::Validation)?;
+    let next = export_service::next_run(&schedule, Utc::now())
+        .ok_or_else(|| ApiError::Validation("schedule never fires again".into()))?;
+    let format = Format::parse(&body.format).map_err(ApiError::Validation)?;
+    let target = match body.destination.kind.as_str() {
+        "webhook" => body.destination.url.as_deref(),
+        _ => body.destination.path.as_deref(),
+    };
+    let destination = Destination::parse(&body.destination.kind, target.unwrap_or(""))
+        .map_err(ApiError::Validation)?;
+
+    let res = sqlx::query(
+        "INSERT INTO export_jobs (name, schedule, format, destination_type, destination, next_run_at) \
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&name)
+    .bind(body.schedule.trim())
+    .bind(format.as_str())
+    .bind(destination.kind())
+    .bind(destination.target())
+    .bind(next.naive_utc())
+    .execute(&state.pool)
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(d) if d.is_unique_violation() => {
+            ApiError::Validation(format!("an export job named '{}' already exists", name))
+        }
+        _ => ApiError::db(e),
+    })?;
+
+    let row = sqlx::query(&format!("SELECT {} FROM export_jobs WHERE id = ?", JOB_COLS))
+        .bind(db::last_insert_id(&res))
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::db)?;
+    Ok((axum::http::StatusCode::CREATED, Json(job_json(&row))))
+}
+
+/// Deletes the job; its run history stays in `export_job_runs`.
+pub async fn delete_export_job(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, ApiError> {
+    let res = sqlx::query("DELETE FROM export_jobs WHERE id = ?")
+        .bind(id)
+        .execute(&state.pool)
+        .await
+        .map_err(ApiError::db)?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Export job not found".into()));
+    }
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+/// Runs a job now, outside its schedule, and returns the recorded run.
+pub async fn run_export_job(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, ApiError> {
+    let job = export_service::load_job(&state.pool, id)
+        .await
+        .map_err(ApiError::Internal)?
+        .ok_or_else(|| ApiError::NotFound("Export job not found".into()))?;
+    let outcome = export_service::run_job(&state, &job, "manual", &StopSignal::default())
+        .await
+        .map_err(ApiError::db)?;
+    Ok(Json(outcome))
+}
+
+/// Latest 50 runs of a job, newest first.
+pub async fn list_export_runs(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, ApiError> {
+    let rows = sqlx::query(concat!(
+        "SELECT id, trigger_by, status, row_count, bytes, location, error, ",
+        sql_iso!("started_at"), " as started_at, ",
+        sql_iso!("finished_at"), " as finished_at ",
+        "FROM export_job_runs WHERE job_id = ? ORDER BY id DESC LIMIT 50"
+    ))
+    .bind(id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+
+    let out: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            serde_json::json!({
+                "id": r.try_get::<i64, _>("id").unwrap_or_default(),
+                "trigger": r.try_get::<String, _>("trigger_by").unwrap_or_default(),
+                "status": r.try_get::<String, _>("status").unwrap_or_default(),
+                "rows": r.try_get::<i32, _>("row_count").unwrap_or_default(),
+                "bytes": r.try_get::<i64, _>("bytes").unwrap_or_default(),
+                "location": r.try_get::<Option<String>, _>("location").ok().flatten(),
+                "error": r.try_get::<Option<String>, _>("error").ok().flatten(),
+                "started_at": r.try_get::<Option<String>, _>("started_at").ok().flatten(),
+                "finished_at": r.try_get::<Option<String>, _>("finished_at").ok().flatten(),
+            })
+        })
+        .collect();
+    Ok(Json(out))
+}
@@ -0,0 +1,79 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+
+use crate::config::AppState;
+use crate::services::migration_service;
+
+async fn db_ping(state: &AppState) -> Result<(), String> {
+    sqlx::query_scalar::<_, i32>("SELECT 1")
+        .fetch_one(&state.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Liveness: the process is up and serving. Never touches the DB.
+pub async fn live() -> impl IntoResponse {
+    (StatusCode::OK, Json(serde_json::json!({ "ok": true })))
+}
+
+/// Startup probe: migrations ran and the first DB ping succeeded. Stays 200 afterwards.
+pub async fn started(State(state): State<AppState>) -> impl IntoResponse {
+    match state.startup.started_at() {
+        Some(at) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "ok": true, "started_at": at.to_rfc3339() })),
+        ),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "ok": false, "reason": "migrations running" })),
+        ),
+    }
+}
+
+/// Readiness: started (and warmed up, with `WARMUP=true`), and (unless `HEALTH_CHECK_DB=false`) the DB answers right now.
+pub async fn ready(State(state): State<AppState>) -> impl IntoResponse {
+    if !state.startup.is_ready() {
+        let reason = if state.startup.started_at().is_none() { "starting" } else { "warming up" };
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "ok": false, "reason": reason })),
+        );
+    }
+    // Past DB_DEGRADED_AFTER_SECS of failed probes: fail fast instead of pinging again
+    if state.db_health.is_degraded() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "ok": false,
+                "reason": "database unavailable",
+                "db": state.db_health.snapshot(),
+            })),
+        );
+    }
+    if !state.runtime.load().health_check_db {
+        return (StatusCode::OK, Json(serde_json::json!({ "ok": true })));
+    }
+    // A binary whose embedded migrations differ from the DB's must not take traffic
+    match migration_service::status(&state.pool).await {
+        Ok(m) if !m.in_sync() => {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(serde_json::json!({ "ok": false, "migrations": m })),
+            )
+        }
+        Ok(_) => {}
+        Err(e) => {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(serde_json::json!({ "ok": false, "db": e.to_string() })),
+            )
+        }
+    }
+    match db_ping(&state).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "ok": true }))),
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "ok": false, "db": e })),
+        ),
+    }
+}
@@ -0,0 +1,492 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+use sqlx::Row;
+use std::time::Duration;
+use utoipa::IntoParams;
+
+use crate::config::AppState;
+use crate::db::DbRow;
+use crate::services::country_repository;
+use crate::services::history_service::{interpolate_population, population_series};
+use crate::services::raw_archive;
+use crate::services::refresh_feed::Outcome;
+use crate::types::path::CountryName;
+use crate::utils::auth::KeyRestriction;
+use crate::utils::case;
+use crate::utils::error::{ApiError, ErrorBody};
+use crate::utils::server_timing::TimedJson;
+use crate::{sql_day, sql_days_ago, sql_iso};
+
+const SNAPSHOT_COLS: &str = concat!(
+    "run_id, name, change_type, capital, region, population, currency_code, \
+     exchange_rate, estimated_gdp, flag_url, changes, ",
+    sql_iso!("recorded_at"), " as recorded_at"
+);
+
+const FIELDS: [&str; 7] = [
+    "capital",
+    "region",
+    "population",
+    "currency_code",
+    "exchange_rate",
+    "estimated_gdp",
+    "flag_url",
+];
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DiffParams {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+fn snapshot_values(r: &DbRow) -> serde_json::Value {
+    serde_json::json!({
+        "capital": r.try_get::<Option<String>, _>("capital").ok().flatten(),
+        "region": r.try_get::<Option<String>, _>("region").ok().flatten(),
+        "population": r.try_get::<i64, _>("population").unwrap_or_default(),
+        "currency_code": r.try_get::<Option<String>, _>("currency_code").ok().flatten(),
+        "exchange_rate": r.try_get::<Option<f64>, _>("exchange_rate").ok().flatten(),
+        "estimated_gdp": r.try_get::<Option<f64>, _>("estimated_gdp").ok().flatten(),
+        "flag_url": r.try_get::<Option<String>, _>("flag_url").ok().flatten(),
+    })
+}
+
+fn changes_json(r: &DbRow) -> serde_json::Value {
+    r.try_get::<String, _>("changes")
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_else(|| serde_json::json!({}))
+}
+
+/// Latest history row for `name` recorded at or before `run_id` (or at all, when `None`).
+async fn snapshot_at(
+    state: &AppState,
+    name: &str,
+    run_id: Option<i64>,
+) -> Result<Option<DbRow>, ApiError> {
+    let sql = format!(
+        "SELECT {} FROM country_history WHERE name = ? AND run_id <= ? ORDER BY run_id DESC, id DESC LIMIT 1",
+        SNAPSHOT_COLS
+    );
+    sqlx::query(&sql)
+        .bind(name)
+        .bind(run_id.unwrap_or(i64::MAX))
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(ApiError::db)
+}
+
+/// A limited key only sees the history of countries it can currently see; deleted
+/// countries have no region or tags left to match.
+async fn require_visible(state: &AppState, restriction: &KeyRestriction, name: &str) -> Result<(), ApiError> {
+    if country_repository::visible(&state.pool, restriction.get(), name).await.map_err(ApiError::db)? {
+        Ok(())
+    } else {
+        Err(ApiError::NotFound("No history for country".into()))
+    }
+}
+
+/// Field-level diff of one country between two refresh runs.
+///
+/// The country's state at run X is its latest recorded change at or before X.
+/// `to` defaults to the latest change, `from` to the change before `to`.
+pub async fn country_diff(
+    State(state): State<AppState>,
+    restriction: KeyRestriction,
+    name: CountryName,
+    Query(p): Query<DiffParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let (Some(from), Some(to)) = (p.from, p.to) {
+        if from > to {
+            return Err(ApiError::Validation("from must be <= to".into()));
+        }
+    }
+
+    let name = name.as_str();
+    require_visible(&state, &restriction, name).await?;
+    let to_row = snapshot_at(&state, name, p.to)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("No history for country".into()))?;
+    let to_run: i64 = to_row.try_get("run_id").unwrap_or_default();
+    let from_row = match p.from {
+        Some(from) => snapshot_at(&state, name, Some(from)).await?,
+        None => snapshot_at(&state, name, Some(to_run - 1)).await?,
+    };
+
+    let to_values = snapshot_values(&to_row);
+    let from_values = from_row.as_ref().map(snapshot_values);
+    let changes: Vec<serde_json::Value> = FIELDS
+        .iter()
+        .filter_map(|f| {
+            let before = from_values
+                .as_ref()
+                .map(|v| v[*f].clone())
+                .unwrap_or(serde_json::Value::Null);
+            let after = to_values[*f].clone();
+            (before != after).then(|| serde_json::json!({ "field": f, "from": before, "to": after }))
+        })
+        .collect();
+
+    Ok(TimedJson(serde_json::json!({
+        "name": to_row.try_get::<String, _>("name").unwrap_or_else(|_| name.to_string()),
+        "from": from_row.as_ref().map(|r| serde_json::json!({
+            "run_id": r.try_get::<i64, _>("run_id").unwrap_or_default(),
+            "recorded_at": r.try_get::<Option<String>, _>("recorded_at").ok().flatten(),
+            "values": from_values,
+        })),
+        "to": {
+            "run_id": to_run,
+            "recorded_at": to_row.try_get::<Option<String>, _>("recorded_at").ok().flatten(),
+            "values": to_values,
+        },
+        "changes": changes,
+    })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PopulationParams {
+    /// RFC 3339 timestamp or `YYYY-MM-DD` (midnight UTC) to estimate the population at
+    pub at: Option<String>,
+}
+
+fn parse_at(s: &str) -> Result<DateTime<Utc>, ApiError> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Ok(t.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|t| t.and_utc())
+        .ok_or_else(|| ApiError::Validation("at must be an RFC 3339 timestamp or YYYY-MM-DD".into()))
+}
+
+/// Population as captured by successive refreshes: one point per recorded change, with
+/// the change, spacing and annualized growth against the previous point. `?at=` adds a
+/// linear estimate between the surrounding points.
+pub async fn population_history(
+    State(state): State<AppState>,
+    restriction: KeyRestriction,
+    name: CountryName,
+    Query(p): Query<PopulationParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let at = p.at.as_deref().map(parse_at).transpose()?;
+    let name = name.as_str();
+    require_visible(&state, &restriction, name).await?;
+    let rows = sqlx::query(concat!(
+        "SELECT run_id, name, population, ",
+        sql_iso!("recorded_at"), " as recorded_at ",
+        "FROM country_history WHERE name = ? ORDER BY run_id ASC, id ASC"
+    ))
+    .bind(name)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+    let Some(first) = rows.first() else {
+        return Err(ApiError::NotFound("No history for country".into()));
+    };
+
+    let samples: Vec<(i64, DateTime<Utc>, i64)> = rows
+        .iter()
+        .filter_map(|r| {
+            let recorded_at = r.try_get::<Option<String>, _>("recorded_at").ok().flatten()?;
+            Some((
+                r.try_get::<i64, _>("run_id").unwrap_or_default(),
+                DateTime::parse_from_rfc3339(&recorded_at).ok()?.with_timezone(&Utc),
+                r.try_get::<i64, _>("population").unwrap_or_default(),
+            ))
+        })
+        .collect();
+    let points = population_series(&samples);
+
+    let estimate = at.map(|at| {
+        serde_json::json!({
+            "at": at,
+            "population": interpolate_population(&points, at),
+            "method": "linear",
+        })
+    });
+
+    Ok(TimedJson(serde_json::json!({
+        "name": first.try_get::<String, _>("name").unwrap_or_else(|_| name.to_string()),
+        "interpolation": "linear",
+        "first_recorded_at": points.first().map(|p| p.recorded_at),
+        "last_recorded_at": points.last().map(|p| p.recorded_at),
+        "points": points,
+        "estimate": estimate,
+    })))
+}
+
+/// Every country inserted or changed by one refresh run.
+pub async fn run_changes(
+    State(state): State<AppState>,
+    Path(run_id): Path<i64>,
+) -> Result<impl IntoResponse, ApiError> {
+    let run = sqlx::query(concat!(
+        "SELECT id, status, ",
+        sql_iso!("started_at"), " as started_at ",
+        "FROM refresh_runs WHERE id = ?"
+    ))
+    .bind(run_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(ApiError::db)?
+    .ok_or_else(|| ApiError::NotFound("Refresh run not found".into()))?;
+
+    let sql = format!(
+        "SELECT {} FROM country_history WHERE run_id = ? ORDER BY name ASC",
+        SNAPSHOT_COLS
+    );
+    let rows = sqlx::query(&sql)
+        .bind(run_id)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(ApiError::db)?;
+
+    let changes: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            serde_json::json!({
+                "name": r.try_get::<String, _>("name").unwrap_or_default(),
+                "change_type": r.try_get::<String, _>("change_type").unwrap_or_default(),
+                "changes": changes_json(r),
+            })
+        })
+        .collect();
+
+    Ok(TimedJson(serde_json::json!({
+        "run_id": run_id,
+        "status": run.try_get::<String, _>("status").unwrap_or_default(),
+        "started_at": run.try_get::<Option<String>, _>("started_at").ok().flatten(),
+        "total": changes.len(),
+        "changes": changes,
+    })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RefreshHistoryParams {
+    /// Window in days, 1-365 (default 30)
+    pub days: Option<u32>,
+}
+
+/// Refresh runs of the last `days` days with what each one cost (upstream calls, bytes
+/// downloaded, rows written), plus per-day and overall totals.
+pub async fn refresh_history(
+    State(state): State<AppState>,
+    Query(p): Query<RefreshHistoryParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let days = p.days.unwrap_or(30);
+    if !(1..=365).contains(&days) {
+        return Err(ApiError::Validation("days must be between 1 and 365".into()));
+    }
+
+    let daily = sqlx::query(concat!(
+        "SELECT ", sql_day!("started_at"), " as day, COUNT(*) as runs, \
+         CAST(SUM(upstream_calls) AS SIGNED) as upstream_calls, \
+         CAST(SUM(bytes_downloaded) AS SIGNED) as bytes_downloaded, \
+         CAST(SUM(rows_written) AS SIGNED) as rows_written \
+         FROM refresh_runs WHERE started_at >= ",
+        sql_days_ago!("?"),
+        " GROUP BY day ORDER BY day ASC"
+    ))
+    .bind(days)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+    let runs = sqlx::query(concat!(
+        "SELECT id, status, upstream_calls, bytes_downloaded, rows_written, ",
+        sql_iso!("started_at"), " as started_at, ",
+        sql_iso!("finished_at"), " as finished_at ",
+        "FROM refresh_runs WHERE started_at >= ",
+        sql_days_ago!("?"),
+        " ORDER BY id DESC LIMIT 100"
+    ))
+    .bind(days)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+
+    let mut totals = [0i64; 4];
+    let daily: Vec<serde_json::Value> = daily
+        .iter()
+        .map(|r| {
+            let row = [
+                r.try_get::<i64, _>("runs").unwrap_or_default(),
+                r.try_get::<i64, _>("upstream_calls").unwrap_or_default(),
+                r.try_get::<i64, _>("bytes_downloaded").unwrap_or_default(),
+                r.try_get::<i64, _>("rows_written").unwrap_or_default(),
+            ];
+            for (t, v) in totals.iter_mut().zip(row) {
+                *t += v;
+            }
+            serde_json::json!({
+                "date": r.try_get::<String, _>("day").unwrap_or_default(),
+                "runs": row[0],
+                "upstream_calls": row[1],
+                "bytes_downloaded": row[2],
+                "rows_written": row[3],
+            })
+        })
+        .collect();
+    let runs: Vec<serde_json::Value> = runs
+        .iter()
+        .map(|r| {
+            serde_json::json!({
+                "id": r.try_get::<i64, _>("id").unwrap_or_default(),
+                "status": r.try_get::<String, _>("status").unwrap_or_default(),
+                "started_at": r.try_get::<Option<String>, _>("started_at").ok().flatten(),
+                "finished_at": r.try_get::<Option<String>, _>("finished_at").ok().flatten(),
+                "upstream_calls": r.try_get::<i32, _>("upstream_calls").unwrap_or_default(),
+                "bytes_downloaded": r.try_get::<i64, _>("bytes_downloaded").unwrap_or_default(),
+                "rows_written": r.try_get::<i32, _>("rows_written").unwrap_or_default(),
+            })
+        })
+        .collect();
+
+    Ok(TimedJson(serde_json::json!({
+        "days": days,
+        "totals": {
+            "runs": totals[0],
+            "upstream_calls": totals[1],
+            "bytes_downloaded": totals[2],
+            "rows_written": totals[3],
+        },
+        "daily": daily,
+        "runs": runs,
+    })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RawParams {
+    /// Allowed: countries | rates
+    pub source: Option<String>,
+}
+
+/// Raw upstream JSON fetched by a refresh run (`RAW_ARCHIVE_RUNS` > 0). Sent gzipped
+/// to clients that accept it, decompressed otherwise.
+pub async fn run_raw(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(run_id): Path<i64>,
+    Query(p): Query<RawParams>,
+) -> Result<Response, ApiError> {
+    let source = p.source.as_deref().unwrap_or("");
+    if !raw_archive::SOURCES.contains(&source) {
+        return Err(ApiError::Validation(format!(
+            "source must be one of {}",
+            raw_archive::SOURCES.join(", ")
+        )));
+    }
+    if state.raw_archive_runs == 0 {
+        return Err(ApiError::NotFound(
+            "Raw payload archive is disabled (set RAW_ARCHIVE_RUNS)".into(),
+        ));
+    }
+    let gz = raw_archive::load(&state, run_id, source)
+        .await
+        .ok_or_else(|| ApiError::NotFound("No raw payload archived for this run".into()))?;
+
+    let accepts_gzip = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|e| e.trim().starts_with("gzip")));
+    // Upstream's own document: `RESPONSE_CASE` must not rename its keys
+    let mut res = Response::builder()
+        .status(axum::http::StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::VARY, "Accept-Encoding")
+        .extension(case::Verbatim);
+    let body = if accepts_gzip {
+        res = res.header(header::CONTENT_ENCODING, "gzip");
+        gz
+    } else {
+        raw_archive::gunzip(&gz)
+            .map_err(|e| ApiError::Internal(format!("corrupt raw payload archive: {}", e)))?
+    };
+    res.body(axum::body::Body::from(body))
+        .map_err(|e| ApiError::Internal(format!("response build failed: {}", e)))
+}
+
+/// Longest a `GET /refresh/wait` may block
+const MAX_WAIT: Duration = Duration::from_secs(120);
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WaitParams {
+    /// How long to wait: `30s`, `500ms`, `2m` or plain seconds, at most 120s (default 30s)
+    pub timeout: Option<String>,
+}
+
+fn wait_timeout(p: &WaitParams) -> Result<Duration, ApiError> {
+    let Some(raw) = p.timeout.as_deref().map(str::trim) else {
+        return Ok(Duration::from_secs(30));
+    };
+    let invalid = || ApiError::Validation("timeout must look like 30s, 500ms or 2m, at most 120s".into());
+    let (num, unit_ms) = if let Some(n) = raw.strip_suffix("ms") {
+        (n, 1)
+    } else if let Some(n) = raw.strip_suffix('s') {
+        (n, 1_000)
+    } else if let Some(n) = raw.strip_suffix('m') {
+        (n, 60_000)
+    } else {
+        (raw, 1_000)
+    };
+    let n: u64 = num.parse().map_err(|_| invalid())?;
+    let timeout = Duration::from_millis(n.checked_mul(unit_ms).ok_or_else(invalid)?);
+    if timeout.is_zero() || timeout > MAX_WAIT {
+        return Err(invalid());
+    }
+    Ok(timeout)
+}
+
+/// Long poll: blocks until the next refresh run finishes, however it was started, and
+/// answers its `RefreshResult`. A failed run answers `502` with the run's error; no run
+/// finishing within `timeout` answers `204`.
+pub async fn wait_for_refresh(
+    State(state): State<AppState>,
+    Query(p): Query<WaitParams>,
+) -> Result<Response, ApiError> {
+    let timeout = wait_timeout(&p)?;
+    Ok(match state.refresh_feed.next(timeout).await {
+        Some(Outcome::Succeeded(res)) => TimedJson(&*res).into_response(),
+        Some(Outcome::Failed { run_id, error }) => (
+            StatusCode::BAD_GATEWAY,
+            TimedJson(ErrorBody {
+                error: "Refresh failed",
+                code: Some("refresh_failed"),
+                details: Some(format!("run {}: {}", run_id, error)),
+            }),
+        )
+            .into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timeout(s: Option<&str>) -> Result<Duration, ApiError> {
+        wait_timeout(&WaitParams { timeout: s.map(String::from) })
+    }
+
+    #[test]
+    fn wait_timeout_units_and_limits() {
+        assert_eq!(timeout(None).unwrap(), Duration::from_secs(30));
+        assert_eq!(timeout(Some("45s")).unwrap(), Duration::from_secs(45));
+        assert_eq!(timeout(Some("500ms")).unwrap(), Duration::from_millis(500));
+        assert_eq!(timeout(Some("2m")).unwrap(), Duration::from_secs(120));
+        assert_eq!(timeout(Some("10")).unwrap(), Duration::from_secs(10));
+        for bad in ["0s", "3m", "121", "soon", "-1s", ""] {
+            assert!(timeout(Some(bad)).is_err(), "{}", bad);
+        }
+    }
+}
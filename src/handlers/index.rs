@@ -0,0 +1,155 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+
+use crate::config::AppState;
+use crate::routes::openapi;
+use crate::routes::registry::{Endpoint, DEPRECATED_PARAMS, ENDPOINTS};
+use crate::sql_iso;
+use crate::utils::error::ApiError;
+use crate::utils::sitemap::{self, Entry};
+
+/// Data freshness for the index. Best effort: the index must load even with the DB down.
+async fn freshness(state: &AppState) -> serde_json::Value {
+    let ts: Option<(String,)> = if state.db_health.state() == "degraded" {
+        None
+    } else {
+        sqlx::query_as("SELECT v FROM app_meta WHERE k='last_refreshed_at'")
+            .fetch_optional(&state.pool)
+            .await
+            .ok()
+            .flatten()
+    };
+    let last_refreshed_at = ts.map(|x| x.0);
+    let age_secs = last_refreshed_at
+        .as_deref()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|t| (Utc::now() - t.with_timezone(&Utc)).num_seconds());
+    let stale_after_secs = state.runtime.load().stale_after_secs;
+    serde_json::json!({
+        "last_refreshed_at": last_refreshed_at,
+        "age_secs": age_secs,
+        "stale": age_secs.map(|a| a > stale_after_secs as i64).unwrap_or(true),
+    })
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn render_html(endpoints: &[&Endpoint], data: &serde_json::Value, admin_link: bool) -> String {
+    let rows: String = endpoints
+        .iter()
+        .map(|e| {
+            let deprecated = match e.deprecated {
+                Some(d) => format!(" <strong>deprecated{}</strong>", d.sunset.map(|s| format!(", removed {}", s)).unwrap_or_default()),
+                None => String::new(),
+            };
+            format!(
+                "<tr><td><code>{}</code></td><td><code>{}</code></td><td>{}{}{}</td></tr>\n",
+                e.method,
+                escape(e.path),
+                escape(e.summary),
+                if e.admin { " <em>(admin)</em>" } else { "" },
+                deprecated
+            )
+        })
+        .collect();
+    let refreshed = match data["last_refreshed_at"].as_str() {
+        Some(at) if data["stale"] == true => format!("Last refresh {} (stale).", escape(at)),
+        Some(at) => format!("Last refresh {}.", escape(at)),
+        None => "No refresh yet: <code>POST /countries/refresh</code> loads the data.".to_string(),
+    };
+    let see_also = if admin_link {
+        "See <a href=\"/status\">/status</a> and <a href=\"/admin/overview\">/admin/overview</a>."
+    } else {
+        "See <a href=\"/status\">/status</a>."
+    };
+    format!(
+        "<!doctype html>\n<html><head><meta charset=\"utf-8\"><title>{name}</title>\
+         <style>body{{font-family:sans-serif;max-width:60em;margin:2em auto}}td{{padding:.2em .8em}}</style>\
+         </head><body>\n<h1>{name} {version}</h1>\n<p>{refreshed} {see_also}</p>\n<table>\n{rows}</table>\n</body></html>\n",
+        name = env!("CARGO_PKG_NAME"),
+        version = env!("CARGO_PKG_VERSION"),
+        refreshed = refreshed,
+        see_also = see_also,
+        rows = rows,
+    )
+}
+
+/// `GET /`: every endpoint (from `routes::registry`) plus data freshness. HTML for
+/// browsers (`Accept: text/html`), JSON otherwise. `/admin/*` is left out when it is
+/// served on the internal `ADMIN_ADDR` listener.
+pub async fn index(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let data = freshness(&state).await;
+    let admin_here = state.admin_addr.is_none();
+    let endpoints: Vec<&Endpoint> =
+        ENDPOINTS.iter().filter(|e| admin_here || !e.path.starts_with("/admin/")).collect();
+    let wants_html = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/html"));
+    if wants_html {
+        return Html(render_html(&endpoints, &data, admin_here)).into_response();
+    }
+    let mut links = serde_json::json!({ "status": "/status", "openapi": "/openapi.json", "docs": "/docs" });
+    if admin_here {
+        links["overview"] = "/admin/overview".into();
+    }
+    Json(serde_json::json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "data": data,
+        "links": links,
+        "endpoints": endpoints,
+        "deprecated_params": DEPRECATED_PARAMS,
+    }))
+    .into_response()
+}
+
+/// `GET /openapi.json` (`routes::openapi`), without `/admin/*` when that has its own
+/// listener. The OpenAPI media type keeps `response_case` and `response_envelope`, which
+/// only rewrite `application/json`, from touching the document.
+pub async fn openapi_json(State(state): State<AppState>) -> Response {
+    let mut doc = openapi::document();
+    if state.admin_addr.is_some() {
+        doc.paths.paths.retain(|path, _| !path.starts_with("/admin/"));
+    }
+    match doc.to_pretty_json() {
+        Ok(body) => ([(header::CONTENT_TYPE, "application/vnd.oai.openapi+json")], body).into_response(),
+        Err(e) => ApiError::Internal(e.to_string()).into_response(),
+    }
+}
+
+/// `GET /sitemap.xml` (`utils::sitemap`): every country's page under `PUBLIC_BASE_URL`.
+/// `404` while that is unset, since sitemap URLs must be absolute.
+pub async fn sitemap_xml(State(state): State<AppState>) -> Result<Response, ApiError> {
+    let cfg = state.runtime.load();
+    let Some(base) = cfg.public_base_url.as_deref() else {
+        return Err(ApiError::NotFound("No sitemap: PUBLIC_BASE_URL is not set".into()));
+    };
+    let rows: Vec<(String, Option<String>)> = sqlx::query_as(concat!(
+        "SELECT name, ",
+        sql_iso!("last_refreshed_at"),
+        " FROM countries ORDER BY name ASC LIMIT ?"
+    ))
+    .bind(sitemap::MAX_URLS as i64)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+    let entries: Vec<Entry> =
+        rows.into_iter().map(|(name, last_refreshed_at)| Entry { name, last_refreshed_at }).collect();
+    let body = sitemap::sitemap_xml(base, &cfg.sitemap_country_path, &entries);
+    Ok(([(header::CONTENT_TYPE, "application/xml; charset=utf-8")], body).into_response())
+}
+
+/// `GET /robots.txt`: disallows `ROBOTS_DISALLOW` and points at the sitemap when there is one.
+pub async fn robots_txt(State(state): State<AppState>) -> Response {
+    let cfg = state.runtime.load();
+    let body = sitemap::robots_txt(&cfg.robots_disallow, cfg.public_base_url.as_deref());
+    ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body).into_response()
+}
@@ -0,0 +1,159 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use sqlx::Row;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::config::AppState;
+use crate::db::DbRow;
+use crate::services::job_queue::{self, JobKind};
+use crate::sql_iso;
+use crate::utils::auth::AdminAuth;
+use crate::utils::error::ApiError;
+
+const JOB_COLS: &str = concat!(
+    "id, kind, params, status, progress, message, result, error, cancel_requested, ",
+    sql_iso!("created_at"), " as created_at, ",
+    sql_iso!("started_at"), " as started_at, ",
+    sql_iso!("finished_at"), " as finished_at"
+);
+
+fn json_col(r: &DbRow, col: &str) -> serde_json::Value {
+    r.try_get::<Option<String>, _>(col)
+        .ok()
+        .flatten()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or(serde_json::Value::Null)
+}
+
+fn job_json(r: &DbRow) -> serde_json::Value {
+    serde_json::json!({
+        "id": r.try_get::<i64, _>("id").unwrap_or_default(),
+        "kind": r.try_get::<String, _>("kind").unwrap_or_default(),
+        "params": json_col(r, "params"),
+        "status": r.try_get::<String, _>("status").unwrap_or_default(),
+        "progress": r.try_get::<i32, _>("progress").unwrap_or_default(),
+        "message": r.try_get::<Option<String>, _>("message").ok().flatten(),
+        "result": json_col(r, "result"),
+        "error": r.try_get::<Option<String>, _>("error").ok().flatten(),
+        "cancel_requested": r.try_get::<bool, _>("cancel_requested").unwrap_or_default(),
+        "created_at": r.try_get::<Option<String>, _>("created_at").ok().flatten(),
+        "started_at": r.try_get::<Option<String>, _>("started_at").ok().flatten(),
+        "finished_at": r.try_get::<Option<String>, _>("finished_at").ok().flatten(),
+    })
+}
+
+async fn load(state: &AppState, id: i64) -> Result<DbRow, ApiError> {
+    sqlx::query(&format!("SELECT {} FROM jobs WHERE id = ?", JOB_COLS))
+        .bind(id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(ApiError::db)?
+        .ok_or_else(|| ApiError::NotFound("Job not found".into()))
+}
+
+/// Queues `kind` and answers `202` with the job and a `Location` to poll.
+pub async fn accepted(state: &AppState, kind: &JobKind) -> Result<Response, ApiError> {
+    let id = job_queue::enqueue(&state.pool, kind).await.map_err(ApiError::db)?;
+    let row = load(state, id).await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, format!("/jobs/{}", id))],
+        Json(job_json(&row)),
+    )
+        .into_response())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct JobListParams {
+    pub status: Option<String>,
+    pub kind: Option<String>,
+}
+
+/// Latest 100 jobs, newest first.
+pub async fn list_jobs(
+    State(state): State<AppState>,
+    Query(p): Query<JobListParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM jobs WHERE (? IS NULL OR status = ?) AND (? IS NULL OR kind = ?) \
+         ORDER BY id DESC LIMIT 100",
+        JOB_COLS
+    ))
+    .bind(&p.status)
+    .bind(&p.status)
+    .bind(&p.kind)
+    .bind(&p.kind)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+    Ok(Json(rows.iter().map(job_json).collect::<Vec<_>>()))
+}
+
+pub async fn get_job(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, ApiError> {
+    Ok(Json(job_json(&load(&state, id).await?)))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateJob {
+    /// refresh | export | render_images
+    pub kind: String,
+    #[serde(default)]
+    pub params: serde_json::Value,
+}
+
+pub async fn create_job(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Json(body): Json<CreateJob>,
+) -> Result<Response, ApiError> {
+    let mut kind = JobKind::parse(&body.kind, &body.params).map_err(ApiError::Validation)?;
+    // Only the scheduler queues "schedule" runs
+    if let JobKind::Export { trigger, .. } = &mut kind {
+        *trigger = "manual".into();
+    }
+    accepted(&state, &kind).await
+}
+
+/// Cancels a queued job at once (`200`). A running job is asked to stop at its next
+/// checkpoint (`202`); poll it until `status` is `cancelled`, or `succeeded` if it got
+/// past its last checkpoint first.
+pub async fn cancel_job(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Response, ApiError> {
+    let res = sqlx::query(
+        "UPDATE jobs SET status = 'cancelled', finished_at = CURRENT_TIMESTAMP WHERE id = ? AND status = 'queued'",
+    )
+    .bind(id)
+    .execute(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+    if res.rows_affected() == 1 {
+        return Ok(Json(job_json(&load(&state, id).await?)).into_response());
+    }
+
+    let res = sqlx::query("UPDATE jobs SET cancel_requested = TRUE WHERE id = ? AND status = 'running'")
+        .bind(id)
+        .execute(&state.pool)
+        .await
+        .map_err(ApiError::db)?;
+    let row = load(&state, id).await?;
+    if res.rows_affected() == 0 {
+        let status = row.try_get::<String, _>("status").unwrap_or_default();
+        // Re-requesting a stop reports the same as the first time
+        if status != "running" {
+            return Err(ApiError::Validation(format!("job {} is already {}", id, status)));
+        }
+    }
+    Ok((StatusCode::ACCEPTED, Json(job_json(&row))).into_response())
+}
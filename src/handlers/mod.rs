@@ -1 +1,14 @@
-pub mod countries;
\ No newline at end of file
+pub mod admin;
+pub mod aliases;
+pub mod countries;
+pub mod currencies;
+pub mod exports;
+pub mod health;
+pub mod history;
+pub mod index;
+pub mod jobs;
+pub mod rates;
+pub mod regions;
+pub mod stats;
+pub mod tags;
+pub mod webhooks;
\ No newline at end of file
@@ -0,0 +1,214 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+use sqlx::Row;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::config::AppState;
+use crate::types::query::CurrencyCode;
+use crate::utils::auth::AdminAuth;
+use crate::utils::error::ApiError;
+use crate::{sql_day, sql_iso, sql_upsert};
+
+#[derive(Deserialize, ToSchema)]
+pub struct RateOverride {
+    pub rate: f64,
+    /// RFC 3339; omit to keep the override until it's deleted
+    pub expires_at: Option<String>,
+    pub reason: Option<String>,
+}
+
+/// Pins the rate for one currency. Takes effect on the next refresh.
+pub async fn put_rate_override(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    Json(body): Json<RateOverride>,
+) -> Result<impl IntoResponse, ApiError> {
+    let code = CurrencyCode::parse(&code)?;
+    if !body.rate.is_finite() || body.rate <= 0.0 {
+        return Err(ApiError::Validation("rate must be a positive number".into()));
+    }
+    let expires_at = match body.expires_at.as_deref() {
+        None => None,
+        Some(s) => {
+            let t = DateTime::parse_from_rfc3339(s)
+                .map_err(|_| ApiError::Validation("expires_at must be an RFC 3339 timestamp".into()))?
+                .with_timezone(&Utc);
+            if t <= Utc::now() {
+                return Err(ApiError::Validation("expires_at must be in the future".into()));
+            }
+            Some(t)
+        }
+    };
+    let reason = body.reason.map(|r| r.trim().chars().take(255).collect::<String>());
+
+    sqlx::query(concat!(
+        "INSERT INTO rate_overrides (currency_code, rate, reason, expires_at) VALUES (?, ?, ?, ?)",
+        sql_upsert!("currency_code"; "rate", "reason", "expires_at"),
+        ", updated_at = CURRENT_TIMESTAMP"
+    ))
+    .bind(code.as_str())
+    .bind(body.rate)
+    .bind(&reason)
+    .bind(expires_at.map(|t| t.naive_utc()))
+    .execute(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+
+    Ok(Json(serde_json::json!({
+        "currency_code": code.as_str(),
+        "rate": body.rate,
+        "reason": reason,
+        "expires_at": expires_at.map(|t| t.format("%Y-%m-%dT%H:%M:%SZ").to_string()),
+    })))
+}
+
+pub async fn delete_rate_override(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let code = CurrencyCode::parse(&code)?;
+    let res = sqlx::query("DELETE FROM rate_overrides WHERE currency_code = ?")
+        .bind(code.as_str())
+        .execute(&state.pool)
+        .await
+        .map_err(ApiError::db)?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Rate override not found".into()));
+    }
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+/// Active overrides (expired ones are ignored by the refresh and hidden here).
+pub async fn list_rate_overrides(
+    _: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let rows = sqlx::query(concat!(
+        "SELECT currency_code, rate, reason, ",
+        sql_iso!("expires_at"), " as expires_at, ",
+        sql_iso!("updated_at"), " as updated_at ",
+        "FROM rate_overrides WHERE expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP \
+         ORDER BY currency_code ASC"
+    ))
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+
+    let out: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            serde_json::json!({
+                "currency_code": r.try_get::<String, _>("currency_code").unwrap_or_default(),
+                "rate": r.try_get::<f64, _>("rate").unwrap_or_default(),
+                "reason": r.try_get::<Option<String>, _>("reason").ok().flatten(),
+                "expires_at": r.try_get::<Option<String>, _>("expires_at").ok().flatten(),
+                "updated_at": r.try_get::<Option<String>, _>("updated_at").ok().flatten(),
+            })
+        })
+        .collect();
+    Ok(Json(out))
+}
+
+/// Longest `from`..`to` span one history request may cover
+const MAX_HISTORY_DAYS: i64 = 3_660;
+/// Span when `from` is omitted
+const DEFAULT_HISTORY_DAYS: i64 = 90;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RateHistoryParams {
+    /// `YYYY-MM-DD`, inclusive; defaults to 89 days before `to`
+    pub from: Option<String>,
+    /// `YYYY-MM-DD`, inclusive; defaults to today (UTC)
+    pub to: Option<String>,
+}
+
+fn history_range(p: &RateHistoryParams, today: NaiveDate) -> Result<(NaiveDate, NaiveDate), ApiError> {
+    let day = |key: &str, s: &str| {
+        NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d")
+            .map_err(|_| ApiError::Validation(format!("{} must be a date like 2024-01-31", key)))
+    };
+    let to = p.to.as_deref().map(|s| day("to", s)).transpose()?.unwrap_or(today);
+    let from = match p.from.as_deref() {
+        Some(s) => day("from", s)?,
+        None => to - chrono::Duration::days(DEFAULT_HISTORY_DAYS - 1),
+    };
+    if from > to {
+        return Err(ApiError::Validation("from must not be after to".into()));
+    }
+    if (to - from).num_days() >= MAX_HISTORY_DAYS {
+        return Err(ApiError::Validation(format!("from..to may span at most {} days", MAX_HISTORY_DAYS)));
+    }
+    Ok((from, to))
+}
+
+/// Daily rate of one currency as recorded by refreshes, oldest first. Days without a
+/// refresh have no point; nothing is interpolated.
+pub async fn rate_history(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    Query(p): Query<RateHistoryParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let code = CurrencyCode::parse(&code)?;
+    let (from, to) = history_range(&p, Utc::now().date_naive())?;
+    let rows = sqlx::query(concat!(
+        "SELECT ",
+        sql_day!("day"),
+        " as day, rate, source FROM exchange_rate_history \
+         WHERE currency_code = ? AND day BETWEEN ? AND ? ORDER BY day ASC"
+    ))
+    .bind(code.as_str())
+    .bind(from)
+    .bind(to)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+
+    let points: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            serde_json::json!({
+                "date": r.try_get::<Option<String>, _>("day").ok().flatten(),
+                "rate": r.try_get::<f64, _>("rate").unwrap_or_default(),
+                "source": r.try_get::<String, _>("source").unwrap_or_default(),
+            })
+        })
+        .collect();
+    Ok(Json(serde_json::json!({
+        "currency_code": code.as_str(),
+        "granularity": "day",
+        "from": from.to_string(),
+        "to": to.to_string(),
+        "points": points,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(from: Option<&str>, to: Option<&str>) -> RateHistoryParams {
+        RateHistoryParams { from: from.map(String::from), to: to.map(String::from) }
+    }
+
+    #[test]
+    fn history_range_defaults_and_limits() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 31).unwrap();
+        let d = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        assert_eq!(history_range(&params(None, None), today).unwrap(), (d("2026-01-01"), today));
+        assert_eq!(
+            history_range(&params(Some("2025-01-01"), Some("2025-01-01")), today).unwrap(),
+            (d("2025-01-01"), d("2025-01-01"))
+        );
+        assert!(history_range(&params(Some("2025-02-01"), Some("2025-01-01")), today).is_err());
+        assert!(history_range(&params(Some("2000-01-01"), None), today).is_err());
+        assert!(history_range(&params(Some("01/02/2025"), None), today).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use axum::{extract::State, response::IntoResponse};
+use sqlx::Row;
+
+use crate::config::AppState;
+use crate::services::country_repository::Restriction;
+use crate::utils::auth::KeyRestriction;
+use crate::utils::error::ApiError;
+use crate::utils::server_timing::TimedJson;
+use crate::utils::single_flight::AggregateKey;
+
+/// Every region with its country count, total population and summed estimated GDP, in one
+/// `GROUP BY`. Countries without a region are grouped under `region: null`, listed last.
+/// Concurrent requests under the same key restriction share one query.
+pub async fn list_regions(
+    State(state): State<AppState>,
+    restriction: KeyRestriction,
+) -> Result<impl IntoResponse, ApiError> {
+    let restriction = restriction.get();
+    let key = AggregateKey::Regions { restriction: restriction.cloned() };
+    let body = state.aggregates.run(key, || compute(&state, restriction)).await?;
+    Ok(TimedJson(body))
+}
+
+async fn compute(state: &AppState, restriction: Option<&Restriction>) -> Result<serde_json::Value, ApiError> {
+    // SUM over BIGINT is DECIMAL in MySQL
+    let mut qb = sqlx::QueryBuilder::new(
+        "SELECT region, COUNT(*) as countries, CAST(SUM(population) AS SIGNED) as population, \
+         SUM(estimated_gdp) as estimated_gdp FROM countries WHERE 1=1",
+    );
+    if let Some(r) = restriction {
+        r.push_sql(&mut qb);
+    }
+    qb.push(" GROUP BY region");
+    let rows = qb.build().fetch_all(&state.pool).await.map_err(ApiError::db)?;
+
+    Ok(region_list(
+        rows.iter()
+            .map(|r| RegionTotals {
+                region: r.try_get("region").ok().flatten(),
+                countries: r.try_get("countries").unwrap_or_default(),
+                population: r.try_get("population").ok().flatten(),
+                estimated_gdp: r.try_get("estimated_gdp").ok().flatten(),
+            })
+            .collect(),
+    ))
+}
+
+struct RegionTotals {
+    region: Option<String>,
+    countries: i64,
+    /// NULL when no country in the group has a population
+    population: Option<i64>,
+    estimated_gdp: Option<f64>,
+}
+
+/// Named regions alphabetically, then the `region: null` group.
+fn region_list(mut totals: Vec<RegionTotals>) -> serde_json::Value {
+    totals.sort_by(|a, b| (a.region.is_none(), &a.region).cmp(&(b.region.is_none(), &b.region)));
+    totals
+        .into_iter()
+        .map(|t| {
+            serde_json::json!({
+                "region": t.region,
+                "countries": t.countries,
+                "population": t.population.unwrap_or(0),
+                "estimated_gdp": t.estimated_gdp,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn totals(region: Option<&str>, countries: i64, population: Option<i64>, gdp: Option<f64>) -> RegionTotals {
+        RegionTotals { region: region.map(str::to_string), countries, population, estimated_gdp: gdp }
+    }
+
+    #[test]
+    fn regions_are_sorted_with_the_unassigned_group_last() {
+        let out = region_list(vec![
+            totals(None, 2, Some(1_000), None),
+            totals(Some("Europe"), 3, Some(9_000), Some(2.5e9)),
+            totals(Some("Africa"), 2, None, Some(1.0e9)),
+        ]);
+        assert_eq!(
+            out,
+            serde_json::json!([
+                {"region": "Africa", "countries": 2, "population": 0, "estimated_gdp": 1.0e9},
+                {"region": "Europe", "countries": 3, "population": 9_000, "estimated_gdp": 2.5e9},
+                {"region": null, "countries": 2, "population": 1_000, "estimated_gdp": null},
+            ])
+        );
+        assert_eq!(region_list(Vec::new()), serde_json::json!([]));
+    }
+}
@@ -0,0 +1,118 @@
+use axum::{extract::State, response::IntoResponse};
+use sqlx::{QueryBuilder, Row};
+
+use crate::config::AppState;
+use crate::db::Db;
+use crate::services::country_repository::Restriction;
+use crate::types::query::{StatsParams, ValidQuery};
+use crate::utils::auth::KeyRestriction;
+use crate::utils::error::ApiError;
+use crate::utils::server_timing::TimedJson;
+use crate::utils::single_flight::AggregateKey;
+
+fn restricted<'a>(qb: &mut QueryBuilder<'a, Db>, restriction: Option<&'a Restriction>) {
+    if let Some(r) = restriction {
+        r.push_sql(qb);
+    }
+}
+
+/// `n` countries with an estimated GDP, highest first or (`desc = false`) lowest first.
+async fn by_gdp(
+    state: &AppState,
+    restriction: Option<&Restriction>,
+    desc: bool,
+    n: u32,
+) -> Result<Vec<serde_json::Value>, ApiError> {
+    let mut qb = QueryBuilder::new("SELECT name, estimated_gdp FROM countries WHERE estimated_gdp IS NOT NULL");
+    restricted(&mut qb, restriction);
+    qb.push(if desc { " ORDER BY estimated_gdp DESC, id ASC" } else { " ORDER BY estimated_gdp ASC, id ASC" });
+    qb.push(" LIMIT ").push_bind(n);
+    let rows = qb.build().fetch_all(&state.pool).await.map_err(ApiError::db)?;
+    Ok(rows
+        .iter()
+        .map(|r| {
+            serde_json::json!({
+                "name": r.try_get::<String, _>("name").unwrap_or_default(),
+                "estimated_gdp": r.try_get::<Option<f64>, _>("estimated_gdp").ok().flatten(),
+            })
+        })
+        .collect())
+}
+
+/// Totals, exchange rate spread, GDP extremes and gaps over the cached countries: the
+/// data of the summary image and more, as JSON. Concurrent identical requests share one
+/// computation (`AppState::aggregates`).
+pub async fn stats(
+    State(state): State<AppState>,
+    restriction: KeyRestriction,
+    ValidQuery(p): ValidQuery<StatsParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let n = p.top;
+    let restriction = restriction.get();
+    let key = AggregateKey::Stats { top: n, restriction: restriction.cloned() };
+    let body = state.aggregates.run(key, || compute(&state, restriction, n)).await?;
+    Ok(TimedJson(body))
+}
+
+async fn compute(state: &AppState, restriction: Option<&Restriction>, n: u32) -> Result<serde_json::Value, ApiError> {
+    // SUM over BIGINT and booleans is DECIMAL in MySQL
+    let mut qb = QueryBuilder::new(
+        "SELECT COUNT(*) as countries, CAST(COALESCE(SUM(population), 0) AS SIGNED) as population, \
+         MIN(exchange_rate) as min_rate, MAX(exchange_rate) as max_rate, AVG(exchange_rate) as avg_rate, \
+         CAST(COALESCE(SUM(exchange_rate IS NULL), 0) AS SIGNED) as missing_rate, \
+         CAST(COALESCE(SUM(currency_code IS NULL), 0) AS SIGNED) as missing_currency \
+         FROM countries WHERE 1=1",
+    );
+    restricted(&mut qb, restriction);
+    let totals = qb.build().fetch_one(&state.pool).await.map_err(ApiError::db)?;
+    let count = |col: &str| totals.try_get::<i64, _>(col).unwrap_or_default();
+    let rate = |col: &str| totals.try_get::<Option<f64>, _>(col).ok().flatten();
+
+    let top = by_gdp(state, restriction, true, n).await?;
+    let bottom = by_gdp(state, restriction, false, n).await?;
+    let last_refreshed_at: Option<(String,)> =
+        sqlx::query_as("SELECT v FROM app_meta WHERE k='last_refreshed_at'")
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(ApiError::db)?;
+
+    Ok(serde_json::json!({
+        "total_countries": count("countries"),
+        "total_population": count("population"),
+        "exchange_rate": { "min": rate("min_rate"), "max": rate("max_rate"), "avg": rate("avg_rate") },
+        "top_by_gdp": top,
+        "bottom_by_gdp": bottom,
+        "missing": { "exchange_rate": count("missing_rate"), "currency_code": count("missing_currency") },
+        "last_refreshed_at": last_refreshed_at.map(|x| x.0),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+    use axum::http::{header, StatusCode, Uri};
+
+    use super::*;
+    use crate::types::query::parse_query;
+
+    fn parse(uri: &'static str) -> Result<StatsParams, ApiError> {
+        parse_query(&Uri::from_static(uri))
+    }
+
+    #[tokio::test]
+    async fn top_is_validated_like_other_query_params() {
+        assert_eq!(parse("/stats").unwrap().top, 5);
+        assert_eq!(parse("/stats?top=10").unwrap().top, 10);
+        assert!(matches!(parse("/stats?top=0"), Err(ApiError::Validation(_))));
+        assert!(matches!(parse("/stats?top=11"), Err(ApiError::Validation(_))));
+
+        // Malformed values get the JSON validation error, not axum's plain-text rejection
+        let res = parse("/stats?top=abc").err().unwrap().into_response();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "application/json");
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "Validation failed");
+        assert!(body["details"].as_str().unwrap().contains("invalid digit"), "{}", body);
+    }
+}
@@ -0,0 +1,107 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use sqlx::Row;
+
+use crate::config::AppState;
+use crate::services::country_repository;
+use crate::sql_insert_ignore;
+use crate::types::path::CountryName;
+use crate::types::query::Tag;
+use crate::utils::auth::{AdminAuth, KeyRestriction};
+use crate::utils::error::ApiError;
+use crate::utils::server_timing::TimedJson;
+
+/// Canonical `countries.name` for a case-insensitive name.
+async fn canonical_name(state: &AppState, name: &CountryName) -> Result<String, ApiError> {
+    let row: Option<(String,)> =
+        sqlx::query_as("SELECT name FROM countries WHERE LOWER(name)=LOWER(?) LIMIT 1")
+            .bind(name.as_str())
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(ApiError::db)?;
+    row.map(|(n,)| n)
+        .ok_or_else(|| ApiError::NotFound("Country not found".into()))
+}
+
+/// Every tag in use, with how many countries carry it.
+pub async fn list_tags(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+    let rows = sqlx::query(
+        "SELECT tag, COUNT(*) as countries FROM country_tags GROUP BY tag ORDER BY tag ASC",
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+
+    let out: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            serde_json::json!({
+                "tag": r.try_get::<String, _>("tag").unwrap_or_default(),
+                "countries": r.try_get::<i64, _>("countries").unwrap_or_default(),
+            })
+        })
+        .collect();
+    Ok(TimedJson(out))
+}
+
+pub async fn country_tags(
+    State(state): State<AppState>,
+    restriction: KeyRestriction,
+    name: CountryName,
+) -> Result<impl IntoResponse, ApiError> {
+    let country = canonical_name(&state, &name).await?;
+    if !country_repository::visible(&state.pool, restriction.get(), &country).await.map_err(ApiError::db)? {
+        return Err(ApiError::NotFound("Country not found".into()));
+    }
+    let tags: Vec<(String,)> =
+        sqlx::query_as("SELECT tag FROM country_tags WHERE country_name = ? ORDER BY tag ASC")
+            .bind(&country)
+            .fetch_all(&state.pool)
+            .await
+            .map_err(ApiError::db)?;
+    Ok(TimedJson(serde_json::json!({
+        "country": country,
+        "tags": tags.into_iter().map(|(t,)| t).collect::<Vec<_>>(),
+    })))
+}
+
+/// Tags a cached country. Idempotent.
+pub async fn put_tag(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Path((name, tag)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let name = CountryName::parse(&name)?;
+    let tag = Tag::parse(&tag)?;
+    let country = canonical_name(&state, &name).await?;
+
+    sqlx::query(concat!(sql_insert_ignore!(), " INTO country_tags (tag, country_name) VALUES (?, ?)"))
+        .bind(tag.as_str())
+        .bind(&country)
+        .execute(&state.pool)
+        .await
+        .map_err(ApiError::db)?;
+
+    Ok(TimedJson(serde_json::json!({ "country": country, "tag": tag.as_str() })))
+}
+
+pub async fn delete_tag(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Path((name, tag)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let name = CountryName::parse(&name)?;
+    let tag = Tag::parse(&tag)?;
+    let res = sqlx::query("DELETE FROM country_tags WHERE tag = ? AND LOWER(country_name)=LOWER(?)")
+        .bind(tag.as_str())
+        .bind(name.as_str())
+        .execute(&state.pool)
+        .await
+        .map_err(ApiError::db)?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Tag not found".into()));
+    }
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
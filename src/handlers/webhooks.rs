@@ -0,0 +1,323 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use reqwest::Url;
+use serde::Deserialize;
+use std::net::IpAddr;
+use sqlx::Row;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::config::AppState;
+use crate::db::{self, Db};
+use crate::services::webhook_service::{generate_secret, is_public, resolve_public};
+use crate::sql_iso;
+use crate::utils::auth::AdminAuth;
+use crate::utils::error::ApiError;
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateWebhook {
+    pub url: String,
+}
+
+/// Checks the URL itself; `create_webhook` also resolves the host. Hosts given as an
+/// address or a `localhost` name must be public, so subscribers can't point the
+/// dispatcher at the server's own network.
+fn validate_url(url: &str) -> Result<Url, ApiError> {
+    let invalid = || ApiError::Validation("url must be an http(s) URL of at most 512 characters".into());
+    if url.len() > 512 {
+        return Err(invalid());
+    }
+    let parsed = Url::parse(url).map_err(|_| invalid())?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(invalid());
+    }
+    let host = parsed.host_str().filter(|h| !h.is_empty()).ok_or_else(invalid)?;
+    let internal = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) => !is_public(ip),
+        Err(_) => {
+            let d = host.trim_end_matches('.').to_ascii_lowercase();
+            d == "localhost" || d.ends_with(".localhost")
+        }
+    };
+    if internal {
+        return Err(ApiError::Validation("url must not point at a loopback, private or link-local address".into()));
+    }
+    Ok(parsed)
+}
+
+/// Every address the host resolves to must be public too. The dispatcher checks again
+/// before each delivery, since the name can be re-pointed later.
+async fn ensure_public_host(url: &Url) -> Result<(), ApiError> {
+    resolve_public(url, is_public).await.map(|_| ()).map_err(ApiError::Validation)
+}
+
+pub async fn create_webhook(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Json(body): Json<CreateWebhook>,
+) -> Result<impl IntoResponse, ApiError> {
+    let url = body.url.trim().to_string();
+    ensure_public_host(&validate_url(&url)?).await?;
+
+    let secret = generate_secret();
+    let res = sqlx::query("INSERT INTO webhooks (url, secret) VALUES (?, ?)")
+        .bind(&url)
+        .bind(&secret)
+        .execute(&state.pool)
+        .await
+        .map_err(ApiError::db)?;
+
+    // The secret is only ever returned here and on rotation
+    Ok((
+        axum::http::StatusCode::CREATED,
+        Json(serde_json::json!({
+            "id": db::last_insert_id(&res),
+            "url": url,
+            "active": true,
+            "secret": secret,
+        })),
+    ))
+}
+
+pub async fn list_webhooks(_: AdminAuth, State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+    let rows = sqlx::query(concat!(
+        "SELECT w.id, w.url, w.active, w.secret <> '' as signed, ",
+        sql_iso!("w.created_at"), " as created_at, \
+         (SELECT COUNT(*) FROM outbox o WHERE o.webhook_id = w.id AND o.status = 'pending') as pending, \
+         (SELECT COUNT(*) FROM outbox o WHERE o.webhook_id = w.id AND o.status = 'failed') as failed \
+         FROM webhooks w ORDER BY w.id ASC"
+    ))
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+
+    let out: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            serde_json::json!({
+                "id": r.try_get::<i32, _>("id").unwrap_or_default(),
+                "url": r.try_get::<String, _>("url").unwrap_or_default(),
+                "active": r.try_get::<bool, _>("active").unwrap_or_default(),
+                "signed": r.try_get::<i64, _>("signed").unwrap_or_default() != 0,
+                "created_at": r.try_get::<Option<String>, _>("created_at").ok().flatten(),
+                "pending": r.try_get::<i64, _>("pending").unwrap_or_default(),
+                "failed": r.try_get::<i64, _>("failed").unwrap_or_default(),
+            })
+        })
+        .collect();
+
+    Ok((axum::http::StatusCode::OK, Json(out)))
+}
+
+/// Replaces the signing secret; deliveries from now on are signed with the new one.
+pub async fn rotate_secret(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, ApiError> {
+    let secret = generate_secret();
+    let res = sqlx::query("UPDATE webhooks SET secret = ? WHERE id = ?")
+        .bind(&secret)
+        .bind(id)
+        .execute(&state.pool)
+        .await
+        .map_err(ApiError::db)?;
+
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Webhook not found".into()));
+    }
+
+    Ok((
+        axum::http::StatusCode::OK,
+        Json(serde_json::json!({ "id": id, "secret": secret })),
+    ))
+}
+
+pub async fn delete_webhook(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, ApiError> {
+    // Deactivate rather than delete, so the outbox history stays attributable
+    let res = sqlx::query("UPDATE webhooks SET active = FALSE WHERE id = ? AND active = TRUE")
+        .bind(id)
+        .execute(&state.pool)
+        .await
+        .map_err(ApiError::db)?;
+
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Webhook not found".into()));
+    }
+
+    Ok((axum::http::StatusCode::OK, Json(serde_json::json!({ "ok": true }))))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeliveriesParams {
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
+
+async fn ensure_webhook(state: &AppState, id: i64) -> Result<(), ApiError> {
+    let found: Option<(i32,)> = sqlx::query_as("SELECT id FROM webhooks WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(ApiError::db)?;
+    match found {
+        Some(_) => Ok(()),
+        None => Err(ApiError::NotFound("Webhook not found".into())),
+    }
+}
+
+/// Recent deliveries (one per event) for a subscription, newest first, each with its attempt log.
+pub async fn list_deliveries(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(p): Query<DeliveriesParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let Some(s) = p.status.as_deref() {
+        if !matches!(s, "pending" | "delivered" | "failed") {
+            return Err(ApiError::Validation(
+                "status must be one of pending, delivered, failed".into(),
+            ));
+        }
+    }
+    let limit = p.limit.unwrap_or(50);
+    if !(1..=200).contains(&limit) {
+        return Err(ApiError::Validation("limit must be between 1 and 200".into()));
+    }
+    ensure_webhook(&state, id).await?;
+
+    let mut qb = sqlx::QueryBuilder::<Db>::new(concat!(
+        "SELECT id, event_type, status, attempts, last_error, ",
+        sql_iso!("created_at"), " as created_at, ",
+        sql_iso!("delivered_at"), " as delivered_at, ",
+        sql_iso!("next_attempt_at"), " as next_attempt_at ",
+        "FROM outbox WHERE webhook_id = "
+    ));
+    qb.push_bind(id);
+    if let Some(s) = p.status.as_deref() {
+        qb.push(" AND status = ").push_bind(s);
+    }
+    qb.push(" ORDER BY id DESC LIMIT ").push_bind(limit);
+    let rows = qb
+        .build()
+        .fetch_all(&state.pool)
+        .await
+        .map_err(ApiError::db)?;
+
+    let ids: Vec<i64> = rows.iter().map(|r| r.try_get::<i64, _>("id").unwrap_or_default()).collect();
+    let mut attempts: std::collections::HashMap<i64, Vec<serde_json::Value>> = Default::default();
+    if !ids.is_empty() {
+        let mut qb = sqlx::QueryBuilder::<Db>::new(concat!(
+            "SELECT outbox_id, attempt, status_code, latency_ms, error, ",
+            sql_iso!("attempted_at"), " as attempted_at ",
+            "FROM webhook_deliveries WHERE outbox_id IN ("
+        ));
+        let mut sep = qb.separated(", ");
+        for i in &ids {
+            sep.push_bind(*i);
+        }
+        qb.push(") ORDER BY id ASC");
+        let log = qb
+            .build()
+            .fetch_all(&state.pool)
+            .await
+            .map_err(ApiError::db)?;
+        for r in log {
+            attempts
+                .entry(r.try_get::<i64, _>("outbox_id").unwrap_or_default())
+                .or_default()
+                .push(serde_json::json!({
+                    "attempt": r.try_get::<i32, _>("attempt").unwrap_or_default(),
+                    "status_code": r.try_get::<Option<i32>, _>("status_code").ok().flatten(),
+                    "latency_ms": r.try_get::<i64, _>("latency_ms").unwrap_or_default(),
+                    "error": r.try_get::<Option<String>, _>("error").ok().flatten(),
+                    "attempted_at": r.try_get::<Option<String>, _>("attempted_at").ok().flatten(),
+                }));
+        }
+    }
+
+    let out: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            let id = r.try_get::<i64, _>("id").unwrap_or_default();
+            serde_json::json!({
+                "id": id,
+                "event_type": r.try_get::<String, _>("event_type").unwrap_or_default(),
+                "status": r.try_get::<String, _>("status").unwrap_or_default(),
+                "attempts": r.try_get::<i32, _>("attempts").unwrap_or_default(),
+                "last_error": r.try_get::<Option<String>, _>("last_error").ok().flatten(),
+                "created_at": r.try_get::<Option<String>, _>("created_at").ok().flatten(),
+                "delivered_at": r.try_get::<Option<String>, _>("delivered_at").ok().flatten(),
+                "next_attempt_at": r.try_get::<Option<String>, _>("next_attempt_at").ok().flatten(),
+                "attempt_log": attempts.remove(&id).unwrap_or_default(),
+            })
+        })
+        .collect();
+
+    Ok((axum::http::StatusCode::OK, Json(out)))
+}
+
+/// Re-queues a delivery (any status) for immediate redelivery with a fresh attempt budget.
+pub async fn replay_delivery(
+    _: AdminAuth,
+    State(state): State<AppState>,
+    Path((id, delivery_id)): Path<(i64, i64)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let res = sqlx::query(
+        "UPDATE outbox SET status = 'pending', attempts = 0, next_attempt_at = CURRENT_TIMESTAMP \
+         WHERE id = ? AND webhook_id = ?",
+    )
+    .bind(delivery_id)
+    .bind(id)
+    .execute(&state.pool)
+    .await
+    .map_err(ApiError::db)?;
+
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Delivery not found".into()));
+    }
+
+    Ok((
+        axum::http::StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "id": delivery_id, "status": "pending" })),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls_must_be_public_http() {
+        for ok in ["https://hooks.example.com/x", "http://93.184.216.34:8080/in", "https://[2606:4700::1111]/"] {
+            assert!(validate_url(ok).is_ok(), "{}", ok);
+        }
+        for bad in [
+            "ftp://example.com/",
+            "https://",
+            "http://localhost:9000/",
+            "http://api.localhost./",
+            "http://127.0.0.1/",
+            "http://10.1.2.3/",
+            "http://172.16.0.1/",
+            "http://192.168.1.1/",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/",
+            "http://0.0.0.0/",
+            "http://[::1]/",
+            "http://[fd00::1]/",
+            "http://[fe80::1]/",
+            "http://[::ffff:127.0.0.1]/",
+        ] {
+            assert!(validate_url(bad).is_err(), "{}", bad);
+        }
+        assert!(validate_url(&format!("https://example.com/{}", "a".repeat(500))).is_err());
+    }
+}
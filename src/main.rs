@@ -3,16 +3,20 @@ use dotenvy::dotenv;
 use std::env;
 use std::net::SocketAddr;
 use tokio::net::TcpListener;
-use tracing::info;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing::{error, info, Level};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
 
 mod config;
+mod db;
 mod routes;
 mod handlers;
 mod services;
 mod models;
 mod types;
 mod utils;
+#[cfg(test)]
+mod tests;
 
 
 async fn shutdown_signal() {
@@ -35,26 +39,157 @@ async fn shutdown_signal() {
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     dotenv().ok();
+    // Filters are per layer: Server-Timing needs SQLx's statement events even when the log doesn't
+    let db_timing = utils::server_timing::enabled_from_env().then(|| {
+        utils::server_timing::DbTimeLayer.with_filter(Targets::new().with_target("sqlx::query", Level::DEBUG))
+    });
     tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
+        .with(tracing_subscriber::fmt::layer().with_filter(tracing_subscriber::EnvFilter::new(
             env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
+        )))
+        .with(db_timing)
         .init();
 
+    // `country-currency-api migrate ...` manages the schema and exits
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("migrate") {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is required");
+        db::check_url(&database_url).map_err(anyhow::Error::msg)?;
+        return services::migration_service::run_cli(&database_url, &args[1..]).await;
+    }
+    // `country-currency-api --bench-seed <rows>` loads synthetic countries for `benches/`
+    if args.first().map(String::as_str) == Some("--bench-seed") {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is required");
+        db::check_url(&database_url).map_err(anyhow::Error::msg)?;
+        return services::bench_seed::run(&database_url, &args[1..]).await;
+    }
+    // `country-currency-api --self-test` prints a JSON report and exits 1 on any failure
+    if args.first().map(String::as_str) == Some("--self-test") {
+        let report = services::self_test::run().await;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        std::process::exit(if report.ok { 0 } else { 1 });
+    }
+    // `country-currency-api --mock-upstreams` refreshes from local fixtures instead
+    if args.first().map(String::as_str) == Some("--mock-upstreams") {
+        services::mock_upstreams::start().await?;
+    }
+
     let cfg = config::AppConfig::from_env()?;
-    let state = cfg.build_state().await?;
-    let app: Router = routes::router(state);
+    utils::error_report::init(
+        cfg.sentry_dsn.as_deref(),
+        cfg.sentry_release.clone(),
+        cfg.sentry_environment.clone(),
+    )?;
+    let mut state = cfg.build_state().await?;
+
+    // Data providers: replace `state.countries_provider` / `state.rates_provider` here to
+    // refresh from another source (`services::providers`)
+
+    // Refresh hooks: register deployment-specific ones here with `.with(MyHook)`
+    if !cfg.refresh_exclude_countries.is_empty() {
+        state.hooks = state
+            .hooks
+            .with(services::hooks::ExcludeCountries::new(&cfg.refresh_exclude_countries));
+    }
+
+    if !cfg.api_keys.is_empty() {
+        info!("{} API key(s) configured; requests need a key with the route's scope", cfg.api_keys.len());
+    }
 
-    // Axum 0.7 style: TcpListener + axum::serve
+    let app: Router = routes::router(state.clone());
+
+    // TcpListener + our own accept loop (`utils::server`, for the HTTP_* knobs)
     let addr = SocketAddr::from(([0, 0, 0, 0], cfg.port));
     let listener = TcpListener::bind(addr).await?;
     info!("🚀 Listening on http://{addr}");
 
+    // Flips on SIGINT/SIGTERM so background loops stop with the server
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+
+    // `/admin/*` on an internal-only listener, away from the public load balancer
+    let mut admin_server = None;
+    if let Some(admin_addr) = cfg.admin_addr {
+        let admin_listener = TcpListener::bind(admin_addr).await?;
+        info!("admin endpoints on http://{admin_addr}");
+        let admin_app = routes::admin_router(state.clone());
+        let tuning = cfg.server.clone();
+        let mut stopped = shutdown_rx.clone();
+        admin_server = Some(tokio::spawn(async move {
+            let shutdown = async move {
+                stopped.changed().await.ok();
+            };
+            if let Err(e) = utils::server::serve(admin_listener, admin_app, &tuning, shutdown).await {
+                error!("admin listener stopped: {}", e);
+            }
+        }));
+    }
+
+    // SIGHUP: reload runtime settings from .env without dropping connections
+    #[cfg(unix)]
+    tokio::spawn({
+        let state = state.clone();
+        async move {
+            use tokio::signal::unix::{signal, SignalKind};
+            let Ok(mut hup) = signal(SignalKind::hangup()) else {
+                return;
+            };
+            while hup.recv().await.is_some() {
+                config::reload(&state);
+            }
+        }
+    });
+
+    // Migrations can take a while on first boot: run them behind the listener so
+    // /health/started and /health/live answer meanwhile. Still fail fast on error.
+    tokio::spawn(async move {
+        if let Err(e) = config::run_startup(&state).await {
+            error!("startup failed: {}", e);
+            std::process::exit(1);
+        }
+        // Tracks DB outages for /status and /health/ready; the pool reconnects by itself
+        services::db_monitor::spawn(
+            state.clone(),
+            std::time::Duration::from_secs(cfg.db_probe_secs.max(1)),
+        );
+        if cfg.warmup {
+            services::warmup::run(&state).await;
+        }
+        // Delivers webhook events recorded in the outbox
+        services::webhook_service::spawn_dispatcher(
+            state.clone(),
+            std::time::Duration::from_secs(cfg.webhook_poll_secs.max(1)),
+            cfg.webhook_max_attempts.max(1),
+        );
+        // Retries summary images / flag sprites that failed after a refresh
+        services::side_effects::spawn_worker(
+            state.clone(),
+            std::time::Duration::from_secs(15),
+            cfg.side_effect_max_attempts.max(1),
+        );
+        // Runs queued background jobs (`/jobs`)
+        services::job_queue::spawn_worker(
+            state.clone(),
+            std::time::Duration::from_secs(2),
+            std::time::Duration::from_secs(cfg.job_max_runtime_secs.max(1)),
+        );
+        // Queues scheduled dataset exports (`/admin/exports`)
+        services::export_service::spawn_scheduler(state.clone(), std::time::Duration::from_secs(30));
+        // Queues refreshes on `REFRESH_CRON` / `REFRESH_INTERVAL_SECS`
+        if let Some(schedule) = cfg.refresh_schedule.clone() {
+            services::refresh_scheduler::spawn(state.clone(), schedule, shutdown_rx);
+        }
+    });
+
     // 🔴 This must be awaited; otherwise the program exits immediately
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    let shutdown = async move {
+        shutdown_signal().await;
+        shutdown_tx.send(true).ok();
+    };
+    utils::server::serve(listener, app, &cfg.server, shutdown).await?;
+    // Let in-flight admin requests finish too
+    if let Some(admin_server) = admin_server {
+        admin_server.await.ok();
+    }
 
     Ok(())
 }
@@ -1,6 +1,7 @@
 use serde::Serialize;
+use utoipa::ToSchema;
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct Country {
     pub id: i64,
     pub name: String,
@@ -11,5 +12,39 @@ pub struct Country {
     pub exchange_rate: Option<f64>,
     pub estimated_gdp: Option<f64>,
     pub flag_url: Option<String>,
+    /// The flag as two regional indicator symbols from the ISO 3166-1 alpha-2 code (e.g.
+    /// "🇬🇭"), for clients that can't load `flag_url`. Null when upstream sent no code
+    pub flag_emoji: Option<String>,
     pub last_refreshed_at: Option<String>,
+    /// Provider of the country fields, e.g. "restcountries"
+    pub data_source: String,
+    /// When that provider's payload was fetched
+    pub source_fetched_at: Option<String>,
+    /// "open.er-api" or "override" (see `/rates`); null when there is no rate
+    pub rate_source: Option<String>,
+}
+
+/// `"GH"` → `"🇬🇭"`. `None` unless `iso` is two ASCII letters.
+pub fn flag_emoji(iso: &str) -> Option<String> {
+    let iso = iso.trim();
+    if iso.len() != 2 || !iso.bytes().all(|b| b.is_ascii_alphabetic()) {
+        return None;
+    }
+    // REGIONAL INDICATOR SYMBOL LETTER A is U+1F1E6
+    iso.bytes().map(|b| char::from_u32(0x1F1E6 + (b.to_ascii_uppercase() - b'A') as u32)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flag_emoji_from_iso_code() {
+        assert_eq!(flag_emoji("GH").as_deref(), Some("🇬🇭"));
+        assert_eq!(flag_emoji("ng").as_deref(), Some("🇳🇬"));
+        assert_eq!(flag_emoji(" gh\n").as_deref(), Some("🇬🇭"));
+        for bad in ["G1", "G-", "", " ", "G", "GHA", "ÉS"] {
+            assert_eq!(flag_emoji(bad), None, "{:?}", bad);
+        }
+    }
 }
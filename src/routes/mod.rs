@@ -1,20 +1,185 @@
-use axum::{routing::{get, post}, Router};
-use tower_http::trace::TraceLayer;
+use axum::{
+    http::{header, HeaderValue, Response},
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use tower_http::{
+    services::{ServeDir, ServeFile},
+    set_header::SetResponseHeaderLayer,
+    trace::TraceLayer,
+};
+use tracing::warn;
+use utoipa_swagger_ui::{Config, SwaggerUi};
 
 use crate::config::AppState;
+use crate::handlers::admin::{
+    audit_log, cache_stats, clear_cache, create_signed_url, data_quality, erase_actor, export_actor, overview,
+    reload_config,
+};
+use crate::handlers::aliases::{delete_alias, list_aliases, put_alias};
 use crate::handlers::countries::{
-    delete_country, get_country, get_image, health, list_countries, refresh, status,
+    autocomplete, delete_country, get_country, get_country_image, get_flag_sprite, get_flag_sprite_map, get_image,
+    get_image_meta, get_bundle, get_capital, get_map, list_countries, missing_rates, dataset_checksum, refresh, status,
+};
+use crate::handlers::currencies::list_currencies;
+use crate::handlers::exports::{
+    create_export_job, delete_export_job, list_export_jobs, list_export_runs, run_export_job,
+};
+use crate::handlers::health;
+use crate::handlers::index::{index, openapi_json, robots_txt, sitemap_xml};
+use crate::handlers::jobs::{cancel_job, create_job, get_job, list_jobs};
+use crate::handlers::history::{
+    country_diff, population_history, refresh_history, run_changes, run_raw, wait_for_refresh,
 };
+use crate::handlers::rates::{delete_rate_override, list_rate_overrides, put_rate_override, rate_history};
+use crate::handlers::regions::list_regions;
+use crate::handlers::stats::stats;
+use crate::handlers::tags::{country_tags, delete_tag, list_tags, put_tag};
+use crate::handlers::webhooks::{
+    create_webhook, delete_webhook, list_deliveries, list_webhooks, replay_delivery, rotate_secret,
+};
+use crate::services::flag_service::{SPRITE_JSON, SPRITE_PNG};
+use crate::utils::auth;
+use crate::utils::case::response_case;
+use crate::utils::chaos;
+use crate::utils::client_ip;
+use crate::utils::deadline;
+use crate::utils::deprecation::deprecation_headers;
+use crate::utils::envelope::response_envelope;
+use crate::utils::error_report;
+use crate::utils::server_timing;
 
-pub fn router(state: AppState) -> Router {
+pub mod openapi;
+pub mod paths;
+pub mod registry;
+
+/// Operator endpoints (`/admin/*`).
+fn admin_routes() -> Router<AppState> {
     Router::new()
+        .route("/admin/overview", get(overview))
+        .route("/admin/reload-config", post(reload_config))
+        .route("/admin/signed-urls", post(create_signed_url))
+        .route("/admin/audit", get(audit_log))
+        .route("/admin/actors/:ip", get(export_actor).delete(erase_actor))
+        .route("/admin/cache", get(cache_stats))
+        .route("/admin/cache/clear", post(clear_cache))
+        .route("/admin/data-quality", get(data_quality))
+        .route("/admin/exports", get(list_export_jobs).post(create_export_job))
+        .route("/admin/exports/:id", axum::routing::delete(delete_export_job))
+        .route("/admin/exports/:id/run", post(run_export_job))
+        .route("/admin/exports/:id/runs", get(list_export_runs))
+}
+
+/// The public API. `/admin/*` is part of it unless `ADMIN_ADDR` gives it its own
+/// listener (`admin_router`).
+pub fn router(state: AppState) -> Router {
+    let mut app = Router::new()
         .route("/countries/refresh", post(refresh))
-        .route("/countries", get(list_countries))
-        .route("/countries/:name", get(get_country).delete(delete_country))
+        .route(paths::COUNTRIES, get(list_countries))
+        .route(paths::COUNTRY_AUTOCOMPLETE, get(autocomplete))
+        .route(paths::COUNTRY_MISSING_RATES, get(missing_rates))
+        .route(paths::COUNTRY_CHECKSUM, get(dataset_checksum))
+        .route(paths::COUNTRY_BUNDLE, get(get_bundle))
+        .route(paths::COUNTRY, get(get_country).delete(delete_country))
+        .route(paths::COUNTRY_IMAGE, get(get_country_image))
+        .route(paths::COUNTRY_DIFF, get(country_diff))
+        .route(paths::COUNTRY_POPULATION_HISTORY, get(population_history))
+        .route(paths::COUNTRY_TAGS, get(country_tags))
+        .route(paths::COUNTRY_TAG, axum::routing::put(put_tag).delete(delete_tag))
+        .route("/tags", get(list_tags))
+        .route("/regions", get(list_regions))
+        .route("/currencies", get(list_currencies))
+        .route("/stats", get(stats))
+        .route(paths::CAPITAL, get(get_capital))
+        .route("/refresh/history", get(refresh_history))
+        .route("/refresh/wait", get(wait_for_refresh))
+        .route("/refresh/:run_id/changes", get(run_changes))
+        .route("/refresh/:run_id/raw", get(run_raw))
+        .route("/map", get(get_map))
         .route("/status", get(status))
         .route("/countries/image", get(get_image))
-        .route("/healthz", get(health)) // DB health check
-        .route("/", get(health)) // DB health check
-        .with_state(state)
-        .layer(TraceLayer::new_for_http())
+        .route("/countries/image/meta", get(get_image_meta))
+        .route("/countries/flags/sprite", get(get_flag_sprite))
+        .route("/countries/flags/sprite.json", get(get_flag_sprite_map))
+        .route("/webhooks", get(list_webhooks).post(create_webhook))
+        .route("/webhooks/:id", axum::routing::delete(delete_webhook))
+        .route("/webhooks/:id/secret", post(rotate_secret))
+        .route("/webhooks/:id/deliveries", get(list_deliveries))
+        .route("/webhooks/:id/deliveries/:delivery_id/replay", post(replay_delivery))
+        .route("/rates", get(list_rate_overrides))
+        .route("/rates/:code", axum::routing::put(put_rate_override).delete(delete_rate_override))
+        .route("/rates/:code/history", get(rate_history))
+        .route("/aliases", get(list_aliases))
+        .route("/aliases/:alias", axum::routing::put(put_alias).delete(delete_alias))
+        .route("/jobs", get(list_jobs).post(create_job))
+        .route("/jobs/:id", get(get_job).delete(cancel_job))
+        .route("/health/live", get(health::live))
+        .route("/health/ready", get(health::ready))
+        .route("/health/started", get(health::started))
+        .route("/healthz", get(health::ready)) // DB health check
+        .route("/", get(index))
+        .route("/openapi.json", get(openapi_json))
+        .route("/sitemap.xml", get(sitemap_xml))
+        .route("/robots.txt", get(robots_txt))
+        .merge(SwaggerUi::new("/docs").config(Config::from("/openapi.json")));
+    if state.admin_addr.is_none() {
+        app = app.merge(admin_routes());
+    }
+
+    // Optional: expose generated artifacts so a CDN can front them directly
+    if let Some(dir) = state.static_dir.clone() {
+        let cache_control = HeaderValue::from_str(&format!(
+            "public, max-age={}",
+            state.static_max_age_secs
+        ))
+        .unwrap_or_else(|_| HeaderValue::from_static("no-cache"));
+        // Only the published artifacts: the cache dir also holds raw upstream payloads, which
+        // need the `export` scope (`GET /refresh/:run_id/raw`), and half-written temp files
+        let mut static_files = Router::new()
+            .route_service(&format!("/static/{}", SPRITE_PNG), ServeFile::new(dir.join(SPRITE_PNG)))
+            .route_service(&format!("/static/{}", SPRITE_JSON), ServeFile::new(dir.join(SPRITE_JSON)))
+            .nest_service("/static/variants", ServeDir::new(dir.join("variants")))
+            // `export` scope, see `auth::unregistered_scope`
+            .nest_service("/static/exports", ServeDir::new(dir.join("exports")));
+        if let Some(name) = state.summary_image_path.file_name().and_then(|n| n.to_str()) {
+            static_files = static_files
+                .route_service(&format!("/static/{}", name), ServeFile::new(state.summary_image_path.clone()));
+        }
+        // Only cache hits; a 404 for a not-yet-generated file shouldn't stick in the CDN
+        let static_files = static_files
+            .layer(SetResponseHeaderLayer::if_not_present(
+                header::CACHE_CONTROL,
+                move |res: &Response<_>| res.status().is_success().then(|| cache_control.clone()),
+            ));
+        app = app.merge(static_files);
+    }
+
+    with_layers(app, state)
+}
+
+/// `/admin/*` alone, for the internal `ADMIN_ADDR` listener.
+pub fn admin_router(state: AppState) -> Router {
+    with_layers(admin_routes(), state)
+}
+
+/// Cross-cutting layers, the same on both listeners.
+fn with_layers(mut app: Router<AppState>, state: AppState) -> Router {
+    // Innermost of the cross-cutting layers, so injected latency counts against deadlines
+    if let Some(chaos) = state.chaos.clone() {
+        warn!("fault injection enabled: {:?}", chaos);
+        app = app.layer(middleware::from_fn_with_state(chaos, chaos::inject));
+    }
+
+    app.layer(middleware::from_fn(deadline::enforce))
+        .layer(middleware::from_fn(deprecation_headers))
+        .layer(middleware::from_fn(error_report::capture_errors))
+        .layer(middleware::from_fn_with_state(state.clone(), auth::authorize))
+        .layer(middleware::from_fn_with_state(state.clone(), response_envelope))
+        .layer(middleware::from_fn_with_state(state.clone(), response_case))
+        .layer(middleware::from_fn_with_state(state.clone(), server_timing::annotate))
+        .with_state(state.clone())
+        .layer(TraceLayer::new_for_http().make_span_with(client_ip::make_span))
+        // Outermost, so the trace span already knows the client
+        .layer(middleware::from_fn_with_state(state.trusted_proxies.clone(), client_ip::identify))
 }
@@ -0,0 +1,250 @@
+// OpenAPI 3.1 document for `GET /openapi.json` and the Swagger UI at `/docs`.
+//
+// Operations come from `registry::ENDPOINTS`, so the document lists exactly the routes
+// `GET /` does, with the scope each one needs and its deprecation. What the registry
+// doesn't know (query parameters, request bodies, non-JSON responses) is looked up in
+// `query_params`, `request_body` and `success`, which point at the structs the handlers
+// deserialize. Those derive `IntoParams` / `ToSchema`, so their doc comments are the
+// descriptions. A new route with parameters or a body needs an arm there.
+
+use utoipa::openapi::path::{HttpMethod, OperationBuilder, Parameter, ParameterBuilder, ParameterIn};
+use utoipa::openapi::request_body::{RequestBody, RequestBodyBuilder};
+use utoipa::openapi::response::{ResponseBuilder, ResponsesBuilder};
+use utoipa::openapi::schema::{ArrayBuilder, KnownFormat, ObjectBuilder, SchemaFormat, Type};
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityRequirement, SecurityScheme};
+use utoipa::openapi::{
+    ComponentsBuilder, ContentBuilder, Deprecated, InfoBuilder, OpenApi, OpenApiBuilder, Paths, Ref, RefOr,
+    Required, Schema,
+};
+use utoipa::{IntoParams, ToSchema};
+
+use super::paths;
+use super::registry::{Endpoint, ENDPOINTS};
+use crate::handlers::{admin, aliases, countries, exports, history, jobs, rates, webhooks};
+use crate::models::country::Country;
+use crate::types::query::{RawAutocompleteParams, RawListParams, RawStatsParams};
+use crate::utils::auth::Scope;
+use crate::utils::error::ErrorBody;
+
+/// `/countries/:name` → `/countries/{name}`
+fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|seg| match seg.strip_prefix(':') {
+            Some(name) => format!("{{{}}}", name),
+            None => seg.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn path_params(path: &str) -> Vec<Parameter> {
+    path.split('/')
+        .filter_map(|seg| seg.strip_prefix(':'))
+        .map(|name| {
+            let ty = if name == "id" || name.ends_with("_id") { Type::Integer } else { Type::String };
+            ParameterBuilder::new()
+                .name(name)
+                .parameter_in(ParameterIn::Path)
+                .required(Required::True)
+                .schema(Some(ObjectBuilder::new().schema_type(ty)))
+                .build()
+        })
+        .collect()
+}
+
+fn query<P: IntoParams>() -> Vec<Parameter> {
+    P::into_params(|| Some(ParameterIn::Query))
+}
+
+fn query_params(e: &Endpoint) -> Vec<Parameter> {
+    match (e.method, e.path) {
+        ("GET", paths::COUNTRIES) => query::<RawListParams>(),
+        ("GET", paths::COUNTRY_AUTOCOMPLETE) => query::<RawAutocompleteParams>(),
+        ("GET", paths::COUNTRY_BUNDLE) => query::<countries::BundleParams>(),
+        ("DELETE", paths::COUNTRY) => query::<countries::DeleteParams>(),
+        ("GET", paths::COUNTRY_IMAGE) | ("GET", "/countries/image") => query::<countries::ImageParams>(),
+        ("GET", paths::COUNTRY_DIFF) => query::<history::DiffParams>(),
+        ("GET", paths::COUNTRY_POPULATION_HISTORY) => query::<history::PopulationParams>(),
+        ("POST", "/countries/refresh") => query::<countries::RefreshParams>(),
+        ("GET", "/refresh/history") => query::<history::RefreshHistoryParams>(),
+        ("GET", "/refresh/wait") => query::<history::WaitParams>(),
+        ("GET", "/refresh/:run_id/raw") => query::<history::RawParams>(),
+        ("GET", "/map") => query::<countries::MapParams>(),
+        ("GET", "/webhooks/:id/deliveries") => query::<webhooks::DeliveriesParams>(),
+        ("GET", "/rates/:code/history") => query::<rates::RateHistoryParams>(),
+        ("GET", "/jobs") => query::<jobs::JobListParams>(),
+        ("GET", "/stats") => query::<RawStatsParams>(),
+        ("GET", "/admin/data-quality") => query::<admin::DataQualityParams>(),
+        _ => Vec::new(),
+    }
+}
+
+fn request_body(e: &Endpoint) -> Option<RequestBody> {
+    let schema = match (e.method, e.path) {
+        ("POST", "/jobs") => jobs::CreateJob::name(),
+        ("POST", "/admin/exports") => exports::CreateExportJob::name(),
+        ("PUT", "/aliases/:alias") => aliases::PutAlias::name(),
+        ("POST", "/webhooks") => webhooks::CreateWebhook::name(),
+        ("PUT", "/rates/:code") => rates::RateOverride::name(),
+        ("POST", "/admin/signed-urls") => admin::SignedUrlBody::name(),
+        _ => return None,
+    };
+    let content = ContentBuilder::new().schema(Some(Ref::from_schema_name(schema))).build();
+    Some(RequestBodyBuilder::new().required(Some(Required::True)).content("application/json", content).build())
+}
+
+fn binary() -> Schema {
+    ObjectBuilder::new()
+        .schema_type(Type::String)
+        .format(Some(SchemaFormat::KnownFormat(KnownFormat::Binary)))
+        .into()
+}
+
+/// Content type and schema of each representation a response can have
+type Bodies = Vec<(&'static str, Option<RefOr<Schema>>)>;
+
+/// Status and bodies of the success response. Routes not listed answer JSON whose shape
+/// is only described by their summary.
+fn success(e: &Endpoint) -> (&'static str, Bodies) {
+    let country = || RefOr::from(Ref::from_schema_name(Country::name()));
+    match (e.method, e.path) {
+        ("GET", paths::COUNTRIES) => {
+            let int = || ObjectBuilder::new().schema_type(Type::Integer);
+            let page = ObjectBuilder::new()
+                .property("data", ArrayBuilder::new().items(country()))
+                .property("page", int())
+                .property("limit", int())
+                .property("total", int())
+                .property("total_pages", int())
+                .property("next_cursor", ObjectBuilder::new().schema_type(Type::String))
+                .required("data")
+                .required("page")
+                .required("limit")
+                .required("total")
+                .required("total_pages")
+                .description(Some("A bare array of countries with `LIST_BARE_ARRAY=true`"));
+            ("200", vec![("application/json", Some(page.into()))])
+        }
+        ("GET", paths::COUNTRY) => ("200", vec![("application/json", Some(country()))]),
+        ("GET", paths::COUNTRY_IMAGE) | ("GET", "/countries/image") => (
+            "200",
+            vec![("image/png", Some(binary().into())), ("image/svg+xml", Some(binary().into()))],
+        ),
+        ("GET", "/map") | ("GET", "/countries/flags/sprite") => ("200", vec![("image/png", Some(binary().into()))]),
+        ("GET", paths::COUNTRY_BUNDLE) => (
+            "200",
+            vec![("application/gzip", Some(binary().into())), ("application/zstd", Some(binary().into()))],
+        ),
+        ("GET", "/openapi.json") => ("200", vec![("application/vnd.oai.openapi+json", None)]),
+        ("GET", "/docs") | ("GET", "/docs/:file") => ("200", vec![("text/html", None)]),
+        ("GET", "/sitemap.xml") => ("200", vec![("application/xml", None)]),
+        ("GET", "/robots.txt") => ("200", vec![("text/plain", None)]),
+        _ => ("2XX", vec![("application/json", None)]),
+    }
+}
+
+fn tag(path: &str) -> &str {
+    match path.split('/').nth(1) {
+        Some("") | None => "index",
+        Some(first) => first,
+    }
+}
+
+fn operation(e: &Endpoint) -> utoipa::openapi::path::Operation {
+    let (status, bodies) = success(e);
+    let ok = bodies.into_iter().fold(ResponseBuilder::new().description("Success"), |r, (ct, schema)| {
+        r.content(ct, ContentBuilder::new().schema(schema).build())
+    });
+    let error = || RefOr::from(Ref::from_response_name("Error"));
+    let mut op = OperationBuilder::new()
+        .tag(tag(e.path))
+        .summary(Some(e.summary))
+        .parameters(Some(path_params(e.path).into_iter().chain(query_params(e))))
+        .request_body(request_body(e))
+        .responses(
+            ResponsesBuilder::new()
+                .response(status, ok)
+                .response("4XX", error())
+                .response("5XX", error()),
+        );
+    if e.scope != Scope::Public {
+        op = op.security(SecurityRequirement::new("bearer", [e.scope.as_str()]));
+        if e.signed {
+            op = op.description(Some("Also accepts a signed URL (`expires`, `signature`) instead of a key."));
+        }
+    }
+    if e.deprecated.is_some() {
+        op = op.deprecated(Some(Deprecated::True));
+    }
+    op.build()
+}
+
+fn method(m: &str) -> Option<HttpMethod> {
+    match m {
+        "GET" => Some(HttpMethod::Get),
+        "POST" => Some(HttpMethod::Post),
+        "PUT" => Some(HttpMethod::Put),
+        "DELETE" => Some(HttpMethod::Delete),
+        "PATCH" => Some(HttpMethod::Patch),
+        _ => None,
+    }
+}
+
+pub fn document() -> OpenApi {
+    let mut paths = Paths::new();
+    for e in ENDPOINTS {
+        if let Some(m) = method(e.method) {
+            paths.add_path_operation(openapi_path(e.path), vec![m], operation(e));
+        }
+    }
+    let error_body = ContentBuilder::new().schema(Some(Ref::from_schema_name(ErrorBody::name()))).build();
+    let components = ComponentsBuilder::new()
+        .schema_from::<Country>()
+        .schema_from::<ErrorBody>()
+        .schema_from::<jobs::CreateJob>()
+        .schema_from::<exports::CreateExportJob>()
+        .schema_from::<exports::DestinationBody>()
+        .schema_from::<aliases::PutAlias>()
+        .schema_from::<webhooks::CreateWebhook>()
+        .schema_from::<rates::RateOverride>()
+        .schema_from::<admin::SignedUrlBody>()
+        .response("Error", ResponseBuilder::new().description("Error").content("application/json", error_body))
+        .security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                Http::builder()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("`ADMIN_TOKEN`, or an `API_KEYS` key with the listed scope"))
+                    .build(),
+            ),
+        )
+        .build();
+    OpenApiBuilder::new()
+        .info(
+            InfoBuilder::new()
+                .title("Country Currency API")
+                .version(env!("CARGO_PKG_VERSION"))
+                .description(Some("Countries with their currencies and exchange rates, cached from upstream")),
+        )
+        .paths(paths)
+        .components(Some(components))
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_registry_route_is_documented() {
+        let doc = document();
+        for e in ENDPOINTS {
+            let m = method(e.method).unwrap();
+            let op = doc.paths.get_path_operation(openapi_path(e.path), m);
+            assert!(op.is_some(), "{} {}", e.method, e.path);
+        }
+        assert_eq!(openapi_path("/countries/:name/tags/:tag"), "/countries/{name}/tags/{tag}");
+        assert_eq!(tag("/"), "index");
+        assert_eq!(tag("/countries/:name"), "countries");
+    }
+}
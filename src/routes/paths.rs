@@ -0,0 +1,33 @@
+// Route templates shared by the router and by link generation (`_links`, JSON:API),
+// so a renamed route can't leave stale links behind.
+
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+pub const COUNTRIES: &str = "/countries";
+pub const COUNTRY_AUTOCOMPLETE: &str = "/countries/autocomplete";
+pub const COUNTRY_MISSING_RATES: &str = "/countries/missing-rates";
+pub const COUNTRY_CHECKSUM: &str = "/countries/checksum";
+pub const COUNTRY_BUNDLE: &str = "/countries/bundle";
+pub const COUNTRY: &str = "/countries/:name";
+pub const COUNTRY_IMAGE: &str = "/countries/:name/image";
+pub const COUNTRY_DIFF: &str = "/countries/:name/diff";
+pub const COUNTRY_POPULATION_HISTORY: &str = "/countries/:name/population/history";
+pub const COUNTRY_TAGS: &str = "/countries/:name/tags";
+pub const COUNTRY_TAG: &str = "/countries/:name/tags/:tag";
+pub const CAPITAL: &str = "/capitals/:name";
+
+/// Fills `:param` segments of `template`, percent-encoding each value.
+pub fn link(template: &str, params: &[(&str, &str)]) -> String {
+    template
+        .split('/')
+        .map(|seg| match seg.strip_prefix(':') {
+            Some(key) => params
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| utf8_percent_encode(v, NON_ALPHANUMERIC).to_string())
+                .unwrap_or_else(|| seg.to_string()),
+            None => seg.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,244 @@
+// Human-readable list of every route, served by `GET /` (handlers::index). Add an entry
+// here whenever a route is added to `routes::router`.
+//
+// The scope an API key needs for each route is declared here too (`utils::auth`): reads
+// need `read`, other methods `write`, and `admin` entries `admin`, unless the entry says
+// otherwise with `.scope(...)`. Keys limited to some countries may only call routes
+// marked `.filtered()`, which apply the limit to what they return.
+//
+// Deprecations are declared here too, with `.deprecated(...)` on an entry or in
+// `DEPRECATED_PARAMS`; `utils::deprecation` turns them into response headers.
+
+use super::paths;
+use crate::utils::auth::Scope;
+
+#[derive(serde::Serialize)]
+pub struct Endpoint {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub summary: &'static str,
+    /// Needs `Authorization: Bearer <ADMIN_TOKEN>`
+    pub admin: bool,
+    /// What an API key needs to call it (`API_KEYS`)
+    pub scope: Scope,
+    /// Accepts a signed, expiring URL instead of a key (`utils::signed_url`)
+    pub signed: bool,
+    /// Serves keys limited to some countries, showing them only those (`region=` / `tag=`)
+    pub filtered: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<Deprecation>,
+}
+
+#[derive(Clone, Copy, Debug, serde::Serialize)]
+pub struct Deprecation {
+    /// Date (YYYY-MM-DD, UTC) it was deprecated: the `Deprecation` header
+    pub since: &'static str,
+    /// Date (YYYY-MM-DD, UTC) it stops working: the `Sunset` header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sunset: Option<&'static str>,
+    /// What to use instead: a `Link: <...>; rel="successor-version"` header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub successor: Option<&'static str>,
+}
+
+/// A query parameter that is deprecated on one route.
+#[derive(serde::Serialize)]
+pub struct DeprecatedParam {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub param: &'static str,
+    pub deprecation: Deprecation,
+}
+
+const fn ep(method: &'static str, path: &'static str, summary: &'static str) -> Endpoint {
+    let scope = if matches!(method.as_bytes(), b"GET") { Scope::Read } else { Scope::Write };
+    Endpoint { method, path, summary, admin: false, scope, signed: false, filtered: false, deprecated: None }
+}
+
+const fn admin(method: &'static str, path: &'static str, summary: &'static str) -> Endpoint {
+    let scope = Scope::Admin;
+    Endpoint { method, path, summary, admin: true, scope, signed: false, filtered: false, deprecated: None }
+}
+
+impl Endpoint {
+    const fn scope(mut self, scope: Scope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    const fn signed(mut self) -> Self {
+        self.signed = true;
+        self
+    }
+
+    const fn filtered(mut self) -> Self {
+        self.filtered = true;
+        self
+    }
+
+    /// e.g. `ep("GET", "/old", "...").deprecated("2026-01-01", Some("2026-07-01"), Some("/new"))`
+    #[allow(dead_code)]
+    const fn deprecated(
+        mut self,
+        since: &'static str,
+        sunset: Option<&'static str>,
+        successor: Option<&'static str>,
+    ) -> Self {
+        self.deprecated = Some(Deprecation { since, sunset, successor });
+        self
+    }
+}
+
+/// Deprecated query parameters. Requests that use one get the headers of its entry.
+pub const DEPRECATED_PARAMS: &[DeprecatedParam] = &[];
+
+/// Whether `path` fits `template` (`:param` segments match any one segment).
+fn matches(template: &str, path: &str) -> bool {
+    let t: Vec<&str> = template.split('/').collect();
+    let p: Vec<&str> = path.split('/').collect();
+    t.len() == p.len() && t.iter().zip(&p).all(|(t, p)| t.starts_with(':') || t == p)
+}
+
+/// Registry entry serving `method path`. A literal route wins over a parameterized one
+/// (`/countries/bundle` over `/countries/:name`), as in the router.
+pub fn lookup(method: &str, path: &str) -> Option<&'static Endpoint> {
+    ENDPOINTS
+        .iter()
+        .filter(|e| e.method == method && matches(e.path, path))
+        .min_by_key(|e| e.path.matches(':').count())
+}
+
+/// Deprecated parameters present in `query_keys` for the route `endpoint`.
+pub fn deprecated_params<'a>(
+    endpoint: &'a Endpoint,
+    query_keys: &'a [String],
+) -> impl Iterator<Item = &'static DeprecatedParam> + 'a {
+    DEPRECATED_PARAMS.iter().filter(move |d| {
+        d.method == endpoint.method && d.path == endpoint.path && query_keys.iter().any(|k| k == d.param)
+    })
+}
+
+pub const ENDPOINTS: &[Endpoint] = &[
+    ep("GET", "/", "This index").scope(Scope::Public),
+    ep("GET", "/openapi.json", "OpenAPI 3.1 document of this API").scope(Scope::Public),
+    ep("GET", "/docs", "Swagger UI for /openapi.json").scope(Scope::Public),
+    ep("GET", "/docs/:file", "Swagger UI assets").scope(Scope::Public),
+    ep("GET", "/sitemap.xml", "Country page URLs for crawlers (needs PUBLIC_BASE_URL)").scope(Scope::Public),
+    ep("GET", "/robots.txt", "Crawler rules").scope(Scope::Public),
+    ep("GET", "/status", "Country count, last refresh, migrations, completeness, summary image health"),
+    ep("POST", "/countries/refresh", "Fetch countries and rates, upsert, rebuild the summary image"),
+    ep("GET", paths::COUNTRIES, "List countries (?region, ?currency, ?tag, ?sort, ?page, ?limit, ?locale)").filtered(),
+    ep("GET", paths::COUNTRY_AUTOCOMPLETE, "Name suggestions for a prefix").filtered(),
+    ep("GET", paths::COUNTRY_MISSING_RATES, "Countries without an exchange rate, and why").filtered(),
+    ep("GET", paths::COUNTRY_CHECKSUM, "SHA-256 of the dataset, overall and per region").filtered(),
+    ep("GET", paths::COUNTRY_BUNDLE, "The latest refresh as one tar archive").scope(Scope::Export).signed().filtered(),
+    ep("GET", paths::COUNTRY, "One country by name or alias").filtered(),
+    ep("DELETE", paths::COUNTRY, "Delete a country (?confirm=<name>)"),
+    ep("GET", paths::COUNTRY_IMAGE, "Per-country card image").signed().filtered(),
+    ep("GET", paths::COUNTRY_DIFF, "Field-level changes between two refresh runs").filtered(),
+    ep("GET", paths::COUNTRY_POPULATION_HISTORY, "Recorded population values and growth").filtered(),
+    ep("GET", paths::COUNTRY_TAGS, "Tags of one country").filtered(),
+    admin("PUT", paths::COUNTRY_TAG, "Tag a country"),
+    admin("DELETE", paths::COUNTRY_TAG, "Remove a tag"),
+    ep("GET", "/tags", "Every tag in use, with counts"),
+    ep("GET", "/regions", "Regions with country count, total population and summed GDP").filtered(),
+    ep("GET", "/currencies", "Currency codes with their rate and the countries using them").filtered(),
+    ep("GET", "/stats", "Totals, exchange rate spread, GDP top and bottom, missing data").filtered(),
+    ep("GET", paths::CAPITAL, "Countries by capital").filtered(),
+    ep("GET", "/refresh/history", "Refresh runs with their cost, per day and in total"),
+    ep("GET", "/refresh/wait", "Block until the next refresh run finishes (?timeout=30s)"),
+    ep("GET", "/refresh/:run_id/changes", "Countries inserted or changed by a run"),
+    ep("GET", "/refresh/:run_id/raw", "Raw upstream payload of a run").scope(Scope::Export),
+    ep("GET", "/map", "Choropleth PNG of a metric (country shapes, or a tile grid without GeoJSON)"),
+    ep("GET", "/countries/image", "Summary image (PNG or SVG, localized)").signed(),
+    ep("GET", "/countries/image/meta", "Refresh run and timestamp the summary image was rendered from"),
+    ep("GET", "/countries/flags/sprite", "All cached flags in one PNG"),
+    ep("GET", "/countries/flags/sprite.json", "Sprite coordinates per country"),
+    admin("GET", "/webhooks", "List webhook subscriptions"),
+    admin("POST", "/webhooks", "Subscribe a URL to events"),
+    admin("DELETE", "/webhooks/:id", "Unsubscribe"),
+    admin("POST", "/webhooks/:id/secret", "Rotate the signing secret"),
+    admin("GET", "/webhooks/:id/deliveries", "Delivery attempts of a subscription"),
+    admin("POST", "/webhooks/:id/deliveries/:delivery_id/replay", "Send a delivery again"),
+    admin("GET", "/rates", "Exchange rate overrides"),
+    admin("PUT", "/rates/:code", "Pin an exchange rate"),
+    admin("DELETE", "/rates/:code", "Remove a rate override"),
+    ep("GET", "/rates/:code/history", "Daily exchange rate history (?from=&to=, YYYY-MM-DD)"),
+    admin("GET", "/aliases", "Alternate country names"),
+    admin("PUT", "/aliases/:alias", "Add an alias"),
+    admin("DELETE", "/aliases/:alias", "Remove an alias"),
+    admin("GET", "/admin/overview", "Ops dashboard: freshness, runs, quarantine, jobs, webhooks, image health"),
+    admin("POST", "/admin/reload-config", "Re-read runtime settings from .env"),
+    admin("POST", "/admin/signed-urls", "Signed, expiring link to an image, the bundle or an export file"),
+    admin("GET", "/admin/audit", "Latest deletes with row snapshots"),
+    admin("GET", "/admin/actors/:ip", "Audit entries attributable to a client address"),
+    admin("DELETE", "/admin/actors/:ip", "Remove a client address from the audit log"),
+    admin("GET", "/admin/cache", "Image variant cache stats"),
+    admin("POST", "/admin/cache/clear", "Empty the image variant cache"),
+    admin("GET", "/admin/data-quality", "Consistency checks over the cached countries"),
+    admin("GET", "/admin/exports", "Scheduled export jobs"),
+    admin("POST", "/admin/exports", "Create an export job"),
+    admin("DELETE", "/admin/exports/:id", "Delete an export job"),
+    admin("POST", "/admin/exports/:id/run", "Run an export job now"),
+    admin("GET", "/admin/exports/:id/runs", "Run history of an export job"),
+    ep("GET", "/jobs", "Background jobs (?status, ?kind)"),
+    admin("POST", "/jobs", "Queue a refresh, export or render_images job"),
+    ep("GET", "/jobs/:id", "Status, progress and result of a job"),
+    admin("DELETE", "/jobs/:id", "Cancel a queued job or stop a running one"),
+    ep("GET", "/health/live", "Liveness probe").scope(Scope::Public),
+    ep("GET", "/health/ready", "Readiness probe").scope(Scope::Public),
+    ep("GET", "/health/started", "Startup probe").scope(Scope::Public),
+    ep("GET", "/healthz", "Readiness probe (alias)").scope(Scope::Public),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn literal_routes_win() {
+        assert_eq!(lookup("GET", "/countries/bundle").unwrap().path, paths::COUNTRY_BUNDLE);
+        assert_eq!(lookup("GET", "/countries/Nigeria").unwrap().path, paths::COUNTRY);
+        assert_eq!(lookup("DELETE", "/countries/Nigeria").unwrap().method, "DELETE");
+        assert!(lookup("GET", "/countries/Nigeria/nope").is_none());
+    }
+
+    #[test]
+    fn deprecation_dates_parse() {
+        let all = ENDPOINTS
+            .iter()
+            .filter_map(|e| e.deprecated)
+            .chain(DEPRECATED_PARAMS.iter().map(|d| d.deprecation));
+        for d in all {
+            for date in std::iter::once(d.since).chain(d.sunset) {
+                assert!(chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok(), "{}", date);
+            }
+        }
+        for d in DEPRECATED_PARAMS {
+            assert!(lookup(d.method, d.path).is_some_and(|e| e.path == d.path), "{} {}", d.method, d.path);
+        }
+    }
+
+    #[test]
+    fn entries_are_unique_routes() {
+        let mut seen = HashSet::new();
+        for e in ENDPOINTS {
+            assert!(e.path.starts_with('/'), "{}", e.path);
+            assert!(seen.insert((e.method, e.path)), "duplicate {} {}", e.method, e.path);
+        }
+    }
+
+    #[test]
+    fn scopes() {
+        for e in ENDPOINTS {
+            assert!(!e.admin || e.scope == Scope::Admin, "{} {}", e.method, e.path);
+            assert!(e.method == "GET" || matches!(e.scope, Scope::Write | Scope::Admin), "{} {}", e.method, e.path);
+            assert!(!e.path.starts_with("/admin/") || e.scope == Scope::Admin, "{} {}", e.method, e.path);
+        }
+        assert_eq!(lookup("POST", "/countries/refresh").unwrap().scope, Scope::Write);
+        assert_eq!(lookup("DELETE", "/countries/Nigeria").unwrap().scope, Scope::Write);
+        assert_eq!(lookup("GET", "/health/ready").unwrap().scope, Scope::Public);
+        assert!(ENDPOINTS.iter().filter(|e| e.signed).all(|e| e.method == "GET" && e.scope != Scope::Admin));
+    }
+}
@@ -0,0 +1,136 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use tokio::time::Instant;
+use tracing::{error, info};
+
+use crate::config::AppState;
+use crate::services::job_queue::{self, JobKind};
+
+/// Token bucket: holds up to `capacity` tokens, refilled at one per `interval`.
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    interval: Duration,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: u32, interval: Duration) -> Self {
+        Self {
+            capacity: capacity as f64,
+            tokens: capacity as f64,
+            interval,
+            last: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let earned = now.duration_since(self.last).as_secs_f64() / self.interval.as_secs_f64().max(1e-3);
+        self.tokens = (self.tokens + earned).min(self.capacity);
+        self.last = now;
+    }
+
+    fn available(&mut self) -> bool {
+        self.refill();
+        self.tokens >= 1.0
+    }
+
+    fn try_take(&mut self) -> bool {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Time until the next token; zero when one is available.
+    fn wait(&mut self) -> Duration {
+        self.refill();
+        self.interval.mul_f64((1.0 - self.tokens).max(0.0))
+    }
+}
+
+/// Politeness throttle for every refresh, manual or automatic: at most `burst`
+/// attempts back to back, then one per `min_interval` (`REFRESH_MIN_INTERVAL_SECS`).
+/// Keeps a runaway cron job from getting our IP banned by the upstream APIs.
+#[derive(Clone)]
+pub struct RefreshThrottle(Arc<Mutex<TokenBucket>>);
+
+impl RefreshThrottle {
+    pub fn new(burst: u32, min_interval: Duration) -> Self {
+        Self(Arc::new(Mutex::new(TokenBucket::new(burst.max(1), min_interval))))
+    }
+
+    /// Takes a slot, or returns how long until the next one.
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let mut b = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if b.try_take() {
+            Ok(())
+        } else {
+            Err(b.wait())
+        }
+    }
+}
+
+/// Stale-data auto-refresh kicked from read paths (`AUTO_REFRESH_ON_STALE`).
+///
+/// A single-token bucket refilled once per staleness window caps it at one
+/// background refresh per window, however many reads see stale data — e.g. the
+/// burst of traffic that arrives after a long idle period.
+#[derive(Clone)]
+pub struct AutoRefresh(Arc<Mutex<TokenBucket>>);
+
+impl AutoRefresh {
+    pub fn new(window: Duration) -> Self {
+        Self(Arc::new(Mutex::new(TokenBucket::new(1, window))))
+    }
+
+    fn available(&self) -> bool {
+        self.0.lock().map(|mut b| b.available()).unwrap_or(false)
+    }
+
+    fn try_take(&self) -> bool {
+        self.0.lock().map(|mut b| b.try_take()).unwrap_or(false)
+    }
+}
+
+/// Called from GET handlers: if the data is stale and the bucket has a token,
+/// queues a refresh job (`services::job_queue`). Never fails the read.
+pub async fn maybe_refresh(state: &AppState) {
+    if !state.runtime.load().auto_refresh_on_stale || !state.refresh_window.allows(Utc::now()) {
+        return;
+    }
+    let gate = &state.auto_refresh;
+    // Cheap check first so hot read paths don't hit app_meta while throttled
+    if !gate.available() {
+        return;
+    }
+
+    let ts: Option<(String,)> =
+        match sqlx::query_as("SELECT v FROM app_meta WHERE k='last_refreshed_at'")
+            .fetch_optional(&state.pool)
+            .await
+        {
+            Ok(ts) => ts,
+            Err(e) => {
+                error!("auto-refresh: staleness check failed: {}", e);
+                return;
+            }
+        };
+    let stale = ts
+        .and_then(|(v,)| DateTime::parse_from_rfc3339(&v).ok())
+        .map(|t| (Utc::now() - t.with_timezone(&Utc)).num_seconds() > state.runtime.load().stale_after_secs as i64)
+        .unwrap_or(true);
+    if !stale || !gate.try_take() {
+        return;
+    }
+
+    match job_queue::enqueue(&state.pool, &JobKind::Refresh { force: false }).await {
+        Ok(id) => info!("auto-refresh: data is stale, queued refresh job {}", id),
+        Err(e) => error!("auto-refresh: could not queue a refresh: {}", e),
+    }
+}
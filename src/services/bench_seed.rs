@@ -0,0 +1,118 @@
+// `country-currency-api --bench-seed <n>`: loads `n` synthetic countries so `benches/`
+// can measure the listing and upsert paths at table sizes the real dataset (~250 rows)
+// never reaches. Seeded rows carry `data_source = 'bench'`; each run replaces the
+// previous ones and leaves real rows alone, and `--bench-seed 0` removes them.
+
+use sqlx::QueryBuilder;
+
+use crate::config::MIGRATOR;
+use crate::db::{self, Db};
+
+pub const DATA_SOURCE: &str = "bench";
+
+const REGIONS: [&str; 6] = ["Africa", "Americas", "Asia", "Europe", "Oceania", "Polar"];
+const CURRENCIES: [(&str, f64); 8] = [
+    ("NGN", 1600.0),
+    ("GHS", 15.34),
+    ("EUR", 0.92),
+    ("USD", 1.0),
+    ("JPY", 151.2),
+    ("BRL", 5.05),
+    ("INR", 83.4),
+    ("AUD", 1.52),
+];
+
+/// Rows per INSERT; well under MySQL's 65535 placeholder limit
+const CHUNK: usize = 500;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedCountry {
+    pub name: String,
+    pub capital: String,
+    pub region: &'static str,
+    pub population: i64,
+    /// Every 10th row has no currency, like the handful of real ones
+    pub currency: Option<(&'static str, f64)>,
+    pub estimated_gdp: Option<f64>,
+}
+
+/// Deterministic rows: the same `n` always yields the same table, so runs compare.
+pub fn synthetic(n: usize) -> Vec<SeedCountry> {
+    (0..n)
+        .map(|i| {
+            let population = 1_000 + (i as i64 * 7_919) % 200_000_000;
+            let currency = (i % 10 != 9).then(|| CURRENCIES[i % CURRENCIES.len()]);
+            SeedCountry {
+                name: format!("Benchland {:06}", i),
+                capital: format!("Bench City {:06}", i),
+                region: REGIONS[i % REGIONS.len()],
+                population,
+                currency,
+                // Fixed multiplier instead of the refresh's random 1000..2000
+                estimated_gdp: currency.map(|(_, rate)| population as f64 * 1500.0 / rate),
+            }
+        })
+        .collect()
+}
+
+pub async fn run(database_url: &str, args: &[String]) -> Result<(), anyhow::Error> {
+    let n: usize = args
+        .first()
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| anyhow::anyhow!("usage: country-currency-api --bench-seed <rows>"))?;
+    let pool = db::connect(database_url).await?;
+    MIGRATOR.run(&pool).await?;
+
+    let mut tx = db::begin(&pool).await?;
+    let removed = sqlx::query("DELETE FROM countries WHERE data_source = ?")
+        .bind(DATA_SOURCE)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    for chunk in synthetic(n).chunks(CHUNK) {
+        let mut qb = QueryBuilder::<Db>::new(
+            "INSERT INTO countries (name, capital, region, population, currency_code, exchange_rate, \
+             estimated_gdp, flag_url, data_source, rate_missing_reason) ",
+        );
+        qb.push_values(chunk, |mut b, c| {
+            b.push_bind(&c.name)
+                .push_bind(&c.capital)
+                .push_bind(c.region)
+                .push_bind(c.population)
+                .push_bind(c.currency.map(|(code, _)| code))
+                .push_bind(c.currency.map(|(_, rate)| rate))
+                .push_bind(c.estimated_gdp)
+                .push_bind(None::<String>)
+                .push_bind(DATA_SOURCE)
+                .push_bind(c.currency.is_none().then_some("no_currency"));
+        });
+        qb.build().execute(&mut *tx).await?;
+    }
+    // So `auto_refresh` doesn't replace the seeded table on the first request
+    sqlx::query("REPLACE INTO app_meta (k, v) VALUES ('last_refreshed_at', ?)")
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM countries").fetch_one(&pool).await?;
+    println!("removed {} bench rows, seeded {}; countries now has {} rows", removed, n, total);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn rows_are_deterministic_and_unique() {
+        let rows = synthetic(1_000);
+        assert_eq!(rows, synthetic(1_000));
+        assert_eq!(rows.iter().map(|c| &c.name).collect::<HashSet<_>>().len(), 1_000);
+        assert!(rows.iter().all(|c| c.name.len() <= 128 && c.population > 0));
+        let without = rows.iter().filter(|c| c.currency.is_none()).count();
+        assert_eq!(without, 100);
+        assert!(rows.iter().all(|c| c.currency.is_some() == c.estimated_gdp.is_some()));
+    }
+}
@@ -0,0 +1,123 @@
+// `GET /countries/bundle`: one tar archive with everything an offline client needs,
+// built from a single read snapshot so the files always agree with each other.
+
+use flate2::{write::GzEncoder, Compression as GzLevel};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Readable everywhere (`tar xzf`, every HTTP client)
+    #[default]
+    Gzip,
+    /// Smaller and faster to unpack; for clients that ship a zstd decoder
+    Zstd,
+}
+
+impl Compression {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            _ => Err("compression must be one of gzip, zstd".into()),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::Gzip => "tar.gz",
+            Compression::Zstd => "tar.zst",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Compression::Gzip => "application/gzip",
+            Compression::Zstd => "application/zstd",
+        }
+    }
+}
+
+/// `manifest.json` entry for one bundled file.
+pub fn manifest_entry(name: &str, bytes: &[u8]) -> serde_json::Value {
+    serde_json::json!({
+        "name": name,
+        "bytes": bytes.len(),
+        "sha256": hex::encode(Sha256::digest(bytes)),
+    })
+}
+
+/// Tars `files` in order and compresses the archive. Every entry gets `mtime` (the
+/// refresh time), so the same data always produces the same tar.
+pub fn build(files: &[(&str, Vec<u8>)], mtime: u64, compression: Compression) -> std::io::Result<Vec<u8>> {
+    let mut tar = tar::Builder::new(Vec::new());
+    for (name, bytes) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(bytes.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_cksum();
+        tar.append_data(&mut header, name, bytes.as_slice())?;
+    }
+    let tar = tar.into_inner()?;
+
+    match compression {
+        Compression::Gzip => {
+            let mut enc = GzEncoder::new(Vec::new(), GzLevel::default());
+            enc.write_all(&tar)?;
+            enc.finish()
+        }
+        Compression::Zstd => zstd::encode_all(tar.as_slice(), 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn entries(tar_bytes: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut archive = tar::Archive::new(tar_bytes);
+        archive
+            .entries()
+            .unwrap()
+            .map(|e| {
+                let mut e = e.unwrap();
+                let name = e.path().unwrap().display().to_string();
+                let mut body = Vec::new();
+                e.read_to_end(&mut body).unwrap();
+                (name, body)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_with_both_compressions() {
+        let files = [("countries.json", b"[]".to_vec()), ("summary.png", vec![0x89, b'P', b'N', b'G'])];
+
+        let gz = build(&files, 1_700_000_000, Compression::Gzip).unwrap();
+        let mut tar_bytes = Vec::new();
+        flate2::read::GzDecoder::new(gz.as_slice()).read_to_end(&mut tar_bytes).unwrap();
+        assert_eq!(entries(&tar_bytes), files.iter().map(|(n, b)| (n.to_string(), b.clone())).collect::<Vec<_>>());
+
+        let zst = build(&files, 1_700_000_000, Compression::Zstd).unwrap();
+        let tar_bytes = zstd::decode_all(zst.as_slice()).unwrap();
+        assert_eq!(entries(&tar_bytes).len(), 2);
+    }
+
+    #[test]
+    fn same_input_same_bytes() {
+        let files = [("a.json", b"{}".to_vec())];
+        assert_eq!(
+            build(&files, 1, Compression::Zstd).unwrap(),
+            build(&files, 1, Compression::Zstd).unwrap()
+        );
+    }
+
+    #[test]
+    fn compression_names() {
+        assert_eq!(Compression::parse("ZST").unwrap(), Compression::Zstd);
+        assert_eq!(Compression::parse("gzip").unwrap().extension(), "tar.gz");
+        assert!(Compression::parse("brotli").is_err());
+    }
+}
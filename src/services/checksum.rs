@@ -0,0 +1,120 @@
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+use crate::models::country::Country;
+
+/// Bumped whenever the canonical row format below changes, so mirrors can tell
+/// "different data" from "different algorithm".
+pub const VERSION: u32 = 1;
+
+/// Rows without a region are grouped under this key.
+pub const NO_REGION: &str = "Unknown";
+
+#[derive(Debug, serde::Serialize)]
+pub struct DatasetChecksum {
+    pub algorithm: &'static str,
+    pub version: u32,
+    pub countries: usize,
+    pub sha256: String,
+    pub regions: BTreeMap<String, RegionChecksum>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct RegionChecksum {
+    pub countries: usize,
+    pub sha256: String,
+}
+
+/// One line per country: the data fields as a JSON array. Ids, refresh timestamps and
+/// provenance are left out, so a mirror with its own ids and load times still matches.
+fn canonical_line(c: &Country) -> String {
+    serde_json::json!([
+        c.name,
+        c.capital,
+        c.region,
+        c.population,
+        c.currency_code,
+        c.exchange_rate,
+        c.estimated_gdp,
+        c.flag_url,
+    ])
+    .to_string()
+}
+
+/// Hashes the dataset independent of row order: lines are sorted by name bytes
+/// (not the DB collation) before hashing.
+pub fn compute(countries: &[Country]) -> DatasetChecksum {
+    let mut rows: Vec<(&Country, String)> = countries.iter().map(|c| (c, canonical_line(c))).collect();
+    rows.sort_by(|a, b| a.0.name.as_bytes().cmp(b.0.name.as_bytes()));
+
+    let mut all = Sha256::new();
+    let mut regions: BTreeMap<String, (usize, Sha256)> = BTreeMap::new();
+    for (c, line) in &rows {
+        all.update(line.as_bytes());
+        all.update(b"\n");
+        let region = c.region.clone().unwrap_or_else(|| NO_REGION.into());
+        let (n, h) = regions.entry(region).or_insert_with(|| (0, Sha256::new()));
+        *n += 1;
+        h.update(line.as_bytes());
+        h.update(b"\n");
+    }
+
+    DatasetChecksum {
+        algorithm: "sha256",
+        version: VERSION,
+        countries: rows.len(),
+        sha256: hex::encode(all.finalize()),
+        regions: regions
+            .into_iter()
+            .map(|(r, (n, h))| (r, RegionChecksum { countries: n, sha256: hex::encode(h.finalize()) }))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn country(id: i64, name: &str, region: Option<&str>, rate: Option<f64>) -> Country {
+        Country {
+            id,
+            name: name.into(),
+            capital: None,
+            region: region.map(String::from),
+            population: 1_000,
+            currency_code: Some("XXX".into()),
+            exchange_rate: rate,
+            estimated_gdp: None,
+            flag_url: None,
+            flag_emoji: None,
+            last_refreshed_at: Some(format!("2026-01-0{}T00:00:00Z", id)),
+            data_source: "restcountries".into(),
+            source_fetched_at: None,
+            rate_source: None,
+        }
+    }
+
+    #[test]
+    fn ignores_order_ids_and_timestamps() {
+        let a = compute(&[country(1, "Ghana", Some("Africa"), Some(1.0)), country(2, "Chad", Some("Africa"), None)]);
+        let b = compute(&[country(7, "Chad", Some("Africa"), None), country(3, "Ghana", Some("Africa"), Some(1.0))]);
+        assert_eq!(a.sha256, b.sha256);
+        assert_eq!(a.regions["Africa"].sha256, b.regions["Africa"].sha256);
+        assert_eq!(a.countries, 2);
+    }
+
+    #[test]
+    fn data_change_only_moves_its_region() {
+        let a = compute(&[country(1, "Ghana", Some("Africa"), Some(1.0)), country(2, "Peru", Some("Americas"), None)]);
+        let b = compute(&[country(1, "Ghana", Some("Africa"), Some(1.5)), country(2, "Peru", Some("Americas"), None)]);
+        assert_ne!(a.sha256, b.sha256);
+        assert_ne!(a.regions["Africa"].sha256, b.regions["Africa"].sha256);
+        assert_eq!(a.regions["Americas"].sha256, b.regions["Americas"].sha256);
+    }
+
+    #[test]
+    fn missing_region_is_grouped() {
+        let c = compute(&[country(1, "Atlantis", None, None)]);
+        assert_eq!(c.regions[NO_REGION].countries, 1);
+    }
+}
@@ -0,0 +1,483 @@
+use sqlx::{Executor, QueryBuilder, Row};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::db::{Db, DbPool, DbRow};
+use crate::models::country::{flag_emoji, Country};
+use crate::types::query::{Cursor, ListParams, SortKey, SortLocale, SortOrder};
+use crate::{sql_iso, sql_like_escape};
+
+/// Columns [`country_from_row`] reads.
+pub const LIST_COLUMNS: &str = concat!(
+    "id,name,capital,region,population,currency_code,exchange_rate,estimated_gdp,flag_url,iso_code,",
+    sql_iso!("last_refreshed_at"), " as last_refreshed_at,",
+    "data_source,", sql_iso!("source_fetched_at"), " as source_fetched_at,rate_source"
+);
+
+/// The countries an API key may see (`region=` / `tag=` entries in `API_KEYS`). A country
+/// is visible when its region is one of `regions` and it carries one of `tags`; an empty
+/// list doesn't narrow. Every read that serves a restricted caller goes through
+/// [`Restriction::push_sql`] or [`visible`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Restriction {
+    pub regions: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+impl Restriction {
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty() && self.tags.is_empty()
+    }
+
+    /// ` AND ...` conditions on `countries` (unaliased).
+    pub fn push_sql<'a>(&'a self, qb: &mut QueryBuilder<'a, Db>) {
+        if !self.regions.is_empty() {
+            qb.push(" AND region IN (");
+            let mut list = qb.separated(", ");
+            for r in &self.regions {
+                list.push_bind(r.as_str());
+            }
+            list.push_unseparated(")");
+        }
+        if !self.tags.is_empty() {
+            qb.push(" AND name IN (SELECT country_name FROM country_tags WHERE tag IN (");
+            let mut list = qb.separated(", ");
+            for t in &self.tags {
+                list.push_bind(t.as_str());
+            }
+            list.push_unseparated("))");
+        }
+    }
+}
+
+/// Whether the country stored as `name` (matched case-insensitively) is visible under
+/// `restriction`. Always true for unrestricted callers, without a query.
+pub async fn visible(pool: &DbPool, restriction: Option<&Restriction>, name: &str) -> Result<bool, sqlx::Error> {
+    let Some(restriction) = restriction else {
+        return Ok(true);
+    };
+    let mut qb = QueryBuilder::new("SELECT COUNT(*) FROM countries WHERE LOWER(name) = LOWER(");
+    qb.push_bind(name).push(")");
+    restriction.push_sql(&mut qb);
+    let (n,): (i64,) = qb.build_query_as().fetch_one(pool).await?;
+    Ok(n > 0)
+}
+
+/// Names of the countries visible under `restriction`, for filtering rows that don't come
+/// from `countries` itself.
+pub async fn visible_names(pool: &DbPool, restriction: &Restriction) -> Result<HashSet<String>, sqlx::Error> {
+    let mut qb = QueryBuilder::new("SELECT name FROM countries WHERE 1=1");
+    restriction.push_sql(&mut qb);
+    let rows: Vec<(String,)> = qb.build_query_as().fetch_all(pool).await?;
+    Ok(rows.into_iter().map(|(n,)| n).collect())
+}
+
+/// One `GET /countries` listing: filters, order and page, independent of the HTTP layer.
+/// New filters go in [`CountryQuery::push_filters`] so the listing and its COUNT stay in step.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CountryQuery {
+    pub region: Option<String>,
+    pub currency: Option<String>,
+    /// Countries carrying this tag in `country_tags`
+    pub tag: Option<String>,
+    /// Free text found in `name` or `capital` (case- and accent-insensitive, by collation)
+    pub q: Option<String>,
+    pub sort: SortOrder,
+    pub locale: Option<SortLocale>,
+    pub limit: usize,
+    pub offset: usize,
+    /// Keyset paging: only rows after this one in `sort` order
+    pub after: Option<Cursor>,
+    /// The caller's key restriction, if any
+    pub restriction: Option<Arc<Restriction>>,
+}
+
+impl CountryQuery {
+    pub fn from_params(p: &ListParams) -> Self {
+        Self {
+            region: p.region.as_ref().map(|r| r.as_str().to_string()),
+            currency: p.currency.as_ref().map(|c| c.as_str().to_string()),
+            tag: p.tag.as_ref().map(|t| t.as_str().to_string()),
+            q: p.q.as_ref().map(|q| format!("%{}%", escape_like(q))),
+            sort: p.sort,
+            locale: p.locale,
+            limit: p.limit,
+            offset: p.offset(),
+            after: p.cursor.clone(),
+            restriction: None,
+        }
+    }
+
+    /// `(column, value)` equality filters, in SQL order.
+    fn filters(&self) -> Vec<(&'static str, &str)> {
+        let mut out = Vec::new();
+        if let Some(r) = &self.region {
+            out.push(("region", r.as_str()));
+        }
+        if let Some(c) = &self.currency {
+            out.push(("currency_code", c.as_str()));
+        }
+        out
+    }
+
+    fn push_filters<'a>(&'a self, qb: &mut QueryBuilder<'a, Db>) {
+        for (column, value) in self.filters() {
+            qb.push(" AND ").push(column).push(" = ").push_bind(value);
+        }
+        if let Some(tag) = &self.tag {
+            qb.push(" AND name IN (SELECT country_name FROM country_tags WHERE tag = ")
+                .push_bind(tag.as_str())
+                .push(")");
+        }
+        if let Some(pattern) = &self.q {
+            qb.push(" AND (name LIKE ")
+                .push_bind(pattern.as_str())
+                .push(concat!(sql_like_escape!(), " OR capital LIKE "))
+                .push_bind(pattern.as_str())
+                .push(concat!(sql_like_escape!(), ")"));
+        }
+        if let Some(r) = &self.restriction {
+            r.push_sql(qb);
+        }
+    }
+
+    /// ` AND ...` keeping the rows that come after `self.after` in `ORDER BY` order: a
+    /// greater (or, descending, smaller) sort value, or the same one and a greater id. NULL
+    /// GDPs sort first ascending and last descending, as MySQL orders them.
+    fn push_after<'a>(&'a self, qb: &mut QueryBuilder<'a, Db>) {
+        let Some(after) = &self.after else { return };
+        let name_col = match self.locale {
+            Some(locale) => locale.name_col(),
+            None => "name".to_string(),
+        };
+        // `Cursor::decode` only accepts keys of the query's own sort
+        match (self.sort, &after.key) {
+            (SortOrder::Id, _) => {
+                qb.push(" AND id > ").push_bind(after.id);
+            }
+            (SortOrder::GdpDesc, SortKey::Gdp(None)) => {
+                qb.push(" AND estimated_gdp IS NULL AND id > ").push_bind(after.id);
+            }
+            (SortOrder::GdpDesc, key) => {
+                push_past(qb, "estimated_gdp", "<", key, after.id);
+                qb.push(" OR estimated_gdp IS NULL)");
+            }
+            (SortOrder::GdpAsc, SortKey::Gdp(None)) => {
+                qb.push(" AND (estimated_gdp IS NOT NULL OR id > ").push_bind(after.id).push(")");
+            }
+            (SortOrder::GdpAsc, key) => {
+                push_past(qb, "estimated_gdp", ">", key, after.id);
+                qb.push(")");
+            }
+            (SortOrder::NameAsc, key) => {
+                push_past(qb, &name_col, ">", key, after.id);
+                qb.push(")");
+            }
+            (SortOrder::PopulationDesc, key) => {
+                push_past(qb, "population", "<", key, after.id);
+                qb.push(")");
+            }
+        }
+    }
+
+    /// The page of rows. `prefix` is prepended verbatim ("EXPLAIN " for query plans).
+    pub fn select(&self, prefix: &str) -> QueryBuilder<'_, Db> {
+        let mut qb = QueryBuilder::new(format!(
+            "{}SELECT {} FROM countries WHERE 1=1",
+            prefix, LIST_COLUMNS
+        ));
+        self.push_filters(&mut qb);
+        self.push_after(&mut qb);
+        match (self.sort, self.locale) {
+            (SortOrder::NameAsc, Some(locale)) => qb.push(locale.order_by_name()),
+            (sort, _) => qb.push(sort.order_by()),
+        };
+        qb.push(" LIMIT ").push_bind(self.limit as i64);
+        qb.push(" OFFSET ").push_bind(self.offset as i64);
+        qb
+    }
+
+    /// Total rows matching the filters, ignoring order and page.
+    pub fn count(&self) -> QueryBuilder<'_, Db> {
+        let mut qb = QueryBuilder::new("SELECT COUNT(*) FROM countries WHERE 1=1");
+        self.push_filters(&mut qb);
+        qb
+    }
+}
+
+/// `s` with the `LIKE` wildcards and the escape character taken literally.
+pub fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// ` AND (col <cmp> key OR (col = key AND id > id)`, left open for the caller to close.
+fn push_past<'a>(qb: &mut QueryBuilder<'a, Db>, col: &str, cmp: &str, key: &'a SortKey, id: i64) {
+    let push_key = |qb: &mut QueryBuilder<'a, Db>| {
+        match key {
+            SortKey::Id => qb.push("NULL"),
+            SortKey::Gdp(v) => qb.push_bind(*v),
+            SortKey::Name(n) => qb.push_bind(n.as_str()),
+            SortKey::Population(p) => qb.push_bind(*p),
+        };
+    };
+    qb.push(format!(" AND ({} {} ", col, cmp));
+    push_key(qb);
+    qb.push(format!(" OR ({} = ", col));
+    push_key(qb);
+    qb.push(" AND id > ").push_bind(id).push(")");
+}
+
+pub fn country_from_row(r: &DbRow) -> Country {
+    Country {
+        id: r.try_get::<i64, _>("id").unwrap_or_default(),
+        name: r.try_get::<String, _>("name").unwrap_or_default(),
+        capital: r.try_get::<Option<String>, _>("capital").ok().flatten(),
+        region: r.try_get::<Option<String>, _>("region").ok().flatten(),
+        population: r.try_get::<i64, _>("population").unwrap_or_default(),
+        currency_code: r.try_get::<Option<String>, _>("currency_code").ok().flatten(),
+        exchange_rate: r.try_get::<Option<f64>, _>("exchange_rate").ok().flatten(),
+        estimated_gdp: r.try_get::<Option<f64>, _>("estimated_gdp").ok().flatten(),
+        flag_url: r.try_get::<Option<String>, _>("flag_url").ok().flatten(),
+        flag_emoji: r.try_get::<Option<String>, _>("iso_code").ok().flatten().as_deref().and_then(flag_emoji),
+        last_refreshed_at: r
+            .try_get::<Option<String>, _>("last_refreshed_at")
+            .ok()
+            .flatten(),
+        data_source: r.try_get::<String, _>("data_source").unwrap_or_default(),
+        source_fetched_at: r
+            .try_get::<Option<String>, _>("source_fetched_at")
+            .ok()
+            .flatten(),
+        rate_source: r.try_get::<Option<String>, _>("rate_source").ok().flatten(),
+    }
+}
+
+/// Share of countries (percent, 0-100) with each field populated.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct Completeness {
+    pub countries: i64,
+    pub capital: f64,
+    pub currency: f64,
+    pub rate: f64,
+    pub flag: f64,
+    /// Mean of the four fields
+    pub overall: f64,
+}
+
+/// Over every country, or only those visible under `restriction`.
+pub async fn completeness<'e, E>(executor: E, restriction: Option<&Restriction>) -> Result<Completeness, sqlx::Error>
+where
+    E: Executor<'e, Database = Db>,
+{
+    let mut qb = QueryBuilder::new(
+        "SELECT COUNT(*) as total, \
+         CAST(COALESCE(SUM(capital IS NOT NULL AND capital <> ''), 0) AS SIGNED) as capital, \
+         CAST(COALESCE(SUM(currency_code IS NOT NULL), 0) AS SIGNED) as currency, \
+         CAST(COALESCE(SUM(exchange_rate IS NOT NULL), 0) AS SIGNED) as rate, \
+         CAST(COALESCE(SUM(flag_url IS NOT NULL AND flag_url <> ''), 0) AS SIGNED) as flag \
+         FROM countries WHERE 1=1",
+    );
+    if let Some(r) = restriction {
+        r.push_sql(&mut qb);
+    }
+    let row = qb.build().fetch_one(executor).await?;
+    let total: i64 = row.try_get("total").unwrap_or_default();
+    if total == 0 {
+        return Ok(Completeness::default());
+    }
+    let pct = |col: &str| {
+        let n: i64 = row.try_get(col).unwrap_or_default();
+        (n as f64 * 10_000.0 / total as f64).round() / 100.0
+    };
+    let (capital, currency, rate, flag) = (pct("capital"), pct("currency"), pct("rate"), pct("flag"));
+    Ok(Completeness {
+        countries: total,
+        capital,
+        currency,
+        rate,
+        flag,
+        overall: ((capital + currency + rate + flag) * 25.0).round() / 100.0,
+    })
+}
+
+pub async fn list(pool: &DbPool, q: &CountryQuery) -> Result<Vec<Country>, sqlx::Error> {
+    let rows = q.select("").build().fetch_all(pool).await?;
+    Ok(rows.iter().map(country_from_row).collect())
+}
+
+/// Every country (visible under `restriction`), unpaged, for whole-dataset views like the
+/// checksum or the bundle.
+pub async fn all<'e, E>(executor: E, restriction: Option<&Restriction>) -> Result<Vec<Country>, sqlx::Error>
+where
+    E: Executor<'e, Database = Db>,
+{
+    let mut qb = QueryBuilder::new(format!("SELECT {} FROM countries WHERE 1=1", LIST_COLUMNS));
+    if let Some(r) = restriction {
+        r.push_sql(&mut qb);
+    }
+    qb.push(" ORDER BY name ASC");
+    let rows = qb.build().fetch_all(executor).await?;
+    Ok(rows.iter().map(country_from_row).collect())
+}
+
+pub async fn count(pool: &DbPool, q: &CountryQuery) -> Result<i64, sqlx::Error> {
+    let (total,): (i64,) = q.count().build_query_as().fetch_one(pool).await?;
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::query::FromQuery;
+
+    const SORTS: [SortOrder; 5] = [
+        SortOrder::Id,
+        SortOrder::GdpDesc,
+        SortOrder::GdpAsc,
+        SortOrder::NameAsc,
+        SortOrder::PopulationDesc,
+    ];
+
+    fn query(region: Option<&str>, currency: Option<&str>, sort: SortOrder) -> CountryQuery {
+        CountryQuery {
+            region: region.map(String::from),
+            currency: currency.map(String::from),
+            tag: None,
+            q: None,
+            sort,
+            locale: None,
+            limit: 50,
+            offset: 100,
+            after: None,
+            restriction: None,
+        }
+    }
+
+    fn where_clause(region: bool, currency: bool) -> String {
+        let mut w = String::from(" WHERE 1=1");
+        if region {
+            w.push_str(" AND region = ?");
+        }
+        if currency {
+            w.push_str(" AND currency_code = ?");
+        }
+        w
+    }
+
+    #[test]
+    fn select_sql_for_every_filter_and_sort() {
+        for region in [None, Some("Africa")] {
+            for currency in [None, Some("NGN")] {
+                for sort in SORTS {
+                    let q = query(region, currency, sort);
+                    let expected = format!(
+                        "SELECT {} FROM countries{}{} LIMIT ? OFFSET ?",
+                        LIST_COLUMNS,
+                        where_clause(region.is_some(), currency.is_some()),
+                        sort.order_by()
+                    );
+                    assert_eq!(q.select("").sql(), expected, "{:?}", q);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn count_sql_ignores_sort_and_page() {
+        for region in [None, Some("Africa")] {
+            for currency in [None, Some("NGN")] {
+                let expected = format!(
+                    "SELECT COUNT(*) FROM countries{}",
+                    where_clause(region.is_some(), currency.is_some())
+                );
+                for sort in SORTS {
+                    assert_eq!(query(region, currency, sort).count().sql(), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn filter_values_are_bound_not_inlined() {
+        let q = query(Some("Africa'; DROP TABLE countries; --"), Some("NGN"), SortOrder::NameAsc);
+        assert!(!q.select("").sql().contains("Africa"));
+        assert!(!q.count().sql().contains("NGN"));
+        assert_eq!(
+            q.filters(),
+            vec![("region", "Africa'; DROP TABLE countries; --"), ("currency_code", "NGN")]
+        );
+    }
+
+    #[test]
+    fn tag_filters_through_country_tags() {
+        let mut q = query(Some("Africa"), None, SortOrder::Id);
+        q.tag = Some("sahel".into());
+        let tail = " AND region = ? AND name IN (SELECT country_name FROM country_tags WHERE tag = ?)";
+        assert!(q.select("").sql().contains(&format!("WHERE 1=1{} ORDER BY", tail)));
+        assert_eq!(q.count().sql(), format!("SELECT COUNT(*) FROM countries WHERE 1=1{}", tail));
+        assert!(!q.select("").sql().contains("sahel"));
+    }
+
+    #[test]
+    fn search_matches_name_or_capital_with_wildcards_escaped() {
+        let raw = serde_json::from_value(serde_json::json!({ "q": "  50%_off\\ ", "region": "Africa" })).unwrap();
+        let p = ListParams::from_raw(raw).unwrap();
+        let q = CountryQuery::from_params(&p);
+        assert_eq!(q.q.as_deref(), Some("%50\\%\\_off\\\\%"));
+        let tail = concat!(
+            " AND region = ? AND (name LIKE ?",
+            sql_like_escape!(),
+            " OR capital LIKE ?",
+            sql_like_escape!(),
+            ")"
+        );
+        assert!(q.select("").sql().contains(&format!("WHERE 1=1{} ORDER BY", tail)));
+        assert_eq!(q.count().sql(), format!("SELECT COUNT(*) FROM countries WHERE 1=1{}", tail));
+    }
+
+    #[test]
+    fn restriction_narrows_listing_and_count() {
+        let mut q = query(None, None, SortOrder::Id);
+        q.restriction = Some(Arc::new(Restriction {
+            regions: vec!["Africa".into(), "Europe".into()],
+            tags: vec!["eu".into()],
+        }));
+        let tail = " AND region IN (?, ?) AND name IN (SELECT country_name FROM country_tags WHERE tag IN (?))";
+        assert!(q.select("").sql().contains(&format!("WHERE 1=1{} ORDER BY", tail)));
+        assert_eq!(q.count().sql(), format!("SELECT COUNT(*) FROM countries WHERE 1=1{}", tail));
+
+        let tagged = Restriction { regions: vec![], tags: vec!["sahel".into()] };
+        let mut qb = QueryBuilder::new("x");
+        tagged.push_sql(&mut qb);
+        assert_eq!(qb.sql(), "x AND name IN (SELECT country_name FROM country_tags WHERE tag IN (?))");
+        assert!(Restriction::default().is_empty());
+    }
+
+    #[test]
+    fn explain_prefix_wraps_the_same_query() {
+        let q = query(Some("Europe"), None, SortOrder::GdpDesc);
+        assert_eq!(q.select("EXPLAIN ").sql(), format!("EXPLAIN {}", q.select("").sql()));
+    }
+
+    #[test]
+    #[cfg(not(feature = "sqlite"))]
+    fn locale_collates_name_order_only() {
+        let mut q = query(Some("Europe"), None, SortOrder::NameAsc);
+        q.locale = Some(SortLocale::Sv);
+        assert!(q
+            .select("")
+            .sql()
+            .ends_with(" ORDER BY CONVERT(name USING utf8mb4) COLLATE utf8mb4_sv_0900_ai_ci ASC, id ASC LIMIT ? OFFSET ?"));
+
+        q.sort = SortOrder::GdpDesc;
+        assert!(q.select("").sql().contains(SortOrder::GdpDesc.order_by()));
+        assert!(!q.count().sql().contains("COLLATE"));
+    }
+
+    #[test]
+    fn every_order_ends_on_id() {
+        for sort in SORTS {
+            assert!(sort.order_by().ends_with("id ASC"), "{:?}", sort);
+        }
+    }
+}
@@ -0,0 +1,260 @@
+// `GET /admin/data-quality`: consistency checks over the cached countries, so bad
+// upstream data shows up in monitoring before a user reports it.
+
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use crate::db::DbPool;
+
+/// Max concurrent flag probes
+const FLAG_PROBE_CONCURRENCY: usize = 16;
+/// Tukey fence multiplier on log10(GDP per capita); 3 keeps only far-out values
+const OUTLIER_FENCE: f64 = 3.0;
+
+pub struct CountryFacts {
+    pub name: String,
+    pub population: i64,
+    pub currency_code: Option<String>,
+    pub exchange_rate: Option<f64>,
+    pub estimated_gdp: Option<f64>,
+    pub flag_url: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    /// "ok" | "failed" | "skipped" (not requested)
+    pub status: &'static str,
+    pub count: usize,
+    pub issues: Vec<Value>,
+}
+
+impl Check {
+    fn from_issues(name: &'static str, issues: Vec<Value>) -> Self {
+        let status = if issues.is_empty() { "ok" } else { "failed" };
+        Check { name, status, count: issues.len(), issues }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    pub ok: bool,
+    pub countries: usize,
+    pub checks: Vec<Check>,
+}
+
+pub async fn load(pool: &DbPool) -> Result<Vec<CountryFacts>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT name, population, currency_code, exchange_rate, estimated_gdp, flag_url \
+         FROM countries ORDER BY name ASC",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|r| CountryFacts {
+            name: r.try_get("name").unwrap_or_default(),
+            population: r.try_get("population").unwrap_or_default(),
+            currency_code: r.try_get("currency_code").ok().flatten(),
+            exchange_rate: r.try_get("exchange_rate").ok().flatten(),
+            estimated_gdp: r.try_get("estimated_gdp").ok().flatten(),
+            flag_url: r.try_get("flag_url").ok().flatten(),
+        })
+        .collect())
+}
+
+/// Runs every check. Flag probes make one outbound request per country, so they only
+/// run when `probe_flags` is set.
+pub async fn run(http: &Client, countries: &[CountryFacts], probe_flags: bool) -> Report {
+    let mut checks = vec![
+        Check::from_issues("duplicate_names", duplicate_names(countries)),
+        Check::from_issues("negative_population", negative_population(countries)),
+        Check::from_issues("currency_without_rate", currency_without_rate(countries)),
+        Check::from_issues("gdp_outliers", gdp_outliers(countries)),
+    ];
+    checks.push(if probe_flags {
+        Check::from_issues("missing_flags", missing_flags(http, countries).await)
+    } else {
+        Check { name: "missing_flags", status: "skipped", count: 0, issues: Vec::new() }
+    });
+    Report {
+        ok: checks.iter().all(|c| c.status != "failed"),
+        countries: countries.len(),
+        checks,
+    }
+}
+
+/// Names that only differ in case or surrounding whitespace.
+pub fn duplicate_names(countries: &[CountryFacts]) -> Vec<Value> {
+    let mut groups: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for c in countries {
+        groups.entry(c.name.trim().to_lowercase()).or_default().push(&c.name);
+    }
+    groups
+        .into_values()
+        .filter(|names| names.len() > 1)
+        .map(|names| json!({ "names": names }))
+        .collect()
+}
+
+pub fn negative_population(countries: &[CountryFacts]) -> Vec<Value> {
+    countries
+        .iter()
+        .filter(|c| c.population < 0)
+        .map(|c| json!({ "country": c.name, "population": c.population }))
+        .collect()
+}
+
+/// Currencies that some country uses but that have no exchange rate, with those countries.
+pub fn currency_without_rate(countries: &[CountryFacts]) -> Vec<Value> {
+    let mut by_code: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for c in countries {
+        if let (Some(code), None) = (c.currency_code.as_deref(), c.exchange_rate) {
+            by_code.entry(code).or_default().push(&c.name);
+        }
+    }
+    by_code
+        .into_iter()
+        .map(|(code, names)| json!({ "currency": code, "countries": names }))
+        .collect()
+}
+
+/// GDP per capita far outside the rest, on a log scale (estimated GDP spans orders of
+/// magnitude, so raw values would flag every large economy). Non-positive or
+/// non-finite GDPs are always reported.
+pub fn gdp_outliers(countries: &[CountryFacts]) -> Vec<Value> {
+    let mut issues = Vec::new();
+    let mut logs = Vec::new();
+    for c in countries {
+        let Some(gdp) = c.estimated_gdp else { continue };
+        if !gdp.is_finite() || gdp <= 0.0 {
+            issues.push(json!({ "country": c.name, "estimated_gdp": gdp, "reason": "non_positive" }));
+        } else if c.population > 0 {
+            logs.push((c, (gdp / c.population as f64).log10()));
+        }
+    }
+    // Too few points for quartiles to mean anything
+    if logs.len() < 4 {
+        return issues;
+    }
+
+    let mut sorted: Vec<f64> = logs.iter().map(|(_, l)| *l).collect();
+    sorted.sort_by(f64::total_cmp);
+    let (q1, q3) = (quantile(&sorted, 0.25), quantile(&sorted, 0.75));
+    let (low, high) = (q1 - OUTLIER_FENCE * (q3 - q1), q3 + OUTLIER_FENCE * (q3 - q1));
+    for (c, l) in logs {
+        if l < low || l > high {
+            issues.push(json!({
+                "country": c.name,
+                "estimated_gdp": c.estimated_gdp,
+                "gdp_per_capita": 10f64.powf(l),
+                "reason": if l < low { "low" } else { "high" },
+            }));
+        }
+    }
+    issues
+}
+
+/// Linear interpolation between closest ranks; `sorted` must be non-empty.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let pos = q * (sorted.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+/// Countries whose `flag_url` answers `404`. Other failures (timeouts, 5xx) are
+/// transient and not reported.
+async fn missing_flags(http: &Client, countries: &[CountryFacts]) -> Vec<Value> {
+    let permits = Arc::new(Semaphore::new(FLAG_PROBE_CONCURRENCY));
+    let mut tasks = tokio::task::JoinSet::new();
+    for c in countries {
+        let Some(url) = c.flag_url.clone() else { continue };
+        let (name, http, permits) = (c.name.clone(), http.clone(), permits.clone());
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await.ok()?;
+            match http.head(&url).send().await {
+                Ok(resp) if resp.status() == StatusCode::NOT_FOUND => Some((name, url)),
+                _ => None,
+            }
+        });
+    }
+
+    let mut missing = Vec::new();
+    while let Some(res) = tasks.join_next().await {
+        if let Ok(Some(flag)) = res {
+            missing.push(flag);
+        }
+    }
+    missing.sort();
+    missing
+        .into_iter()
+        .map(|(name, url)| json!({ "country": name, "flag_url": url }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn country(name: &str, population: i64, gdp: Option<f64>) -> CountryFacts {
+        CountryFacts {
+            name: name.into(),
+            population,
+            currency_code: Some("USD".into()),
+            exchange_rate: Some(1.0),
+            estimated_gdp: gdp,
+            flag_url: None,
+        }
+    }
+
+    #[test]
+    fn duplicates_ignore_case_and_whitespace() {
+        let countries = [country("Chad", 1, None), country("chad ", 1, None), country("Mali", 1, None)];
+        assert_eq!(duplicate_names(&countries), vec![json!({ "names": ["Chad", "chad "] })]);
+    }
+
+    #[test]
+    fn currencies_without_rate_are_grouped() {
+        let mut a = country("A", 1, None);
+        let mut b = country("B", 1, None);
+        for c in [&mut a, &mut b] {
+            c.currency_code = Some("XYZ".into());
+            c.exchange_rate = None;
+        }
+        let mut no_currency = country("C", 1, None);
+        no_currency.currency_code = None;
+        no_currency.exchange_rate = None;
+        assert_eq!(
+            currency_without_rate(&[a, b, no_currency, country("D", 1, None)]),
+            vec![json!({ "currency": "XYZ", "countries": ["A", "B"] })]
+        );
+    }
+
+    #[test]
+    fn gdp_outliers_use_per_capita_on_a_log_scale() {
+        // Per-capita GDP 1000-2000 is normal regardless of population
+        let mut countries: Vec<CountryFacts> = (0..10)
+            .map(|i| country(&format!("C{}", i), 10i64.pow(i % 6 + 3), None))
+            .collect();
+        for (i, c) in countries.iter_mut().enumerate() {
+            c.estimated_gdp = Some(c.population as f64 * (1000.0 + 100.0 * i as f64));
+        }
+        assert!(gdp_outliers(&countries).is_empty());
+
+        countries.push(country("Tiny", 1_000_000, Some(1.0)));
+        countries.push(country("Broken", 5, Some(-3.0)));
+        let flagged: Vec<_> = gdp_outliers(&countries).iter().map(|v| v["country"].clone()).collect();
+        assert_eq!(flagged, vec![json!("Broken"), json!("Tiny")]);
+    }
+
+    #[test]
+    fn quantile_interpolates() {
+        assert_eq!(quantile(&[1.0, 2.0, 3.0, 4.0, 5.0], 0.25), 2.0);
+        assert_eq!(quantile(&[1.0, 2.0], 0.5), 1.5);
+    }
+}
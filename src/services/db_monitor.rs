@@ -0,0 +1,96 @@
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::config::AppState;
+
+/// DB reachability as seen by the background probe, shared with `/status` and `/health/ready`.
+///
+/// The pool reconnects on its own; this only tracks how long the DB has been unreachable,
+/// so a MySQL failover longer than `DB_DEGRADED_AFTER_SECS` marks the service degraded
+/// instead of every request timing out on its own.
+#[derive(Clone)]
+pub struct DbHealth {
+    inner: Arc<RwLock<DbHealthState>>,
+    degraded_after: Duration,
+}
+
+#[derive(Clone, Default, serde::Serialize)]
+pub struct DbHealthState {
+    pub last_ok_at: Option<DateTime<Utc>>,
+    /// First failed probe of the current outage
+    pub down_since: Option<DateTime<Utc>>,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+impl DbHealth {
+    pub fn new(degraded_after: Duration) -> Self {
+        Self { inner: Arc::default(), degraded_after }
+    }
+
+    fn record(&self, result: Result<(), String>) {
+        let now = Utc::now();
+        let mut st = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        match result {
+            Ok(()) => {
+                if let Some(since) = st.down_since.take() {
+                    info!(
+                        "database reachable again after {}s ({} failed probe(s))",
+                        (now - since).num_seconds(),
+                        st.consecutive_failures
+                    );
+                }
+                st.consecutive_failures = 0;
+                st.last_ok_at = Some(now);
+            }
+            Err(e) => {
+                if st.down_since.is_none() {
+                    warn!("database probe failed: {}", e);
+                    st.down_since = Some(now);
+                }
+                st.consecutive_failures += 1;
+                st.last_error = Some(e);
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> DbHealthState {
+        self.inner.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// "up", "down" (failing, not for long yet) or "degraded" (down past the threshold).
+    pub fn state(&self) -> &'static str {
+        match self.snapshot().down_since {
+            None => "up",
+            Some(since) if (Utc::now() - since).to_std().unwrap_or_default() >= self.degraded_after => {
+                "degraded"
+            }
+            Some(_) => "down",
+        }
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.state() == "degraded"
+    }
+}
+
+/// Pings the DB every `every` until the process exits.
+pub fn spawn(state: AppState, every: Duration) {
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(every);
+        tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tick.tick().await;
+            // Bounded by the pool's acquire timeout as well, but don't wait that long here
+            let ping = sqlx::query_scalar::<_, i32>("SELECT 1").fetch_one(&state.pool);
+            let res = match tokio::time::timeout(every.max(Duration::from_secs(1)), ping).await {
+                Ok(Ok(_)) => Ok(()),
+                Ok(Err(e)) => Err(e.to_string()),
+                Err(_) => Err("ping timed out".to_string()),
+            };
+            state.db_health.record(res);
+        }
+    });
+}
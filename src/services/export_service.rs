@@ -0,0 +1,407 @@
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use sqlx::Row;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::config::AppState;
+use crate::db::{self, DbPool};
+use crate::models::country::Country;
+use crate::services::country_repository;
+use crate::services::job_queue::{self, JobKind, StopSignal};
+use crate::sql_skip_locked;
+
+/// Jobs claimed per scheduler tick
+const BATCH: i64 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+impl Format {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err("format must be one of json, csv".into()),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Csv => "csv",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Csv => "text/csv; charset=utf-8",
+        }
+    }
+}
+
+/// Where a finished export goes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Destination {
+    /// Sub-directory of `<cache dir>/exports` (served under `/static/exports` with `SERVE_STATIC`)
+    Dir(String),
+    /// POSTed as the request body
+    Webhook(String),
+}
+
+impl Destination {
+    pub fn parse(kind: &str, target: &str) -> Result<Self, String> {
+        let target = target.trim();
+        match kind {
+            "dir" => {
+                let relative = Path::new(target);
+                let safe = !target.is_empty()
+                    && target.len() <= 128
+                    && relative.components().all(|c| matches!(c, Component::Normal(_)));
+                if !safe {
+                    return Err("dir must be a relative path without '..' (1-128 characters)".into());
+                }
+                Ok(Destination::Dir(target.to_string()))
+            }
+            "webhook" => {
+                let ok = (target.starts_with("https://") || target.starts_with("http://")) && target.len() <= 512;
+                if !ok {
+                    return Err("url must be an http(s) URL of at most 512 characters".into());
+                }
+                Ok(Destination::Webhook(target.to_string()))
+            }
+            "s3" => Err("s3 destinations are not supported; export to a dir and sync it, or use a webhook".into()),
+            _ => Err("destination type must be one of dir, webhook".into()),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Destination::Dir(_) => "dir",
+            Destination::Webhook(_) => "webhook",
+        }
+    }
+
+    pub fn target(&self) -> &str {
+        match self {
+            Destination::Dir(s) | Destination::Webhook(s) => s,
+        }
+    }
+}
+
+/// Cron expression, evaluated in UTC. Classic 5-field expressions (`30 2 * * *`) get a
+/// leading seconds field; the `cron` crate's 6/7-field form is accepted as is.
+pub fn parse_schedule(expr: &str) -> Result<Schedule, String> {
+    let expr = expr.trim();
+    let full = if expr.split_whitespace().count() == 5 {
+        format!("0 {}", expr)
+    } else {
+        expr.to_string()
+    };
+    Schedule::from_str(&full).map_err(|e| format!("invalid cron expression '{}': {}", expr, e))
+}
+
+pub fn next_run(schedule: &Schedule, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    schedule.after(&after).next()
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// RFC 4180 CSV with a header row; empty cells for nulls.
+pub fn to_csv(countries: &[Country]) -> String {
+    let opt = |v: &Option<String>| v.as_deref().map(csv_field).unwrap_or_default();
+    let num = |v: Option<f64>| v.map(|n| n.to_string()).unwrap_or_default();
+    let mut out = String::from(
+        "name,capital,region,population,currency_code,exchange_rate,estimated_gdp,flag_url,flag_emoji,\
+         last_refreshed_at\r\n",
+    );
+    for c in countries {
+        let row = [
+            csv_field(&c.name),
+            opt(&c.capital),
+            opt(&c.region),
+            c.population.to_string(),
+            opt(&c.currency_code),
+            num(c.exchange_rate),
+            num(c.estimated_gdp),
+            opt(&c.flag_url),
+            opt(&c.flag_emoji),
+            opt(&c.last_refreshed_at),
+        ];
+        out.push_str(&row.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+pub fn render(countries: &[Country], format: Format) -> Vec<u8> {
+    match format {
+        Format::Json => serde_json::to_vec(countries).unwrap_or_default(),
+        Format::Csv => to_csv(countries).into_bytes(),
+    }
+}
+
+pub struct Job {
+    pub id: i32,
+    pub name: String,
+    pub format: Format,
+    pub destination: Destination,
+}
+
+#[derive(serde::Serialize)]
+pub struct RunOutcome {
+    pub run_id: i64,
+    pub status: &'static str,
+    pub rows: usize,
+    pub bytes: usize,
+    pub location: Option<String>,
+    pub error: Option<String>,
+}
+
+async fn deliver(state: &AppState, job: &Job, body: Vec<u8>) -> Result<String, String> {
+    let file = format!(
+        "countries-{}.{}",
+        Utc::now().format("%Y%m%dT%H%M%SZ"),
+        job.format.as_str()
+    );
+    match &job.destination {
+        Destination::Dir(sub) => {
+            let dir: PathBuf = state.cache_dir.join("exports").join(sub);
+            let path = dir.join(&file);
+            let tmp = path.with_extension("tmp");
+            async {
+                tokio::fs::create_dir_all(&dir).await?;
+                tokio::fs::write(&tmp, &body).await?;
+                tokio::fs::rename(&tmp, &path).await
+            }
+            .await
+            .map_err(|e| format!("writing {} failed: {}", path.display(), e))?;
+            Ok(path.display().to_string())
+        }
+        Destination::Webhook(url) => {
+            let res = state
+                .http
+                .post(url)
+                .timeout(state.runtime.load().external_timeout())
+                .header(reqwest::header::CONTENT_TYPE, job.format.content_type())
+                .header("X-Export-Job", job.name.as_str())
+                .header("X-Export-File", file.as_str())
+                .body(body)
+                .send()
+                .await
+                .map_err(|e| format!("POST {} failed: {}", url, e))?;
+            if !res.status().is_success() {
+                return Err(format!("POST {} answered {}", url, res.status()));
+            }
+            Ok(url.clone())
+        }
+    }
+}
+
+/// One export job by id. `Err` when the stored definition can't be read.
+pub async fn load_job(pool: &DbPool, id: i32) -> Result<Option<Job>, String> {
+    let row = sqlx::query("SELECT id, name, format, destination_type, destination FROM export_jobs WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let format = Format::parse(&row.try_get::<String, _>("format").unwrap_or_default())?;
+    let destination = Destination::parse(
+        &row.try_get::<String, _>("destination_type").unwrap_or_default(),
+        &row.try_get::<String, _>("destination").unwrap_or_default(),
+    )?;
+    Ok(Some(Job {
+        id,
+        name: row.try_get("name").unwrap_or_default(),
+        format,
+        destination,
+    }))
+}
+
+/// Runs one export now and records it in `export_job_runs`. Failures are recorded, not returned.
+/// A set `stop` is checked before delivery; the run is then recorded as `cancelled`.
+pub async fn run_job(
+    state: &AppState,
+    job: &Job,
+    trigger_by: &str,
+    stop: &StopSignal,
+) -> Result<RunOutcome, sqlx::Error> {
+    let run = sqlx::query("INSERT INTO export_job_runs (job_id, trigger_by, status) VALUES (?, ?, 'running')")
+        .bind(job.id)
+        .bind(trigger_by)
+        .execute(&state.pool)
+        .await?;
+    let run_id = db::last_insert_id(&run);
+
+    // Any failure past this point lands in the run row instead of leaving it "running"
+    let (mut rows, mut bytes) = (0, 0);
+    let result = match country_repository::all(&state.pool, None).await {
+        Ok(countries) => {
+            let body = render(&countries, job.format);
+            (rows, bytes) = (countries.len(), body.len());
+            match stop.reason() {
+                Some(reason) => Err(format!("stopped before delivery ({:?})", reason)),
+                None => deliver(state, job, body).await,
+            }
+        }
+        Err(e) => Err(format!("reading countries failed: {}", e)),
+    };
+
+    let (status, location, err) = match result {
+        Ok(loc) => ("succeeded", Some(loc), None),
+        Err(e) => {
+            warn!("export job '{}' failed: {}", job.name, e);
+            (if stop.is_set() { "cancelled" } else { "failed" }, None, Some(e))
+        }
+    };
+    sqlx::query(
+        "UPDATE export_job_runs SET status = ?, finished_at = CURRENT_TIMESTAMP, row_count = ?, bytes = ?, \
+         location = ?, error = ? WHERE id = ?",
+    )
+    .bind(status)
+    .bind(rows as i64)
+    .bind(bytes as i64)
+    .bind(&location)
+    .bind(&err)
+    .bind(run_id)
+    .execute(&state.pool)
+    .await?;
+    sqlx::query("UPDATE export_jobs SET last_run_at = CURRENT_TIMESTAMP WHERE id = ?")
+        .bind(job.id)
+        .execute(&state.pool)
+        .await?;
+
+    Ok(RunOutcome { run_id, status, rows, bytes, location, error: err })
+}
+
+/// Claims due jobs and moves each `next_run_at` to its next cron slot in the same
+/// transaction, so several instances never run one slot twice.
+async fn claim_due(state: &AppState) -> Result<Vec<Job>, sqlx::Error> {
+    let mut tx = db::begin(&state.pool).await?;
+    let rows = sqlx::query(concat!(
+        "SELECT id, name, schedule, format, destination_type, destination FROM export_jobs \
+         WHERE active = TRUE AND next_run_at <= CURRENT_TIMESTAMP \
+         ORDER BY next_run_at ASC LIMIT ?",
+        sql_skip_locked!()
+    ))
+    .bind(BATCH)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut due = Vec::new();
+    for r in &rows {
+        let id: i32 = r.try_get("id").unwrap_or_default();
+        let name: String = r.try_get("name").unwrap_or_default();
+        let schedule = parse_schedule(&r.try_get::<String, _>("schedule").unwrap_or_default());
+        let format = Format::parse(&r.try_get::<String, _>("format").unwrap_or_default());
+        let destination = Destination::parse(
+            &r.try_get::<String, _>("destination_type").unwrap_or_default(),
+            &r.try_get::<String, _>("destination").unwrap_or_default(),
+        );
+        let next = schedule.as_ref().ok().and_then(|s| next_run(s, Utc::now()));
+        // A job that can't be read (or has no future slot) is parked instead of retried every tick
+        sqlx::query("UPDATE export_jobs SET next_run_at = ?, active = ? WHERE id = ?")
+            .bind(next.map(|t| t.naive_utc()))
+            .bind(next.is_some())
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        match (format, destination, next) {
+            (Ok(format), Ok(destination), Some(_)) => due.push(Job { id, name, format, destination }),
+            _ => error!("export job '{}' has an invalid definition; deactivated", name),
+        }
+    }
+    tx.commit().await?;
+    Ok(due)
+}
+
+/// Background loop queueing due export jobs (`services::job_queue` runs them).
+pub fn spawn_scheduler(state: AppState, poll: Duration) {
+    tokio::spawn(async move {
+        info!("export scheduler started (poll {:?})", poll);
+        loop {
+            match claim_due(&state).await {
+                Ok(jobs) => {
+                    // Runs on the job worker, like every other background task
+                    for job in &jobs {
+                        let kind = JobKind::Export { export_id: job.id, trigger: "schedule".into() };
+                        if let Err(e) = job_queue::enqueue(&state.pool, &kind).await {
+                            error!("export job '{}': could not queue the run: {}", job.name, e);
+                        }
+                    }
+                }
+                Err(e) => error!("export scheduler: {}", e),
+            }
+            tokio::time::sleep(poll).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn country(name: &str, capital: Option<&str>) -> Country {
+        Country {
+            id: 1,
+            name: name.into(),
+            capital: capital.map(String::from),
+            region: Some("Africa".into()),
+            population: 1_000,
+            currency_code: Some("NGN".into()),
+            exchange_rate: Some(1600.5),
+            estimated_gdp: None,
+            flag_url: None,
+            flag_emoji: Some("🇳🇬".into()),
+            last_refreshed_at: None,
+            data_source: "restcountries".into(),
+            source_fetched_at: None,
+            rate_source: None,
+        }
+    }
+
+    #[test]
+    fn five_field_cron_is_accepted() {
+        let s = parse_schedule("30 2 * * *").unwrap();
+        let after = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+        assert_eq!(next_run(&s, after), Some(Utc.with_ymd_and_hms(2026, 3, 2, 2, 30, 0).unwrap()));
+        assert!(parse_schedule("0 30 2 * * *").is_ok());
+        assert!(parse_schedule("every night").is_err());
+    }
+
+    #[test]
+    fn csv_quotes_when_needed() {
+        let csv = to_csv(&[country("Bonaire, Sint Eustatius and Saba", None), country("Nigeria", Some("Abuja"))]);
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert!(lines[0].starts_with("name,capital,"));
+        assert!(lines[1].starts_with("\"Bonaire, Sint Eustatius and Saba\",,Africa,1000,NGN,1600.5,,,🇳🇬,"));
+        assert!(lines[2].starts_with("Nigeria,Abuja,"));
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+
+    #[test]
+    fn destinations_are_checked() {
+        assert_eq!(Destination::parse("dir", "nightly/full").unwrap(), Destination::Dir("nightly/full".into()));
+        assert!(Destination::parse("dir", "../etc").is_err());
+        assert!(Destination::parse("dir", "/etc").is_err());
+        assert!(Destination::parse("webhook", "ftp://x").is_err());
+        assert!(Destination::parse("s3", "bucket/key").unwrap_err().contains("not supported"));
+    }
+}
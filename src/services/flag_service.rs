@@ -0,0 +1,153 @@
+use reqwest::Client;
+use sqlx::Row;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+
+use crate::config::AppState;
+use crate::utils::image::compose_flag_sprite;
+
+/// Max concurrent flag downloads while building the sprite
+const FLAG_FETCH_CONCURRENCY: usize = 16;
+
+pub const SPRITE_PNG: &str = "flags-sprite.png";
+pub const SPRITE_JSON: &str = "flags-sprite.json";
+
+/// restcountries v2 links flags as flagcdn SVGs, which we can't rasterize;
+/// flagcdn serves the same flag as PNG under `/w{width}/{code}.png`.
+fn raster_flag_url(url: &str) -> String {
+    match url.strip_prefix("https://flagcdn.com/").and_then(|f| f.strip_suffix(".svg")) {
+        Some(code) if !code.contains('/') => format!("https://flagcdn.com/w160/{}.png", code),
+        _ => url.to_string(),
+    }
+}
+
+/// Best effort flag download; `None` when missing, unreachable or non-2xx.
+pub async fn fetch_flag(http: &Client, url: &str) -> Option<Vec<u8>> {
+    match http.get(raster_flag_url(url)).send().await {
+        Ok(resp) if resp.status().is_success() => resp.bytes().await.ok().map(|b| b.to_vec()),
+        _ => None,
+    }
+}
+
+/// Downloads every cached country's flag and writes `flags-sprite.png` plus a
+/// `flags-sprite.json` coordinate map into the cache dir. Flags that fail to
+/// download or decode are left out of the sheet.
+pub async fn build_flag_sprite(state: &AppState) -> Result<usize, String> {
+    let rows = sqlx::query("SELECT name, flag_url FROM countries WHERE flag_url IS NOT NULL ORDER BY name ASC")
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let permits = Arc::new(Semaphore::new(FLAG_FETCH_CONCURRENCY));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (i, r) in rows.iter().enumerate() {
+        let name: String = r.try_get("name").unwrap_or_default();
+        let url: String = r.try_get("flag_url").unwrap_or_default();
+        let http = state.http.clone();
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await.ok()?;
+            fetch_flag(&http, &url).await.map(|bytes| (i, name, bytes))
+        });
+    }
+
+    let mut flags = Vec::with_capacity(rows.len());
+    while let Some(res) = tasks.join_next().await {
+        if let Ok(Some(flag)) = res {
+            flags.push(flag);
+        }
+    }
+    // Keep the sheet layout stable (alphabetical) regardless of download order
+    flags.sort_by_key(|(i, _, _)| *i);
+    let flags: Vec<(String, Vec<u8>)> = flags.into_iter().map(|(_, n, b)| (n, b)).collect();
+
+    let (png, map) = tokio::task::spawn_blocking(move || compose_flag_sprite(flags))
+        .await
+        .map_err(|e| format!("spawn failed: {:?}", e))??;
+    let count = map["frames"].as_object().map(|f| f.len()).unwrap_or(0);
+
+    // PNG first, then the map that points into it
+    write_atomic(&state.cache_dir.join(SPRITE_PNG), &png).await?;
+    write_atomic(&state.cache_dir.join(SPRITE_JSON), map.to_string().as_bytes()).await?;
+
+    if count < rows.len() {
+        warn!("flag sprite: {} of {} flags could not be fetched/decoded", rows.len() - count, rows.len());
+    }
+    info!("flag sprite built with {} flags", count);
+    Ok(count)
+}
+
+/// Writes via a uniquely named temp file next to `path` (`flags-sprite.png.<random>.tmp`),
+/// so the sheet and its map, or two builds racing, never share one.
+async fn write_atomic(path: &std::path::Path, bytes: &[u8]) -> Result<(), String> {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("sprite");
+    let tmp = path.with_file_name(format!("{}.{:016x}.tmp", name, rand::random::<u64>()));
+    if let Err(e) = tokio::fs::write(&tmp, bytes).await {
+        tokio::fs::remove_file(&tmp).await.ok();
+        return Err(format!("write {} failed: {}", tmp.display(), e));
+    }
+    tokio::fs::rename(&tmp, path)
+        .await
+        .map_err(|e| format!("rename to {} failed: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::image::{encode_png, Canvas};
+    use image::{ImageBuffer, Rgba};
+
+    fn flag(w: u32, h: u32) -> Vec<u8> {
+        let img: Canvas = ImageBuffer::from_pixel(w, h, Rgba([200, 0, 0, 255]));
+        encode_png(&img).unwrap()
+    }
+
+    #[test]
+    fn sprite_lays_out_decodable_flags_in_order() {
+        let flags = vec![
+            ("Ghana".to_string(), flag(30, 20)),
+            ("Broken".to_string(), b"not an image".to_vec()),
+            ("Nigeria".to_string(), flag(40, 20)),
+        ];
+        let (png, map) = compose_flag_sprite(flags).unwrap();
+
+        let frames = map["frames"].as_object().unwrap();
+        assert_eq!(frames.len(), 2, "undecodable flags are left out");
+        assert_eq!(map["cell"], serde_json::json!({ "w": 80, "h": 60 }));
+        // Two flags fit one row of 80x60 cells; each is scaled to fit its cell
+        assert_eq!((map["width"].as_u64(), map["height"].as_u64()), (Some(160), Some(60)));
+        assert_eq!(frames["Ghana"], serde_json::json!({ "x": 0, "y": 0, "w": 80, "h": 53 }));
+        assert_eq!(frames["Nigeria"], serde_json::json!({ "x": 80, "y": 0, "w": 80, "h": 40 }));
+
+        let sheet = image::load_from_memory(&png).unwrap();
+        assert_eq!((sheet.width(), sheet.height()), (160, 60));
+    }
+
+    #[test]
+    fn sprite_wraps_rows_and_survives_no_flags() {
+        let flags: Vec<_> = (0..17).map(|i| (format!("c{:02}", i), flag(8, 6))).collect();
+        let (_, map) = compose_flag_sprite(flags).unwrap();
+        assert_eq!((map["width"].as_u64(), map["height"].as_u64()), (Some(16 * 80), Some(2 * 60)));
+        assert_eq!(map["frames"]["c16"]["x"], 0);
+        assert_eq!(map["frames"]["c16"]["y"], 60);
+
+        let (_, map) = compose_flag_sprite(Vec::new()).unwrap();
+        assert_eq!(map["frames"], serde_json::json!({}));
+    }
+
+    #[tokio::test]
+    async fn sheet_and_map_use_separate_temp_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (png, json) = (dir.path().join(SPRITE_PNG), dir.path().join(SPRITE_JSON));
+        let (a, b) = tokio::join!(write_atomic(&png, b"png"), write_atomic(&json, b"{}"));
+        a.unwrap();
+        b.unwrap();
+        assert_eq!(std::fs::read(&png).unwrap(), b"png");
+        assert_eq!(std::fs::read(&json).unwrap(), b"{}");
+        let mut names: Vec<String> =
+            std::fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name().into_string().unwrap()).collect();
+        names.sort();
+        assert_eq!(names, [SPRITE_JSON, SPRITE_PNG], "no temp files left behind");
+    }
+}
@@ -0,0 +1,221 @@
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value};
+use sqlx::{QueryBuilder, Row, Transaction};
+use std::collections::HashMap;
+
+use crate::db::Db;
+use crate::services::hooks::CountryRecord;
+use crate::{sql_upsert, sql_utc_date};
+
+/// Current `countries` rows keyed by lowercased name, read inside the refresh
+/// transaction so the diff is against exactly what the upsert will overwrite.
+pub async fn load_current(
+    tx: &mut Transaction<'_, Db>,
+) -> Result<HashMap<String, CountryRecord>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT name, iso_code, capital, region, population, currency_code, exchange_rate, estimated_gdp, flag_url FROM countries",
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|r| {
+            let rec = CountryRecord {
+                name: r.try_get("name").unwrap_or_default(),
+                iso_code: r.try_get("iso_code").ok().flatten(),
+                capital: r.try_get("capital").ok().flatten(),
+                region: r.try_get("region").ok().flatten(),
+                population: r.try_get("population").unwrap_or_default(),
+                currency_code: r.try_get("currency_code").ok().flatten(),
+                exchange_rate: r.try_get("exchange_rate").ok().flatten(),
+                estimated_gdp: r.try_get("estimated_gdp").ok().flatten(),
+                flag_url: r.try_get("flag_url").ok().flatten(),
+            };
+            (rec.name.to_lowercase(), rec)
+        })
+        .collect())
+}
+
+fn fields(r: &CountryRecord) -> [(&'static str, Value); 7] {
+    [
+        ("capital", json!(r.capital)),
+        ("region", json!(r.region)),
+        ("population", json!(r.population)),
+        ("currency_code", json!(r.currency_code)),
+        ("exchange_rate", json!(r.exchange_rate)),
+        ("estimated_gdp", json!(r.estimated_gdp)),
+        ("flag_url", json!(r.flag_url)),
+    ]
+}
+
+/// `None` when nothing changed; otherwise the change type and `{field: {from, to}}`.
+pub fn diff(prev: Option<&CountryRecord>, next: &CountryRecord) -> Option<(&'static str, Map<String, Value>)> {
+    let mut changes = Map::new();
+    match prev {
+        None => {
+            for (k, v) in fields(next) {
+                changes.insert(k.into(), json!({ "from": null, "to": v }));
+            }
+            Some(("inserted", changes))
+        }
+        Some(p) => {
+            for ((k, before), (_, after)) in fields(p).into_iter().zip(fields(next)) {
+                if before != after {
+                    changes.insert(k.into(), json!({ "from": before, "to": after }));
+                }
+            }
+            (!changes.is_empty()).then_some(("updated", changes))
+        }
+    }
+}
+
+pub async fn record_change(
+    tx: &mut Transaction<'_, Db>,
+    run_id: i64,
+    rec: &CountryRecord,
+    change_type: &str,
+    changes: &Map<String, Value>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO country_history \
+         (run_id, name, change_type, capital, region, population, currency_code, exchange_rate, estimated_gdp, flag_url, changes) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(run_id)
+    .bind(&rec.name)
+    .bind(change_type)
+    .bind(&rec.capital)
+    .bind(&rec.region)
+    .bind(rec.population)
+    .bind(&rec.currency_code)
+    .bind(rec.exchange_rate)
+    .bind(rec.estimated_gdp)
+    .bind(&rec.flag_url)
+    .bind(Value::Object(changes.clone()).to_string())
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Writes today's (UTC) point for each `(currency_code, rate, source)`, replacing one an
+/// earlier refresh wrote today. Returns the rows written.
+pub async fn record_rates(
+    tx: &mut Transaction<'_, Db>,
+    run_id: i64,
+    rates: &[(String, f64, &str)],
+) -> Result<u64, sqlx::Error> {
+    let mut written = 0;
+    for chunk in rates.chunks(500) {
+        let mut qb = QueryBuilder::<Db>::new(
+            "INSERT INTO exchange_rate_history (currency_code, day, rate, source, run_id) ",
+        );
+        qb.push_values(chunk, |mut b, (code, rate, source)| {
+            b.push_bind(code).push(sql_utc_date!()).push_bind(rate).push_bind(*source).push_bind(run_id);
+        });
+        qb.push(concat!(
+            sql_upsert!("currency_code", "day"; "rate", "source", "run_id"),
+            ", recorded_at = CURRENT_TIMESTAMP"
+        ));
+        qb.build().execute(&mut **tx).await?;
+        written += chunk.len() as u64;
+    }
+    Ok(written)
+}
+
+/// One recorded population value: the history row where it first appeared.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct PopulationPoint {
+    pub run_id: i64,
+    pub recorded_at: DateTime<Utc>,
+    pub population: i64,
+    /// Against the previous point; `None` on the first
+    pub change: Option<i64>,
+    pub days_since_previous: Option<f64>,
+    /// Compound annual growth since the previous point; `None` when the points are
+    /// under [`MIN_GROWTH_SPAN_DAYS`] apart (annualizing a few days is noise)
+    pub annualized_growth_pct: Option<f64>,
+}
+
+pub const MIN_GROWTH_SPAN_DAYS: f64 = 30.0;
+
+/// Turns history rows `(run_id, recorded_at, population)`, oldest first, into the points
+/// where the population changed. Rows written for other fields repeat the value and are skipped.
+pub fn population_series(rows: &[(i64, DateTime<Utc>, i64)]) -> Vec<PopulationPoint> {
+    let mut out: Vec<PopulationPoint> = Vec::new();
+    for &(run_id, recorded_at, population) in rows {
+        let prev = out.last();
+        if prev.is_some_and(|p| p.population == population) {
+            continue;
+        }
+        let days = prev.map(|p| (recorded_at - p.recorded_at).num_seconds() as f64 / 86_400.0);
+        let growth = match (prev, days) {
+            (Some(p), Some(d)) if d >= MIN_GROWTH_SPAN_DAYS && p.population > 0 && population > 0 => {
+                let rate = (population as f64 / p.population as f64).powf(365.25 / d) - 1.0;
+                Some((rate * 10_000.0).round() / 100.0)
+            }
+            _ => None,
+        };
+        out.push(PopulationPoint {
+            run_id,
+            recorded_at,
+            population,
+            change: prev.map(|p| population - p.population),
+            days_since_previous: days.map(|d| (d * 100.0).round() / 100.0),
+            annualized_growth_pct: growth,
+        });
+    }
+    out
+}
+
+/// Linear estimate at `at` between the surrounding points. Values are only known from
+/// the moment they were recorded, so there's no extrapolation outside the series.
+pub fn interpolate_population(points: &[PopulationPoint], at: DateTime<Utc>) -> Option<i64> {
+    let first = points.first()?;
+    let last = points.last()?;
+    if at < first.recorded_at || at > last.recorded_at {
+        return None;
+    }
+    let i = points.partition_point(|p| p.recorded_at <= at);
+    let before = &points[i - 1];
+    let Some(after) = points.get(i) else {
+        return Some(before.population);
+    };
+    let span = (after.recorded_at - before.recorded_at).num_seconds() as f64;
+    let t = (at - before.recorded_at).num_seconds() as f64 / span;
+    Some((before.population as f64 + t * (after.population - before.population) as f64).round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn day(d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + chrono::Duration::days(d as i64)
+    }
+
+    #[test]
+    fn series_keeps_only_changes() {
+        let rows = [(1, day(0), 1_000), (2, day(10), 1_000), (3, day(365), 1_100), (4, day(366), 1_101)];
+        let s = population_series(&rows);
+        assert_eq!(s.iter().map(|p| p.run_id).collect::<Vec<_>>(), vec![1, 3, 4]);
+        assert_eq!(s[0].change, None);
+        assert_eq!(s[1].change, Some(100));
+        assert_eq!(s[1].days_since_previous, Some(365.0));
+        // 10% over 365 days, annualized over 365.25
+        assert!((s[1].annualized_growth_pct.unwrap() - 10.0).abs() < 0.01);
+        // one day apart: too short to annualize
+        assert_eq!(s[2].annualized_growth_pct, None);
+    }
+
+    #[test]
+    fn interpolates_inside_the_range_only() {
+        let s = population_series(&[(1, day(0), 1_000), (2, day(100), 2_000)]);
+        assert_eq!(interpolate_population(&s, day(0)), Some(1_000));
+        assert_eq!(interpolate_population(&s, day(25)), Some(1_250));
+        assert_eq!(interpolate_population(&s, day(100)), Some(2_000));
+        assert_eq!(interpolate_population(&s, day(101)), None);
+        assert_eq!(interpolate_population(&[], day(0)), None);
+    }
+}
@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use crate::services::refresh_service::RefreshResult;
+
+/// One country as it is about to be upserted by `refresh_cache`, after
+/// normalization and GDP estimation. Hooks may edit any field.
+#[derive(Debug, Clone)]
+pub struct CountryRecord {
+    pub name: String,
+    /// ISO 3166-1 alpha-2, upper case
+    pub iso_code: Option<String>,
+    pub capital: Option<String>,
+    pub region: Option<String>,
+    pub population: i64,
+    pub currency_code: Option<String>,
+    pub exchange_rate: Option<f64>,
+    pub estimated_gdp: Option<f64>,
+    pub flag_url: Option<String>,
+}
+
+pub enum HookDecision {
+    Keep,
+    /// Drop this record from the refresh; the reason is logged
+    Skip(String),
+}
+
+/// Extension point for deployments that need to enrich or veto records during a
+/// refresh without forking `refresh_service`. Register implementations from `main`
+/// with [`RefreshHooks::with`]; hooks run in registration order.
+///
+/// Hooks run inside the refresh transaction, so keep `before_upsert` cheap and
+/// non-blocking; spawn a task from `after_refresh` for slow follow-up work.
+pub trait RefreshHook: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn before_upsert(&self, _record: &mut CountryRecord) -> HookDecision {
+        HookDecision::Keep
+    }
+
+    /// Called once the refresh has committed.
+    fn after_refresh(&self, _result: &RefreshResult) {}
+}
+
+#[derive(Clone, Default)]
+pub struct RefreshHooks(Arc<Vec<Arc<dyn RefreshHook>>>);
+
+impl RefreshHooks {
+    pub fn with(mut self, hook: impl RefreshHook + 'static) -> Self {
+        Arc::make_mut(&mut self.0).push(Arc::new(hook));
+        self
+    }
+
+    /// Runs every hook's `before_upsert`; the first `Skip` wins and later hooks don't see the record.
+    pub fn before_upsert(&self, record: &mut CountryRecord) -> HookDecision {
+        for h in self.0.iter() {
+            if let HookDecision::Skip(reason) = h.before_upsert(record) {
+                return HookDecision::Skip(format!("{}: {}", h.name(), reason));
+            }
+        }
+        HookDecision::Keep
+    }
+
+    pub fn after_refresh(&self, result: &RefreshResult) {
+        for h in self.0.iter() {
+            h.after_refresh(result);
+        }
+    }
+}
+
+/// Built-in hook: drops countries by (case-insensitive) name, e.g. territories a
+/// deployment doesn't want to publish. Configured via `REFRESH_EXCLUDE_COUNTRIES`.
+pub struct ExcludeCountries(Vec<String>);
+
+impl ExcludeCountries {
+    pub fn new(names: &[String]) -> Self {
+        Self(names.iter().map(|n| n.trim().to_lowercase()).collect())
+    }
+}
+
+impl RefreshHook for ExcludeCountries {
+    fn name(&self) -> &str {
+        "exclude_countries"
+    }
+
+    fn before_upsert(&self, record: &mut CountryRecord) -> HookDecision {
+        if self.0.contains(&record.name.to_lowercase()) {
+            HookDecision::Skip("excluded by REFRESH_EXCLUDE_COUNTRIES".into())
+        } else {
+            HookDecision::Keep
+        }
+    }
+}
@@ -0,0 +1,454 @@
+// Persisted background jobs (`jobs` table). One worker loop per instance claims queued
+// jobs, runs them and records progress and the result, so refreshes, exports and image
+// re-renders started in the background all share one status/progress/cancel surface.
+
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use crate::config::AppState;
+use crate::db::{self, DbPool};
+use crate::services::export_service;
+use crate::services::flag_service::build_flag_sprite;
+use crate::services::refresh_service::refresh_cache_stoppable;
+use crate::utils::image::build_summary_image;
+use crate::{sql_secs_ago, sql_skip_locked};
+
+/// A running job touches `heartbeat_at` and checks for cancellation this often
+const HEARTBEAT: Duration = Duration::from_secs(5);
+/// How long a job asked to stop (timeout) gets to reach a checkpoint before it's abandoned
+const STOP_GRACE: Duration = Duration::from_secs(30);
+/// A running job without a heartbeat for this long lost its worker (crash, redeploy)
+const LOST_AFTER_SECS: i64 = 120;
+
+pub const KINDS: [&str; 3] = ["refresh", "export", "render_images"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JobKind {
+    /// `refresh_service::refresh_cache`
+    Refresh { force: bool },
+    /// One run of an `/admin/exports` job
+    Export { export_id: i32, trigger: String },
+    /// Summary image and flag sprite, from the data already stored
+    RenderImages,
+}
+
+impl JobKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobKind::Refresh { .. } => "refresh",
+            JobKind::Export { .. } => "export",
+            JobKind::RenderImages => "render_images",
+        }
+    }
+
+    pub fn params(&self) -> Value {
+        match self {
+            JobKind::Refresh { force } => json!({ "force": force }),
+            JobKind::Export { export_id, trigger } => json!({ "export_id": export_id, "trigger": trigger }),
+            JobKind::RenderImages => json!({}),
+        }
+    }
+
+    pub fn parse(kind: &str, params: &Value) -> Result<Self, String> {
+        match kind {
+            "refresh" => Ok(JobKind::Refresh {
+                force: params.get("force").and_then(Value::as_bool).unwrap_or(false),
+            }),
+            "export" => {
+                let export_id = params
+                    .get("export_id")
+                    .and_then(Value::as_i64)
+                    .and_then(|v| i32::try_from(v).ok())
+                    .ok_or("export needs params.export_id")?;
+                let trigger = params.get("trigger").and_then(Value::as_str).unwrap_or("manual");
+                Ok(JobKind::Export { export_id, trigger: trigger.to_string() })
+            }
+            "render_images" => Ok(JobKind::RenderImages),
+            _ => Err(format!("kind must be one of {}", KINDS.join(", "))),
+        }
+    }
+}
+
+pub async fn enqueue(pool: &DbPool, kind: &JobKind) -> Result<i64, sqlx::Error> {
+    let res = sqlx::query("INSERT INTO jobs (kind, params) VALUES (?, ?)")
+        .bind(kind.name())
+        .bind(kind.params().to_string())
+        .execute(pool)
+        .await?;
+    Ok(db::last_insert_id(&res))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// `DELETE /jobs/:id`
+    Cancelled,
+    /// Ran past `JOB_MAX_RUNTIME_SECS`
+    TimedOut,
+}
+
+/// Cooperative stop request. Long-running work checks it between chunks and bails out;
+/// the first reason set wins.
+#[derive(Clone, Default)]
+pub struct StopSignal(Arc<AtomicU8>);
+
+impl StopSignal {
+    pub fn reason(&self) -> Option<StopReason> {
+        match self.0.load(Ordering::Relaxed) {
+            1 => Some(StopReason::Cancelled),
+            2 => Some(StopReason::TimedOut),
+            _ => None,
+        }
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.reason().is_some()
+    }
+
+    fn set(&self, reason: StopReason) {
+        let v = match reason {
+            StopReason::Cancelled => 1,
+            StopReason::TimedOut => 2,
+        };
+        self.0.compare_exchange(0, v, Ordering::Relaxed, Ordering::Relaxed).ok();
+    }
+}
+
+/// Progress reported from inside long work, where an `await` per update would cost a
+/// query each time. The next heartbeat saves the latest value.
+#[derive(Clone, Default)]
+pub struct Progress(Arc<Mutex<Option<(u8, String)>>>);
+
+impl Progress {
+    pub fn set(&self, percent: u8, message: impl Into<String>) {
+        if let Ok(mut p) = self.0.lock() {
+            *p = Some((percent.min(100), message.into()));
+        }
+    }
+
+    /// The latest value not saved yet
+    fn take(&self) -> Option<(u8, String)> {
+        self.0.lock().ok().and_then(|mut p| p.take())
+    }
+}
+
+/// Handle a running job uses to report progress and see stop requests.
+pub struct JobCtx {
+    pub id: i64,
+    pub stop: StopSignal,
+    pub reported: Progress,
+    pool: DbPool,
+}
+
+impl JobCtx {
+    /// Best effort: a lost progress update never fails the job.
+    pub async fn progress(&self, percent: u8, message: &str) {
+        let res = sqlx::query(
+            "UPDATE jobs SET progress = ?, message = ?, heartbeat_at = CURRENT_TIMESTAMP WHERE id = ?",
+        )
+        .bind(percent.min(100))
+        .bind(message.chars().take(255).collect::<String>())
+        .bind(self.id)
+        .execute(&self.pool)
+        .await;
+        if let Err(e) = res {
+            warn!("job {}: progress update failed: {}", self.id, e);
+        }
+    }
+
+    /// Touches `heartbeat_at`, saves `reported` progress and picks up a `DELETE /jobs/:id`.
+    async fn heartbeat(&self) {
+        if let Some((percent, message)) = self.reported.take() {
+            self.progress(percent, &message).await;
+        }
+        if let Err(e) = sqlx::query("UPDATE jobs SET heartbeat_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(self.id)
+            .execute(&self.pool)
+            .await
+        {
+            warn!("job {}: heartbeat failed: {}", self.id, e);
+        }
+        let cancel: Result<Option<(bool,)>, _> = sqlx::query_as("SELECT cancel_requested FROM jobs WHERE id = ?")
+            .bind(self.id)
+            .fetch_optional(&self.pool)
+            .await;
+        if let Ok(Some((true,))) = cancel {
+            self.stop.set(StopReason::Cancelled);
+        }
+    }
+}
+
+/// Takes the oldest queued job and marks it running. Several instances can share the
+/// table: `SKIP LOCKED` hands each job to exactly one of them.
+async fn claim(pool: &DbPool) -> Result<Option<(i64, Result<JobKind, String>)>, sqlx::Error> {
+    let mut tx = db::begin(pool).await?;
+    let row = sqlx::query(concat!(
+        "SELECT id, kind, params FROM jobs WHERE status = 'queued' ORDER BY id ASC LIMIT 1",
+        sql_skip_locked!()
+    ))
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let id: i64 = row.try_get("id").unwrap_or_default();
+    sqlx::query(
+        "UPDATE jobs SET status = 'running', started_at = CURRENT_TIMESTAMP, heartbeat_at = CURRENT_TIMESTAMP \
+         WHERE id = ?",
+    )
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let params: Value = row
+        .try_get::<String, _>("params")
+        .ok()
+        .and_then(|p| serde_json::from_str(&p).ok())
+        .unwrap_or_else(|| json!({}));
+    let kind = JobKind::parse(&row.try_get::<String, _>("kind").unwrap_or_default(), &params);
+    Ok(Some((id, kind)))
+}
+
+async fn execute(state: &AppState, ctx: &JobCtx, kind: &JobKind) -> Result<Value, String> {
+    match kind {
+        JobKind::Refresh { force } => {
+            ctx.progress(0, "fetching upstream data").await;
+            let res = refresh_cache_stoppable(state, *force, &ctx.stop, &ctx.reported)
+                .await
+                .map_err(|e| e.to_string())?;
+            serde_json::to_value(&res).map_err(|e| e.to_string())
+        }
+        JobKind::Export { export_id, trigger } => {
+            let job = export_service::load_job(&state.pool, *export_id)
+                .await?
+                .ok_or_else(|| format!("export job {} not found", export_id))?;
+            ctx.progress(0, &format!("exporting '{}'", job.name)).await;
+            let outcome = export_service::run_job(state, &job, trigger, &ctx.stop)
+                .await
+                .map_err(|e| e.to_string())?;
+            match &outcome.error {
+                Some(e) => Err(format!("export run {} failed: {}", outcome.run_id, e)),
+                None => serde_json::to_value(&outcome).map_err(|e| e.to_string()),
+            }
+        }
+        JobKind::RenderImages => {
+            ctx.progress(0, "rendering summary image").await;
+            let summary =
+                build_summary_image(&state.pool, &state.summary_image_path, &state.branding, state.summary_ranking)
+                    .await;
+            state.image_health.record(&summary);
+            summary?;
+            if ctx.stop.is_set() {
+                return Err("stopped before the flag sprite".into());
+            }
+            ctx.progress(50, "building flag sprite").await;
+            let flags = build_flag_sprite(state).await?;
+            Ok(json!({ "summary_image": true, "flags": flags }))
+        }
+    }
+}
+
+/// Final status of a job: work that finished despite a stop request still succeeded.
+fn outcome(result: Result<Value, String>, stop: Option<StopReason>, max_runtime: Duration) -> (&'static str, Result<Value, String>) {
+    match (result, stop) {
+        (Ok(v), _) => ("succeeded", Ok(v)),
+        (Err(e), Some(StopReason::Cancelled)) => ("cancelled", Err(format!("cancelled: {}", e))),
+        (Err(e), Some(StopReason::TimedOut)) => (
+            "failed",
+            Err(format!("exceeded max runtime of {}s: {}", max_runtime.as_secs(), e)),
+        ),
+        (Err(e), None) => ("failed", Err(e)),
+    }
+}
+
+async fn finish(pool: &DbPool, id: i64, status: &str, result: &Result<Value, String>) -> Result<(), sqlx::Error> {
+    let (body, err) = match result {
+        Ok(v) => (Some(v.to_string()), None),
+        Err(e) => (None, Some(e.as_str())),
+    };
+    sqlx::query(
+        "UPDATE jobs SET status = ?, result = ?, error = ?, finished_at = CURRENT_TIMESTAMP, \
+         progress = CASE WHEN ? = 'succeeded' THEN 100 ELSE progress END WHERE id = ?",
+    )
+    .bind(status)
+    .bind(body)
+    .bind(err)
+    .bind(status)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Fails running jobs whose worker stopped heartbeating.
+async fn reap_lost(pool: &DbPool) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query(concat!(
+        "UPDATE jobs SET status = 'failed', finished_at = CURRENT_TIMESTAMP, error = 'worker lost (no heartbeat)' \
+         WHERE status = 'running' AND heartbeat_at < ",
+        sql_secs_ago!("?")
+    ))
+    .bind(LOST_AFTER_SECS)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+/// After a job was abandoned mid-flight, its own run record (`refresh_runs`,
+/// `export_job_runs`) would say "running" forever. Runs older than the max runtime can't
+/// still be alive on any instance.
+async fn abandon_runs(pool: &DbPool, kind: &JobKind, max_runtime: Duration) -> Result<u64, sqlx::Error> {
+    let table = match kind {
+        JobKind::Refresh { .. } => "refresh_runs",
+        JobKind::Export { .. } => "export_job_runs",
+        JobKind::RenderImages => return Ok(0),
+    };
+    let res = sqlx::query(&format!(
+        concat!(
+            "UPDATE {} SET status = 'failed', finished_at = CURRENT_TIMESTAMP, \
+             error = 'abandoned: job exceeded its max runtime' WHERE status = 'running' AND started_at < ",
+            sql_secs_ago!("?")
+        ),
+        table
+    ))
+    .bind(max_runtime.as_secs() as i64)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+async fn run_one(state: &AppState, id: i64, kind: Result<JobKind, String>, max_runtime: Duration) {
+    let ctx = JobCtx { id, stop: StopSignal::default(), reported: Progress::default(), pool: state.pool.clone() };
+    let result = match &kind {
+        Ok(kind) => {
+            info!("job {} ({}) started", id, kind.name());
+            let started = Instant::now();
+            let abandon = tokio::time::sleep(max_runtime + STOP_GRACE);
+            tokio::pin!(abandon);
+            let mut beat = tokio::time::interval(HEARTBEAT);
+            beat.tick().await;
+            // Scoped so abandoned work is dropped before the cleanup below
+            let result = {
+                let work = execute(state, &ctx, kind);
+                tokio::pin!(work);
+                loop {
+                    tokio::select! {
+                        r = &mut work => break r,
+                        _ = beat.tick() => {
+                            ctx.heartbeat().await;
+                            if started.elapsed() >= max_runtime {
+                                ctx.stop.set(StopReason::TimedOut);
+                            }
+                        }
+                        // Stuck somewhere without a checkpoint: drop the work (a pending
+                        // transaction rolls back) and clean up after it
+                        _ = &mut abandon => {
+                            ctx.stop.set(StopReason::TimedOut);
+                            break Err("abandoned: no checkpoint reached after the stop request".to_string());
+                        }
+                    }
+                }
+            };
+            if abandon.is_elapsed() {
+                match abandon_runs(&state.pool, kind, max_runtime).await {
+                    Ok(n) if n > 0 => warn!("job {}: marked {} abandoned run(s) failed", id, n),
+                    Ok(_) => {}
+                    Err(e) => error!("job {}: could not clean up abandoned runs: {}", id, e),
+                }
+            }
+            result
+        }
+        Err(e) => Err(format!("invalid job: {}", e)),
+    };
+    let (status, result) = outcome(result, ctx.stop.reason(), max_runtime);
+    match &result {
+        Ok(_) => info!("job {} succeeded", id),
+        Err(e) => warn!("job {} {}: {}", id, status, e),
+    }
+    if let Err(e) = finish(&state.pool, id, status, &result).await {
+        error!("job {}: could not record the outcome: {}", id, e);
+    }
+}
+
+/// Background loop: drains the queue one job at a time, then polls every `poll`.
+pub fn spawn_worker(state: AppState, poll: Duration, max_runtime: Duration) {
+    tokio::spawn(async move {
+        info!("job worker started (poll {:?}, max runtime {:?})", poll, max_runtime);
+        loop {
+            match reap_lost(&state.pool).await {
+                Ok(0) => {}
+                Ok(n) => warn!("job worker: failed {} job(s) that lost their worker", n),
+                Err(e) => error!("job worker: {}", e),
+            }
+            match claim(&state.pool).await {
+                Ok(Some((id, kind))) => {
+                    run_one(&state, id, kind, max_runtime).await;
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) => error!("job worker: {}", e),
+            }
+            tokio::time::sleep(poll).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds_round_trip() {
+        for kind in [
+            JobKind::Refresh { force: true },
+            JobKind::Export { export_id: 3, trigger: "schedule".into() },
+            JobKind::RenderImages,
+        ] {
+            assert_eq!(JobKind::parse(kind.name(), &kind.params()).unwrap(), kind);
+        }
+    }
+
+    #[test]
+    fn stop_reason_decides_the_status() {
+        let max = Duration::from_secs(60);
+        assert_eq!(outcome(Ok(json!(1)), Some(StopReason::Cancelled), max).0, "succeeded");
+        assert_eq!(outcome(Err("x".into()), Some(StopReason::Cancelled), max).0, "cancelled");
+        let (status, res) = outcome(Err("x".into()), Some(StopReason::TimedOut), max);
+        assert_eq!(status, "failed");
+        assert!(res.unwrap_err().contains("60s"));
+        assert_eq!(outcome(Err("x".into()), None, max).0, "failed");
+    }
+
+    #[test]
+    fn progress_is_saved_once() {
+        let p = Progress::default();
+        assert_eq!(p.take(), None);
+        p.set(20, "fetched countries");
+        p.set(140, "writing");
+        assert_eq!(p.take(), Some((100, "writing".to_string())));
+        assert_eq!(p.take(), None);
+    }
+
+    #[test]
+    fn first_stop_reason_wins() {
+        let s = StopSignal::default();
+        assert!(!s.is_set());
+        s.set(StopReason::Cancelled);
+        s.set(StopReason::TimedOut);
+        assert_eq!(s.reason(), Some(StopReason::Cancelled));
+    }
+
+    #[test]
+    fn parse_defaults_and_errors() {
+        assert_eq!(JobKind::parse("refresh", &json!({})).unwrap(), JobKind::Refresh { force: false });
+        assert_eq!(
+            JobKind::parse("export", &json!({ "export_id": 1 })).unwrap(),
+            JobKind::Export { export_id: 1, trigger: "manual".into() }
+        );
+        assert!(JobKind::parse("export", &json!({})).is_err());
+        assert!(JobKind::parse("backup", &json!({})).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+use sqlx::migrate::{Migrate, MigrateError};
+use std::collections::HashSet;
+
+use crate::config::MIGRATOR;
+use crate::db::{self, DbPool};
+
+/// Embedded migrations vs. what the database has applied.
+#[derive(serde::Serialize)]
+pub struct MigrationStatus {
+    pub applied: Vec<i64>,
+    /// Embedded but not applied (binary ahead of the DB)
+    pub pending: Vec<i64>,
+    /// Applied but not embedded (DB ahead of the binary)
+    pub unknown: Vec<i64>,
+    /// A migration that failed half-way
+    pub dirty: Option<i64>,
+    pub embedded_latest: Option<i64>,
+    pub db_latest: Option<i64>,
+}
+
+impl MigrationStatus {
+    pub fn in_sync(&self) -> bool {
+        self.pending.is_empty() && self.unknown.is_empty() && self.dirty.is_none()
+    }
+}
+
+fn embedded_versions() -> Vec<i64> {
+    MIGRATOR
+        .iter()
+        .filter(|m| m.migration_type.is_up_migration())
+        .map(|m| m.version)
+        .collect()
+}
+
+pub async fn status(pool: &DbPool) -> Result<MigrationStatus, MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let dirty = conn.dirty_version().await?;
+    let mut applied: Vec<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| m.version)
+        .collect();
+    applied.sort_unstable();
+
+    let embedded = embedded_versions();
+    let applied_set: HashSet<i64> = applied.iter().copied().collect();
+    let embedded_set: HashSet<i64> = embedded.iter().copied().collect();
+
+    Ok(MigrationStatus {
+        pending: embedded.iter().copied().filter(|v| !applied_set.contains(v)).collect(),
+        unknown: applied.iter().copied().filter(|v| !embedded_set.contains(v)).collect(),
+        dirty,
+        embedded_latest: embedded.iter().copied().max(),
+        db_latest: applied.last().copied(),
+        applied,
+    })
+}
+
+fn describe(version: i64) -> String {
+    MIGRATOR
+        .iter()
+        .find(|m| m.version == version)
+        .map(|m| format!("{:04} {}", version, m.description))
+        .unwrap_or_else(|| format!("{:04} (not embedded)", version))
+}
+
+const USAGE: &str = "usage: country-currency-api migrate [status | up [--dry-run] | down [--to <version>] [--dry-run]]";
+
+/// `migrate` subcommand. `up` applies pending migrations; `down` reverts applied ones
+/// newer than `--to` (default: just the latest) using the paired `.down.sql` files.
+pub async fn run_cli(database_url: &str, args: &[String]) -> Result<(), anyhow::Error> {
+    let pool = db::connect(database_url).await?;
+    let dry_run = args.iter().any(|a| a == "--dry-run");
+    let target = match args.iter().position(|a| a == "--to") {
+        Some(i) => Some(
+            args.get(i + 1)
+                .and_then(|v| v.parse::<i64>().ok())
+                .ok_or_else(|| anyhow::anyhow!("--to needs a migration version\n{}", USAGE))?,
+        ),
+        None => None,
+    };
+
+    let st = status(&pool).await?;
+    match args.first().map(String::as_str).unwrap_or("up") {
+        "status" => {
+            for v in &st.applied {
+                println!("applied  {}", describe(*v));
+            }
+            for v in &st.pending {
+                println!("pending  {}", describe(*v));
+            }
+            if let Some(v) = st.dirty {
+                println!("DIRTY    {}", describe(v));
+            }
+            println!("{}", if st.in_sync() { "in sync" } else { "out of sync" });
+        }
+        "up" | "--dry-run" => {
+            if st.pending.is_empty() {
+                println!("nothing to apply");
+            }
+            for v in &st.pending {
+                println!("{} {}", if dry_run { "would apply" } else { "applying" }, describe(*v));
+            }
+            if !dry_run && !st.pending.is_empty() {
+                MIGRATOR.run(&pool).await?;
+                println!("done");
+            }
+        }
+        "down" => {
+            let Some(latest) = st.db_latest else {
+                println!("nothing to revert");
+                return Ok(());
+            };
+            // Default: step back exactly one migration
+            let target = target.unwrap_or_else(|| {
+                st.applied.iter().rev().nth(1).copied().unwrap_or(0)
+            });
+            let to_revert: Vec<i64> = st.applied.iter().rev().copied().filter(|v| *v > target).collect();
+            let irreversible: Vec<i64> = to_revert
+                .iter()
+                .copied()
+                .filter(|v| !MIGRATOR.iter().any(|m| m.version == *v && m.migration_type.is_down_migration()))
+                .collect();
+            if !irreversible.is_empty() {
+                anyhow::bail!("no .down.sql for migration(s) {:?}; nothing was reverted", irreversible);
+            }
+            for v in &to_revert {
+                println!("{} {}", if dry_run { "would revert" } else { "reverting" }, describe(*v));
+            }
+            if !dry_run && target < latest {
+                MIGRATOR.undo(&pool, target).await?;
+                println!("done");
+            }
+        }
+        other => anyhow::bail!("unknown migrate command {:?}\n{}", other, USAGE),
+    }
+    Ok(())
+}
+
+/// Columns the code reads, as the embedded migrations create them (information_schema
+/// DATA_TYPE; the SQLite migrations declare the same types, except for the rowid key).
+const EXPECTED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("countries", "id", if cfg!(feature = "sqlite") { "integer" } else { "int" }),
+    ("countries", "name", "varchar"),
+    ("countries", "iso_code", "char"),
+    ("countries", "capital", "varchar"),
+    ("countries", "region", "varchar"),
+    ("countries", "population", "bigint"),
+    ("countries", "currency_code", "char"),
+    ("countries", "exchange_rate", "double"),
+    ("countries", "estimated_gdp", "double"),
+    ("countries", "flag_url", "varchar"),
+    ("countries", "last_refreshed_at", "datetime"),
+    ("countries", "data_source", "varchar"),
+    ("countries", "source_fetched_at", "datetime"),
+    ("countries", "rate_source", "varchar"),
+    ("countries", "rate_missing_reason", "varchar"),
+    ("app_meta", "k", "varchar"),
+    ("app_meta", "v", "varchar"),
+];
+
+/// (table, column, type) of the live `countries` and `app_meta` columns
+#[cfg(not(feature = "sqlite"))]
+const LIVE_COLUMNS: &str = "SELECT CAST(TABLE_NAME AS CHAR), CAST(COLUMN_NAME AS CHAR), CAST(DATA_TYPE AS CHAR) \
+     FROM information_schema.COLUMNS \
+     WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME IN ('countries', 'app_meta')";
+#[cfg(feature = "sqlite")]
+const LIVE_COLUMNS: &str = "SELECT m.name, c.name, c.type FROM sqlite_master m, pragma_table_info(m.name) c \
+     WHERE m.type = 'table' AND m.name IN ('countries', 'app_meta')";
+
+/// A declared type without its length: SQLite reports `VARCHAR(255)` where
+/// information_schema DATA_TYPE says `varchar`.
+fn base_type(ty: &str) -> &str {
+    ty.split('(').next().unwrap_or(ty).trim()
+}
+
+/// Compares the live `countries` / `app_meta` columns with what the migrations
+/// produce. Returns one line per missing column or type mismatch; empty = no drift.
+pub async fn schema_drift(pool: &DbPool) -> Result<Vec<String>, sqlx::Error> {
+    let rows: Vec<(String, String, String)> = sqlx::query_as(LIVE_COLUMNS).fetch_all(pool).await?;
+    Ok(drift(&rows))
+}
+
+/// `schema_drift` over (table, column, type) rows already read.
+fn drift(rows: &[(String, String, String)]) -> Vec<String> {
+    EXPECTED_COLUMNS
+        .iter()
+        .filter_map(|(table, column, ty)| {
+            let live = rows.iter().find(|(t, c, _)| {
+                t.eq_ignore_ascii_case(table) && c.eq_ignore_ascii_case(column)
+            });
+            match live {
+                None => Some(format!("{}.{}: missing (expected {})", table, column, ty)),
+                Some((_, _, live_ty)) if !base_type(live_ty).eq_ignore_ascii_case(ty) => {
+                    Some(format!("{}.{}: is {}, expected {}", table, column, live_ty, ty))
+                }
+                Some(_) => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expected_rows() -> Vec<(String, String, String)> {
+        EXPECTED_COLUMNS.iter().map(|(t, c, ty)| (t.to_string(), c.to_string(), ty.to_string())).collect()
+    }
+
+    #[test]
+    fn lengths_are_not_part_of_the_type() {
+        assert_eq!(base_type("VARCHAR(255)"), "VARCHAR");
+        assert_eq!(base_type("double"), "double");
+        assert_eq!(base_type("decimal (10,2)"), "decimal");
+    }
+
+    #[test]
+    fn missing_columns_and_type_changes_are_reported() {
+        assert_eq!(drift(&expected_rows()), Vec::<String>::new());
+
+        // What SQLite reports: upper case, with lengths
+        let mut rows: Vec<_> = expected_rows()
+            .into_iter()
+            .map(|(t, c, ty)| (t.to_uppercase(), c, format!("{}(8)", ty.to_uppercase())))
+            .collect();
+        assert_eq!(drift(&rows), Vec::<String>::new());
+
+        rows.retain(|(_, c, _)| c != "rate_source");
+        rows.iter_mut().find(|(_, c, _)| c == "population").unwrap().2 = "int".into();
+        assert_eq!(
+            drift(&rows),
+            ["countries.population: is int, expected bigint", "countries.rate_source: missing (expected varchar)"]
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn a_freshly_migrated_database_has_no_drift() {
+        let pool = db::connect("sqlite::memory:").await.unwrap();
+        MIGRATOR.run(&pool).await.unwrap();
+        assert_eq!(schema_drift(&pool).await.unwrap(), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,133 @@
+// `country-currency-api --mock-upstreams`: serves fixture `/countries` and `/rates` on a
+// local port and points the refresh at them, for development without network access
+// or a hand-built wiremock setup.
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+const COUNTRIES: &str = include_str!("../../fixtures/countries.json");
+const RATES: &str = include_str!("../../fixtures/rates.json");
+
+/// (COUNTRIES_URL, RATES_URL) while the mocks run; re-applied after a config reload
+static URLS: OnceLock<(String, String)> = OnceLock::new();
+
+/// `MOCK_FIXTURES_DIR` replaces the built-in fixtures; its files are re-read on every
+/// request, so edits show up on the next refresh.
+#[derive(Clone)]
+struct Fixtures(Option<PathBuf>);
+
+impl Fixtures {
+    async fn read(&self, file: &str, builtin: &'static str) -> Result<String, String> {
+        match &self.0 {
+            None => Ok(builtin.to_string()),
+            Some(dir) => tokio::fs::read_to_string(dir.join(file))
+                .await
+                .map_err(|e| format!("{}: {}", dir.join(file).display(), e)),
+        }
+    }
+}
+
+/// Binds `127.0.0.1:MOCK_UPSTREAMS_PORT` (default `0`, any free port), serves the
+/// fixtures in the background and sets `COUNTRIES_URL` / `RATES_URL` to them.
+pub async fn start() -> anyhow::Result<()> {
+    let port: u16 = match std::env::var("MOCK_UPSTREAMS_PORT") {
+        Ok(v) if !v.trim().is_empty() => {
+            v.trim().parse().map_err(|_| anyhow::anyhow!("MOCK_UPSTREAMS_PORT must be a port number"))?
+        }
+        _ => 0,
+    };
+    let dir = std::env::var("MOCK_FIXTURES_DIR").ok().filter(|d| !d.trim().is_empty());
+    let fixtures = Fixtures(dir.map(PathBuf::from));
+
+    let app = Router::new()
+        .route("/countries", get(countries))
+        .route("/rates", get(rates))
+        .with_state(fixtures.clone());
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], port))).await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            warn!("mock upstreams stopped: {}", e);
+        }
+    });
+
+    let base = format!("http://{}", addr);
+    let _ = URLS.set((format!("{}/countries", base), format!("{}/rates", base)));
+    apply_env();
+    match &fixtures.0 {
+        Some(dir) => info!("mock upstreams on {} (fixtures from {})", base, dir.display()),
+        None => info!("mock upstreams on {} (built-in fixtures)", base),
+    }
+    Ok(())
+}
+
+/// Points `COUNTRIES_URL` / `RATES_URL` at the mocks, if they run. `config::reload`
+/// calls this after re-reading `.env`, which may set its own URLs.
+pub fn apply_env() {
+    if let Some((countries, rates)) = URLS.get() {
+        std::env::set_var("COUNTRIES_URL", countries);
+        std::env::set_var("RATES_URL", rates);
+    }
+}
+
+async fn countries(State(f): State<Fixtures>, headers: HeaderMap) -> Response {
+    serve(f.read("countries.json", COUNTRIES).await, &headers)
+}
+
+async fn rates(State(f): State<Fixtures>, headers: HeaderMap) -> Response {
+    serve(f.read("rates.json", RATES).await, &headers)
+}
+
+/// JSON with a content ETag, honouring `If-None-Match` like the real upstreams do.
+fn serve(body: Result<String, String>, headers: &HeaderMap) -> Response {
+    let body = match body {
+        Ok(b) => b,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+    let etag = format!("\"{}\"", &hex::encode(Sha256::digest(body.as_bytes()))[..16]);
+    let etag_value = HeaderValue::from_str(&etag).expect("hex etag is a valid header");
+    if headers.get(header::IF_NONE_MATCH).is_some_and(|v| v.as_bytes() == etag.as_bytes()) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag_value)]).into_response();
+    }
+    (
+        [(header::CONTENT_TYPE, HeaderValue::from_static("application/json")), (header::ETAG, etag_value)],
+        body,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::external::{parse_countries, parse_rates};
+
+    #[test]
+    fn builtin_fixtures_parse_cleanly() {
+        let parsed = parse_countries(COUNTRIES.as_bytes()).unwrap();
+        assert_eq!((parsed.countries.len(), parsed.invalid), (10, 0));
+        assert!(parsed.warnings.is_empty(), "{:?}", parsed.warnings);
+        let (rates, warnings) = parse_rates(RATES.as_bytes()).unwrap();
+        assert!(rates.contains_key("NGN") && warnings.is_empty());
+    }
+
+    #[test]
+    fn matching_etag_is_not_modified() {
+        let first = serve(Ok("[]".into()), &HeaderMap::new());
+        assert_eq!(first.status(), StatusCode::OK);
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, first.headers()[header::ETAG].clone());
+        assert_eq!(serve(Ok("[]".into()), &headers).status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(serve(Ok("[1]".into()), &headers).status(), StatusCode::OK);
+    }
+}
@@ -1 +1,24 @@
-pub mod refresh_service;
\ No newline at end of file
+pub mod auto_refresh;
+pub mod bench_seed;
+pub mod bundle;
+pub mod checksum;
+pub mod country_repository;
+pub mod data_quality;
+pub mod db_monitor;
+pub mod export_service;
+pub mod flag_service;
+pub mod history_service;
+pub mod hooks;
+pub mod job_queue;
+pub mod migration_service;
+pub mod mock_upstreams;
+pub mod providers;
+pub mod raw_archive;
+pub mod refresh_feed;
+pub mod refresh_scheduler;
+pub mod refresh_service;
+pub mod refresh_window;
+pub mod self_test;
+pub mod side_effects;
+pub mod warmup;
+pub mod webhook_service;
\ No newline at end of file
@@ -0,0 +1,184 @@
+// Where a refresh gets its data. `refresh_service` only talks to the `CountriesProvider`
+// and `RatesProvider` in `AppState`; it never sees URLs. The defaults download
+// restcountries and open.er-api from `COUNTRIES_URL` / `RATES_URL`; swap them in `main`
+// for another source. `--mock-upstreams` keeps the HTTP providers and points them at
+// local fixtures instead.
+
+use axum::async_trait;
+use reqwest::Client;
+use std::collections::HashMap;
+
+use crate::config::RuntimeConfig;
+use crate::types::external::{self, ParsedCountries, SchemaWarning};
+use crate::utils::deadline;
+use crate::utils::error::ApiError;
+
+/// `ETag` / `Last-Modified` an upstream sent with its last stored payload.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    fn from_headers(headers: &reqwest::header::HeaderMap) -> Self {
+        let get = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+                .filter(|v| !v.is_empty() && v.len() <= 512)
+        };
+        Validators {
+            etag: get(reqwest::header::ETAG),
+            last_modified: get(reqwest::header::LAST_MODIFIED),
+        }
+    }
+}
+
+/// One fetch. `body` is `None` when the source is unchanged since the validators it was given.
+pub struct Payload {
+    pub body: Option<Vec<u8>>,
+    pub validators: Validators,
+}
+
+/// What both kinds of provider share. Each fetch counts as one upstream call in the run's budget.
+#[async_trait]
+pub trait Upstream: Send + Sync {
+    /// Used in errors and stored with the data (`countries.data_source`, `rate_source`)
+    fn name(&self) -> &str;
+
+    /// What stored validators belong to; when it changes they are dropped (the URL for HTTP).
+    fn location(&self, cfg: &RuntimeConfig) -> String;
+
+    /// `cfg` is the run's config snapshot. Unconditional when `prev` is empty.
+    async fn fetch(&self, cfg: &RuntimeConfig, prev: &Validators) -> Result<Payload, ApiError>;
+}
+
+pub trait CountriesProvider: Upstream {
+    fn parse(&self, body: &[u8]) -> Result<ParsedCountries, String>;
+}
+
+pub trait RatesProvider: Upstream {
+    /// Rates per ISO 4217 code against the base currency
+    fn parse(&self, body: &[u8]) -> Result<(HashMap<String, f64>, Vec<SchemaWarning>), String>;
+}
+
+fn upstream_error(source: &str, e: reqwest::Error) -> ApiError {
+    if e.is_timeout() {
+        ApiError::Timeout(format!("{} did not answer in time", source))
+    } else {
+        ApiError::External(format!("Could not fetch data from {}: {}", source, e))
+    }
+}
+
+/// Conditional GET, bounded by the external timeout and the request deadline.
+async fn http_get(
+    http: &Client,
+    source: &str,
+    url: &str,
+    cfg: &RuntimeConfig,
+    prev: &Validators,
+) -> Result<Payload, ApiError> {
+    let mut req = http.get(url).timeout(deadline::budget(cfg.external_timeout()));
+    if let Some(etag) = &prev.etag {
+        req = req.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    if let Some(lm) = &prev.last_modified {
+        req = req.header(reqwest::header::IF_MODIFIED_SINCE, lm);
+    }
+    let resp = req.send().await.map_err(|e| upstream_error(source, e))?;
+    if resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(Payload { body: None, validators: prev.clone() });
+    }
+    let validators = Validators::from_headers(resp.headers());
+    let body = resp.bytes().await.map_err(|e| upstream_error(source, e))?;
+    Ok(Payload { body: Some(body.to_vec()), validators })
+}
+
+/// restcountries v2 from `COUNTRIES_URL`
+pub struct HttpCountries(pub Client);
+
+#[async_trait]
+impl Upstream for HttpCountries {
+    fn name(&self) -> &str {
+        "restcountries"
+    }
+
+    fn location(&self, cfg: &RuntimeConfig) -> String {
+        cfg.countries_url.clone()
+    }
+
+    async fn fetch(&self, cfg: &RuntimeConfig, prev: &Validators) -> Result<Payload, ApiError> {
+        http_get(&self.0, self.name(), &cfg.countries_url, cfg, prev).await
+    }
+}
+
+impl CountriesProvider for HttpCountries {
+    fn parse(&self, body: &[u8]) -> Result<ParsedCountries, String> {
+        external::parse_countries(body)
+    }
+}
+
+/// open.er-api `latest` from `RATES_URL`
+pub struct HttpRates(pub Client);
+
+#[async_trait]
+impl Upstream for HttpRates {
+    fn name(&self) -> &str {
+        "open.er-api"
+    }
+
+    fn location(&self, cfg: &RuntimeConfig) -> String {
+        cfg.rates_url.clone()
+    }
+
+    async fn fetch(&self, cfg: &RuntimeConfig, prev: &Validators) -> Result<Payload, ApiError> {
+        http_get(&self.0, self.name(), &cfg.rates_url, cfg, prev).await
+    }
+}
+
+impl RatesProvider for HttpRates {
+    fn parse(&self, body: &[u8]) -> Result<(HashMap<String, f64>, Vec<SchemaWarning>), String> {
+        external::parse_rates(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn http_fetch_is_conditional() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rates"))
+            .and(header("if-none-match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rates"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("etag", "\"v1\"")
+                    .set_body_string(r#"{"result":"success","rates":{"USD":1,"NGN":1600.5}}"#),
+            )
+            .mount(&server)
+            .await;
+        let cfg = RuntimeConfig { rates_url: format!("{}/rates", server.uri()), ..RuntimeConfig::from_env() };
+        let rates = HttpRates(Client::new());
+
+        let first = rates.fetch(&cfg, &Validators::default()).await.unwrap();
+        assert_eq!(first.validators.etag.as_deref(), Some("\"v1\""));
+        let (parsed, _) = rates.parse(&first.body.unwrap()).unwrap();
+        assert_eq!(parsed.get("NGN"), Some(&1600.5));
+
+        let again = rates.fetch(&cfg, &first.validators).await.unwrap();
+        assert!(again.body.is_none());
+        assert_eq!(again.validators, first.validators);
+        assert_eq!(rates.location(&cfg), cfg.rates_url);
+    }
+}
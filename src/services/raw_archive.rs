@@ -0,0 +1,100 @@
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+use crate::config::AppState;
+
+/// Upstreams whose raw responses are archived, as used in `?source=`.
+pub const SOURCES: [&str; 2] = ["countries", "rates"];
+
+fn dir(state: &AppState) -> PathBuf {
+    state.cache_dir.join("raw")
+}
+
+fn path(state: &AppState, run_id: i64, source: &str) -> PathBuf {
+    dir(state).join(format!("run-{}-{}.json.gz", run_id, source))
+}
+
+fn run_id_of(file: &str) -> Option<i64> {
+    file.strip_prefix("run-")?.split('-').next()?.parse().ok()
+}
+
+/// Gzips and stores one upstream body for `run_id` (`RAW_ARCHIVE_RUNS` > 0).
+/// Best effort: the refresh never fails because the archive couldn't be written.
+pub async fn store(state: &AppState, run_id: i64, source: &'static str, body: &[u8]) {
+    if state.raw_archive_runs == 0 {
+        return;
+    }
+    let body = body.to_vec();
+    let gz = tokio::task::spawn_blocking(move || {
+        let mut enc = GzEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(&body)?;
+        enc.finish()
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r.map_err(|e| e.to_string()));
+    let gz = match gz {
+        Ok(gz) => gz,
+        Err(e) => {
+            warn!("raw archive: compressing {} failed: {}", source, e);
+            return;
+        }
+    };
+
+    let path = path(state, run_id, source);
+    let tmp = path.with_extension("tmp");
+    let res = async {
+        tokio::fs::create_dir_all(dir(state)).await?;
+        tokio::fs::write(&tmp, &gz).await?;
+        tokio::fs::rename(&tmp, &path).await
+    }
+    .await;
+    if let Err(e) = res {
+        warn!("raw archive: writing {} failed: {}", path.display(), e);
+        tokio::fs::remove_file(&tmp).await.ok();
+    }
+}
+
+/// Deletes archives of all but the newest `RAW_ARCHIVE_RUNS` runs.
+pub async fn prune(state: &AppState) {
+    if state.raw_archive_runs == 0 {
+        return;
+    }
+    let Ok(mut rd) = tokio::fs::read_dir(dir(state)).await else {
+        return;
+    };
+    let mut files = Vec::new();
+    while let Ok(Some(entry)) = rd.next_entry().await {
+        if let Some(id) = run_id_of(&entry.file_name().to_string_lossy()) {
+            files.push((id, entry.path()));
+        }
+    }
+    let mut runs: Vec<i64> = files.iter().map(|(id, _)| *id).collect();
+    runs.sort_unstable_by(|a, b| b.cmp(a));
+    runs.dedup();
+    let Some(&oldest_kept) = runs.get(state.raw_archive_runs.saturating_sub(1)) else {
+        return;
+    };
+    let mut removed = 0;
+    for (id, path) in files {
+        if id < oldest_kept && tokio::fs::remove_file(&path).await.is_ok() {
+            removed += 1;
+        }
+    }
+    if removed > 0 {
+        info!("raw archive: removed {} old payload(s)", removed);
+    }
+}
+
+/// The gzipped body archived for `run_id`, if any.
+pub async fn load(state: &AppState, run_id: i64, source: &str) -> Option<Vec<u8>> {
+    tokio::fs::read(path(state, run_id, source)).await.ok()
+}
+
+pub fn gunzip(gz: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    GzDecoder::new(gz).read_to_end(&mut out)?;
+    Ok(out)
+}
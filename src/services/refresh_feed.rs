@@ -0,0 +1,69 @@
+// How the latest refresh run ended, for `GET /refresh/wait`. Every run that gets a
+// `refresh_runs` row publishes here when it finishes, whoever started it (the HTTP
+// endpoint, a queued job, the schedule, a stale read). Waiters only see outcomes published
+// after they started waiting. Throttled attempts never run and publish nothing.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::services::refresh_service::RefreshResult;
+
+#[derive(Clone)]
+pub enum Outcome {
+    Succeeded(Arc<RefreshResult>),
+    Failed { run_id: i64, error: String },
+}
+
+#[derive(Clone)]
+pub struct RefreshFeed(Arc<watch::Sender<Option<Outcome>>>);
+
+impl Default for RefreshFeed {
+    fn default() -> Self {
+        RefreshFeed(Arc::new(watch::channel(None).0))
+    }
+}
+
+impl RefreshFeed {
+    pub fn publish(&self, outcome: Outcome) {
+        self.0.send_replace(Some(outcome));
+    }
+
+    /// The first outcome published after this call, or `None` after `timeout`.
+    pub async fn next(&self, timeout: Duration) -> Option<Outcome> {
+        let mut rx = self.0.subscribe();
+        match tokio::time::timeout(timeout, rx.changed()).await {
+            Ok(Ok(())) => rx.borrow_and_update().clone(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_id(o: Option<Outcome>) -> Option<i64> {
+        match o? {
+            Outcome::Succeeded(r) => Some(r.run_id),
+            Outcome::Failed { run_id, .. } => Some(run_id),
+        }
+    }
+
+    #[tokio::test]
+    async fn waiters_get_the_next_outcome_only() {
+        let feed = RefreshFeed::default();
+        feed.publish(Outcome::Failed { run_id: 1, error: "earlier".into() });
+        assert!(feed.next(Duration::from_millis(10)).await.is_none());
+
+        let waiter = tokio::spawn({
+            let feed = feed.clone();
+            async move { feed.next(Duration::from_secs(5)).await }
+        });
+        while feed.0.receiver_count() == 0 {
+            tokio::task::yield_now().await;
+        }
+        feed.publish(Outcome::Succeeded(Arc::new(RefreshResult { run_id: 2, ..Default::default() })));
+        assert_eq!(run_id(waiter.await.unwrap()), Some(2));
+    }
+}
@@ -0,0 +1,127 @@
+// Scheduled refreshes (`REFRESH_CRON` or `REFRESH_INTERVAL_SECS`), so no external cron
+// has to POST `/countries/refresh`. Each tick queues a refresh job on the job worker,
+// like stale reads and export schedules do; the job row and `refresh_runs` record the
+// outcome.
+
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{error, info};
+
+use crate::config::AppState;
+use crate::services::export_service::parse_schedule;
+use crate::services::job_queue::{self, JobKind};
+
+#[derive(Debug, Clone)]
+pub enum RefreshSchedule {
+    /// Cron expression, evaluated in UTC
+    Cron(Box<Schedule>),
+    /// Fixed gap, counted from server start
+    Every(Duration),
+}
+
+impl RefreshSchedule {
+    /// `REFRESH_CRON` wins when both are set; `None` when neither is.
+    pub fn parse(cron: &str, interval_secs: &str) -> Result<Option<Self>, String> {
+        if !cron.trim().is_empty() {
+            return parse_schedule(cron).map(|s| Some(RefreshSchedule::Cron(Box::new(s))));
+        }
+        match interval_secs.trim() {
+            "" | "0" => Ok(None),
+            v => match v.parse::<u64>() {
+                Ok(secs) if secs >= 60 => Ok(Some(RefreshSchedule::Every(Duration::from_secs(secs)))),
+                _ => Err("REFRESH_INTERVAL_SECS must be a number of seconds, at least 60".into()),
+            },
+        }
+    }
+
+    pub fn next_after(&self, t: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            RefreshSchedule::Cron(s) => s.after(&t).next(),
+            RefreshSchedule::Every(d) => chrono::Duration::from_std(*d).ok().map(|d| t + d),
+        }
+    }
+}
+
+/// True while an earlier refresh job hasn't finished; a tick then queues nothing.
+async fn refresh_pending(state: &AppState) -> Result<bool, sqlx::Error> {
+    let n: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM jobs WHERE kind = 'refresh' AND status IN ('queued', 'running')",
+    )
+    .fetch_one(&state.pool)
+    .await?;
+    Ok(n > 0)
+}
+
+async fn tick(state: &AppState) {
+    if !state.refresh_window.allows(Utc::now()) {
+        info!("scheduled refresh: outside REFRESH_WINDOW, skipped");
+        return;
+    }
+    match refresh_pending(state).await {
+        Ok(true) => {
+            info!("scheduled refresh: previous refresh still queued or running, skipped");
+            return;
+        }
+        Ok(false) => {}
+        Err(e) => {
+            error!("scheduled refresh: could not check pending jobs: {}", e);
+            return;
+        }
+    }
+    match job_queue::enqueue(&state.pool, &JobKind::Refresh { force: false }).await {
+        Ok(id) => info!("scheduled refresh: queued job {}", id),
+        Err(e) => error!("scheduled refresh: could not queue the run: {}", e),
+    }
+}
+
+/// Runs until `shutdown` flips; no tick is queued after that. A refresh already on the
+/// worker isn't affected.
+pub fn spawn(state: AppState, schedule: RefreshSchedule, mut shutdown: watch::Receiver<bool>) {
+    tokio::spawn(async move {
+        let mut last = Utc::now();
+        loop {
+            let Some(next) = schedule.next_after(last) else {
+                info!("refresh schedule has no further runs; scheduler stopped");
+                return;
+            };
+            info!("next scheduled refresh at {}", next.to_rfc3339());
+            let wait = (next - Utc::now()).to_std().unwrap_or_default();
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => tick(&state).await,
+                _ = shutdown.changed() => {
+                    info!("refresh scheduler stopped");
+                    return;
+                }
+            }
+            last = next;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn cron_wins_and_interval_has_a_floor() {
+        assert!(RefreshSchedule::parse("", "").unwrap().is_none());
+        assert!(RefreshSchedule::parse("", "0").unwrap().is_none());
+        assert!(matches!(RefreshSchedule::parse("0 3 * * *", "600").unwrap(), Some(RefreshSchedule::Cron(_))));
+        assert!(matches!(RefreshSchedule::parse(" ", "600").unwrap(), Some(RefreshSchedule::Every(_))));
+        assert!(RefreshSchedule::parse("", "30").is_err());
+        assert!(RefreshSchedule::parse("", "hourly").is_err());
+        assert!(RefreshSchedule::parse("at three", "").is_err());
+    }
+
+    #[test]
+    fn next_run() {
+        let t = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+        let cron = RefreshSchedule::parse("0 3 * * *", "").unwrap().unwrap();
+        assert_eq!(cron.next_after(t), Some(Utc.with_ymd_and_hms(2026, 3, 2, 3, 0, 0).unwrap()));
+        let every = RefreshSchedule::parse("", "3600").unwrap().unwrap();
+        assert_eq!(every.next_after(t), Some(Utc.with_ymd_and_hms(2026, 3, 1, 13, 0, 0).unwrap()));
+    }
+}
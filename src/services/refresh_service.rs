@@ -1,122 +1,629 @@
-use crate::config::AppState;
-use crate::types::external::{ErRates, RcCountry};
+use crate::config::{AppState, RuntimeConfig};
+use crate::db;
+use crate::services::country_repository::{self, Completeness};
+use crate::services::history_service::{diff, load_current, record_change, record_rates};
+use crate::services::hooks::{CountryRecord, HookDecision};
+use crate::services::job_queue::{Progress, StopSignal};
+use crate::services::providers::{Upstream, Validators};
+use crate::services::raw_archive;
+use crate::services::refresh_feed::Outcome;
+use crate::services::side_effects::{self, Effect};
+use crate::services::webhook_service::enqueue_event;
+use crate::sql_upsert;
+use crate::types::external::SchemaWarning;
+use crate::utils::currency;
+use crate::utils::deadline;
 use crate::utils::error::ApiError;
-use crate::utils::image::build_summary_image;
+use crate::utils::error_report;
+use crate::utils::server_timing::{self, Metric};
+use crate::utils::telemetry;
 use chrono::Utc;
 use rand::Rng;
-use std::env;
-use tracing::error;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{error, info, warn};
 
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, Default, Clone)]
 pub struct RefreshResult {
+    /// `refresh_runs.id` of this run
+    pub run_id: i64,
+    /// Both upstreams answered `304`: nothing was written and every count is zero
+    pub not_modified: bool,
     pub inserted: u64,
+    /// Existing countries with at least one changed field (see `country_history`)
     pub updated: u64,
+    /// Existing countries whose fields all came back the same
+    pub unchanged: u64,
+    /// Stored countries missing from this upstream payload. They are kept, not deleted.
+    pub removed: u64,
+    /// Records vetoed by a refresh hook
+    pub skipped: u64,
+    /// Upstream records that can't be stored (no name, or not an object); dropped from the run
+    pub quarantined: u64,
+    /// Upstream currency codes that aren't ISO 4217; stored as no currency
+    pub unknown_currencies: Vec<UnknownCurrency>,
+    pub countries_fetched: usize,
+    pub rates_fetched: usize,
+    pub upstream: UpstreamStats,
+    /// Fetches through the data write (the summary image and flag sprite render afterwards)
+    pub duration_ms: u64,
+    /// Unexpected or reshaped upstream fields that were tolerated (see `types::external`)
+    pub schema_warnings: Vec<SchemaWarning>,
+    pub budget: RefreshBudget,
+    /// Field coverage of the whole dataset after this run
+    pub completeness: Completeness,
     pub last_refreshed_at: String,
 }
 
-pub async fn refresh_cache(state: &AppState) -> Result<RefreshResult, ApiError> {
-    // Allow tests / env to override the external endpoints
-    let default_countries = "https://restcountries.com/v2/all?fields=name,capital,region,population,flag,currencies".to_string();
-    let countries_url = env::var("COUNTRIES_URL").unwrap_or(default_countries);
+#[derive(serde::Serialize, Default, Clone)]
+pub struct UpstreamStats {
+    pub countries: FetchStats,
+    pub rates: FetchStats,
+}
+
+#[derive(serde::Serialize, Default, Clone)]
+pub struct FetchStats {
+    pub latency_ms: u64,
+    pub bytes: usize,
+    /// The upstream answered `304` to our validators
+    pub not_modified: bool,
+}
+
+/// What a run cost: stored per run in `refresh_runs` and summed by `GET /refresh/history`.
+#[derive(serde::Serialize, Default, Clone, Debug)]
+pub struct RefreshBudget {
+    /// HTTP requests sent upstream, including `304`s and refetches
+    pub upstream_calls: u32,
+    /// Response body bytes received
+    pub bytes_downloaded: u64,
+    /// Country, history and `app_meta` rows written (0 unless the run committed)
+    pub rows_written: u64,
+}
+
+/// One provider fetch. `body` is `None` when the upstream answered `304`.
+struct Fetched {
+    body: Option<Vec<u8>>,
+    stats: FetchStats,
+    validators: Validators,
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct UnknownCurrency {
+    pub country: String,
+    pub code: String,
+}
 
-    let base = env::var("BASE_CURRENCY").unwrap_or_else(|_| "USD".into());
-    let default_rates = format!("https://open.er-api.com/v6/latest/{}", base);
-    let rates_url = env::var("RATES_URL").unwrap_or(default_rates);
+/// Runs a refresh and records it in `refresh_runs` (succeeded/failed + counts).
+#[tracing::instrument(
+    name = "refresh",
+    skip_all,
+    fields(
+        refresh.run_id = tracing::field::Empty,
+        upstream.countries.bytes = tracing::field::Empty,
+        upstream.countries.count = tracing::field::Empty,
+        upstream.rates.bytes = tracing::field::Empty,
+        refresh.inserted = tracing::field::Empty,
+        refresh.updated = tracing::field::Empty,
+        refresh.skipped = tracing::field::Empty,
+        refresh.upstream_calls = tracing::field::Empty,
+        refresh.bytes_downloaded = tracing::field::Empty,
+        refresh.rows_written = tracing::field::Empty,
+    )
+)]
+/// `force` ignores stored validators and always downloads both payloads.
+pub async fn refresh_cache(state: &AppState, force: bool) -> Result<RefreshResult, ApiError> {
+    refresh_cache_stoppable(state, force, &StopSignal::default(), &Progress::default()).await
+}
+
+/// Bails out at the next checkpoint (between fetches, before the write, every
+/// `CHECKPOINT_EVERY` countries) once `stop` is set; the run is recorded as `cancelled`
+/// and nothing is written. Reports its phase and how many countries it wrote to `progress`.
+pub async fn refresh_cache_stoppable(
+    state: &AppState,
+    force: bool,
+    stop: &StopSignal,
+    progress: &Progress,
+) -> Result<RefreshResult, ApiError> {
+    // Throttled attempts never reach upstream and aren't recorded as runs
+    if let Err(wait) = state.refresh_throttle.try_acquire() {
+        let retry_after_secs = wait.as_secs_f64().ceil().max(1.0) as u64;
+        warn!("refresh throttled; next attempt allowed in {}s", retry_after_secs);
+        return Err(ApiError::RateLimited {
+            retry_after_secs,
+            next_allowed_at: Utc::now() + chrono::Duration::seconds(retry_after_secs as i64),
+        });
+    }
 
-    let countries: Vec<RcCountry> = state
-        .http
-        .get(&countries_url)
-        .send()
+    let run = sqlx::query("INSERT INTO refresh_runs (status) VALUES ('running')")
+        .execute(&state.pool)
         .await
-        .map_err(|e| ApiError::External(format!("Could not fetch data from restcountries: {}", e)))?
-        .json()
+        .map_err(|e| ApiError::Internal(format!("could not record refresh run: {}", e)))?;
+    let run_id = db::last_insert_id(&run);
+    telemetry::record("refresh.run_id", run_id);
+    let guard = RunGuard { state: Some(state.clone()), run_id };
+
+    let mut budget = RefreshBudget::default();
+    let res = run_refresh(state, run_id, force, stop, progress, &mut budget).await;
+    telemetry::record("refresh.upstream_calls", budget.upstream_calls);
+    telemetry::record("refresh.bytes_downloaded", budget.bytes_downloaded);
+    telemetry::record("refresh.rows_written", budget.rows_written);
+    if let Err(e) = &res {
+        error_report::capture("refresh", &e.to_string(), serde_json::json!({ "run_id": run_id }));
+        // Success is recorded inside the refresh transaction; failures land here
+        let msg: String = e.to_string().chars().take(512).collect();
+        let status = if stop.is_set() { "cancelled" } else { "failed" };
+        if let Err(db) = sqlx::query(
+            "UPDATE refresh_runs SET status = ?, finished_at = CURRENT_TIMESTAMP, error = ?, \
+             upstream_calls = ?, bytes_downloaded = ?, rows_written = 0 WHERE id = ?",
+        )
+        .bind(status)
+        .bind(msg)
+        .bind(budget.upstream_calls)
+        .bind(budget.bytes_downloaded as i64)
+        .bind(run_id)
+        .execute(&state.pool)
         .await
-        .map_err(|e| ApiError::External(format!("Could not parse countries: {}", e)))?;
+        {
+            error!("could not mark refresh run {} failed: {}", run_id, db);
+        }
+    }
+    guard.settle();
+    // After the failure is recorded, so waiters looking the run up find it finished
+    state.refresh_feed.publish(match &res {
+        Ok(r) => Outcome::Succeeded(Arc::new(r.clone())),
+        Err(e) => Outcome::Failed { run_id, error: e.to_string() },
+    });
+    res
+}
+
+/// Finishes the run row when the refresh future is dropped before it could: a request
+/// deadline (`utils::deadline`) or a client that went away. The open transaction rolls
+/// back by itself, so the run is recorded as `cancelled`. Runs that already committed
+/// are left alone.
+struct RunGuard {
+    state: Option<AppState>,
+    run_id: i64,
+}
+
+impl RunGuard {
+    /// The run row is up to date; nothing to do on drop.
+    fn settle(mut self) {
+        self.state = None;
+    }
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        let (Some(state), Ok(rt)) = (self.state.take(), tokio::runtime::Handle::try_current()) else {
+            return;
+        };
+        let run_id = self.run_id;
+        warn!("refresh run {} abandoned before it finished", run_id);
+        let error = "abandoned: the request was cancelled (deadline exceeded or client gone)";
+        rt.spawn(async move {
+            let res = sqlx::query(
+                "UPDATE refresh_runs SET status = 'cancelled', finished_at = CURRENT_TIMESTAMP, error = ? \
+                 WHERE id = ? AND status = 'running'",
+            )
+            .bind(error)
+            .bind(run_id)
+            .execute(&state.pool)
+            .await;
+            match res {
+                Ok(r) if r.rows_affected() > 0 => {
+                    state.refresh_feed.publish(Outcome::Failed { run_id, error: error.into() });
+                }
+                Ok(_) => {}
+                Err(e) => error!("could not mark refresh run {} cancelled: {}", run_id, e),
+            }
+        });
+    }
+}
+
+/// Unexpired `rate_overrides`, keyed by currency code.
+async fn load_rate_overrides(
+    tx: &mut sqlx::Transaction<'_, db::Db>,
+) -> Result<HashMap<String, f64>, sqlx::Error> {
+    let rows: Vec<(String, f64)> = sqlx::query_as(
+        "SELECT currency_code, rate FROM rate_overrides WHERE expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP",
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    Ok(rows.into_iter().collect())
+}
+
+/// Countries upserted between two stop checks
+const CHECKPOINT_EVERY: usize = 25;
+
+fn checkpoint(stop: &StopSignal) -> Result<(), ApiError> {
+    match stop.reason() {
+        None => Ok(()),
+        Some(reason) => Err(ApiError::Internal(format!("refresh stopped ({:?})", reason))),
+    }
+}
 
-    let rates_resp: ErRates = state
-        .http
-        .get(&rates_url)
-        .send()
+/// Validators stored by the last successful refresh for `kind` ("countries" | "rates").
+/// They only apply to the provider location they came from, so changing
+/// `COUNTRIES_URL`/`RATES_URL` (or the provider) drops them.
+async fn load_validators(state: &AppState, kind: &str, url: &str) -> Validators {
+    let rows: Vec<(String, String)> = match sqlx::query_as("SELECT k, v FROM app_meta WHERE k LIKE ?")
+        .bind(format!("upstream.{}.%", kind))
+        .fetch_all(&state.pool)
         .await
-        .map_err(|e| ApiError::External(format!("Could not fetch data from open-er-api: {}", e)))?
-        .json()
+    {
+        Ok(rows) => rows,
+        Err(e) => {
+            // Worst case is a full download
+            warn!("refresh: could not load {} validators: {}", kind, e);
+            return Validators::default();
+        }
+    };
+    let meta: HashMap<String, String> = rows.into_iter().collect();
+    let get = |field: &str| meta.get(&format!("upstream.{}.{}", kind, field)).filter(|v| !v.is_empty()).cloned();
+    if get("url").as_deref() != Some(url) {
+        return Validators::default();
+    }
+    Validators { etag: get("etag"), last_modified: get("last_modified") }
+}
+
+async fn save_validators(
+    tx: &mut sqlx::Transaction<'_, db::Db>,
+    kind: &str,
+    url: &str,
+    v: &Validators,
+) -> Result<u64, sqlx::Error> {
+    let fields = [
+        ("url", Some(url)),
+        ("etag", v.etag.as_deref()),
+        ("last_modified", v.last_modified.as_deref()),
+    ];
+    for (field, value) in fields {
+        sqlx::query("REPLACE INTO app_meta (k, v) VALUES (?, ?)")
+            .bind(format!("upstream.{}.{}", kind, field))
+            .bind(value.unwrap_or(""))
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(fields.len() as u64)
+}
+
+/// Provider fetch, conditional when `prev` holds validators, plus how long it took.
+/// The size goes on the refresh span as `bytes_field`.
+async fn fetch_body<P: Upstream + ?Sized>(
+    provider: &P,
+    cfg: &RuntimeConfig,
+    budget: &mut RefreshBudget,
+    bytes_field: &str,
+    prev: &Validators,
+) -> Result<Fetched, ApiError> {
+    let t = Instant::now();
+    budget.upstream_calls += 1;
+    let payload = provider.fetch(cfg, prev).await;
+    server_timing::record(Metric::Upstream, t.elapsed());
+    let payload = payload?;
+    let bytes = payload.body.as_ref().map_or(0, Vec::len);
+    budget.bytes_downloaded += bytes as u64;
+    telemetry::record(bytes_field, bytes);
+    Ok(Fetched {
+        stats: FetchStats {
+            latency_ms: t.elapsed().as_millis() as u64,
+            bytes,
+            not_modified: payload.body.is_none(),
+        },
+        body: payload.body,
+        validators: payload.validators,
+    })
+}
+
+/// Repeats an unchanged (`304`) fetch without validators. Used when only one upstream
+/// changed: the refresh needs both payloads and nothing keeps the other one between runs.
+async fn refetch<P: Upstream + ?Sized>(
+    provider: &P,
+    cfg: &RuntimeConfig,
+    budget: &mut RefreshBudget,
+    fetched: Fetched,
+    bytes_field: &str,
+) -> Result<(Vec<u8>, FetchStats, Validators), ApiError> {
+    let f = match fetched.body {
+        Some(_) => fetched,
+        None => fetch_body(provider, cfg, budget, bytes_field, &Validators::default()).await?,
+    };
+    let body = f.body.ok_or_else(|| {
+        ApiError::External(format!("{} answered 304 to an unconditional request", provider.name()))
+    })?;
+    Ok((body, f.stats, f.validators))
+}
+
+/// Both upstreams answered `304`: record the run and stop. The data, `last_refreshed_at`,
+/// images and webhooks are left alone since nothing changed.
+async fn finish_not_modified(
+    state: &AppState,
+    run_id: i64,
+    countries: FetchStats,
+    rates: FetchStats,
+    started: Instant,
+    budget: &RefreshBudget,
+) -> Result<RefreshResult, ApiError> {
+    info!("refresh: both upstreams unchanged (304), nothing to write");
+    telemetry::record("refresh.inserted", 0);
+    telemetry::record("refresh.updated", 0);
+    telemetry::record("refresh.skipped", 0);
+
+    let completeness = country_repository::completeness(&state.pool, None)
         .await
-        .map_err(|e| ApiError::External(format!("Could not parse rates: {}", e)))?;
+        .map_err(|e| ApiError::Internal(format!("completeness check failed: {}", e)))?;
+    sqlx::query(
+        "UPDATE refresh_runs SET status = 'not_modified', finished_at = CURRENT_TIMESTAMP, \
+         inserted = 0, updated = 0, skipped = 0, completeness = ?, \
+         upstream_calls = ?, bytes_downloaded = ?, rows_written = 0 WHERE id = ?",
+    )
+    .bind(completeness.overall)
+    .bind(budget.upstream_calls)
+    .bind(budget.bytes_downloaded as i64)
+    .bind(run_id)
+    .execute(&state.pool)
+    .await
+    .map_err(|e| ApiError::Internal(format!("run update failed: {}", e)))?;
 
-    let mut tx = state
-        .pool
-        .begin()
+    let last: Option<(String,)> = sqlx::query_as("SELECT v FROM app_meta WHERE k = 'last_refreshed_at'")
+        .fetch_optional(&state.pool)
         .await
-        .map_err(|e| ApiError::Internal(e.to_string()))?;
+        .map_err(ApiError::db)?;
+
+    Ok(RefreshResult {
+        run_id,
+        not_modified: true,
+        upstream: UpstreamStats { countries, rates },
+        duration_ms: started.elapsed().as_millis() as u64,
+        completeness,
+        budget: budget.clone(),
+        last_refreshed_at: last.map(|(v,)| v).unwrap_or_default(),
+        ..Default::default()
+    })
+}
+
+async fn run_refresh(
+    state: &AppState,
+    run_id: i64,
+    force: bool,
+    stop: &StopSignal,
+    progress: &Progress,
+    budget: &mut RefreshBudget,
+) -> Result<RefreshResult, ApiError> {
+    // One snapshot for the whole run, even if the config is reloaded meanwhile
+    let cfg = state.runtime.load_full();
+    let started = Instant::now();
+    let countries_provider = state.countries_provider.clone();
+    let rates_provider = state.rates_provider.clone();
+    let countries_location = countries_provider.location(&cfg);
+    let rates_location = rates_provider.location(&cfg);
+
+    let (prev_countries, prev_rates) = if force {
+        (Validators::default(), Validators::default())
+    } else {
+        (
+            load_validators(state, "countries", &countries_location).await,
+            load_validators(state, "rates", &rates_location).await,
+        )
+    };
+    let countries =
+        fetch_body(&*countries_provider, &cfg, budget, "upstream.countries.bytes", &prev_countries).await?;
+    let countries_fetched_at = Utc::now();
+    checkpoint(stop)?;
+    progress.set(20, "fetching rates");
+    let rates = fetch_body(&*rates_provider, &cfg, budget, "upstream.rates.bytes", &prev_rates).await?;
+
+    checkpoint(stop)?;
+    if countries.body.is_none() && rates.body.is_none() {
+        return finish_not_modified(state, run_id, countries.stats, rates.stats, started, budget).await;
+    }
+    let (body, countries_stats, countries_validators) =
+        refetch(&*countries_provider, &cfg, budget, countries, "upstream.countries.bytes").await?;
+    let (rates_body, rates_stats, rates_validators) =
+        refetch(&*rates_provider, &cfg, budget, rates, "upstream.rates.bytes").await?;
+
+    // Archived before parsing: an unparseable payload is exactly what needs keeping
+    raw_archive::store(state, run_id, "countries", &body).await;
+    let parsed = countries_provider
+        .parse(&body)
+        .map_err(|e| ApiError::External(format!("Could not parse countries: {}", e)))?;
+    let countries = parsed.countries;
+    let mut schema_warnings = parsed.warnings;
+    telemetry::record("upstream.countries.count", countries.len());
+    let countries_fetched = countries.len() + parsed.invalid;
+
+    raw_archive::store(state, run_id, "rates", &rates_body).await;
+    raw_archive::prune(state).await;
+    let (rates, rate_warnings) = rates_provider
+        .parse(&rates_body)
+        .map_err(|e| ApiError::External(format!("Could not parse rates: {}", e)))?;
+    schema_warnings.extend(rate_warnings);
+    let rates_fetched = rates.len();
+    for w in &schema_warnings {
+        warn!(
+            "refresh: {} payload: {} {} ({} time(s), e.g. {})",
+            w.source,
+            w.kind,
+            w.field.as_deref().unwrap_or("-"),
+            w.count,
+            w.example.as_deref().unwrap_or("-")
+        );
+    }
+
+    // Don't start writing with no budget left: a cancelled transaction would leave the run "running"
+    if deadline::remaining().is_some_and(|r| r.is_zero()) {
+        return Err(ApiError::Timeout("request deadline exceeded before saving".into()));
+    }
+    checkpoint(stop)?;
+    let total = countries.len();
+    progress.set(40, format!("writing {} countries", total));
+
+    let mut tx = db::begin(&state.pool).await.map_err(ApiError::db)?;
 
     let mut inserted = 0u64;
     let mut updated = 0u64;
+    let mut unchanged = 0u64;
+    let mut skipped = 0u64;
+    // Upstream elements that weren't even objects
+    let mut quarantined = parsed.invalid as u64;
+    let mut unknown_currencies = Vec::new();
+    let mut seen = HashSet::new();
+
+    let overrides = load_rate_overrides(&mut tx)
+        .await
+        .map_err(|e| ApiError::Internal(format!("rate overrides failed: {}", e)))?;
+    // Operator overrides win over whatever upstream published
+    let rate_with_source = |code: &str| match overrides.get(code) {
+        Some(r) => Some((*r, "override")),
+        None => rates.get(code).map(|r| (*r, rates_provider.name())),
+    };
+
+    let current = load_current(&mut tx)
+        .await
+        .map_err(|e| ApiError::Internal(format!("history snapshot failed: {}", e)))?;
 
-    for c in countries {
+    for (i, c) in countries.into_iter().enumerate() {
+        // Returning drops the transaction: nothing from this run is kept
+        if i % CHECKPOINT_EVERY == 0 {
+            checkpoint(stop)?;
+            // 40% after the fetches, 95% once every row is written
+            progress.set((40 + 55 * i / total.max(1)) as u8, format!("writing countries {}/{}", i, total));
+        }
         let name = c.name.trim().to_string();
+        if name.is_empty() {
+            warn!("refresh: quarantined a record without a name");
+            quarantined += 1;
+            continue;
+        }
+        seen.insert(name.to_lowercase());
+        let iso_code = c
+            .alpha2_code
+            .map(|s| s.trim().to_ascii_uppercase())
+            .filter(|s| s.len() == 2);
         let population = c.population.unwrap_or(0);
         let capital = c.capital.map(|s| s.trim().to_string());
         let region = c.region.map(|s| s.trim().to_string());
         let flag_url = c.flag.map(|s| s.trim().to_string());
 
-        let currency_code = c
-            .currencies
-            .as_ref()
-            .and_then(|v| v.first())
-            .and_then(|cur| cur.code.as_ref())
-            .map(|s| s.trim().to_string());
+        let raw_code = c.currency_code.map(|s| s.trim().to_string());
+        let currency_code = raw_code.as_deref().and_then(currency::normalize);
+        if let (Some(raw), None) = (&raw_code, &currency_code) {
+            unknown_currencies.push(UnknownCurrency { country: name.clone(), code: raw.clone() });
+        }
 
-        let (exchange_rate, estimated_gdp): (Option<f64>, Option<f64>) =
+        // The third value says why there's no rate (see GET /countries/missing-rates)
+        let (exchange_rate, estimated_gdp, missing_reason): (Option<f64>, Option<f64>, Option<&str>) =
             match currency_code.as_deref() {
-                None => (None, Some(0.0)),
-                Some(code) => match rates_resp.rates.get(code) {
-                    None => (None, None),
-                    Some(rate) if *rate > 0.0 => {
+                // An unrecognised code means "unknown", not "no currency": leave GDP empty
+                None if raw_code.is_some() => (None, None, Some("unknown_code")),
+                None => (None, Some(0.0), Some("no_currency")),
+                Some(code) => match rate_with_source(code) {
+                    None => (None, None, Some("provider_omitted")),
+                    Some((rate, _)) if rate > 0.0 => {
                         let mut rng = rand::thread_rng();
                         let multiplier: f64 = rng.gen_range(1000.0..=2000.0);
-                        let est = (population as f64 * multiplier) / *rate;
-                        (Some(*rate), Some(est))
+                        let est = (population as f64 * multiplier) / rate;
+                        (Some(rate), Some(est), None)
                     }
-                    _ => (None, None),
+                    _ => (None, None, Some("non_positive_rate")),
                 },
             };
 
-        let res = sqlx::query(
+        let mut record = CountryRecord {
+            name,
+            iso_code,
+            capital,
+            region,
+            population,
+            currency_code,
+            exchange_rate,
+            estimated_gdp,
+            flag_url,
+        };
+        if let HookDecision::Skip(reason) = state.hooks.before_upsert(&mut record) {
+            info!("refresh: skipped {:?} ({})", record.name, reason);
+            skipped += 1;
+            continue;
+        }
+        // After hooks, which may have cleared the rate
+        let rate_source = record
+            .exchange_rate
+            .and(record.currency_code.as_deref().and_then(rate_with_source))
+            .map(|(_, source)| source);
+        let missing_reason = match record.exchange_rate {
+            Some(_) => None,
+            None => Some(missing_reason.unwrap_or("hook")),
+        };
+
+        let prev = current.get(&record.name.to_lowercase());
+        match diff(prev, &record) {
+            Some((change_type, changes)) => {
+                record_change(&mut tx, run_id, &record, change_type, &changes)
+                    .await
+                    .map_err(|e| ApiError::Internal(format!("history insert failed: {}", e)))?;
+                budget.rows_written += 1;
+                if prev.is_some() {
+                    updated += 1;
+                }
+            }
+            None => unchanged += 1,
+        }
+
+        sqlx::query(concat!(
             r#"
             INSERT INTO countries
-                (name, capital, region, population, currency_code, exchange_rate, estimated_gdp, flag_url, last_refreshed_at)
+                (name, iso_code, capital, region, population, currency_code, exchange_rate, estimated_gdp, flag_url,
+                 data_source, source_fetched_at, rate_source, rate_missing_reason, last_refreshed_at)
             VALUES
-                (?,    ?,       ?,      ?,          ?,             ?,             ?,              ?,        NOW())
-            ON DUPLICATE KEY UPDATE
-                capital=VALUES(capital),
-                region=VALUES(region),
-                population=VALUES(population),
-                currency_code=VALUES(currency_code),
-                exchange_rate=VALUES(exchange_rate),
-                estimated_gdp=VALUES(estimated_gdp),
-                flag_url=VALUES(flag_url),
-                last_refreshed_at=NOW()
+                (?,    ?,        ?,       ?,      ?,          ?,             ?,             ?,              ?,
+                 ?,           ?,                 ?,           ?,                   CURRENT_TIMESTAMP)
             "#,
-        )
-        .bind(&name)
-        .bind(capital)
-        .bind(region)
-        .bind(population)
-        .bind(currency_code)
-        .bind(exchange_rate)
-        .bind(estimated_gdp)
-        .bind(flag_url)
+            sql_upsert!(
+                "name";
+                "iso_code", "capital", "region", "population", "currency_code", "exchange_rate", "estimated_gdp",
+                "flag_url", "data_source", "source_fetched_at", "rate_source", "rate_missing_reason"
+            ),
+            ", last_refreshed_at = CURRENT_TIMESTAMP"
+        ))
+        .bind(&record.name)
+        .bind(record.iso_code)
+        .bind(record.capital)
+        .bind(record.region)
+        .bind(record.population)
+        .bind(record.currency_code)
+        .bind(record.exchange_rate)
+        .bind(record.estimated_gdp)
+        .bind(record.flag_url)
+        .bind(countries_provider.name())
+        .bind(countries_fetched_at.naive_utc())
+        .bind(rate_source)
+        .bind(missing_reason)
         .execute(&mut *tx)
         .await
         .map_err(|e| ApiError::Internal(format!("db upsert failed: {}", e)))?;
-
-        let n = res.rows_affected();
-        if n == 1 {
+        budget.rows_written += 1;
+        if prev.is_none() {
             inserted += 1;
-        } else if n == 2 {
-            updated += 1;
         }
     }
+    let removed = current.keys().filter(|k| !seen.contains(*k)).count() as u64;
+
+    // Daily rate history, for every ISO currency with a rate (not only those in use)
+    let codes: BTreeSet<&str> = rates.keys().chain(overrides.keys()).map(String::as_str).collect();
+    let day_rates: Vec<(String, f64, &str)> = codes
+        .into_iter()
+        .filter(|code| currency::is_iso4217(code))
+        .filter_map(|code| rate_with_source(code).map(|(rate, source)| (code.to_string(), rate, source)))
+        .filter(|(_, rate, _)| rate.is_finite() && *rate > 0.0)
+        .collect();
+    budget.rows_written += record_rates(&mut tx, run_id, &day_rates)
+        .await
+        .map_err(|e| ApiError::Internal(format!("rate history insert failed: {}", e)))?;
+
+    if !unknown_currencies.is_empty() {
+        warn!("refresh: {} country(ies) with non-ISO 4217 currency codes", unknown_currencies.len());
+    }
 
     let now_iso = Utc::now().to_rfc3339();
     sqlx::query("REPLACE INTO app_meta (k, v) VALUES ('last_refreshed_at', ?)")
@@ -124,18 +631,86 @@ pub async fn refresh_cache(state: &AppState) -> Result<RefreshResult, ApiError>
         .execute(&mut *tx)
         .await
         .map_err(|e| ApiError::Internal(format!("meta update failed: {}", e)))?;
+    budget.rows_written += 1;
+    // Only a committed refresh may make the next one conditional
+    for (kind, url, v) in [
+        ("countries", &countries_location, &countries_validators),
+        ("rates", &rates_location, &rates_validators),
+    ] {
+        budget.rows_written += save_validators(&mut tx, kind, url, v)
+            .await
+            .map_err(|e| ApiError::Internal(format!("meta update failed: {}", e)))?;
+    }
 
-    tx.commit()
+    let completeness = country_repository::completeness(&mut *tx, None)
         .await
-        .map_err(|e| ApiError::Internal(e.to_string()))?;
+        .map_err(|e| ApiError::Internal(format!("completeness check failed: {}", e)))?;
 
-    if let Err(e) = build_summary_image(&state.pool, &state.summary_image_path).await {
-        error!("summary image failed: {}", e);
-    }
+    telemetry::record("refresh.inserted", inserted);
+    telemetry::record("refresh.updated", updated);
+    telemetry::record("refresh.skipped", skipped);
 
-    Ok(RefreshResult {
+    let result = RefreshResult {
+        run_id,
+        not_modified: false,
         inserted,
         updated,
-        last_refreshed_at: now_iso,
-    })
+        unchanged,
+        removed,
+        skipped,
+        quarantined,
+        unknown_currencies,
+        countries_fetched,
+        rates_fetched,
+        upstream: UpstreamStats { countries: countries_stats, rates: rates_stats },
+        duration_ms: started.elapsed().as_millis() as u64,
+        schema_warnings,
+        budget: budget.clone(),
+        completeness,
+        last_refreshed_at: now_iso.clone(),
+    };
+
+    sqlx::query(
+        "UPDATE refresh_runs SET status = 'succeeded', finished_at = CURRENT_TIMESTAMP, \
+         inserted = ?, updated = ?, skipped = ?, quarantined = ?, completeness = ?, \
+         upstream_calls = ?, bytes_downloaded = ?, rows_written = ? WHERE id = ?",
+    )
+    .bind(inserted as i64)
+    .bind(updated as i64)
+    .bind(skipped as i64)
+    .bind(quarantined as i64)
+    .bind(result.completeness.overall)
+    .bind(budget.upstream_calls)
+    .bind(budget.bytes_downloaded as i64)
+    .bind(budget.rows_written as i64)
+    .bind(run_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| ApiError::Internal(format!("run update failed: {}", e)))?;
+
+    // Same transaction as the data: subscribers are notified iff the refresh committed
+    let event = serde_json::json!({ "event": "refresh.completed", "data": &result });
+    enqueue_event(&mut tx, "refresh.completed", &event)
+        .await
+        .map_err(|e| ApiError::Internal(format!("outbox insert failed: {}", e)))?;
+
+    progress.set(95, "committing");
+    tx.commit()
+        .await
+        .map_err(ApiError::db)?;
+
+    // The data is committed; failures from here on are queued for retry instead
+    progress.set(97, "rendering summary image");
+    side_effects::attempt(state, Effect::SummaryImage, run_id).await;
+    state.image_cache.gc(&now_iso).await;
+
+    // ~250 flag downloads: don't hold the refresh response for them
+    tokio::spawn({
+        let state = state.clone();
+        async move { side_effects::attempt(&state, Effect::FlagSprite, run_id).await }
+    });
+
+    state.hooks.after_refresh(&result);
+
+    Ok(result)
 }
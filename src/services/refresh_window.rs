@@ -0,0 +1,107 @@
+use chrono::{DateTime, Datelike, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+
+/// When automatic (stale-triggered) refreshes may run: an optional local-time window
+/// in `REFRESH_TIMEZONE`, optionally weekdays only. Manual `POST /countries/refresh`
+/// ignores it.
+#[derive(Clone, Debug)]
+pub struct RefreshWindow {
+    tz: Tz,
+    /// `None` = any time of day. `start > end` wraps past midnight.
+    hours: Option<(NaiveTime, NaiveTime)>,
+    skip_weekends: bool,
+}
+
+impl Default for RefreshWindow {
+    fn default() -> Self {
+        Self { tz: Tz::UTC, hours: None, skip_weekends: false }
+    }
+}
+
+fn parse_time(s: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M")
+        .map_err(|_| format!("invalid time {:?} in REFRESH_WINDOW, expected HH:MM", s))
+}
+
+impl RefreshWindow {
+    /// `window` is "HH:MM-HH:MM" (empty = all day), `tz` an IANA name like "Africa/Lagos".
+    pub fn parse(window: &str, tz: &str, skip_weekends: bool) -> Result<Self, String> {
+        let tz: Tz = tz
+            .trim()
+            .parse()
+            .map_err(|_| format!("unknown REFRESH_TIMEZONE {:?}", tz))?;
+        let window = window.trim();
+        let hours = if window.is_empty() {
+            None
+        } else {
+            let (start, end) = window
+                .split_once('-')
+                .ok_or_else(|| format!("invalid REFRESH_WINDOW {:?}, expected HH:MM-HH:MM", window))?;
+            Some((parse_time(start)?, parse_time(end)?))
+        };
+        Ok(Self { tz, hours, skip_weekends })
+    }
+
+    pub fn allows(&self, now: DateTime<Utc>) -> bool {
+        let local = now.with_timezone(&self.tz);
+        if self.skip_weekends && matches!(local.weekday(), Weekday::Sat | Weekday::Sun) {
+            return false;
+        }
+        match self.hours {
+            None => true,
+            Some((start, end)) => {
+                let t = local.time();
+                if start <= end {
+                    start <= t && t < end
+                } else {
+                    t >= start || t < end
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn lagos_business_hours() {
+        // Lagos is UTC+1 all year
+        let w = RefreshWindow::parse("06:00-22:00", "Africa/Lagos", true).unwrap();
+        assert!(w.allows(at(2024, 5, 15, 5, 0))); // Wed 06:00 local
+        assert!(!w.allows(at(2024, 5, 15, 4, 59)));
+        assert!(!w.allows(at(2024, 5, 15, 21, 0))); // 22:00 local, end is exclusive
+        assert!(!w.allows(at(2024, 5, 18, 12, 0))); // Saturday
+        assert!(!w.allows(at(2024, 5, 17, 22, 30))); // Fri 23:30 local
+    }
+
+    #[test]
+    fn window_wrapping_midnight() {
+        let w = RefreshWindow::parse("22:00-02:00", "UTC", false).unwrap();
+        assert!(w.allows(at(2024, 5, 15, 23, 0)));
+        assert!(w.allows(at(2024, 5, 16, 1, 59)));
+        assert!(!w.allows(at(2024, 5, 16, 2, 0)));
+        assert!(!w.allows(at(2024, 5, 16, 12, 0)));
+    }
+
+    #[test]
+    fn weekend_is_local() {
+        // Sunday 23:30 UTC is already Monday in Lagos
+        let w = RefreshWindow::parse("", "Africa/Lagos", true).unwrap();
+        assert!(w.allows(at(2024, 5, 19, 23, 30)));
+        assert!(!w.allows(at(2024, 5, 19, 22, 30)));
+    }
+
+    #[test]
+    fn rejects_bad_config() {
+        assert!(RefreshWindow::parse("6-22", "UTC", false).is_err());
+        assert!(RefreshWindow::parse("06:00", "UTC", false).is_err());
+        assert!(RefreshWindow::parse("", "Mars/Olympus", false).is_err());
+    }
+}
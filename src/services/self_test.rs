@@ -0,0 +1,185 @@
+// `country-currency-api --self-test`: checks that this binary can run here (config,
+// DB, migrations, upstreams, image rendering) and prints a JSON report. Exits non-zero
+// when any check fails, so a deploy pipeline can gate on it.
+
+use serde::Serialize;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::config::AppConfig;
+use crate::db::{self, DbPoolOptions};
+use crate::services::migration_service;
+use crate::utils::image::{self, Branding};
+
+#[derive(Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    /// "ok" | "failed" | "skipped" (an earlier check it depends on failed)
+    pub status: &'static str,
+    pub detail: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    pub ok: bool,
+    pub version: &'static str,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    async fn check<F>(&mut self, name: &'static str, f: F) -> bool
+    where
+        F: Future<Output = Result<String, String>>,
+    {
+        let t = Instant::now();
+        let res = f.await;
+        let ok = res.is_ok();
+        self.checks.push(Check {
+            name,
+            status: if ok { "ok" } else { "failed" },
+            detail: res.unwrap_or_else(|e| e),
+            duration_ms: t.elapsed().as_millis() as u64,
+        });
+        ok
+    }
+
+    fn skip(&mut self, name: &'static str, because: &str) {
+        self.checks.push(Check {
+            name,
+            status: "skipped",
+            detail: format!("{} failed", because),
+            duration_ms: 0,
+        });
+    }
+}
+
+pub async fn run() -> Report {
+    let mut report = Report { ok: true, version: env!("CARGO_PKG_VERSION"), checks: Vec::new() };
+
+    let mut cfg = None;
+    report
+        .check("config", async {
+            // `AppConfig::from_env` panics without it
+            if std::env::var("DATABASE_URL").is_err() {
+                return Err("DATABASE_URL is required".into());
+            }
+            let c = AppConfig::from_env().map_err(|e| e.to_string())?;
+            let detail = format!("port {}", c.port);
+            cfg = Some(c);
+            Ok(detail)
+        })
+        .await;
+    let Some(cfg) = cfg else {
+        for name in ["database", "migrations", "upstream.countries", "upstream.rates", "image.font", "image.render", "cache_dir"] {
+            report.skip(name, "config");
+        }
+        report.ok = false;
+        return report;
+    };
+
+    let pool = db::connect_options(&cfg.database_url).map(|opts| {
+        DbPoolOptions::new()
+            .max_connections(1)
+            .acquire_timeout(Duration::from_secs(5))
+            .connect_lazy_with(opts)
+    });
+    let db_ok = report
+        .check("database", async {
+            let pool = pool.as_ref().map_err(|e| e.to_string())?;
+            sqlx::query_scalar::<_, i32>("SELECT 1")
+                .fetch_one(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok("SELECT 1 answered".into())
+        })
+        .await;
+    match pool {
+        Ok(pool) if db_ok => {
+            report
+                .check("migrations", async {
+                    let s = migration_service::status(&pool).await.map_err(|e| e.to_string())?;
+                    if let Some(v) = s.dirty {
+                        return Err(format!("migration {} is dirty (failed half-way)", v));
+                    }
+                    if !s.unknown.is_empty() {
+                        return Err(format!("database has migrations this binary doesn't know: {:?}", s.unknown));
+                    }
+                    if !s.pending.is_empty() {
+                        // Applied at startup, so not a failure
+                        return Ok(format!("{} pending, applied on startup: {:?}", s.pending.len(), s.pending));
+                    }
+                    let drift = migration_service::schema_drift(&pool).await.map_err(|e| e.to_string())?;
+                    if !drift.is_empty() {
+                        return Err(format!("schema drift: {}", drift.join("; ")));
+                    }
+                    Ok(format!("up to date at {:?}", s.db_latest))
+                })
+                .await;
+        }
+        _ => report.skip("migrations", "database"),
+    }
+
+    let http = reqwest::Client::new();
+    for (name, url) in [
+        ("upstream.countries", &cfg.runtime.countries_url),
+        ("upstream.rates", &cfg.runtime.rates_url),
+    ] {
+        report
+            .check(name, async {
+                let res = http
+                    .get(url)
+                    .timeout(cfg.runtime.external_timeout())
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                // Headers are enough; the body is not downloaded
+                if !res.status().is_success() {
+                    return Err(format!("{} answered {}", url, res.status()));
+                }
+                Ok(format!("{} answered {}", url, res.status()))
+            })
+            .await;
+    }
+
+    let mut branding = None;
+    report
+        .check("image.font", async {
+            let b = Branding::load(&cfg.branding)?;
+            branding = Some(b);
+            Ok("font, logo and colours loaded".into())
+        })
+        .await;
+    match branding {
+        Some(b) => {
+            report
+                .check("image.render", async {
+                    let png = image::render_probe(&b)?;
+                    Ok(format!("rendered a {} byte PNG", png.len()))
+                })
+                .await;
+        }
+        None => report.skip("image.render", "image.font"),
+    }
+
+    report
+        .check("cache_dir", async {
+            let dir = match cfg.summary_image_path.parent() {
+                Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+                _ => std::path::PathBuf::from("."),
+            };
+            tokio::fs::create_dir_all(&dir)
+                .await
+                .map_err(|e| format!("create {}: {}", dir.display(), e))?;
+            let probe = dir.join(".self-test");
+            tokio::fs::write(&probe, b"ok")
+                .await
+                .map_err(|e| format!("{} is not writable: {}", dir.display(), e))?;
+            tokio::fs::remove_file(&probe).await.ok();
+            Ok(format!("{} is writable", dir.display()))
+        })
+        .await;
+
+    report.ok = report.checks.iter().all(|c| c.status == "ok");
+    report
+}
@@ -0,0 +1,253 @@
+// Work a refresh does after its transaction commits: the summary image and the flag
+// sprite. Their failure can't undo the refresh, so a failed attempt is recorded in
+// `side_effect_retries` and a background worker retries it with exponential backoff,
+// up to `SIDE_EFFECT_MAX_ATTEMPTS`. Each retry redoes the work from the data current at
+// that time, so there is at most one pending row per kind, and a later refresh that
+// gets the work done supersedes it. Webhook events need none of this: they are written
+// to the outbox inside the refresh transaction and retried by `webhook_service`.
+
+use sqlx::Row;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::config::AppState;
+use crate::db;
+use crate::services::flag_service::build_flag_sprite;
+use crate::utils::image::build_summary_image;
+use crate::{sql_for_update, sql_secs_from_now, sql_skip_locked};
+
+/// Rows claimed per worker tick
+const BATCH: i64 = 10;
+/// A claimed row is retried by another worker if not settled within this lease
+/// (the flag sprite downloads ~250 images)
+const LEASE_SECS: i64 = 600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    SummaryImage,
+    FlagSprite,
+}
+
+impl Effect {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Effect::SummaryImage => "summary_image",
+            Effect::FlagSprite => "flag_sprite",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "summary_image" => Some(Effect::SummaryImage),
+            "flag_sprite" => Some(Effect::FlagSprite),
+            _ => None,
+        }
+    }
+
+    async fn run(self, state: &AppState) -> Result<(), String> {
+        match self {
+            Effect::SummaryImage => {
+                let res =
+                    build_summary_image(&state.pool, &state.summary_image_path, &state.branding, state.summary_ranking)
+                        .await;
+                state.image_health.record(&res);
+                res
+            }
+            Effect::FlagSprite => build_flag_sprite(state).await.map(|_| ()),
+        }
+    }
+}
+
+/// Delay before retry number `attempts + 1`: 30s, 60s, 120s, ... capped at 1h. Webhook
+/// deliveries retry on the same schedule.
+pub fn backoff_secs(attempts: i32) -> i64 {
+    (30i64 << attempts.clamp(0, 7)).min(3600)
+}
+
+/// Runs `effect` for refresh `run_id`. A failure is queued for retry; a success
+/// supersedes any retry still pending for it.
+pub async fn attempt(state: &AppState, effect: Effect, run_id: i64) {
+    let recorded = match effect.run(state).await {
+        Ok(()) => supersede(state, effect).await,
+        Err(e) => {
+            error!("{} failed after refresh run {}, queued for retry: {}", effect.as_str(), run_id, e);
+            enqueue(state, effect, run_id, &e).await
+        }
+    };
+    if let Err(e) = recorded {
+        error!("could not record {} outcome in side_effect_retries: {}", effect.as_str(), e);
+    }
+}
+
+async fn enqueue(state: &AppState, effect: Effect, run_id: i64, err: &str) -> Result<(), sqlx::Error> {
+    let err: String = err.chars().take(512).collect();
+    let mut tx = db::begin(&state.pool).await?;
+    let pending: Option<i64> = sqlx::query_scalar(concat!(
+        "SELECT id FROM side_effect_retries WHERE kind = ? AND status = 'pending' LIMIT 1",
+        sql_for_update!()
+    ))
+    .bind(effect.as_str())
+    .fetch_optional(&mut *tx)
+    .await?;
+    match pending {
+        // Keeps its attempts and schedule, so repeated refreshes don't reset the backoff
+        Some(id) => {
+            sqlx::query("UPDATE side_effect_retries SET run_id = ?, last_error = ? WHERE id = ?")
+                .bind(run_id)
+                .bind(&err)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        None => {
+            sqlx::query(concat!(
+                "INSERT INTO side_effect_retries (kind, run_id, last_error, next_attempt_at) VALUES (?, ?, ?, ",
+                sql_secs_from_now!("?"),
+                ")"
+            ))
+            .bind(effect.as_str())
+            .bind(run_id)
+            .bind(&err)
+            .bind(backoff_secs(0))
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    tx.commit().await
+}
+
+async fn supersede(state: &AppState, effect: Effect) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE side_effect_retries SET status = 'superseded', finished_at = CURRENT_TIMESTAMP \
+         WHERE kind = ? AND status = 'pending'",
+    )
+    .bind(effect.as_str())
+    .execute(&state.pool)
+    .await
+    .map(|_| ())
+}
+
+struct Due {
+    id: i64,
+    kind: String,
+    run_id: i64,
+    attempts: i32,
+}
+
+async fn claim_due(state: &AppState) -> Result<Vec<Due>, sqlx::Error> {
+    // SKIP LOCKED lets several instances share the table without double-claiming
+    let mut tx = db::begin(&state.pool).await?;
+    let rows = sqlx::query(concat!(
+        "SELECT id, kind, run_id, attempts FROM side_effect_retries \
+         WHERE status = 'pending' AND next_attempt_at <= CURRENT_TIMESTAMP \
+         ORDER BY id ASC LIMIT ?",
+        sql_skip_locked!()
+    ))
+    .bind(BATCH)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let due: Vec<Due> = rows
+        .iter()
+        .map(|r| Due {
+            id: r.try_get("id").unwrap_or_default(),
+            kind: r.try_get("kind").unwrap_or_default(),
+            run_id: r.try_get("run_id").unwrap_or_default(),
+            attempts: r.try_get("attempts").unwrap_or_default(),
+        })
+        .collect();
+
+    for d in &due {
+        sqlx::query(concat!(
+            "UPDATE side_effect_retries SET next_attempt_at = ",
+            sql_secs_from_now!("?"),
+            " WHERE id = ?"
+        ))
+        .bind(LEASE_SECS)
+        .bind(d.id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(due)
+}
+
+async fn retry_due(state: &AppState, max_attempts: i32) -> Result<usize, sqlx::Error> {
+    let due = claim_due(state).await?;
+    let n = due.len();
+    for d in due {
+        let attempts = d.attempts + 1;
+        let outcome = match Effect::parse(&d.kind) {
+            Some(effect) => effect.run(state).await,
+            None => Err(format!("unknown side effect kind '{}'", d.kind)),
+        };
+        match outcome {
+            Ok(()) => {
+                info!("{} for refresh run {} succeeded on retry {}", d.kind, d.run_id, attempts);
+                sqlx::query(
+                    "UPDATE side_effect_retries SET status = 'done', attempts = ?, finished_at = CURRENT_TIMESTAMP \
+                     WHERE id = ?",
+                )
+                .bind(attempts)
+                .bind(d.id)
+                .execute(&state.pool)
+                .await?;
+            }
+            Err(e) => {
+                let give_up = attempts >= max_attempts || Effect::parse(&d.kind).is_none();
+                let status = if give_up { "failed" } else { "pending" };
+                warn!(
+                    "{} for refresh run {} failed (retry {}/{}): {}",
+                    d.kind, d.run_id, attempts, max_attempts, e
+                );
+                sqlx::query(concat!(
+                    "UPDATE side_effect_retries SET status = ?, attempts = ?, last_error = ?, next_attempt_at = ",
+                    sql_secs_from_now!("?"),
+                    ", finished_at = CASE WHEN ? = 'failed' THEN CURRENT_TIMESTAMP END WHERE id = ?"
+                ))
+                .bind(status)
+                .bind(attempts)
+                .bind(e.chars().take(512).collect::<String>())
+                .bind(backoff_secs(attempts))
+                .bind(status)
+                .bind(d.id)
+                .execute(&state.pool)
+                .await?;
+            }
+        }
+    }
+    Ok(n)
+}
+
+/// Background loop retrying due rows of `side_effect_retries`.
+pub fn spawn_worker(state: AppState, poll: Duration, max_attempts: i32) {
+    tokio::spawn(async move {
+        info!("side effect retry worker started (poll {:?}, max attempts {})", poll, max_attempts);
+        loop {
+            if let Err(e) = retry_due(&state, max_attempts).await {
+                error!("side effect retry worker: {}", e);
+            }
+            tokio::time::sleep(poll).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_an_hour() {
+        let delays: Vec<i64> = (0..9).map(backoff_secs).collect();
+        assert_eq!(delays, [30, 60, 120, 240, 480, 960, 1920, 3600, 3600]);
+        assert_eq!(backoff_secs(-1), 30);
+    }
+
+    #[test]
+    fn kinds_round_trip() {
+        for e in [Effect::SummaryImage, Effect::FlagSprite] {
+            assert_eq!(Effect::parse(e.as_str()), Some(e));
+        }
+        assert_eq!(Effect::parse("s3_upload"), None);
+    }
+}
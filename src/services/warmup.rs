@@ -0,0 +1,64 @@
+use axum::extract::State;
+use axum::http::HeaderMap;
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::config::AppState;
+use crate::handlers::countries::list_countries;
+use crate::types::query::{ListParams, SortOrder, ValidQuery};
+use crate::utils::auth::KeyRestriction;
+use crate::utils::image::build_summary_image;
+
+/// Connections to prime: each keeps its own prepared-statement cache
+const PRIME_CONNECTIONS: usize = 4;
+
+/// Optional warm-up (`WARMUP=true`) run after migrations, before readiness turns green:
+/// primes the default listing's prepared statements on a few pool connections and
+/// renders the summary image if it's missing. Failures are logged, never fatal.
+pub async fn run(state: &AppState) {
+    let t = Instant::now();
+
+    // Same code path as `GET /countries`, so the exact SQL text gets prepared
+    let mut tasks = tokio::task::JoinSet::new();
+    for _ in 0..PRIME_CONNECTIONS {
+        let state = state.clone();
+        tasks.spawn(async move {
+            let params = ListParams {
+                region: None,
+                currency: None,
+                tag: None,
+                q: None,
+                sort: SortOrder::default(),
+                locale: None,
+                page: 1,
+                limit: 50,
+                cursor: None,
+            };
+            list_countries(State(state), HeaderMap::new(), KeyRestriction::default(), ValidQuery(params)).await
+        });
+    }
+    while let Some(res) = tasks.join_next().await {
+        if let Ok(Err(e)) = res {
+            warn!("warm-up: listing query failed: {}", e);
+        }
+    }
+
+    if tokio::fs::metadata(&state.summary_image_path).await.is_err() {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM countries")
+            .fetch_one(&state.pool)
+            .await
+            .unwrap_or((0,));
+        // Nothing to draw before the first refresh
+        if count > 0 {
+            let ranking = state.summary_ranking;
+            let res = build_summary_image(&state.pool, &state.summary_image_path, &state.branding, ranking).await;
+            if let Err(e) = &res {
+                warn!("warm-up: summary image failed: {}", e);
+            }
+            state.image_health.record(&res);
+        }
+    }
+
+    state.startup.mark_warmed();
+    info!("✅ Warm-up done in {} ms", t.elapsed().as_millis());
+}
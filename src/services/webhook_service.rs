@@ -0,0 +1,318 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use reqwest::{redirect, Client, Url};
+use sha2::Sha256;
+use sqlx::{Row, Transaction};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use crate::config::AppState;
+use crate::db::{self, Db};
+use crate::services::side_effects;
+use crate::{sql_secs_from_now, sql_skip_locked};
+
+/// Rows claimed per dispatcher tick
+const BATCH: i64 = 50;
+/// A claimed row is retried by another dispatcher if not settled within this lease
+const LEASE_SECS: i64 = 60;
+
+/// New random signing secret for a subscription
+pub fn generate_secret() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    format!("whsec_{}", hex::encode(bytes))
+}
+
+/// `sha256=<hex HMAC-SHA256(secret, "{timestamp}.{body}")>` — the `X-Signature` value.
+/// Binding the timestamp into the MAC lets receivers enforce a replay window on `X-Timestamp`.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Whether the dispatcher may POST to `ip`: not loopback, private, link-local,
+/// carrier-grade NAT, multicast or unspecified (IPv4-mapped IPv6 is checked as IPv4).
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => {
+                let first = v6.segments()[0];
+                !(v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_multicast()
+                    || (first & 0xfe00) == 0xfc00 // unique local
+                    || (first & 0xffc0) == 0xfe80) // link-local
+            }
+        },
+    }
+}
+
+/// Resolves the host of `url` and checks every address with `allow` (`is_public` outside
+/// tests). Returns the addresses so the connection can be pinned to what was checked.
+pub async fn resolve_public(url: &Url, allow: fn(IpAddr) -> bool) -> Result<Vec<SocketAddr>, String> {
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return Err("url has no host".into());
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|_| format!("url host {} does not resolve", host))?
+        .collect();
+    if addrs.is_empty() || addrs.iter().any(|a| !allow(a.ip())) {
+        return Err("url must not point at a loopback, private or link-local address".into());
+    }
+    Ok(addrs)
+}
+
+/// Records `event_type` for every active subscription inside the caller's
+/// transaction, so the event exists if and only if the data change committed.
+pub async fn enqueue_event(
+    tx: &mut Transaction<'_, Db>,
+    event_type: &str,
+    payload: &serde_json::Value,
+) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query(
+        "INSERT INTO outbox (webhook_id, event_type, payload) \
+         SELECT id, ?, ? FROM webhooks WHERE active = TRUE",
+    )
+    .bind(event_type)
+    .bind(payload.to_string())
+    .execute(&mut **tx)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+struct Due {
+    id: i64,
+    webhook_id: i32,
+    url: String,
+    secret: String,
+    event_type: String,
+    payload: String,
+    attempts: i32,
+}
+
+/// Background loop delivering outbox rows with exponential backoff.
+/// Delivery is at-least-once: receivers should dedupe on `X-Delivery-Id`.
+pub fn spawn_dispatcher(state: AppState, poll: Duration, max_attempts: i32) {
+    tokio::spawn(async move {
+        info!("webhook dispatcher started (poll {:?}, max attempts {})", poll, max_attempts);
+        loop {
+            match dispatch_due(&state, max_attempts).await {
+                Ok(0) => {}
+                Ok(n) => info!("webhook dispatcher: processed {} delivery(ies)", n),
+                Err(e) => error!("webhook dispatcher: {}", e),
+            }
+            tokio::time::sleep(poll).await;
+        }
+    });
+}
+
+async fn claim_due(state: &AppState) -> Result<Vec<Due>, sqlx::Error> {
+    // SKIP LOCKED lets several instances share the outbox without double-claiming
+    let mut tx = db::begin(&state.pool).await?;
+    let rows = sqlx::query(concat!(
+        "SELECT o.id, o.webhook_id, w.url, w.secret, o.event_type, o.payload, o.attempts \
+         FROM outbox o JOIN webhooks w ON w.id = o.webhook_id \
+         WHERE o.status = 'pending' AND o.next_attempt_at <= CURRENT_TIMESTAMP AND w.active = TRUE \
+         ORDER BY o.id ASC LIMIT ?",
+        sql_skip_locked!()
+    ))
+    .bind(BATCH)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let due: Vec<Due> = rows
+        .iter()
+        .map(|r| Due {
+            id: r.try_get("id").unwrap_or_default(),
+            webhook_id: r.try_get("webhook_id").unwrap_or_default(),
+            url: r.try_get("url").unwrap_or_default(),
+            secret: r.try_get("secret").unwrap_or_default(),
+            event_type: r.try_get("event_type").unwrap_or_default(),
+            payload: r.try_get("payload").unwrap_or_default(),
+            attempts: r.try_get("attempts").unwrap_or_default(),
+        })
+        .collect();
+
+    for d in &due {
+        sqlx::query(concat!("UPDATE outbox SET next_attempt_at = ", sql_secs_from_now!("?"), " WHERE id = ?"))
+            .bind(LEASE_SECS)
+            .bind(d.id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(due)
+}
+
+/// POSTs one delivery: (HTTP status, error). The URL was checked when the subscription
+/// was created, but its host may resolve elsewhere now, so every attempt resolves and
+/// checks it again and connects only to the addresses checked. Redirects are not
+/// followed: a public URL answering 302 to an internal one would get there otherwise.
+async fn deliver(d: &Due, timeout: Duration, allow: fn(IpAddr) -> bool) -> (Option<u16>, Option<String>) {
+    let url = match Url::parse(&d.url) {
+        Ok(u) => u,
+        Err(e) => return (None, Some(format!("invalid url: {}", e))),
+    };
+    let addrs = match resolve_public(&url, allow).await {
+        Ok(a) => a,
+        Err(e) => return (None, Some(format!("refused: {}", e))),
+    };
+    let host = url.host_str().unwrap_or_default().trim_start_matches('[').trim_end_matches(']');
+    let client = match Client::builder()
+        .timeout(timeout)
+        .redirect(redirect::Policy::none())
+        .resolve_to_addrs(host, &addrs)
+        .build()
+    {
+        Ok(c) => c,
+        Err(e) => return (None, Some(e.to_string())),
+    };
+
+    let mut req = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Event-Type", &d.event_type)
+        .header("X-Delivery-Id", d.id.to_string());
+    // Signed per attempt, so retries carry a fresh timestamp
+    if !d.secret.is_empty() {
+        let ts = Utc::now().timestamp();
+        req = req
+            .header("X-Timestamp", ts.to_string())
+            .header("X-Signature", sign(&d.secret, ts, &d.payload));
+    }
+    match req.body(d.payload.clone()).send().await {
+        Ok(resp) if resp.status().is_success() => (Some(resp.status().as_u16()), None),
+        Ok(resp) => (Some(resp.status().as_u16()), Some(format!("HTTP {}", resp.status().as_u16()))),
+        Err(e) => (None, Some(e.to_string())),
+    }
+}
+
+async fn dispatch_due(state: &AppState, max_attempts: i32) -> Result<usize, sqlx::Error> {
+    let due = claim_due(state).await?;
+    let n = due.len();
+    let timeout = state.runtime.load().external_timeout();
+    for d in due {
+        let started = Instant::now();
+        let (status_code, err) = deliver(&d, timeout, is_public).await;
+        let latency_ms = started.elapsed().as_millis() as i64;
+
+        sqlx::query(
+            "INSERT INTO webhook_deliveries (outbox_id, webhook_id, attempt, status_code, latency_ms, error) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(d.id)
+        .bind(d.webhook_id)
+        .bind(d.attempts + 1)
+        .bind(status_code)
+        .bind(latency_ms)
+        .bind(err.as_ref().map(|e| e.chars().take(512).collect::<String>()))
+        .execute(&state.pool)
+        .await?;
+
+        match err {
+            None => {
+                sqlx::query(
+                    "UPDATE outbox SET status = 'delivered', attempts = attempts + 1, \
+                     delivered_at = CURRENT_TIMESTAMP, last_error = NULL WHERE id = ?",
+                )
+                .bind(d.id)
+                .execute(&state.pool)
+                .await?;
+            }
+            Some(e) => {
+                let attempts = d.attempts + 1;
+                let status = if attempts >= max_attempts { "failed" } else { "pending" };
+                let backoff = side_effects::backoff_secs(attempts - 1);
+                warn!(
+                    "webhook delivery {} to {} failed (attempt {}/{}): {}",
+                    d.id, d.url, attempts, max_attempts, e
+                );
+                sqlx::query(concat!(
+                    "UPDATE outbox SET status = ?, attempts = ?, last_error = ?, next_attempt_at = ",
+                    sql_secs_from_now!("?"),
+                    " WHERE id = ?"
+                ))
+                .bind(status)
+                .bind(attempts)
+                .bind(e.chars().take(512).collect::<String>())
+                .bind(backoff)
+                .bind(d.id)
+                .execute(&state.pool)
+                .await?;
+            }
+        }
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn signature_matches_a_known_answer() {
+        let body = r#"{"event":"refresh.completed"}"#;
+        // printf '%s' '1700000000.{"event":"refresh.completed"}' | openssl dgst -sha256 -hmac whsec_test
+        assert_eq!(
+            sign("whsec_test", 1_700_000_000, body),
+            "sha256=d8976142a4ba8ff7000292c2dc99e4dffd749fbd1101fabd2e5a2a8fbcb5a986"
+        );
+        // The timestamp is under the MAC: replaying the body with a fresh X-Timestamp fails
+        assert_ne!(sign("whsec_test", 1_700_000_001, body), sign("whsec_test", 1_700_000_000, body));
+    }
+
+    fn due(url: String) -> Due {
+        Due {
+            id: 1,
+            webhook_id: 1,
+            url,
+            secret: String::new(),
+            event_type: "refresh.completed".into(),
+            payload: "{}".into(),
+            attempts: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn deliveries_do_not_follow_redirects_to_internal_hosts() {
+        let internal = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(200)).expect(0).mount(&internal).await;
+        let target = MockServer::start().await;
+        let location = format!("http://127.0.0.1:{}/latest/meta-data", internal.address().port());
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(302).insert_header("location", location.as_str()))
+            .mount(&target)
+            .await;
+
+        // Both mock servers are on loopback, so this lets the first hop through
+        let (status, err) = deliver(&due(target.uri()), Duration::from_secs(5), |_| true).await;
+        assert_eq!(status, Some(302));
+        assert_eq!(err.as_deref(), Some("HTTP 302"));
+
+        // With the real check the loopback target itself is refused before anything is sent
+        let (status, err) = deliver(&due(internal.uri()), Duration::from_secs(5), is_public).await;
+        assert_eq!(status, None);
+        assert!(err.unwrap().starts_with("refused: url must not point at"));
+    }
+}
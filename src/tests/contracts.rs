@@ -0,0 +1,226 @@
+// Response contracts: each test renders a response body from fixed fixtures through the
+// same serializer the handler uses and compares it with its golden file.
+
+use axum::{body::to_bytes, http::header, response::IntoResponse};
+use chrono::{TimeZone, Utc};
+use serde_json::{json, Value};
+
+use super::golden::assert_golden;
+use crate::handlers::countries::{country_links, list_link_header, list_page};
+use crate::models::country::Country;
+use crate::routes::openapi;
+use crate::routes::registry::{DEPRECATED_PARAMS, ENDPOINTS};
+use crate::services::data_quality::{self, CountryFacts};
+use crate::services::job_queue::JobKind;
+use crate::services::refresh_service::RefreshResult;
+use crate::services::{bundle, checksum};
+use crate::types::query::{Cursor, FromQuery, ListParams, SortOrder};
+use crate::utils::error::ApiError;
+use crate::utils::{case, envelope, jsonapi};
+
+fn ghana() -> Country {
+    Country {
+        id: 2,
+        name: "Ghana".into(),
+        capital: Some("Accra".into()),
+        region: Some("Africa".into()),
+        population: 31072940,
+        currency_code: Some("GHS".into()),
+        exchange_rate: Some(15.34),
+        estimated_gdp: Some(3029834520.6),
+        flag_url: Some("https://flagcdn.com/gh.svg".into()),
+        flag_emoji: Some("🇬🇭".into()),
+        last_refreshed_at: Some("2024-01-01T00:00:00Z".into()),
+        data_source: "restcountries".into(),
+        source_fetched_at: Some("2024-01-01T00:00:00Z".into()),
+        rate_source: Some("open.er-api".into()),
+    }
+}
+
+/// Every optional field empty
+fn antarctica() -> Country {
+    Country {
+        id: 9,
+        name: "Antarctica".into(),
+        capital: None,
+        region: None,
+        population: 1000,
+        currency_code: None,
+        exchange_rate: None,
+        estimated_gdp: None,
+        flag_url: None,
+        flag_emoji: None,
+        last_refreshed_at: Some("2024-01-01T00:00:00Z".into()),
+        data_source: "restcountries".into(),
+        source_fetched_at: None,
+        rate_source: None,
+    }
+}
+
+#[test]
+fn country() {
+    // GET /countries, GET /countries/:name, JSON exports
+    assert_golden("country", &json!([ghana(), antarctica()]));
+}
+
+#[test]
+fn country_page() {
+    // GET /countries?region=Africa&page=2&limit=2 (LIST_BARE_ARRAY=false)
+    let raw = serde_json::from_value(json!({ "region": "Africa", "page": 2, "limit": 2 })).unwrap();
+    let p = ListParams::from_raw(raw).unwrap();
+    let data = [ghana(), antarctica()].iter().map(|c| serde_json::to_value(c).unwrap()).collect();
+    assert_golden(
+        "country_page",
+        &json!({ "body": list_page(data, &p, 5, 3, None), "link": list_link_header(&p, 3, None) }),
+    );
+
+    // GET /countries?sort=gdp_desc&limit=1&cursor=<after Ghana>: Antarctica, without GDP, is next
+    let after_ghana = Cursor::after(&ghana(), SortOrder::GdpDesc).encode(SortOrder::GdpDesc, None);
+    let raw = serde_json::from_value(json!({ "sort": "gdp_desc", "limit": 1, "cursor": after_ghana })).unwrap();
+    let p = ListParams::from_raw(raw).unwrap();
+    let next = Cursor::after(&antarctica(), SortOrder::GdpDesc).encode(SortOrder::GdpDesc, None);
+    assert_golden(
+        "country_page_cursor",
+        &json!({
+            "body": list_page(vec![serde_json::to_value(antarctica()).unwrap()], &p, 2, 2, Some(next.clone())),
+            "link": list_link_header(&p, 2, Some(&next)),
+        }),
+    );
+}
+
+#[test]
+fn country_with_links() {
+    // HATEOAS_LINKS=true
+    let body: Vec<Value> = [ghana(), antarctica()]
+        .iter()
+        .map(|c| {
+            let mut v = serde_json::to_value(c).unwrap();
+            v["_links"] = country_links(c);
+            v
+        })
+        .collect();
+    assert_golden("country_links", &json!(body));
+}
+
+#[test]
+fn country_jsonapi() {
+    let resources: Vec<Value> = [ghana(), antarctica()].iter().map(jsonapi::country_resource).collect();
+    assert_golden("country_jsonapi", &json!(resources));
+}
+
+#[test]
+fn country_camel_case() {
+    assert_golden("country_camel", &case::camelize(serde_json::to_value(ghana()).unwrap()));
+}
+
+#[test]
+fn envelope() {
+    use axum::http::StatusCode;
+    assert_golden(
+        "envelope",
+        &json!({
+            "list": envelope::wrap(StatusCode::OK, json!([{ "name": "Ghana" }])),
+            "error": envelope::wrap(StatusCode::NOT_FOUND, json!({ "error": "Country not found" })),
+        }),
+    );
+}
+
+#[tokio::test]
+async fn errors() {
+    let next_allowed_at = Utc.with_ymd_and_hms(2024, 1, 1, 0, 1, 0).unwrap();
+    let cases = [
+        ("validation", ApiError::Validation("limit must be between 1 and 200".into())),
+        ("unauthorized", ApiError::Unauthorized("missing bearer token".into())),
+        ("forbidden", ApiError::Forbidden("API key \"analytics\" lacks the \"write\" scope".into())),
+        ("not_found", ApiError::NotFound("Country not found".into())),
+        ("external", ApiError::External("Could not fetch data from rates".into())),
+        ("timeout", ApiError::Timeout("request deadline exceeded".into())),
+        ("pool_exhausted", ApiError::PoolExhausted("no database connection available".into())),
+        (
+            "unknown_params",
+            ApiError::UnknownParams { unknown: vec!["regoin".into()], allowed: &["region", "sort"] },
+        ),
+        ("rate_limited", ApiError::RateLimited { retry_after_secs: 60, next_allowed_at }),
+        ("locked_out", ApiError::LockedOut { retry_after_secs: 120 }),
+        ("internal", ApiError::Internal("boom".into())),
+    ];
+    let mut out = serde_json::Map::new();
+    for (name, err) in cases {
+        let res = err.into_response();
+        let status = res.status().as_u16();
+        let retry_after = res.headers().get(header::RETRY_AFTER).map(|v| v.to_str().unwrap().to_string());
+        let body: Value = serde_json::from_slice(&to_bytes(res.into_body(), usize::MAX).await.unwrap()).unwrap();
+        out.insert(name.into(), json!({ "status": status, "retry_after": retry_after, "body": body }));
+    }
+    assert_golden("errors", &Value::Object(out));
+}
+
+#[test]
+fn refresh_result() {
+    // POST /countries/refresh; counts are zero, the shape is what matters
+    let result = RefreshResult { last_refreshed_at: "2024-01-01T00:00:00Z".into(), ..Default::default() };
+    assert_golden("refresh_result", &serde_json::to_value(result).unwrap());
+}
+
+#[test]
+fn dataset_checksum() {
+    assert_golden("checksum", &serde_json::to_value(checksum::compute(&[ghana(), antarctica()])).unwrap());
+}
+
+#[tokio::test]
+async fn data_quality_report() {
+    // A bad copy of Ghana trips most checks
+    let broken = Country {
+        name: "ghana ".into(),
+        population: -1,
+        currency_code: Some("XYZ".into()),
+        exchange_rate: None,
+        estimated_gdp: Some(0.0),
+        ..ghana()
+    };
+    let facts: Vec<CountryFacts> = [ghana(), antarctica(), broken]
+        .into_iter()
+        .map(|c| CountryFacts {
+            name: c.name,
+            population: c.population,
+            currency_code: c.currency_code,
+            exchange_rate: c.exchange_rate,
+            estimated_gdp: c.estimated_gdp,
+            flag_url: c.flag_url,
+        })
+        .collect();
+    let report = data_quality::run(&reqwest::Client::new(), &facts, false).await;
+    assert_golden("data_quality", &serde_json::to_value(report).unwrap());
+}
+
+#[test]
+fn index_endpoints() {
+    // GET / lists every route; a new or changed route shows up here
+    assert_golden(
+        "index_endpoints",
+        &json!({ "endpoints": ENDPOINTS, "deprecated_params": DEPRECATED_PARAMS }),
+    );
+}
+
+#[test]
+fn openapi_document() {
+    // GET /openapi.json; parameter and body descriptions come from handler doc comments
+    assert_golden("openapi", &serde_json::to_value(openapi::document()).unwrap());
+}
+
+#[test]
+fn bundle_manifest_entry() {
+    assert_golden("bundle_manifest_entry", &bundle::manifest_entry("countries.json", b"[]"));
+}
+
+#[test]
+fn job_params() {
+    // `params` of GET /jobs/:id
+    let kinds = [
+        JobKind::Refresh { force: true },
+        JobKind::Export { export_id: 3, trigger: "schedule".into() },
+        JobKind::RenderImages,
+    ];
+    let out: serde_json::Map<String, Value> = kinds.iter().map(|k| (k.name().to_string(), k.params())).collect();
+    assert_golden("job_params", &Value::Object(out));
+}
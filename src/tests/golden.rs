@@ -0,0 +1,30 @@
+// Golden files for response contracts: `src/tests/golden/<name>.json` holds the exact
+// JSON a serializer produced when the contract was last accepted. A mismatch fails the
+// test with a diff; after an intended change, `UPDATE_GOLDEN=1 cargo test` rewrites the
+// files and the diff shows up in review.
+
+use serde_json::Value;
+use std::path::PathBuf;
+
+fn path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/tests/golden")
+        .join(format!("{}.json", name))
+}
+
+pub fn assert_golden(name: &str, actual: &Value) {
+    let path = path(name);
+    let actual = serde_json::to_string_pretty(actual).expect("serializable") + "\n";
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, &actual).unwrap_or_else(|e| panic!("write {}: {}", path.display(), e));
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!("no golden file {}; run with UPDATE_GOLDEN=1 to create it", path.display())
+    });
+    assert_eq!(
+        expected, actual,
+        "response contract '{}' changed; if intended, rerun with UPDATE_GOLDEN=1",
+        name
+    );
+}
@@ -0,0 +1,4 @@
+mod contracts;
+mod golden;
+mod integration;
+mod query_props;
@@ -1,18 +1,345 @@
+// Upstream payloads, parsed leniently: restcountries and open.er-api have both changed
+// shapes before (v2 -> v3 turned `capital` into a list and `currencies` into a map).
+// Unknown or reshaped fields become `SchemaWarning`s in the refresh result; only a
+// payload with nothing usable at the top level fails the refresh.
+
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Deserialize)]
-pub struct RcCurrency { pub code: Option<String> }
+/// Any JSON value, so a field that changed type doesn't fail its whole record.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Loose {
+    Text(String),
+    Int(i64),
+    Float(f64),
+    List(Vec<Loose>),
+    Object(BTreeMap<String, Loose>),
+    Other(serde_json::Value),
+}
+
+impl Loose {
+    fn shape(&self) -> &'static str {
+        match self {
+            Loose::Text(_) => "string",
+            Loose::Int(_) | Loose::Float(_) => "number",
+            Loose::List(_) => "list",
+            Loose::Object(_) => "object",
+            Loose::Other(_) => "other",
+        }
+    }
+
+    /// The text itself, the first text in a list, or the first of `keys` in an object.
+    fn text(&self, keys: &[&str]) -> Option<String> {
+        match self {
+            Loose::Text(s) => Some(s.clone()),
+            Loose::List(items) => items.iter().find_map(|i| i.text(keys)),
+            Loose::Object(map) => keys.iter().find_map(|k| map.get(*k)).and_then(|v| v.text(keys)),
+            _ => None,
+        }
+    }
+
+    fn number(&self) -> Option<f64> {
+        match self {
+            Loose::Int(n) => Some(*n as f64),
+            Loose::Float(n) => Some(*n),
+            Loose::Text(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn example(&self) -> String {
+        let s = match self {
+            Loose::Text(s) => s.clone(),
+            Loose::Int(n) => n.to_string(),
+            Loose::Float(n) => n.to_string(),
+            Loose::Other(v) => v.to_string(),
+            Loose::List(_) => "[…]".into(),
+            Loose::Object(map) => format!("{{{}}}", map.keys().cloned().collect::<Vec<_>>().join(",")),
+        };
+        s.chars().take(80).collect()
+    }
+}
 
 #[derive(Deserialize)]
 pub struct RcCountry {
+    #[serde(default)]
+    pub name: Option<Loose>,
+    #[serde(default, rename = "alpha2Code", alias = "cca2")]
+    pub alpha2_code: Option<Loose>,
+    #[serde(default)]
+    pub capital: Option<Loose>,
+    #[serde(default)]
+    pub region: Option<Loose>,
+    #[serde(default)]
+    pub population: Option<Loose>,
+    #[serde(default, alias = "flags")]
+    pub flag: Option<Loose>,
+    #[serde(default)]
+    pub currencies: Option<Loose>,
+    /// Fields the `fields=` filter didn't ask for
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// One restcountries record after normalization; `name` is empty when it had none.
+#[derive(Debug, Default, PartialEq)]
+pub struct UpstreamCountry {
     pub name: String,
+    pub alpha2_code: Option<String>,
     pub capital: Option<String>,
     pub region: Option<String>,
     pub population: Option<i64>,
     pub flag: Option<String>,
-    pub currencies: Option<Vec<RcCurrency>>,
+    /// First listed currency
+    pub currency_code: Option<String>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ErResult {
+    Success,
+    Error,
+    #[serde(other)]
+    Unknown,
 }
 
 #[derive(Deserialize)]
-pub struct ErRates { pub rates: HashMap<String, f64> }
+pub struct ErRates {
+    #[serde(default)]
+    pub result: Option<ErResult>,
+    #[serde(default, rename = "error-type")]
+    pub error_type: Option<String>,
+    // exchangerate-api.com's v6 spelling
+    #[serde(default, alias = "conversion_rates")]
+    pub rates: Option<HashMap<String, Loose>>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct SchemaWarning {
+    /// "restcountries" | "open-er-api"
+    pub source: &'static str,
+    /// "unexpected_field" | "shape_changed" | "invalid_record" | "invalid_value" | "unexpected_value"
+    pub kind: &'static str,
+    pub field: Option<String>,
+    /// Records (or rates) affected
+    pub count: usize,
+    /// First offending value, truncated
+    pub example: Option<String>,
+}
+
+/// (source, kind, field)
+type WarningKey = (&'static str, &'static str, Option<String>);
+
+/// Collapses repeated warnings into one entry per key, keeping the first example.
+#[derive(Default)]
+struct Warnings(BTreeMap<WarningKey, (usize, Option<String>)>);
+
+impl Warnings {
+    fn add(&mut self, source: &'static str, kind: &'static str, field: Option<&str>, example: Option<String>) {
+        let e = self.0.entry((source, kind, field.map(String::from))).or_insert((0, example));
+        e.0 += 1;
+    }
+
+    fn into_vec(self) -> Vec<SchemaWarning> {
+        self.0
+            .into_iter()
+            .map(|((source, kind, field), (count, example))| SchemaWarning { source, kind, field, count, example })
+            .collect()
+    }
+}
+
+const RC: &str = "restcountries";
+const ER: &str = "open-er-api";
+
+pub struct ParsedCountries {
+    pub countries: Vec<UpstreamCountry>,
+    /// Elements that weren't objects at all; dropped
+    pub invalid: usize,
+    pub warnings: Vec<SchemaWarning>,
+}
+
+/// Text field in the v2 shape (a string), else warn and take what's usable.
+fn text_field(w: &mut Warnings, field: &str, v: Option<Loose>, keys: &[&str]) -> Option<String> {
+    let v = v?;
+    if !matches!(v, Loose::Text(_)) {
+        w.add(RC, "shape_changed", Some(field), Some(v.shape().into()));
+    }
+    let text = v.text(keys);
+    if text.is_none() {
+        w.add(RC, "invalid_value", Some(field), Some(v.example()));
+    }
+    text
+}
+
+pub fn parse_countries(body: &[u8]) -> Result<ParsedCountries, String> {
+    let items: Vec<serde_json::Value> = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+    let mut w = Warnings::default();
+    let mut invalid = 0;
+    let mut countries = Vec::with_capacity(items.len());
+
+    for item in items {
+        let c: RcCountry = match serde_json::from_value(item.clone()) {
+            Ok(c) => c,
+            Err(_) => {
+                invalid += 1;
+                w.add(RC, "invalid_record", None, Some(item.to_string().chars().take(80).collect()));
+                continue;
+            }
+        };
+        for key in c.extra.keys() {
+            w.add(RC, "unexpected_field", Some(key), None);
+        }
+
+        // v3 spells the plain name `name.common` and the flag `flags.svg`
+        let name = text_field(&mut w, "name", c.name, &["common", "official"]).unwrap_or_default();
+        let alpha2_code = text_field(&mut w, "alpha2Code", c.alpha2_code, &[]);
+        let capital = text_field(&mut w, "capital", c.capital, &[]);
+        let region = text_field(&mut w, "region", c.region, &[]);
+        let flag = text_field(&mut w, "flag", c.flag, &["svg", "png"]);
+
+        let population = c.population.and_then(|p| {
+            if !matches!(p, Loose::Int(_)) {
+                w.add(RC, "shape_changed", Some("population"), Some(p.shape().into()));
+            }
+            let n = p.number().filter(|n| n.is_finite() && *n >= 0.0).map(|n| n.round() as i64);
+            if n.is_none() {
+                w.add(RC, "invalid_value", Some("population"), Some(p.example()));
+            }
+            n
+        });
+
+        // v2: [{"code": "GHS", ...}], v3: {"GHS": {...}}
+        let currency_code = c.currencies.and_then(|cur| match cur {
+            Loose::List(items) => items.first().and_then(|i| i.text(&["code"])),
+            Loose::Object(map) => {
+                w.add(RC, "shape_changed", Some("currencies"), Some("object".into()));
+                map.keys().next().cloned()
+            }
+            other => {
+                w.add(RC, "invalid_value", Some("currencies"), Some(other.example()));
+                None
+            }
+        });
+
+        countries.push(UpstreamCountry { name, alpha2_code, capital, region, population, flag, currency_code });
+    }
+
+    Ok(ParsedCountries { countries, invalid, warnings: w.into_vec() })
+}
+
+/// Usable rates plus warnings. Fails only when the provider reports an error or sends no rates.
+pub fn parse_rates(body: &[u8]) -> Result<(HashMap<String, f64>, Vec<SchemaWarning>), String> {
+    let resp: ErRates = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+    let mut w = Warnings::default();
+    match resp.result {
+        Some(ErResult::Error) => {
+            return Err(format!("provider error: {}", resp.error_type.as_deref().unwrap_or("unknown")));
+        }
+        Some(ErResult::Unknown) => w.add(ER, "unexpected_value", Some("result"), None),
+        _ => {}
+    }
+    let raw = resp.rates.ok_or_else(|| "no `rates` object".to_string())?;
+
+    let mut rates = HashMap::with_capacity(raw.len());
+    for (code, v) in raw {
+        if !matches!(v, Loose::Int(_) | Loose::Float(_)) {
+            w.add(ER, "shape_changed", Some("rates"), Some(v.shape().into()));
+        }
+        match v.number() {
+            Some(r) => {
+                rates.insert(code, r);
+            }
+            None => w.add(ER, "invalid_value", Some("rates"), Some(format!("{}: {}", code, v.example()))),
+        }
+    }
+    Ok((rates, w.into_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v2_payload_has_no_warnings() {
+        let body = br#"[{"name":"Ghana","alpha2Code":"GH","capital":"Accra","region":"Africa",
+            "population":31072940,"flag":"https://flagcdn.com/gh.svg","currencies":[{"code":"GHS","name":"Ghanaian cedi"}]}]"#;
+        let p = parse_countries(body).unwrap();
+        assert!(p.warnings.is_empty(), "{:?}", p.warnings);
+        assert_eq!(p.invalid, 0);
+        assert_eq!(
+            p.countries[0],
+            UpstreamCountry {
+                name: "Ghana".into(),
+                alpha2_code: Some("GH".into()),
+                capital: Some("Accra".into()),
+                region: Some("Africa".into()),
+                population: Some(31072940),
+                flag: Some("https://flagcdn.com/gh.svg".into()),
+                currency_code: Some("GHS".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn v3_shapes_are_read_and_reported() {
+        let body = br#"[{"name":{"common":"Ghana","official":"Republic of Ghana"},"cca2":"GH",
+            "capital":["Accra"],"region":"Africa","population":31072940.0,
+            "flags":{"png":"gh.png","svg":"gh.svg"},"currencies":{"GHS":{"name":"Ghanaian cedi"}},"area":238533}]"#;
+        let p = parse_countries(body).unwrap();
+        let c = &p.countries[0];
+        assert_eq!(c.name, "Ghana");
+        assert_eq!(c.alpha2_code.as_deref(), Some("GH"));
+        assert_eq!(c.capital.as_deref(), Some("Accra"));
+        assert_eq!(c.flag.as_deref(), Some("gh.svg"));
+        assert_eq!(c.population, Some(31072940));
+        assert_eq!(c.currency_code.as_deref(), Some("GHS"));
+
+        let fields: Vec<_> = p.warnings.iter().map(|w| (w.kind, w.field.as_deref().unwrap_or(""))).collect();
+        assert!(fields.contains(&("unexpected_field", "area")));
+        assert!(fields.contains(&("shape_changed", "capital")));
+        assert!(fields.contains(&("shape_changed", "currencies")));
+        assert!(fields.contains(&("shape_changed", "name")));
+    }
+
+    #[test]
+    fn bad_records_are_dropped_not_fatal() {
+        let body = br#"[42, {"capital":"Nowhere"}, {"name":"Chad","population":"abc"}, {"name":"Peru"}, {"name":"Togo"}]"#;
+        let p = parse_countries(body).unwrap();
+        assert_eq!(p.invalid, 1);
+        assert_eq!(p.countries.len(), 4);
+        assert_eq!(p.countries[0].name, "");
+        assert_eq!(p.countries[1].population, None);
+        let invalid = p.warnings.iter().find(|w| w.kind == "invalid_value").unwrap();
+        assert_eq!(invalid.field.as_deref(), Some("population"));
+        assert!(parse_countries(br#"{"message":"moved"}"#).is_err());
+    }
+
+    #[test]
+    fn repeated_warnings_are_counted_once() {
+        let body = br#"[{"name":"A","capital":["x"]},{"name":"B","capital":["y"]}]"#;
+        let p = parse_countries(body).unwrap();
+        assert_eq!(p.warnings.len(), 1);
+        assert_eq!(p.warnings[0].count, 2);
+        assert_eq!(p.warnings[0].example.as_deref(), Some("list"));
+    }
+
+    #[test]
+    fn rates_are_lenient() {
+        let body = br#"{"result":"success","rates":{"USD":1,"GHS":"15.3","XXX":null,"EUR":0.92}}"#;
+        let (rates, warnings) = parse_rates(body).unwrap();
+        assert_eq!(rates.len(), 3);
+        assert_eq!(rates["GHS"], 15.3);
+        assert_eq!(rates["USD"], 1.0);
+        assert!(warnings.iter().any(|w| w.kind == "invalid_value" && w.example.as_deref() == Some("XXX: null")));
+
+        let (rates, warnings) = parse_rates(br#"{"result":"partial","conversion_rates":{"USD":1}}"#).unwrap();
+        assert_eq!(rates.len(), 1);
+        assert_eq!(warnings[0].kind, "unexpected_value");
+
+        assert!(parse_rates(br#"{"result":"error","error-type":"invalid-key"}"#)
+            .unwrap_err()
+            .contains("invalid-key"));
+        assert!(parse_rates(br#"{"result":"success"}"#).is_err());
+    }
+}
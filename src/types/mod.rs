@@ -1 +1,3 @@
-pub mod external;
\ No newline at end of file
+pub mod external;
+pub mod path;
+pub mod query;
\ No newline at end of file
@@ -0,0 +1,127 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path},
+    http::request::Parts,
+};
+
+use crate::utils::error::ApiError;
+
+/// Longest accepted `:name` segment, in characters. Real names top out around 60
+/// ("The United Kingdom of Great Britain and Northern Ireland"); aliases are shorter.
+pub const MAX_NAME_CHARS: usize = 100;
+
+/// `:name` path segment for country routes, checked before any query runs:
+/// trimmed, 1-100 characters, no control characters (including NUL).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CountryName(String);
+
+impl CountryName {
+    pub fn parse(s: &str) -> Result<Self, ApiError> {
+        // Multi-kilobyte segments are refused on byte length alone, before any scan
+        if s.len() > MAX_NAME_CHARS * 4 + 64 {
+            return Err(too_long());
+        }
+        // Before trimming, so a trailing "%0A" isn't silently dropped
+        if s.chars().any(char::is_control) {
+            return Err(ApiError::Validation(
+                "country name must not contain control characters".into(),
+            ));
+        }
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ApiError::Validation("country name must not be empty".into()));
+        }
+        if s.chars().count() > MAX_NAME_CHARS {
+            return Err(too_long());
+        }
+        Ok(CountryName(s.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+fn too_long() -> ApiError {
+    ApiError::Validation(format!("country name must be at most {} characters", MAX_NAME_CHARS))
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for CountryName {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(raw) = Path::<String>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| ApiError::Validation(e.body_text()))?;
+        CountryName::parse(&raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn accepts_real_names() {
+        for name in [
+            "Nigeria",
+            "Côte d'Ivoire",
+            "São Tomé and Príncipe",
+            "Bosnia and Herzegovina",
+            "The United Kingdom of Great Britain and Northern Ireland",
+            "日本",
+            "  Ghana  ",
+        ] {
+            let parsed = CountryName::parse(name).unwrap();
+            assert_eq!(parsed.as_str(), name.trim());
+        }
+    }
+
+    #[test]
+    fn rejects_absurd_inputs() {
+        for name in [
+            "",
+            "   ",
+            "Nige\0ria",
+            "\0",
+            "Ghana\n",
+            "Gh\u{7f}ana",
+            "Gh\u{1b}[31mana",
+            "\u{85}Chad",
+        ] {
+            assert!(CountryName::parse(name).is_err(), "{:?}", name);
+        }
+        assert!(CountryName::parse(&"a".repeat(MAX_NAME_CHARS)).is_ok());
+        assert!(CountryName::parse(&"a".repeat(MAX_NAME_CHARS + 1)).is_err());
+        assert!(CountryName::parse(&"é".repeat(MAX_NAME_CHARS)).is_ok());
+        assert!(CountryName::parse(&"x".repeat(64 * 1024)).is_err());
+    }
+
+    #[test]
+    fn fuzz_random_segments() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0x5eed);
+        for _ in 0..5_000 {
+            let len = match rng.gen_range(0..10) {
+                0 => rng.gen_range(100..5_000),
+                _ => rng.gen_range(0..120),
+            };
+            let s: String = (0..len)
+                .map(|_| match rng.gen_range(0..4) {
+                    0 => rng.gen_range(0u8..0x80) as char,
+                    1 => rng.gen_range('\u{0}'..='\u{20}'),
+                    _ => rng.gen::<char>(),
+                })
+                .collect();
+            // Never panics; anything accepted satisfies the invariants
+            if let Ok(name) = CountryName::parse(&s) {
+                let n = name.as_str();
+                assert!(!n.is_empty());
+                assert!(n.chars().count() <= MAX_NAME_CHARS);
+                assert!(!n.chars().any(char::is_control));
+                assert_eq!(n, n.trim());
+            }
+        }
+    }
+}
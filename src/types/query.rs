@@ -0,0 +1,484 @@
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, Query},
+    http::request::Parts,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use utoipa::IntoParams;
+
+use crate::config::AppState;
+use crate::db;
+use crate::models::country::Country;
+use crate::utils::currency;
+use crate::utils::error::ApiError;
+
+/// Query-string extractor that deserializes `T::Raw` and validates it into `T`.
+/// Both malformed input (`?page=abc`) and invalid values become `ApiError::Validation`,
+/// so handlers only ever see typed, already-checked params.
+/// With `STRICT_QUERY_PARAMS=true`, keys outside `T::KNOWN_PARAMS` are rejected too.
+pub struct ValidQuery<T>(pub T);
+
+pub trait FromQuery: Sized {
+    type Raw: DeserializeOwned + Send;
+    /// Every key the endpoint accepts, for strict mode. `None` opts out of the check.
+    const KNOWN_PARAMS: Option<&'static [&'static str]> = None;
+    fn from_raw(raw: Self::Raw) -> Result<Self, ApiError>;
+}
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidQuery<T>
+where
+    T: FromQuery + Send,
+    S: Send + Sync,
+    AppState: FromRef<S>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(allowed) = T::KNOWN_PARAMS {
+            if AppState::from_ref(state).runtime.load().strict_query_params {
+                reject_unknown(&parts.uri, allowed)?;
+            }
+        }
+        parse_query(&parts.uri).map(ValidQuery)
+    }
+}
+
+/// The typed half of [`ValidQuery`]: deserialize the query string into `T::Raw`, then validate.
+pub fn parse_query<T: FromQuery>(uri: &axum::http::Uri) -> Result<T, ApiError> {
+    let Query(raw) = Query::<T::Raw>::try_from_uri(uri).map_err(|e| ApiError::Validation(e.body_text()))?;
+    T::from_raw(raw)
+}
+
+/// Catches typos like `?regoin=Africa` that serde would otherwise ignore (returning everything).
+fn reject_unknown(uri: &axum::http::Uri, allowed: &'static [&'static str]) -> Result<(), ApiError> {
+    let Ok(Query(pairs)) = Query::<Vec<(String, String)>>::try_from_uri(uri) else {
+        // Malformed query strings are reported by the typed parse below
+        return Ok(());
+    };
+    let mut unknown: Vec<String> = pairs
+        .into_iter()
+        .map(|(k, _)| k)
+        .filter(|k| !allowed.contains(&k.as_str()))
+        .collect();
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    unknown.sort();
+    unknown.dedup();
+    Err(ApiError::UnknownParams { unknown, allowed })
+}
+
+/// `?sort=` for `/countries`. The variant list is the single source for parsing,
+/// the error message and the ORDER BY clause.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Id,
+    GdpDesc,
+    GdpAsc,
+    NameAsc,
+    PopulationDesc,
+}
+
+impl SortOrder {
+    const CHOICES: [SortOrder; 4] = [
+        SortOrder::GdpDesc,
+        SortOrder::GdpAsc,
+        SortOrder::NameAsc,
+        SortOrder::PopulationDesc,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortOrder::Id => "id",
+            SortOrder::GdpDesc => "gdp_desc",
+            SortOrder::GdpAsc => "gdp_asc",
+            SortOrder::NameAsc => "name_asc",
+            SortOrder::PopulationDesc => "population_desc",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, ApiError> {
+        Self::CHOICES
+            .into_iter()
+            .find(|o| o.as_str() == s)
+            .ok_or_else(|| {
+                let allowed: Vec<&str> = Self::CHOICES.iter().map(|o| o.as_str()).collect();
+                ApiError::Validation(format!("sort must be one of {}", allowed.join(", ")))
+            })
+    }
+
+    /// Every order ends on the unique `id`, so rows with equal sort values (same
+    /// population, NULL GDP, ...) keep a stable position and pages never overlap or skip.
+    pub fn order_by(self) -> &'static str {
+        match self {
+            SortOrder::Id => " ORDER BY id ASC",
+            SortOrder::GdpDesc => " ORDER BY estimated_gdp DESC, id ASC",
+            SortOrder::GdpAsc => " ORDER BY estimated_gdp ASC, id ASC",
+            SortOrder::NameAsc => " ORDER BY name ASC, id ASC",
+            SortOrder::PopulationDesc => " ORDER BY population DESC, id ASC",
+        }
+    }
+}
+
+/// `?locale=` for name ordering: picks the MySQL 8 UCA collation whose rules match
+/// what readers of that language expect (e.g. Swedish puts "Å" after "Z").
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortLocale {
+    /// Root collation; also right for en, fr, pt, it, nl
+    Root,
+    De,
+    Es,
+    Sv,
+    Da,
+    Pl,
+    Tr,
+}
+
+impl SortLocale {
+    pub const ALLOWED: &'static str = "en, fr, pt, it, nl, de, es, sv, da, pl, tr";
+
+    /// Accepts full tags like "sv-SE" by looking at the primary subtag only.
+    pub fn parse(s: &str) -> Result<Self, ApiError> {
+        let primary = s.split(['-', '_']).next().unwrap_or("").to_ascii_lowercase();
+        match primary.as_str() {
+            "en" | "fr" | "pt" | "it" | "nl" => Ok(SortLocale::Root),
+            "de" => Ok(SortLocale::De),
+            "es" => Ok(SortLocale::Es),
+            "sv" => Ok(SortLocale::Sv),
+            "da" => Ok(SortLocale::Da),
+            "pl" => Ok(SortLocale::Pl),
+            "tr" => Ok(SortLocale::Tr),
+            _ => Err(ApiError::Validation(format!("locale must be one of {}", Self::ALLOWED))),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortLocale::Root => "en",
+            SortLocale::De => "de",
+            SortLocale::Es => "es",
+            SortLocale::Sv => "sv",
+            SortLocale::Da => "da",
+            SortLocale::Pl => "pl",
+            SortLocale::Tr => "tr",
+        }
+    }
+
+    pub fn collation(self) -> &'static str {
+        match self {
+            SortLocale::Root => "utf8mb4_0900_ai_ci",
+            // Phonebook order: "ä" as "ae"
+            SortLocale::De => "utf8mb4_de_pb_0900_ai_ci",
+            SortLocale::Es => "utf8mb4_es_0900_ai_ci",
+            SortLocale::Sv => "utf8mb4_sv_0900_ai_ci",
+            SortLocale::Da => "utf8mb4_da_0900_ai_ci",
+            SortLocale::Pl => "utf8mb4_pl_0900_ai_ci",
+            SortLocale::Tr => "utf8mb4_tr_0900_ai_ci",
+        }
+    }
+
+    /// `name` compared under this locale's collation
+    pub fn name_col(self) -> String {
+        db::collate("name", self.collation())
+    }
+
+    /// `ORDER BY` for `sort=name_asc` under this locale. Can't use the name index.
+    pub fn order_by_name(self) -> String {
+        format!(" ORDER BY {} ASC, id ASC", self.name_col())
+    }
+}
+
+/// Sort value of the last row a keyset page served, by sort order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SortKey {
+    /// `sort=id`: the id alone orders the rows
+    Id,
+    /// `gdp_desc` / `gdp_asc`; NULL GDPs sort last / first
+    Gdp(Option<f64>),
+    Name(String),
+    Population(i64),
+}
+
+/// `?cursor=` for keyset paging: where the previous page ended, as `(sort value, id)`.
+/// Opaque to clients (hex of `sort|locale|id|value`); only valid with the `sort` and
+/// `locale` it was issued for. Unlike `?page=`, it neither skips nor repeats rows
+/// when countries are added or removed between requests, and costs the same at any depth.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cursor {
+    pub id: i64,
+    pub key: SortKey,
+}
+
+impl Cursor {
+    /// The cursor continuing after `c` under `sort`.
+    pub fn after(c: &Country, sort: SortOrder) -> Self {
+        let key = match sort {
+            SortOrder::Id => SortKey::Id,
+            SortOrder::GdpDesc | SortOrder::GdpAsc => SortKey::Gdp(c.estimated_gdp),
+            SortOrder::NameAsc => SortKey::Name(c.name.clone()),
+            SortOrder::PopulationDesc => SortKey::Population(c.population),
+        };
+        Cursor { id: c.id, key }
+    }
+
+    pub fn encode(&self, sort: SortOrder, locale: Option<SortLocale>) -> String {
+        let value = match &self.key {
+            SortKey::Id | SortKey::Gdp(None) => String::new(),
+            SortKey::Gdp(Some(v)) => v.to_string(),
+            SortKey::Name(n) => n.clone(),
+            SortKey::Population(p) => p.to_string(),
+        };
+        let locale = locale.map(SortLocale::as_str).unwrap_or("");
+        hex::encode(format!("{}|{}|{}|{}", sort.as_str(), locale, self.id, value))
+    }
+
+    pub fn decode(s: &str, sort: SortOrder, locale: Option<SortLocale>) -> Result<Self, ApiError> {
+        let invalid = || ApiError::Validation("cursor is invalid; use a next_cursor from a previous page".into());
+        let text = hex::decode(s.trim()).ok().and_then(|b| String::from_utf8(b).ok()).ok_or_else(invalid)?;
+        let mut parts = text.splitn(4, '|');
+        let (Some(cursor_sort), Some(cursor_locale), Some(id), Some(value)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        if cursor_sort != sort.as_str() || cursor_locale != locale.map(SortLocale::as_str).unwrap_or("") {
+            return Err(ApiError::Validation(
+                "cursor belongs to a different sort or locale; start again without it".into(),
+            ));
+        }
+        let id = id.parse().map_err(|_| invalid())?;
+        let key = match sort {
+            SortOrder::Id if value.is_empty() => SortKey::Id,
+            SortOrder::Id => return Err(invalid()),
+            SortOrder::GdpDesc | SortOrder::GdpAsc if value.is_empty() => SortKey::Gdp(None),
+            SortOrder::GdpDesc | SortOrder::GdpAsc => {
+                SortKey::Gdp(Some(value.parse::<f64>().ok().filter(|v| v.is_finite()).ok_or_else(invalid)?))
+            }
+            SortOrder::NameAsc => SortKey::Name(value.to_string()),
+            SortOrder::PopulationDesc => SortKey::Population(value.parse().map_err(|_| invalid())?),
+        };
+        Ok(Cursor { id, key })
+    }
+}
+
+/// Upper-cased ISO 4217 code (see `utils::currency`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CurrencyCode(String);
+
+impl CurrencyCode {
+    pub fn parse(s: &str) -> Result<Self, ApiError> {
+        if s.len() != 3 || !s.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(ApiError::Validation(
+                "currency must be a 3-letter ISO code (e.g., NGN)".into(),
+            ));
+        }
+        currency::normalize(s).map(CurrencyCode).ok_or_else(|| {
+            ApiError::Validation(format!(
+                "currency '{}' is not an ISO 4217 code (e.g., NGN, USD, EUR)",
+                s
+            ))
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Region name as stored from upstream. Not an enum: the set comes from
+/// restcountries, and an unknown region should match nothing rather than 400.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region(String);
+
+impl Region {
+    pub fn parse(s: &str) -> Result<Self, ApiError> {
+        let s = s.trim();
+        if s.is_empty() || s.len() > 64 {
+            return Err(ApiError::Validation("region must be 1-64 characters".into()));
+        }
+        Ok(Region(s.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Country tag (see `country_tags`): lower-cased, 1-64 of `a-z`, `0-9`, `-`, `_`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tag(String);
+
+impl Tag {
+    pub fn parse(s: &str) -> Result<Self, ApiError> {
+        let s = s.trim().to_ascii_lowercase();
+        let valid = (1..=64).contains(&s.len())
+            && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(ApiError::Validation(
+                "tag must be 1-64 characters of a-z, 0-9, '-' or '_'".into(),
+            ));
+        }
+        Ok(Tag(s))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RawListParams {
+    /// e.g. Africa
+    pub region: Option<String>,
+    /// ISO 4217 code, e.g. NGN
+    pub currency: Option<String>,
+    /// Countries carrying this tag, e.g. sahel
+    pub tag: Option<String>,
+    /// Text to find in the name or capital, case-insensitive, up to 64 characters
+    pub q: Option<String>,
+    /// gdp_desc | gdp_asc | name_asc | population_desc (default: by id)
+    pub sort: Option<String>,
+    /// 1-based, default 1
+    pub page: Option<usize>,
+    /// 1-200, default 50
+    pub limit: Option<usize>,
+    /// Collation for sort=name_asc: en, fr, pt, it, nl, de, es, sv, da, pl, tr
+    pub locale: Option<String>,
+    /// `next_cursor` of the previous page, instead of `page`
+    pub cursor: Option<String>,
+}
+
+/// Deepest OFFSET `GET /countries` accepts.
+pub const MAX_OFFSET: usize = 10_000;
+
+/// Validated `GET /countries` params.
+pub struct ListParams {
+    pub region: Option<Region>,
+    pub currency: Option<CurrencyCode>,
+    pub tag: Option<Tag>,
+    /// Trimmed search text, 1-64 characters; an empty `?q=` is no search
+    pub q: Option<String>,
+    pub sort: SortOrder,
+    /// Collation for `sort=name_asc`; ignored by the other orders
+    pub locale: Option<SortLocale>,
+    /// 1-based
+    pub page: usize,
+    /// 1..=200, default 50
+    pub limit: usize,
+    /// Keyset position; `page` is 1 when set
+    pub cursor: Option<Cursor>,
+}
+
+impl ListParams {
+    pub fn offset(&self) -> usize {
+        (self.page - 1) * self.limit
+    }
+}
+
+impl FromQuery for ListParams {
+    type Raw = RawListParams;
+    // `case` is read by the response-case middleware
+    const KNOWN_PARAMS: Option<&'static [&'static str]> =
+        Some(&["region", "currency", "tag", "q", "sort", "page", "limit", "locale", "cursor", "case"]);
+
+    fn from_raw(raw: RawListParams) -> Result<Self, ApiError> {
+        let page = raw.page.unwrap_or(1);
+        if page < 1 {
+            return Err(ApiError::Validation("page must be >= 1".into()));
+        }
+        let limit = raw.limit.unwrap_or(50);
+        if !(1..=200).contains(&limit) {
+            return Err(ApiError::Validation("limit must be between 1 and 200".into()));
+        }
+        // OFFSET n still reads and discards n rows; refuse deep pages outright
+        let deep = (page - 1).checked_mul(limit).is_none_or(|o| o > MAX_OFFSET);
+        if deep {
+            return Err(ApiError::Validation(format!(
+                "page * limit may not skip more than {} rows; narrow the listing with region/currency filters instead of paging this deep",
+                MAX_OFFSET
+            )));
+        }
+        if raw.cursor.is_some() && raw.page.is_some() {
+            return Err(ApiError::Validation("cursor and page can't be combined".into()));
+        }
+        let q = raw.q.as_deref().map(str::trim).filter(|q| !q.is_empty()).map(str::to_string);
+        if q.as_ref().is_some_and(|q| q.chars().count() > 64) {
+            return Err(ApiError::Validation("q must be at most 64 characters".into()));
+        }
+        let sort = raw.sort.as_deref().map(SortOrder::parse).transpose()?.unwrap_or_default();
+        let locale = raw.locale.as_deref().map(SortLocale::parse).transpose()?;
+        Ok(ListParams {
+            region: raw.region.as_deref().map(Region::parse).transpose()?,
+            currency: raw.currency.as_deref().map(CurrencyCode::parse).transpose()?,
+            tag: raw.tag.as_deref().map(Tag::parse).transpose()?,
+            q,
+            sort,
+            locale,
+            page,
+            limit,
+            cursor: raw.cursor.as_deref().map(|c| Cursor::decode(c, sort, locale)).transpose()?,
+        })
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RawAutocompleteParams {
+    /// Name or alias prefix, 1-64 characters
+    pub q: Option<String>,
+    /// 1-20, default 8
+    pub limit: Option<usize>,
+}
+
+/// Validated `GET /countries/autocomplete` params.
+pub struct AutocompleteParams {
+    /// Trimmed prefix, 1-64 characters
+    pub q: String,
+    /// 1..=20, default 8
+    pub limit: usize,
+}
+
+impl FromQuery for AutocompleteParams {
+    type Raw = RawAutocompleteParams;
+
+    fn from_raw(raw: RawAutocompleteParams) -> Result<Self, ApiError> {
+        let q = raw.q.as_deref().map(str::trim).unwrap_or("").to_string();
+        if q.is_empty() || q.chars().count() > 64 {
+            return Err(ApiError::Validation("q must be 1-64 characters".into()));
+        }
+        let limit = raw.limit.unwrap_or(8);
+        if !(1..=20).contains(&limit) {
+            return Err(ApiError::Validation("limit must be between 1 and 20".into()));
+        }
+        Ok(AutocompleteParams { q, limit })
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RawStatsParams {
+    /// Countries in `top_by_gdp` and `bottom_by_gdp`, 1-10 (default 5)
+    pub top: Option<u32>,
+}
+
+/// Validated `GET /stats` params.
+pub struct StatsParams {
+    /// 1..=10, default 5
+    pub top: u32,
+}
+
+impl FromQuery for StatsParams {
+    type Raw = RawStatsParams;
+    const KNOWN_PARAMS: Option<&'static [&'static str]> = Some(&["top", "case"]);
+
+    fn from_raw(raw: RawStatsParams) -> Result<Self, ApiError> {
+        let top = raw.top.unwrap_or(5);
+        if !(1..=10).contains(&top) {
+            return Err(ApiError::Validation("top must be between 1 and 10".into()));
+        }
+        Ok(StatsParams { top })
+    }
+}
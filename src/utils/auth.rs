@@ -0,0 +1,378 @@
+// Who may call what. `ADMIN_TOKEN` is the operator's token and passes everything.
+// `API_KEYS` adds named keys limited to scopes. Once any key is configured, every route
+// except `GET /` and the health probes needs a key (or the admin token) whose scopes
+// include the one the route is registered with in `routes::registry`. Routes marked
+// `signed` there also accept a valid signed URL instead (`utils::signed_url`).
+//
+// A key can also be limited to some countries with `region=` / `tag=` entries next to its
+// scopes. It may then only call routes marked `filtered`, and `KeyRestriction` hands the
+// limit to the handler, which passes it to `country_repository`.
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use crate::config::AppState;
+use crate::routes::registry;
+use crate::services::country_repository::Restriction;
+use crate::utils::client_ip::ClientIp;
+use crate::utils::error::ApiError;
+use crate::utils::lockout::{self, Subject};
+
+/// Extractor guarding operator endpoints: requires `Authorization: Bearer <ADMIN_TOKEN>`
+/// or an API key with the `admin` scope. With neither configured every request is refused.
+pub struct AdminAuth;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// No key needed
+    Public,
+    Read,
+    /// Changes data or triggers work (refresh, delete, tags)
+    Write,
+    /// Operator endpoints; an `admin` key also has every other scope
+    Admin,
+    /// Bulk downloads (bundle, raw payloads)
+    Export,
+}
+
+impl Scope {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "read" => Some(Scope::Read),
+            "write" => Some(Scope::Write),
+            "admin" => Some(Scope::Admin),
+            "export" => Some(Scope::Export),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::Public => "public",
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+            Scope::Export => "export",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ApiKey {
+    pub name: String,
+    token: String,
+    scopes: Vec<Scope>,
+    restriction: Option<Arc<Restriction>>,
+}
+
+impl ApiKey {
+    pub fn allows(&self, scope: Scope) -> bool {
+        scope == Scope::Public || self.scopes.iter().any(|s| *s == scope || *s == Scope::Admin)
+    }
+}
+
+/// `API_KEYS`; empty = keys off, non-admin routes are open as before.
+#[derive(Clone, Default)]
+pub struct ApiKeys(Vec<ApiKey>);
+
+impl ApiKeys {
+    /// `name:scope,scope:token` entries separated by `;`, e.g.
+    /// `analytics:read,export:<token>; deploy:write:<token>`. `region=Africa` and `tag=eu`
+    /// among the scopes limit the key to those countries (any listed region, any listed tag).
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut keys: Vec<ApiKey> = Vec::new();
+        for entry in s.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let mut parts = entry.splitn(3, ':');
+            let (Some(name), Some(scopes), Some(token)) = (parts.next(), parts.next(), parts.next()) else {
+                return Err(format!("API_KEYS entry {:?} must look like name:read,write:token", entry));
+            };
+            let (name, token) = (name.trim(), token.trim());
+            if name.is_empty() {
+                return Err("API_KEYS entries need a name".into());
+            }
+            if token.len() < 16 {
+                return Err(format!("API_KEYS: the token of {:?} must be at least 16 characters", name));
+            }
+            let mut restriction = Restriction::default();
+            let scopes = scopes
+                .split(',')
+                .map(str::trim)
+                .filter(|s| match s.split_once('=') {
+                    Some(("region", v)) => {
+                        restriction.regions.push(v.trim().to_string());
+                        false
+                    }
+                    Some(("tag", v)) => {
+                        restriction.tags.push(v.trim().to_ascii_lowercase());
+                        false
+                    }
+                    _ => true,
+                })
+                .map(|s| Scope::parse(s).ok_or_else(|| format!("API_KEYS: unknown scope {:?} for {:?}", s, name)))
+                .collect::<Result<Vec<_>, _>>()?;
+            if restriction.regions.iter().chain(&restriction.tags).any(String::is_empty) {
+                return Err(format!("API_KEYS: empty region= or tag= for {:?}", name));
+            }
+            if !restriction.is_empty() && scopes.contains(&Scope::Admin) {
+                return Err(format!("API_KEYS: {:?} has the admin scope and can't be limited to some countries", name));
+            }
+            if keys.iter().any(|k| k.name == name || k.token == token) {
+                return Err(format!("API_KEYS: {:?} repeats a name or token", name));
+            }
+            keys.push(ApiKey {
+                name: name.to_string(),
+                token: token.to_string(),
+                scopes,
+                restriction: (!restriction.is_empty()).then(|| Arc::new(restriction)),
+            });
+        }
+        Ok(ApiKeys(keys))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn find(&self, token: &str) -> Option<&ApiKey> {
+        self.0.iter().find(|k| constant_time_eq(token.as_bytes(), k.token.as_bytes()))
+    }
+}
+
+/// Set by `authorize` for requests made with an API key.
+#[derive(Debug, Clone)]
+pub struct Caller {
+    /// The key has the `admin` scope, so `AdminAuth` lets it through
+    pub admin: bool,
+    pub restriction: Option<Arc<Restriction>>,
+}
+
+/// The countries the caller may see; `None` = all (no key, the admin token, an
+/// unrestricted key or a signed URL).
+#[derive(Debug, Clone, Default)]
+pub struct KeyRestriction(pub Option<Arc<Restriction>>);
+
+impl KeyRestriction {
+    pub fn get(&self) -> Option<&Restriction> {
+        self.0.as_deref()
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for KeyRestriction {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(KeyRestriction(parts.extensions.get::<Caller>().and_then(|c| c.restriction.clone())))
+    }
+}
+
+fn locked_out(state: &AppState, subject: Option<Subject>) -> Result<(), ApiError> {
+    match state.auth_lockout.check(subject.as_slice(), tokio::time::Instant::now()) {
+        // Rounded up, so a retry after `Retry-After` isn't still locked
+        Some(wait) => Err(ApiError::LockedOut { retry_after_secs: wait.as_secs() + 1 }),
+        None => Ok(()),
+    }
+}
+
+fn bearer(headers: &HeaderMap) -> &str {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or("")
+        .trim()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AdminAuth {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        if parts.extensions.get::<Caller>().is_some_and(|c| c.admin) {
+            return Ok(AdminAuth);
+        }
+        let Some(expected) = state.admin_token.as_deref() else {
+            return Err(ApiError::Unauthorized("admin endpoints are disabled (ADMIN_TOKEN unset)".into()));
+        };
+        let ip = parts.extensions.get::<ClientIp>().and_then(|c| c.0);
+        locked_out(state, ip.map(Subject::Ip))?;
+        let token = bearer(&parts.headers);
+        if constant_time_eq(token.as_bytes(), expected.as_bytes()) {
+            if let Some(ip) = ip {
+                state.auth_lockout.succeed(ip);
+            }
+            Ok(AdminAuth)
+        } else {
+            lockout::record_failure(state, ip, None, token);
+            Err(ApiError::Unauthorized("missing or invalid bearer token".into()))
+        }
+    }
+}
+
+/// Scope for paths missing from the registry: published files under `/static/exports/`
+/// are bulk downloads like the bundle; other `/static/*` files and unknown paths need `read`.
+fn unregistered_scope(path: &str) -> Scope {
+    if path.starts_with("/static/exports/") {
+        Scope::Export
+    } else {
+        Scope::Read
+    }
+}
+
+/// Paths a signed URL may open: GET routes registered with `.signed()`, and files under
+/// `/static/exports/` (not the directory itself, and no `..` segments).
+pub fn signable(method: &str, path: &str) -> bool {
+    match registry::lookup(method, path) {
+        Some(e) => e.signed,
+        None => {
+            method == "GET"
+                && path
+                    .strip_prefix("/static/exports/")
+                    .is_some_and(|file| !file.is_empty() && !file.split('/').any(|s| s == ".."))
+        }
+    }
+}
+
+/// Middleware: checks the caller's key against the scope the route is registered with.
+/// Routes missing from the registry fall back to [`unregistered_scope`].
+pub async fn authorize(State(state): State<AppState>, mut req: Request, next: Next) -> Response {
+    if state.api_keys.is_empty() {
+        return next.run(req).await;
+    }
+    let method = if req.method() == Method::HEAD { Method::GET } else { req.method().clone() };
+    let endpoint = registry::lookup(method.as_str(), req.uri().path());
+    let scope = endpoint.map_or_else(|| unregistered_scope(req.uri().path()), |e| e.scope);
+    if scope == Scope::Public {
+        return next.run(req).await;
+    }
+    let ip = req.extensions().get::<ClientIp>().and_then(|c| c.0);
+    if let Err(e) = locked_out(&state, ip.map(Subject::Ip)) {
+        return e.into_response();
+    }
+    if let (true, Some(signer)) = (signable(method.as_str(), req.uri().path()), &state.url_signer) {
+        match signer.verify(req.uri().path(), req.uri().query(), chrono::Utc::now().timestamp()) {
+            Some(Ok(())) => return next.run(req).await,
+            Some(Err(reason)) => {
+                lockout::record_failure(&state, ip, None, "");
+                return ApiError::Unauthorized(reason.into()).into_response();
+            }
+            // Not signed: needs a key like any other request
+            None => {}
+        }
+    }
+    let token = bearer(req.headers());
+    if state.admin_token.as_deref().is_some_and(|t| constant_time_eq(token.as_bytes(), t.as_bytes())) {
+        if let Some(ip) = ip {
+            state.auth_lockout.succeed(ip);
+        }
+        return next.run(req).await;
+    }
+    let Some(key) = state.api_keys.find(token) else {
+        lockout::record_failure(&state, ip, None, token);
+        return ApiError::Unauthorized("missing or invalid API key".into()).into_response();
+    };
+    if let Err(e) = locked_out(&state, Some(Subject::Key(key.name.clone()))) {
+        return e.into_response();
+    }
+    if !key.allows(scope) {
+        // The key is known by name; no need to put part of a valid token in the audit log
+        lockout::record_failure(&state, ip, Some(&key.name), "");
+        return ApiError::Forbidden(format!("API key {:?} lacks the {:?} scope", key.name, scope.as_str()))
+            .into_response();
+    }
+    if key.restriction.is_some() && !endpoint.is_some_and(|e| e.filtered) {
+        lockout::record_failure(&state, ip, Some(&key.name), "");
+        let msg = format!("API key {:?} is limited to some countries and can't call this route", key.name);
+        return ApiError::Forbidden(msg).into_response();
+    }
+    if let Some(ip) = ip {
+        state.auth_lockout.succeed(ip);
+    }
+    tracing::Span::current().record("api_key", key.name.as_str());
+    req.extensions_mut().insert(Caller { admin: key.allows(Scope::Admin), restriction: key.restriction.clone() });
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keys_and_scopes() {
+        let keys = ApiKeys::parse(
+            "analytics:read,export:0123456789abcdef; ops : admin : fedcba9876543210:with:colons ;",
+        )
+        .unwrap();
+        assert_eq!(keys.len(), 2);
+        let analytics = keys.find("0123456789abcdef").unwrap();
+        assert_eq!(analytics.name, "analytics");
+        assert!(analytics.allows(Scope::Read) && analytics.allows(Scope::Export) && analytics.allows(Scope::Public));
+        assert!(!analytics.allows(Scope::Write) && !analytics.allows(Scope::Admin));
+        let ops = keys.find("fedcba9876543210:with:colons").unwrap();
+        assert!(ops.allows(Scope::Write) && ops.allows(Scope::Export));
+        assert!(keys.find("0123456789abcdeX").is_none() && keys.find("").is_none());
+        assert!(ApiKeys::parse(" ; ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parses_country_limits() {
+        let keys = ApiKeys::parse("partner:read, region=Africa ,tag=EU,region=Europe,export:0123456789abcdef").unwrap();
+        let partner = keys.find("0123456789abcdef").unwrap();
+        assert!(partner.allows(Scope::Read) && partner.allows(Scope::Export));
+        assert_eq!(
+            partner.restriction.as_deref(),
+            Some(&Restriction { regions: vec!["Africa".into(), "Europe".into()], tags: vec!["eu".into()] })
+        );
+        let plain = ApiKeys::parse("a:read:0123456789abcdef").unwrap();
+        assert!(plain.find("0123456789abcdef").unwrap().restriction.is_none());
+        assert!(ApiKeys::parse("a:read,region=:0123456789abcdef").is_err());
+        assert!(ApiKeys::parse("a:admin,tag=eu:0123456789abcdef").is_err());
+        assert!(ApiKeys::parse("a:read,currency=NGN:0123456789abcdef").is_err());
+    }
+
+    #[test]
+    fn rejects_bad_entries() {
+        assert!(ApiKeys::parse("analytics:read").is_err());
+        assert!(ApiKeys::parse("analytics:read:short").is_err());
+        assert!(ApiKeys::parse("analytics:refresh:0123456789abcdef").is_err());
+        assert!(ApiKeys::parse(":read:0123456789abcdef").is_err());
+        assert!(ApiKeys::parse("a:read:0123456789abcdef;a:write:fedcba9876543210").is_err());
+        assert!(ApiKeys::parse("a:read:0123456789abcdef;b:write:0123456789abcdef").is_err());
+    }
+
+    #[test]
+    fn static_exports_need_the_export_scope() {
+        assert_eq!(unregistered_scope("/static/exports/daily/countries-20260101T000000Z.csv"), Scope::Export);
+        assert_eq!(unregistered_scope("/static/summary.png"), Scope::Read);
+        assert_eq!(unregistered_scope("/static/variants/abc.svg"), Scope::Read);
+        assert_eq!(unregistered_scope("/nope"), Scope::Read);
+    }
+
+    #[test]
+    fn export_files_can_be_signed_like_signed_routes() {
+        assert!(signable("GET", "/countries/image"));
+        assert!(signable("GET", "/countries/bundle"));
+        assert!(!signable("GET", "/countries"));
+        assert!(signable("GET", "/static/exports/nightly/countries-20260301T023000Z.csv"));
+        assert!(!signable("HEAD", "/static/exports/nightly/countries.csv"));
+        assert!(!signable("GET", "/static/exports/"));
+        assert!(!signable("GET", "/static/exports/../countries_raw.json"));
+        assert!(!signable("GET", "/static/summary.png"));
+    }
+}
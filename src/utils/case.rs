@@ -0,0 +1,155 @@
+// Response key casing (`RESPONSE_CASE=camel` or per request `?case=camel|snake`).
+//
+// Handlers build most bodies with `json!`, so a serde `rename_all` on each model
+// wouldn't reach them; instead JSON responses are rewritten once, on the way out.
+// Keys that are data rather than field names (map fields in `DATA_KEYED`) are kept,
+// and responses marked `Verbatim` (raw upstream payloads) are not touched at all.
+
+use axum::{
+    extract::{Query, Request, State},
+    http::Uri,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::config::AppState;
+use crate::utils::error::ApiError;
+use crate::utils::json_body::rewrite_json_body;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyCase {
+    #[default]
+    Snake,
+    Camel,
+}
+
+impl KeyCase {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "snake" => Some(KeyCase::Snake),
+            "camel" => Some(KeyCase::Camel),
+            _ => None,
+        }
+    }
+}
+
+/// Response extension: the body is a stored document, not ours to rename.
+#[derive(Clone, Copy, Debug)]
+pub struct Verbatim;
+
+/// Fields whose object value is keyed by data (missing-rate reasons, job states, country
+/// and region names). Those keys are returned as stored; the values below them are renamed.
+const DATA_KEYED: [&str; 4] = ["by_reason", "by_status", "frames", "regions"];
+
+fn to_camel(key: &str) -> String {
+    let mut out = String::with_capacity(key.len());
+    let mut upper = false;
+    for c in key.chars() {
+        if c == '_' && !out.is_empty() {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Recursively renames object keys from snake_case to camelCase, except the keys of
+/// `DATA_KEYED` maps.
+pub fn camelize(v: Value) -> Value {
+    match v {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| {
+                    let v = match v {
+                        Value::Object(entries) if DATA_KEYED.contains(&k.as_str()) => {
+                            Value::Object(entries.into_iter().map(|(key, v)| (key, camelize(v))).collect())
+                        }
+                        v => camelize(v),
+                    };
+                    (to_camel(&k), v)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(camelize).collect()),
+        other => other,
+    }
+}
+
+#[derive(Deserialize)]
+struct CaseParam {
+    case: Option<String>,
+}
+
+/// The casing this request gets: its `?case=`, else the runtime default.
+pub fn requested_case(state: &AppState, uri: &Uri) -> Result<KeyCase, ApiError> {
+    let requested = Query::<CaseParam>::try_from_uri(uri).ok().and_then(|Query(p)| p.case);
+    match requested.as_deref() {
+        None => Ok(state.runtime.load().response_case),
+        Some(s) => KeyCase::parse(s).ok_or_else(|| ApiError::Validation("case must be one of snake, camel".into())),
+    }
+}
+
+/// Middleware: rewrites `application/json` bodies when camelCase is requested.
+pub async fn response_case(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let case = match requested_case(&state, req.uri()) {
+        Ok(c) => c,
+        Err(e) => return e.into_response(),
+    };
+
+    let res = next.run(req).await;
+    if case == KeyCase::Snake || res.extensions().get::<Verbatim>().is_some() {
+        return res;
+    }
+    rewrite_json_body(res, camelize).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn snake_keys_become_camel() {
+        assert_eq!(to_camel("last_refreshed_at"), "lastRefreshedAt");
+        assert_eq!(to_camel("name"), "name");
+        assert_eq!(to_camel("top_5"), "top5");
+        // A leading underscore is kept (`_links`); doubled and trailing ones collapse
+        assert_eq!(to_camel("_links"), "_links");
+        assert_eq!(to_camel("rate__source"), "rateSource");
+        assert_eq!(to_camel("trailing_"), "trailing");
+        assert_eq!(to_camel("already_Camel"), "alreadyCamel");
+    }
+
+    #[test]
+    fn camelize_keeps_data_keys() {
+        let body = json!({
+            "total_count": 2,
+            "by_reason": { "no_currency": 1, "rate_missing": 1 },
+            "by_status": { "dead_letter": { "oldest_at": null } },
+            "countries": [{ "currency_code": "GHS", "last_refreshed_at": null }],
+            "exchange_rate": { "min_rate": 1.0 },
+        });
+        assert_eq!(
+            camelize(body),
+            json!({
+                "totalCount": 2,
+                "byReason": { "no_currency": 1, "rate_missing": 1 },
+                "byStatus": { "dead_letter": { "oldestAt": null } },
+                "countries": [{ "currencyCode": "GHS", "lastRefreshedAt": null }],
+                "exchangeRate": { "minRate": 1.0 },
+            })
+        );
+        // A data-keyed name holding a list is renamed like any other value
+        assert_eq!(
+            camelize(json!({ "regions": [{ "region_name": "Africa" }] })),
+            json!({ "regions": [{ "regionName": "Africa" }] })
+        );
+    }
+}
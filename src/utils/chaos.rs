@@ -0,0 +1,180 @@
+// Fault injection for resilience testing (`CHAOS_*`, debug builds only).
+//
+// A share of requests gets extra latency, a random 5xx, or the response a failed DB
+// acquire produces, so clients' retries and timeouts can be exercised against a real
+// instance. Injected responses carry `X-Chaos-Fault`, so they can't be mistaken for
+// real failures. Health probes are never touched.
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use rand::Rng;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::utils::error::{ApiError, ErrorBody};
+
+pub const FAULT_HEADER: &str = "x-chaos-fault";
+
+const STATUSES: [StatusCode; 4] = [
+    StatusCode::INTERNAL_SERVER_ERROR,
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chaos {
+    latency_percent: f64,
+    /// Inclusive range the added delay is drawn from
+    latency_ms: (u64, u64),
+    error_percent: f64,
+    db_error_percent: f64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Failure {
+    Status(StatusCode),
+    Db,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Faults {
+    pub latency: Option<Duration>,
+    pub failure: Option<Failure>,
+}
+
+fn percent(key: &str, v: &str) -> Result<f64, String> {
+    match v.trim() {
+        "" => Ok(0.0),
+        v => v
+            .parse::<f64>()
+            .ok()
+            .filter(|p| (0.0..=100.0).contains(p))
+            .ok_or_else(|| format!("{} must be a percentage between 0 and 100", key)),
+    }
+}
+
+impl Chaos {
+    /// `latency_ms` is one value (`"300"`) or a range (`"100-800"`). `None` when every
+    /// percentage is zero.
+    pub fn parse(
+        latency_percent: &str,
+        latency_ms: &str,
+        error_percent: &str,
+        db_error_percent: &str,
+    ) -> Result<Option<Self>, String> {
+        let bad_latency = || "CHAOS_LATENCY_MS must be milliseconds or a range like 100-800".to_string();
+        let ms = |s: &str| s.trim().parse::<u64>().map_err(|_| bad_latency());
+        let latency_ms = match latency_ms.trim() {
+            "" => (0, 0),
+            v => match v.split_once('-') {
+                Some((lo, hi)) => (ms(lo)?, ms(hi)?),
+                None => (ms(v)?, ms(v)?),
+            },
+        };
+        if latency_ms.0 > latency_ms.1 {
+            return Err(bad_latency());
+        }
+        let chaos = Chaos {
+            latency_percent: percent("CHAOS_LATENCY_PERCENT", latency_percent)?,
+            latency_ms,
+            error_percent: percent("CHAOS_ERROR_PERCENT", error_percent)?,
+            db_error_percent: percent("CHAOS_DB_ERROR_PERCENT", db_error_percent)?,
+        };
+        let active = chaos.latency_percent > 0.0 || chaos.error_percent > 0.0 || chaos.db_error_percent > 0.0;
+        Ok(active.then_some(chaos))
+    }
+
+    /// Rolls each fault independently. A request gets at most one failure; a DB fault
+    /// wins over a plain 5xx.
+    pub fn decide(&self, rng: &mut impl Rng) -> Faults {
+        let mut hit = |p: f64| p > 0.0 && rng.gen_range(0.0..100.0) < p;
+        let (latency, db, error) = (hit(self.latency_percent), hit(self.db_error_percent), hit(self.error_percent));
+        let failure = if db {
+            Some(Failure::Db)
+        } else if error {
+            Some(Failure::Status(STATUSES[rng.gen_range(0..STATUSES.len())]))
+        } else {
+            None
+        };
+        Faults {
+            latency: latency.then(|| Duration::from_millis(rng.gen_range(self.latency_ms.0..=self.latency_ms.1))),
+            failure,
+        }
+    }
+}
+
+/// Middleware: applies the rolled faults. Sits inside `deadline::enforce`, so injected
+/// latency counts against a request's deadline.
+pub async fn inject(State(chaos): State<Arc<Chaos>>, req: Request, next: Next) -> Response {
+    if req.uri().path().starts_with("/health") {
+        return next.run(req).await;
+    }
+    let faults = chaos.decide(&mut rand::thread_rng());
+    let mut applied = Vec::new();
+    if let Some(d) = faults.latency {
+        tokio::time::sleep(d).await;
+        applied.push(format!("latency={}ms", d.as_millis()));
+    }
+    let mut res = match faults.failure {
+        None => next.run(req).await,
+        Some(Failure::Db) => {
+            applied.push("db".into());
+            ApiError::PoolExhausted("injected fault".into()).into_response()
+        }
+        Some(Failure::Status(status)) => {
+            applied.push(format!("status={}", status.as_u16()));
+            let body = ErrorBody { error: "Injected fault", code: Some("chaos"), details: None };
+            (status, Json(body)).into_response()
+        }
+    };
+    if !applied.is_empty() {
+        if let Ok(v) = HeaderValue::from_str(&applied.join(",")) {
+            res.headers_mut().insert(FAULT_HEADER, v);
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn parses_percentages_and_latency() {
+        assert_eq!(Chaos::parse("", "", "", "").unwrap(), None);
+        assert_eq!(Chaos::parse("0", "500", "0", "0").unwrap(), None);
+        let c = Chaos::parse("50", "100-800", "5", "").unwrap().unwrap();
+        assert_eq!((c.latency_ms, c.error_percent, c.db_error_percent), ((100, 800), 5.0, 0.0));
+        assert_eq!(Chaos::parse("10", "300", "", "").unwrap().unwrap().latency_ms, (300, 300));
+        assert!(Chaos::parse("101", "", "", "").is_err());
+        assert!(Chaos::parse("10", "800-100", "", "").is_err());
+        assert!(Chaos::parse("", "", "lots", "").is_err());
+    }
+
+    #[test]
+    fn zero_never_fires_and_hundred_always_does() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let quiet = Chaos { latency_percent: 0.0, latency_ms: (5, 5), error_percent: 0.0, db_error_percent: 0.0 };
+        let loud = Chaos { latency_percent: 100.0, latency_ms: (5, 9), error_percent: 100.0, db_error_percent: 0.0 };
+        for _ in 0..200 {
+            assert_eq!(quiet.decide(&mut rng), Faults::default());
+            let f = loud.decide(&mut rng);
+            assert!(f.latency.is_some_and(|d| (5..=9).contains(&d.as_millis())));
+            assert!(matches!(f.failure, Some(Failure::Status(s)) if s.is_server_error()));
+        }
+    }
+
+    #[test]
+    fn db_fault_wins_over_status() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let both = Chaos { latency_percent: 0.0, latency_ms: (0, 0), error_percent: 100.0, db_error_percent: 100.0 };
+        assert_eq!(both.decide(&mut rng).failure, Some(Failure::Db));
+    }
+}
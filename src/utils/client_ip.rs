@@ -0,0 +1,223 @@
+// Client address behind load balancers (`TRUSTED_PROXIES`).
+//
+// The TCP peer is the client unless it is a trusted proxy. Then the forwarding headers
+// (`Forwarded`, else `X-Forwarded-For`) are walked from the nearest hop outwards, and
+// the first address not itself a trusted proxy is the client. Headers from an
+// untrusted peer are ignored, and entries a client prepended are never reached while
+// a trusted hop sits in front of them, so the address can't be spoofed.
+
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{request::Parts, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cidr {
+    net: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    fn parse(s: &str) -> Result<Self, String> {
+        let bad = || format!("invalid TRUSTED_PROXIES entry {:?}, expected an IP or CIDR like 10.0.0.0/8", s);
+        let (ip, prefix) = match s.split_once('/') {
+            Some((ip, p)) => (ip, Some(p.parse::<u8>().map_err(|_| bad())?)),
+            None => (s, None),
+        };
+        let net: IpAddr = ip.parse().map_err(|_| bad())?;
+        let max = if net.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        if prefix > max {
+            return Err(bad());
+        }
+        Ok(Cidr { net, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.net, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Proxies whose forwarding headers are believed. Empty = none, headers are ignored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrustedProxies(Vec<Cidr>);
+
+impl TrustedProxies {
+    /// Comma-separated IPs and CIDRs, e.g. `10.0.0.0/8, 127.0.0.1`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .map(Cidr::parse)
+            .collect::<Result<_, _>>()
+            .map(TrustedProxies)
+    }
+
+    fn trusts(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|c| c.contains(ip))
+    }
+
+    /// The client address for a request from `peer` carrying `headers`.
+    pub fn resolve(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.trusts(peer) {
+            return peer;
+        }
+        // Nearest hop last; each header line may hold several comma-separated hops
+        let forwarded: Vec<&str> = headers.get_all("forwarded").iter().filter_map(|v| v.to_str().ok()).collect();
+        let hops: Vec<Option<IpAddr>> = if !forwarded.is_empty() {
+            forwarded.iter().flat_map(|v| v.split(',')).map(forwarded_for).collect()
+        } else {
+            headers
+                .get_all("x-forwarded-for")
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(','))
+                .map(parse_addr)
+                .collect()
+        };
+        let mut client = peer;
+        for hop in hops.into_iter().rev() {
+            if !self.trusts(client) {
+                break;
+            }
+            // `unknown`, obfuscated or garbled: the nearest known hop is all there is
+            let Some(ip) = hop else { break };
+            client = ip;
+        }
+        client
+    }
+}
+
+/// `for=` of one `Forwarded` element (RFC 7239).
+fn forwarded_for(element: &str) -> Option<IpAddr> {
+    element
+        .split(';')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case("for"))
+        .and_then(|(_, v)| parse_addr(v))
+}
+
+/// `1.2.3.4`, `1.2.3.4:80`, `2001:db8::1`, `[2001:db8::1]:80`, optionally quoted.
+fn parse_addr(s: &str) -> Option<IpAddr> {
+    let s = s.trim().trim_matches('"');
+    s.parse::<IpAddr>()
+        .ok()
+        .or_else(|| s.parse::<SocketAddr>().ok().map(|a| a.ip()))
+        .or_else(|| s.strip_prefix('[')?.strip_suffix(']')?.parse().ok())
+}
+
+/// Resolved client address, set by `identify`. `None` when the connection's peer
+/// address isn't known (requests built in-process, e.g. tests).
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+/// Middleware (outermost): stores `ClientIp` for the trace span, handlers and audit.
+pub async fn identify(State(trusted): State<Arc<TrustedProxies>>, mut req: Request, next: Next) -> Response {
+    let peer = req.extensions().get::<ConnectInfo<SocketAddr>>().map(|c| c.0.ip());
+    let ip = peer.map(|peer| trusted.resolve(peer, req.headers()));
+    req.extensions_mut().insert(ClientIp(ip));
+    next.run(req).await
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<ClientIp>().copied().unwrap_or(ClientIp(None)))
+    }
+}
+
+/// `TraceLayer` span: tower-http's default fields plus `client_ip`, and `api_key` (the
+/// key's name) once `auth::authorize` has checked it.
+pub fn make_span(req: &Request<Body>) -> tracing::Span {
+    let ip = req.extensions().get::<ClientIp>().and_then(|c| c.0);
+    tracing::debug_span!(
+        "request",
+        method = %req.method(),
+        uri = %req.uri(),
+        version = ?req.version(),
+        client_ip = ip.map(tracing::field::display),
+        api_key = tracing::field::Empty,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut h = HeaderMap::new();
+        for (k, v) in pairs {
+            h.append(*k, v.parse().unwrap());
+        }
+        h
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_ips_and_cidrs() {
+        let t = TrustedProxies::parse("10.0.0.0/8, 127.0.0.1 ,fd00::/8").unwrap();
+        assert!(t.trusts(ip("10.1.2.3")) && t.trusts(ip("127.0.0.1")) && t.trusts(ip("fd12::1")));
+        assert!(t.trusts(ip("::ffff:10.0.0.1")));
+        assert!(!t.trusts(ip("11.0.0.1")) && !t.trusts(ip("127.0.0.2")) && !t.trusts(ip("2001:db8::1")));
+        assert!(TrustedProxies::parse("0.0.0.0/0").unwrap().trusts(ip("8.8.8.8")));
+        assert_eq!(TrustedProxies::parse(" , ").unwrap(), TrustedProxies::default());
+        assert!(TrustedProxies::parse("10.0.0.0/33").is_err());
+        assert!(TrustedProxies::parse("lb.internal").is_err());
+    }
+
+    #[test]
+    fn untrusted_peer_headers_are_ignored() {
+        let t = TrustedProxies::parse("10.0.0.0/8").unwrap();
+        let h = headers(&[("x-forwarded-for", "1.1.1.1"), ("forwarded", "for=1.1.1.1")]);
+        assert_eq!(t.resolve(ip("203.0.113.9"), &h), ip("203.0.113.9"));
+        assert_eq!(TrustedProxies::default().resolve(ip("10.0.0.1"), &h), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn walks_x_forwarded_for_past_trusted_hops() {
+        let t = TrustedProxies::parse("10.0.0.0/8").unwrap();
+        // The client prepended a fake entry; the LB appended the real address
+        let h = headers(&[("x-forwarded-for", "6.6.6.6, 198.51.100.7"), ("x-forwarded-for", "10.0.0.2")]);
+        assert_eq!(t.resolve(ip("10.0.0.1"), &h), ip("198.51.100.7"));
+        let all_trusted = headers(&[("x-forwarded-for", "10.0.0.3, 10.0.0.2")]);
+        assert_eq!(t.resolve(ip("10.0.0.1"), &all_trusted), ip("10.0.0.3"));
+        let garbled = headers(&[("x-forwarded-for", "1.1.1.1, nonsense")]);
+        assert_eq!(t.resolve(ip("10.0.0.1"), &garbled), ip("10.0.0.1"));
+        assert_eq!(t.resolve(ip("10.0.0.1"), &HeaderMap::new()), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn forwarded_wins_over_x_forwarded_for() {
+        let t = TrustedProxies::parse("10.0.0.0/8").unwrap();
+        let h = headers(&[
+            ("x-forwarded-for", "9.9.9.9"),
+            ("forwarded", "for=192.0.2.60;proto=http, For=\"[2001:db8:cafe::17]:4711\";by=10.0.0.1"),
+        ]);
+        assert_eq!(t.resolve(ip("10.0.0.1"), &h), ip("2001:db8:cafe::17"));
+        let obfuscated = headers(&[("forwarded", "for=_hidden, for=10.0.0.5")]);
+        assert_eq!(t.resolve(ip("10.0.0.1"), &obfuscated), ip("10.0.0.5"));
+        assert_eq!(parse_addr("192.0.2.60:8080"), Some(ip("192.0.2.60")));
+    }
+}
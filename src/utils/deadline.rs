@@ -0,0 +1,126 @@
+// Per-request deadline (`X-Request-Deadline` / `X-Request-Timeout`).
+//
+// The middleware runs the handler inside a task-local scope holding the deadline and
+// wraps it in a timeout, so every DB query and upstream call the request awaits is
+// cancelled once the client's budget is spent. Code that makes upstream calls can
+// also read the remaining budget (`budget`) to fail with a proper timeout error first.
+// Cancelling drops the handler future mid-way: open transactions roll back, and a refresh
+// records its run as `cancelled` from a drop guard (`refresh_service::RunGuard`).
+
+use axum::{
+    extract::Request,
+    http::HeaderMap,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::utils::error::ApiError;
+
+tokio::task_local! {
+    static DEADLINE: Instant;
+}
+
+/// Absolute deadline: unix epoch milliseconds or an RFC 3339 timestamp
+pub const DEADLINE_HEADER: &str = "x-request-deadline";
+/// Relative budget in milliseconds
+pub const TIMEOUT_HEADER: &str = "x-request-timeout";
+
+fn parse_deadline(headers: &HeaderMap) -> Result<Option<Instant>, ApiError> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+
+    if let Some(v) = header(TIMEOUT_HEADER) {
+        let ms: u64 = v
+            .parse()
+            .map_err(|_| ApiError::Validation("X-Request-Timeout must be milliseconds".into()))?;
+        return Ok(Some(Instant::now() + Duration::from_millis(ms)));
+    }
+    if let Some(v) = header(DEADLINE_HEADER) {
+        let at = match v.parse::<i64>() {
+            Ok(ms) => DateTime::<Utc>::from_timestamp_millis(ms),
+            Err(_) => DateTime::parse_from_rfc3339(v).ok().map(|t| t.with_timezone(&Utc)),
+        }
+        .ok_or_else(|| {
+            ApiError::Validation(
+                "X-Request-Deadline must be unix milliseconds or an RFC 3339 timestamp".into(),
+            )
+        })?;
+        // Past deadlines give a zero budget, which fails straight away
+        let left = (at - Utc::now()).to_std().unwrap_or(Duration::ZERO);
+        return Ok(Some(Instant::now() + left));
+    }
+    Ok(None)
+}
+
+/// Time left for the current request, if it carries a deadline.
+pub fn remaining() -> Option<Duration> {
+    DEADLINE
+        .try_with(|d| d.saturating_duration_since(Instant::now()))
+        .ok()
+}
+
+/// `ceiling`, shortened to the request's remaining budget.
+pub fn budget(ceiling: Duration) -> Duration {
+    remaining().map_or(ceiling, |r| r.min(ceiling))
+}
+
+/// Middleware: enforces the deadline and answers 504 once it passes.
+pub async fn enforce(req: Request, next: Next) -> Response {
+    let deadline = match parse_deadline(req.headers()) {
+        Ok(Some(d)) => d,
+        Ok(None) => return next.run(req).await,
+        Err(e) => return e.into_response(),
+    };
+    if deadline <= Instant::now() {
+        return ApiError::Timeout("request deadline already passed".into()).into_response();
+    }
+    let run = tokio::time::timeout_at(deadline, next.run(req));
+    match DEADLINE.scope(deadline, run).await {
+        Ok(res) => res,
+        Err(_) => ApiError::Timeout("request deadline exceeded".into()).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut h = HeaderMap::new();
+        for (k, v) in pairs {
+            h.insert(*k, HeaderValue::from_str(v).unwrap());
+        }
+        h
+    }
+
+    fn budget_ms(pairs: &[(&'static str, &str)]) -> Option<u128> {
+        let at = parse_deadline(&headers(pairs)).unwrap()?;
+        Some(at.saturating_duration_since(Instant::now()).as_millis())
+    }
+
+    #[test]
+    fn parses_relative_and_absolute_deadlines() {
+        assert_eq!(budget_ms(&[]), None);
+        assert!(budget_ms(&[(TIMEOUT_HEADER, " 1500 ")]).is_some_and(|ms| (1400..=1500).contains(&ms)));
+
+        let in_2s = Utc::now() + chrono::Duration::seconds(2);
+        let unix_ms = in_2s.timestamp_millis().to_string();
+        assert!(budget_ms(&[(DEADLINE_HEADER, &unix_ms)]).is_some_and(|ms| (1500..=2000).contains(&ms)));
+        let rfc3339 = in_2s.to_rfc3339();
+        assert!(budget_ms(&[(DEADLINE_HEADER, &rfc3339)]).is_some_and(|ms| (1500..=2000).contains(&ms)));
+        // A deadline in the past leaves no budget
+        assert_eq!(budget_ms(&[(DEADLINE_HEADER, "2020-01-01T00:00:00Z")]), Some(0));
+        // The relative budget wins when both are sent
+        assert!(budget_ms(&[(TIMEOUT_HEADER, "100"), (DEADLINE_HEADER, &unix_ms)]).is_some_and(|ms| ms <= 100));
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        for pairs in [[(TIMEOUT_HEADER, "soon")], [(TIMEOUT_HEADER, "-5")], [(DEADLINE_HEADER, "tomorrow")]] {
+            assert!(matches!(parse_deadline(&headers(&pairs)), Err(ApiError::Validation(_))));
+        }
+    }
+}
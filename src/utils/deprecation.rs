@@ -0,0 +1,73 @@
+// `Deprecation` (RFC 9745), `Sunset` (RFC 8594) and successor `Link` headers for the
+// routes and query parameters marked deprecated in `routes::registry`.
+
+use axum::{
+    extract::{Query, Request},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{NaiveDate, TimeZone, Utc};
+
+use crate::routes::registry::{self, Deprecation};
+
+/// `@<unix seconds>` at midnight UTC of a YYYY-MM-DD date.
+fn deprecation_value(date: &str) -> Option<String> {
+    let d = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some(format!("@{}", Utc.from_utc_datetime(&d.and_hms_opt(0, 0, 0)?).timestamp()))
+}
+
+/// HTTP-date (`Wed, 01 Jul 2026 00:00:00 GMT`) at midnight UTC of a YYYY-MM-DD date.
+fn sunset_value(date: &str) -> Option<String> {
+    let d = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some(
+        Utc.from_utc_datetime(&d.and_hms_opt(0, 0, 0)?)
+            .format("%a, %d %b %Y %H:%M:%S GMT")
+            .to_string(),
+    )
+}
+
+fn apply(res: &mut Response, d: &Deprecation) {
+    let headers = res.headers_mut();
+    let mut set = |name, value: Option<String>| {
+        if let Some(v) = value.and_then(|v| HeaderValue::from_str(&v).ok()) {
+            headers.append(name, v);
+        }
+    };
+    set(header::HeaderName::from_static("deprecation"), deprecation_value(d.since));
+    set(header::HeaderName::from_static("sunset"), d.sunset.and_then(sunset_value));
+    set(header::LINK, d.successor.map(|s| format!("<{}>; rel=\"successor-version\"", s)));
+}
+
+/// Middleware: adds the headers when the request hits a deprecated route or uses a
+/// deprecated parameter. Route deprecation wins when both apply.
+pub async fn deprecation_headers(req: Request, next: Next) -> Response {
+    let endpoint = registry::lookup(req.method().as_str(), req.uri().path());
+    let deprecation = endpoint.and_then(|e| {
+        e.deprecated.or_else(|| {
+            let keys: Vec<String> = Query::<Vec<(String, String)>>::try_from_uri(req.uri())
+                .map(|Query(pairs)| pairs.into_iter().map(|(k, _)| k).collect())
+                .unwrap_or_default();
+            let param = registry::deprecated_params(e, &keys).next();
+            param.map(|d| d.deprecation)
+        })
+    });
+
+    let mut res = next.run(req).await;
+    if let Some(d) = deprecation {
+        apply(&mut res, &d);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_formats() {
+        assert_eq!(deprecation_value("2026-01-01").as_deref(), Some("@1767225600"));
+        assert_eq!(sunset_value("2026-07-01").as_deref(), Some("Wed, 01 Jul 2026 00:00:00 GMT"));
+        assert_eq!(sunset_value("July 1st"), None);
+    }
+}
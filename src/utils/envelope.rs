@@ -0,0 +1,101 @@
+// Uniform `{ "data", "meta", "errors" }` envelope (`RESPONSE_ENVELOPE=true` or per request
+// `X-Envelope: true|false`), for client generators that expect one shape everywhere.
+//
+// Like key casing, it's applied once on the way out instead of in every handler.
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+
+use crate::config::AppState;
+use crate::utils::error::ApiError;
+use crate::utils::json_body::{is_json, rewrite_json_body};
+
+pub const HEADER: &str = "x-envelope";
+
+fn requested(headers: &HeaderMap) -> Result<Option<bool>, ApiError> {
+    let Some(v) = headers.get(HEADER) else {
+        return Ok(None);
+    };
+    match v.to_str().unwrap_or("").trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" => Ok(Some(true)),
+        "0" | "false" | "no" => Ok(Some(false)),
+        _ => Err(ApiError::Validation("X-Envelope must be true or false".into())),
+    }
+}
+
+/// Whether this request gets the envelope: its `X-Envelope` header, else the runtime default.
+pub fn enabled(state: &AppState, headers: &HeaderMap) -> Result<bool, ApiError> {
+    Ok(requested(headers)?.unwrap_or_else(|| state.runtime.load().response_envelope))
+}
+
+/// Wraps a plain JSON body. Error responses (4xx/5xx) move their body into `errors`.
+pub fn wrap(status: StatusCode, body: Value) -> Value {
+    let mut meta = json!({ "status": status.as_u16() });
+    if let Value::Array(items) = &body {
+        meta["count"] = json!(items.len());
+    }
+    if status.is_client_error() || status.is_server_error() {
+        json!({ "data": null, "meta": meta, "errors": [body] })
+    } else {
+        json!({ "data": body, "meta": meta, "errors": [] })
+    }
+}
+
+/// Middleware: envelopes `application/json` responses when enabled. Images and
+/// JSON:API documents (which have their own top level) pass through untouched. Every
+/// response the header could reshape (JSON, or a 304 standing in for JSON) varies on it.
+pub async fn response_envelope(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let enabled = match enabled(&state, req.headers()) {
+        Ok(on) => on,
+        Err(e) => return e.into_response(),
+    };
+
+    let mut res = next.run(req).await;
+    if is_json(&res) || res.status() == StatusCode::NOT_MODIFIED {
+        res.headers_mut().append(header::VARY, HeaderValue::from_static("X-Envelope"));
+    }
+    if !enabled {
+        return res;
+    }
+    let status = res.status();
+    rewrite_json_body(res, |v| wrap(status, v)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn success_goes_under_data() {
+        let v = wrap(StatusCode::OK, json!([{ "name": "Ghana" }]));
+        assert_eq!(v["data"][0]["name"], "Ghana");
+        assert_eq!(v["meta"]["status"], 200);
+        assert_eq!(v["meta"]["count"], 1);
+        assert_eq!(v["errors"], json!([]));
+    }
+
+    #[test]
+    fn errors_go_under_errors() {
+        let v = wrap(StatusCode::NOT_FOUND, json!({ "error": "Country not found" }));
+        assert!(v["data"].is_null());
+        assert_eq!(v["meta"]["status"], 404);
+        assert_eq!(v["errors"][0]["error"], "Country not found");
+    }
+
+    #[test]
+    fn header_values() {
+        let mut h = HeaderMap::new();
+        assert_eq!(requested(&h).unwrap(), None);
+        h.insert(HEADER, "TRUE".parse().unwrap());
+        assert_eq!(requested(&h).unwrap(), Some(true));
+        h.insert(HEADER, "0".parse().unwrap());
+        assert_eq!(requested(&h).unwrap(), Some(false));
+        h.insert(HEADER, "maybe".parse().unwrap());
+        assert!(requested(&h).is_err());
+    }
+}
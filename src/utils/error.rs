@@ -1,22 +1,67 @@
-use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use axum::{http::{header, StatusCode}, response::{IntoResponse, Response}, Json};
 use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
 use thiserror::Error;
+use tracing::warn;
+use utoipa::ToSchema;
 
-#[derive(Error, Debug)]
+use crate::utils::error_report::InternalError;
+
+/// Times a request gave up waiting for a pool connection (saturation metric, shown in `/status`)
+pub static POOL_TIMEOUTS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Error, Debug, Clone)]
 pub enum ApiError {
     #[error("validation: {0}")]
     Validation(String),
+    #[error("unauthorized: {0}")]
+    Unauthorized(String),
+    /// Authenticated, but the API key lacks the route's scope
+    #[error("forbidden: {0}")]
+    Forbidden(String),
     #[error("not_found: {0}")]
     NotFound(String),
     #[error("external_unavailable: {0}")]
     External(String),
+    #[error("timeout: {0}")]
+    Timeout(String),
+    /// No pool connection became free within the acquire timeout
+    #[error("db_pool_exhausted: {0}")]
+    PoolExhausted(String),
+    /// Strict mode (`STRICT_QUERY_PARAMS=true`): the query string had keys the endpoint doesn't know
+    #[error("unknown query parameters: {unknown:?}")]
+    UnknownParams { unknown: Vec<String>, allowed: &'static [&'static str] },
+    /// Refresh attempted before the politeness throttle allows another one
+    #[error("rate_limited: retry in {retry_after_secs}s")]
+    RateLimited { retry_after_secs: u64, next_allowed_at: chrono::DateTime<chrono::Utc> },
+    /// Too many failed authentications from this address or with this key (`utils::lockout`)
+    #[error("auth_locked_out: retry in {retry_after_secs}s")]
+    LockedOut { retry_after_secs: u64 },
     #[error("internal: {0}")]
     Internal(String),
 }
 
-#[derive(Serialize)]
+impl ApiError {
+    /// Maps a DB error, keeping pool exhaustion apart from real SQL failures.
+    pub fn db(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::PoolTimedOut => {
+                let n = POOL_TIMEOUTS.fetch_add(1, Ordering::Relaxed) + 1;
+                warn!(pool_timeouts_total = n, "db pool exhausted: acquire timed out");
+                ApiError::PoolExhausted("no database connection available".into())
+            }
+            e => ApiError::Internal(e.to_string()),
+        }
+    }
+}
+
+/// Body of every error response. `unknown_query_params` adds `unknown` and `allowed`;
+/// `refresh_throttled` and `auth_locked_out` add `retry_after_secs`.
+#[derive(Serialize, ToSchema)]
 pub struct ErrorBody<'a> {
     pub error: &'a str,
+    /// Machine-readable reason, for errors clients should handle specifically
+    #[serde(skip_serializing_if = "Option::is_none")] pub code: Option<&'static str>,
     #[serde(skip_serializing_if = "Option::is_none")] pub details: Option<String>,
 }
 
@@ -25,20 +70,71 @@ impl IntoResponse for ApiError {
         match self {
             ApiError::Validation(msg) => (
                 StatusCode::BAD_REQUEST,
-                Json(ErrorBody { error: "Validation failed", details: Some(msg) }),
+                Json(ErrorBody { error: "Validation failed", code: None, details: Some(msg) }),
+            ).into_response(),
+            ApiError::Unauthorized(msg) => (
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorBody { error: "Unauthorized", code: None, details: Some(msg) }),
             ).into_response(),
-            ApiError::NotFound(_) => (
+            ApiError::Forbidden(msg) => (
+                StatusCode::FORBIDDEN,
+                Json(ErrorBody { error: "Forbidden", code: Some("insufficient_scope"), details: Some(msg) }),
+            ).into_response(),
+            ApiError::NotFound(msg) => (
                 StatusCode::NOT_FOUND,
-                Json(ErrorBody { error: "Country not found", details: None }),
+                Json(ErrorBody { error: &msg, code: None, details: None }),
             ).into_response(),
             ApiError::External(msg) => (
                 StatusCode::SERVICE_UNAVAILABLE,
-                Json(ErrorBody { error: "External data source unavailable", details: Some(msg) }),
+                Json(ErrorBody { error: "External data source unavailable", code: None, details: Some(msg) }),
+            ).into_response(),
+            ApiError::Timeout(msg) => (
+                StatusCode::GATEWAY_TIMEOUT,
+                Json(ErrorBody { error: "Deadline exceeded", code: None, details: Some(msg) }),
+            ).into_response(),
+            ApiError::PoolExhausted(msg) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(header::RETRY_AFTER, "1")],
+                Json(ErrorBody { error: "Database busy", code: Some("db_pool_exhausted"), details: Some(msg) }),
+            ).into_response(),
+            ApiError::UnknownParams { unknown, allowed } => (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": "Validation failed",
+                    "code": "unknown_query_params",
+                    "details": format!("unknown query parameter(s): {}", unknown.join(", ")),
+                    "unknown": unknown,
+                    "allowed": allowed,
+                })),
+            ).into_response(),
+            ApiError::RateLimited { retry_after_secs, next_allowed_at } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                Json(serde_json::json!({
+                    "error": "Too many refresh attempts",
+                    "code": "refresh_throttled",
+                    "retry_after_secs": retry_after_secs,
+                    "next_allowed_at": next_allowed_at.to_rfc3339(),
+                })),
             ).into_response(),
-            ApiError::Internal(msg) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorBody { error: "Internal server error", details: Some(msg) }),
+            ApiError::LockedOut { retry_after_secs } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                Json(serde_json::json!({
+                    "error": "Too many failed authentication attempts",
+                    "code": "auth_locked_out",
+                    "retry_after_secs": retry_after_secs,
+                })),
             ).into_response(),
+            ApiError::Internal(msg) => {
+                let mut res = (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ErrorBody { error: "Internal server error", code: None, details: Some(msg.clone()) }),
+                ).into_response();
+                // Picked up by `error_report::capture_errors`
+                res.extensions_mut().insert(InternalError(msg));
+                res
+            }
         }
     }
 }
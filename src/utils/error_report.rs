@@ -0,0 +1,149 @@
+// Optional error reporting to a Sentry-compatible endpoint (`SENTRY_DSN`).
+// Speaks the envelope protocol directly over reqwest; events are fire-and-forget,
+// so a slow or missing collector never delays a response.
+use axum::{extract::Request, middleware::Next, response::Response};
+use chrono::Utc;
+use rand::RngCore;
+use reqwest::{Client, Url};
+use std::sync::OnceLock;
+use tracing::warn;
+
+static REPORTER: OnceLock<Reporter> = OnceLock::new();
+
+struct Reporter {
+    http: Client,
+    envelope_url: Url,
+    auth: String,
+    dsn: String,
+    release: String,
+    environment: String,
+}
+
+/// Message of an `ApiError::Internal`, left on the response for [`capture_errors`].
+#[derive(Clone)]
+pub struct InternalError(pub String);
+
+/// `https://<key>@<host>[:port]/<project>` → (envelope URL, public key).
+fn parse_dsn(dsn: &str) -> Result<(Url, String), String> {
+    let url = Url::parse(dsn).map_err(|e| format!("invalid SENTRY_DSN: {}", e))?;
+    let key = url.username();
+    if key.is_empty() {
+        return Err("invalid SENTRY_DSN: missing public key".into());
+    }
+    let path = url.path().trim_end_matches('/');
+    let (prefix, project) = path.rsplit_once('/').unwrap_or(("", path));
+    if project.is_empty() {
+        return Err("invalid SENTRY_DSN: missing project id".into());
+    }
+    let host = url.host_str().ok_or("invalid SENTRY_DSN: missing host")?;
+    let port = url.port().map(|p| format!(":{}", p)).unwrap_or_default();
+    let envelope = format!("{}://{}{}{}/api/{}/envelope/", url.scheme(), host, port, prefix, project);
+    let envelope = Url::parse(&envelope).map_err(|e| format!("invalid SENTRY_DSN: {}", e))?;
+    Ok((envelope, key.to_string()))
+}
+
+/// Enables reporting. Without a DSN every `capture` is a no-op.
+pub fn init(dsn: Option<&str>, release: String, environment: String) -> Result<(), anyhow::Error> {
+    let Some(dsn) = dsn else {
+        return Ok(());
+    };
+    let (envelope_url, key) = parse_dsn(dsn).map_err(anyhow::Error::msg)?;
+    let auth = format!(
+        "Sentry sentry_version=7, sentry_key={}, sentry_client={}/{}",
+        key,
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+    let http = Client::builder().timeout(std::time::Duration::from_secs(5)).build()?;
+    REPORTER
+        .set(Reporter { http, envelope_url, auth, dsn: dsn.to_string(), release, environment })
+        .ok();
+    install_panic_hook();
+    Ok(())
+}
+
+/// Sends one error event in the background. `context` lands in the event's `extra`;
+/// a `request` object (method/url) in it becomes the event's request interface.
+pub fn capture(kind: &str, message: &str, mut context: serde_json::Value) {
+    let Some(r) = REPORTER.get() else {
+        return;
+    };
+    let Ok(rt) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    let mut id = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut id);
+    let event_id = hex::encode(id);
+    let request = context.as_object_mut().and_then(|o| o.remove("request"));
+    let event = serde_json::json!({
+        "event_id": event_id,
+        "timestamp": Utc::now().to_rfc3339(),
+        "platform": "rust",
+        "level": "error",
+        "logger": kind,
+        "release": r.release,
+        "environment": r.environment,
+        "message": { "formatted": message },
+        "exception": { "values": [{ "type": kind, "value": message }] },
+        "request": request,
+        "extra": context,
+    });
+    let envelope = format!(
+        "{}\n{}\n{}\n",
+        serde_json::json!({ "event_id": event_id, "dsn": r.dsn }),
+        serde_json::json!({ "type": "event" }),
+        event
+    );
+    let req = r
+        .http
+        .post(r.envelope_url.clone())
+        .header("X-Sentry-Auth", &r.auth)
+        .header("Content-Type", "application/x-sentry-envelope")
+        .body(envelope);
+    rt.spawn(async move {
+        match req.send().await {
+            Ok(res) if !res.status().is_success() => {
+                warn!("error report rejected: HTTP {}", res.status())
+            }
+            Err(e) => warn!("error report failed: {}", e),
+            Ok(_) => {}
+        }
+    });
+}
+
+fn install_panic_hook() {
+    let prev = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panic".into());
+        let location = info.location().map(|l| format!("{}:{}", l.file(), l.line()));
+        capture("panic", &message, serde_json::json!({ "location": location }));
+        prev(info);
+    }));
+}
+
+/// Middleware: reports `ApiError::Internal` responses with the request they answered.
+pub async fn capture_errors(req: Request, next: Next) -> Response {
+    if REPORTER.get().is_none() {
+        return next.run(req).await;
+    }
+    let method = req.method().to_string();
+    // Path only: query strings may carry tokens
+    let path = req.uri().path().to_string();
+    let res = next.run(req).await;
+    if let Some(InternalError(msg)) = res.extensions().get::<InternalError>() {
+        capture(
+            "ApiError::Internal",
+            msg,
+            serde_json::json!({
+                "request": { "method": method, "url": path },
+                "status": res.status().as_u16(),
+            }),
+        );
+    }
+    res
+}
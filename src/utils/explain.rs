@@ -0,0 +1,44 @@
+use sqlx::{QueryBuilder, Row};
+use tracing::{info, warn};
+
+use crate::db::{self, Db, DbPool, DbRow};
+
+/// Runs an `EXPLAIN` query (built with the same binds as the real one) and logs a
+/// warning for every table it would read with a full scan. Debug aid behind
+/// `EXPLAIN_QUERIES`; failures are only logged.
+pub async fn warn_on_full_scan(pool: &DbPool, mut explain: QueryBuilder<'_, Db>) {
+    let sql = explain.sql().to_string();
+    let rows = match explain.build().fetch_all(pool).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            info!("EXPLAIN failed: {}", e);
+            return;
+        }
+    };
+    for r in rows {
+        if let Some(table) = full_scan(&r) {
+            warn!("full table scan on {}: {}", table, sql.trim_start_matches(db::EXPLAIN));
+        }
+    }
+}
+
+/// The table (and estimated rows) a plan row reads in full
+#[cfg(not(feature = "sqlite"))]
+fn full_scan(r: &DbRow) -> Option<String> {
+    let access: Option<String> = r.try_get("type").ok().flatten();
+    if access.as_deref() != Some("ALL") {
+        return None;
+    }
+    let table: Option<String> = r.try_get("table").ok().flatten();
+    let est_rows: Option<u64> = r.try_get("rows").ok().flatten();
+    Some(format!("{} (~{} rows)", table.as_deref().unwrap_or("?"), est_rows.unwrap_or(0)))
+}
+
+/// `EXPLAIN QUERY PLAN` details read `SCAN countries` for a full scan, and
+/// `SEARCH ...` or `SCAN ... USING INDEX ...` when an index does the work
+#[cfg(feature = "sqlite")]
+fn full_scan(r: &DbRow) -> Option<String> {
+    let detail: String = r.try_get("detail").ok()?;
+    let table = detail.strip_prefix("SCAN ")?;
+    (!table.contains(" USING ")).then(|| table.to_string())
+}
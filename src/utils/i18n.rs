@@ -0,0 +1,227 @@
+// Labels + number formatting for rendered images (`?lang=`).
+// There is no translations table yet, so the catalogue lives here; unknown keys fall back to English.
+
+use crate::utils::image::RankMetric;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    En,
+    Fr,
+    De,
+    Es,
+    Pt,
+}
+
+#[derive(Clone, Copy)]
+pub enum Label {
+    TotalCountries,
+    Timestamp,
+    Capital,
+    Region,
+    Population,
+    Currency,
+    EstimatedGdp,
+    RegionAverage,
+}
+
+impl Lang {
+    pub const ALLOWED: &'static str = "en, fr, de, es, pt";
+
+    pub fn parse(s: &str) -> Option<Self> {
+        // Accept full tags like "fr-FR" by looking at the primary subtag only
+        let primary = s.split(['-', '_']).next().unwrap_or("").to_ascii_lowercase();
+        match primary.as_str() {
+            "en" => Some(Lang::En),
+            "fr" => Some(Lang::Fr),
+            "de" => Some(Lang::De),
+            "es" => Some(Lang::Es),
+            "pt" => Some(Lang::Pt),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::Fr => "fr",
+            Lang::De => "de",
+            Lang::Es => "es",
+            Lang::Pt => "pt",
+        }
+    }
+
+    pub fn label(self, l: Label) -> &'static str {
+        use Label::*;
+        match (self, l) {
+            (Lang::Fr, TotalCountries) => "Nombre de pays",
+            (Lang::Fr, Timestamp) => "Horodatage",
+            (Lang::Fr, Capital) => "Capitale",
+            (Lang::Fr, Region) => "Région",
+            (Lang::Fr, Population) => "Population",
+            (Lang::Fr, Currency) => "Devise",
+            (Lang::Fr, EstimatedGdp) => "PIB est.",
+            (Lang::Fr, RegionAverage) => "Moy. région",
+
+            (Lang::De, TotalCountries) => "Länder gesamt",
+            (Lang::De, Timestamp) => "Zeitstempel",
+            (Lang::De, Capital) => "Hauptstadt",
+            (Lang::De, Region) => "Region",
+            (Lang::De, Population) => "Bevölkerung",
+            (Lang::De, Currency) => "Währung",
+            (Lang::De, EstimatedGdp) => "Gesch. BIP",
+            (Lang::De, RegionAverage) => "Regionsschnitt",
+
+            (Lang::Es, TotalCountries) => "Total de países",
+            (Lang::Es, Timestamp) => "Marca de tiempo",
+            (Lang::Es, Capital) => "Capital",
+            (Lang::Es, Region) => "Región",
+            (Lang::Es, Population) => "Población",
+            (Lang::Es, Currency) => "Moneda",
+            (Lang::Es, EstimatedGdp) => "PIB est.",
+            (Lang::Es, RegionAverage) => "Prom. región",
+
+            (Lang::Pt, TotalCountries) => "Total de países",
+            (Lang::Pt, Timestamp) => "Data/hora",
+            (Lang::Pt, Capital) => "Capital",
+            (Lang::Pt, Region) => "Região",
+            (Lang::Pt, Population) => "População",
+            (Lang::Pt, Currency) => "Moeda",
+            (Lang::Pt, EstimatedGdp) => "PIB est.",
+            (Lang::Pt, RegionAverage) => "Média região",
+
+            (_, TotalCountries) => "Total countries",
+            (_, Timestamp) => "Timestamp",
+            (_, Capital) => "Capital",
+            (_, Region) => "Region",
+            (_, Population) => "Population",
+            (_, Currency) => "Currency",
+            (_, EstimatedGdp) => "Est. GDP",
+            (_, RegionAverage) => "Region avg",
+        }
+    }
+
+    /// Heading of the summary's top list, e.g. "Top 5 by estimated GDP:".
+    pub fn top_heading(self, n: usize, metric: RankMetric) -> String {
+        use RankMetric::*;
+        match (self, metric) {
+            (Lang::Fr, Gdp) => format!("Top {} par PIB estimé :", n),
+            (Lang::Fr, Population) => format!("Top {} par population :", n),
+            (Lang::Fr, ExchangeRate) => format!("Top {} par taux de change :", n),
+            (Lang::De, Gdp) => format!("Top {} nach geschätztem BIP:", n),
+            (Lang::De, Population) => format!("Top {} nach Bevölkerung:", n),
+            (Lang::De, ExchangeRate) => format!("Top {} nach Wechselkurs:", n),
+            (Lang::Es, Gdp) => format!("Top {} por PIB estimado:", n),
+            (Lang::Es, Population) => format!("Top {} por población:", n),
+            (Lang::Es, ExchangeRate) => format!("Top {} por tipo de cambio:", n),
+            (Lang::Pt, Gdp) => format!("Top {} por PIB estimado:", n),
+            (Lang::Pt, Population) => format!("Top {} por população:", n),
+            (Lang::Pt, ExchangeRate) => format!("Top {} por taxa de câmbio:", n),
+            (Lang::En, Gdp) => format!("Top {} by estimated GDP:", n),
+            (Lang::En, Population) => format!("Top {} by population:", n),
+            (Lang::En, ExchangeRate) => format!("Top {} by exchange rate:", n),
+        }
+    }
+
+    fn separators(self) -> (char, char) {
+        // (thousands, decimal); French groups with a narrow no-break space, so a wrapped
+        // line never splits a number
+        match self {
+            Lang::En => (',', '.'),
+            Lang::Fr => ('\u{202F}', ','),
+            Lang::De | Lang::Es | Lang::Pt => ('.', ','),
+        }
+    }
+
+    pub fn format_int(self, n: i64) -> String {
+        let (group, _) = self.separators();
+        let mut out = group_digits(&n.unsigned_abs().to_string(), group);
+        if n < 0 {
+            out.insert(0, '-');
+        }
+        out
+    }
+
+    pub fn format_num(self, v: f64, decimals: usize) -> String {
+        if !v.is_finite() {
+            return v.to_string();
+        }
+        let (group, dec) = self.separators();
+        let fixed = format!("{:.*}", decimals, v.abs());
+        let (int_part, frac_part) = match fixed.split_once('.') {
+            Some((i, f)) => (i, Some(f)),
+            None => (fixed.as_str(), None),
+        };
+        let mut out = group_digits(int_part, group);
+        // Don't print "-0,00" for tiny negatives that round to zero
+        if v < 0.0 && fixed.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            out.insert(0, '-');
+        }
+        if let Some(f) = frac_part {
+            out.push(dec);
+            out.push_str(f);
+        }
+        out
+    }
+}
+
+fn group_digits(digits: &str, group: char) -> String {
+    // `digits` is ASCII, so byte slicing is safe
+    let head = match digits.len() % 3 {
+        0 => digits.len().min(3),
+        n => n,
+    };
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    out.push_str(&digits[..head]);
+    for chunk in digits.as_bytes()[head..].chunks(3) {
+        out.push(group);
+        out.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digits_group_in_threes_from_the_right() {
+        assert_eq!(group_digits("7", ','), "7");
+        assert_eq!(group_digits("123", ','), "123");
+        assert_eq!(group_digits("1234", ','), "1,234");
+        assert_eq!(group_digits("1234567", ','), "1,234,567");
+    }
+
+    #[test]
+    fn integers_use_each_language_separator() {
+        let n = 1_234_567;
+        assert_eq!(Lang::En.format_int(n), "1,234,567");
+        assert_eq!(Lang::Fr.format_int(n), "1\u{202F}234\u{202F}567");
+        assert_eq!(Lang::De.format_int(n), "1.234.567");
+        assert_eq!(Lang::Es.format_int(n), "1.234.567");
+        assert_eq!(Lang::Pt.format_int(n), "1.234.567");
+        assert_eq!(Lang::En.format_int(0), "0");
+        assert_eq!(Lang::En.format_int(-1234), "-1,234");
+        // No overflow negating the minimum
+        assert_eq!(Lang::En.format_int(i64::MIN), "-9,223,372,036,854,775,808");
+    }
+
+    #[test]
+    fn decimals_use_each_language_separator() {
+        assert_eq!(Lang::En.format_num(1234.5, 2), "1,234.50");
+        assert_eq!(Lang::Fr.format_num(1234.5, 2), "1\u{202F}234,50");
+        assert_eq!(Lang::De.format_num(1234.5, 2), "1.234,50");
+        assert_eq!(Lang::Es.format_num(1234.5, 2), "1.234,50");
+        assert_eq!(Lang::Pt.format_num(1234.5, 2), "1.234,50");
+        assert_eq!(Lang::En.format_num(1234.5, 0), "1,234");
+        assert_eq!(Lang::De.format_num(-1234.567, 2), "-1.234,57");
+    }
+
+    #[test]
+    fn negatives_rounding_to_zero_lose_the_sign() {
+        assert_eq!(Lang::En.format_num(-0.001, 2), "0.00");
+        assert_eq!(Lang::De.format_num(-0.4, 0), "0");
+        assert_eq!(Lang::En.format_num(-0.005001, 2), "-0.01");
+        assert_eq!(Lang::En.format_num(f64::NAN, 2), "NaN");
+    }
+}
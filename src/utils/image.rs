@@ -1,61 +1,491 @@
-use chrono::Utc;
-use image::{ImageBuffer, Rgba};
-use imageproc::drawing::draw_text_mut;
-use sqlx::{mysql::MySqlRow, MySql, Pool, Row};
-use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use image::{imageops::FilterType, ImageBuffer, ImageFormat, Rgba};
+use imageproc::drawing::{draw_filled_rect_mut, draw_text_mut};
+use imageproc::rect::Rect;
+use sha2::{Digest, Sha256};
+use sqlx::Row;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 
 // Use ab_glyph for fonts with imageproc 0.24+
 use ab_glyph::FontArc;
 
-pub async fn build_summary_image(pool: &Pool<MySql>, path: &PathBuf) -> Result<(), String> {
+use crate::db::{DbPool, DbRow};
+use crate::models::country::Country;
+use crate::utils::i18n::{Label, Lang};
+use crate::utils::map::MapShapes;
+use crate::utils::png_text;
+use crate::utils::text_layout::{self, Block, Frame, Layout, Line};
+
+pub(crate) type Canvas = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+/// Canvas sizes (width, height) of the rendered images
+pub const SUMMARY_SIZE: (u32, u32) = (1000, 600);
+pub const CARD_SIZE: (u32, u32) = (640, 320);
+
+const DEFAULT_BACKGROUND: Rgba<u8> = Rgba([245, 247, 250, 255]);
+const DEFAULT_TEXT: Rgba<u8> = Rgba([20, 23, 26, 255]);
+const DEFAULT_ACCENT: Rgba<u8> = Rgba([37, 99, 235, 255]);
+const DEFAULT_MUTED: Rgba<u8> = Rgba([148, 163, 184, 255]);
+
+/// Operator-configurable look of every rendered image (font, palette, logo, title).
+/// Loaded once at startup; cheap to clone (font and logo are shared).
+#[derive(Clone)]
+pub struct Branding {
+    pub font: FontArc,
+    pub logo: Option<Arc<Canvas>>,
+    pub background: Rgba<u8>,
+    pub text: Rgba<u8>,
+    pub accent: Rgba<u8>,
+    pub muted: Rgba<u8>,
+    pub title: Option<String>,
+    /// Country outlines for `GET /map` (`MAP_GEOJSON_PATH`); `None` = tile-grid map
+    pub map_shapes: Option<Arc<MapShapes>>,
+    /// SHA-256 (hex) over everything above as loaded: font, logo and geometry bytes, the
+    /// palette and the title. Cached variants are keyed by it (`ImageCache`).
+    pub fingerprint: String,
+}
+
+#[derive(Default, Debug)]
+pub struct BrandingConfig {
+    pub font_path: Option<PathBuf>,
+    pub logo_path: Option<PathBuf>,
+    pub background: Option<String>,
+    pub text: Option<String>,
+    pub accent: Option<String>,
+    pub muted: Option<String>,
+    pub title: Option<String>,
+    pub map_geojson_path: Option<PathBuf>,
+}
+
+impl Branding {
+    pub fn load(cfg: &BrandingConfig) -> Result<Self, String> {
+        let mut theme = Sha256::new();
+        let mut field = |label: &str, bytes: &[u8]| theme_field(&mut theme, label, bytes);
+
+        let font = match &cfg.font_path {
+            Some(p) => {
+                let data = std::fs::read(p)
+                    .map_err(|e| format!("could not read font {}: {}", p.display(), e))?;
+                field("font", &data);
+                FontArc::try_from_vec(data)
+                    .map_err(|_| format!("font load failed: {}", p.display()))?
+            }
+            None => {
+                field("font", EMBEDDED_FONT);
+                load_font()?
+            }
+        };
+        let logo = match &cfg.logo_path {
+            Some(p) => {
+                let img = std::fs::read(p)
+                    .map_err(|e| e.to_string())
+                    .and_then(|data| {
+                        field("logo", &data);
+                        image::load_from_memory(&data).map_err(|e| e.to_string())
+                    })
+                    .map_err(|e| format!("could not load logo {}: {}", p.display(), e))?;
+                // Keep it to a corner badge
+                Some(Arc::new(img.resize(160, 80, FilterType::Triangle).to_rgba8()))
+            }
+            None => None,
+        };
+        let map_shapes = match &cfg.map_geojson_path {
+            Some(p) => {
+                let raw = std::fs::read_to_string(p)
+                    .map_err(|e| format!("could not read map geometry {}: {}", p.display(), e))?;
+                field("map", raw.as_bytes());
+                let shapes = MapShapes::parse(&raw).map_err(|e| format!("map geometry {}: {}", p.display(), e))?;
+                Some(Arc::new(shapes))
+            }
+            None => None,
+        };
+        let color = |v: &Option<String>, default: Rgba<u8>| match v.as_deref() {
+            Some(s) => parse_hex_color(s),
+            None => Ok(default),
+        };
+        let (background, text) = (color(&cfg.background, DEFAULT_BACKGROUND)?, color(&cfg.text, DEFAULT_TEXT)?);
+        let (accent, muted) = (color(&cfg.accent, DEFAULT_ACCENT)?, color(&cfg.muted, DEFAULT_MUTED)?);
+        let title = cfg.title.clone().filter(|t| !t.trim().is_empty());
+        for (label, c) in [("background", background), ("text", text), ("accent", accent), ("muted", muted)] {
+            field(label, &c.0);
+        }
+        field("title", title.as_deref().unwrap_or("").as_bytes());
+
+        Ok(Self {
+            font,
+            logo,
+            background,
+            text,
+            accent,
+            muted,
+            title,
+            map_shapes,
+            fingerprint: hex::encode(theme.finalize()),
+        })
+    }
+}
+
+/// Outcome of the most recent summary render, shared between the refresh path
+/// (writer) and `/status` + `/countries/image` (readers).
+#[derive(Clone, Default)]
+pub struct ImageHealth(Arc<RwLock<ImageHealthState>>);
+
+#[derive(Clone, Default, serde::Serialize)]
+pub struct ImageHealthState {
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+}
+
+impl ImageHealthState {
+    /// True when the latest attempt failed, i.e. the file on disk (if any) is from an older refresh.
+    pub fn is_stale(&self) -> bool {
+        match (&self.last_error_at, &self.last_success_at) {
+            (Some(err), Some(ok)) => err > ok,
+            (Some(_), None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl ImageHealth {
+    pub fn record(&self, result: &Result<(), String>) {
+        let now = Utc::now();
+        let mut st = self.0.write().unwrap_or_else(|e| e.into_inner());
+        match result {
+            Ok(()) => st.last_success_at = Some(now),
+            Err(e) => {
+                st.last_error = Some(e.clone());
+                st.last_error_at = Some(now);
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> ImageHealthState {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Parses `#rrggbb` / `rrggbb` (optionally `#rrggbbaa`).
+fn parse_hex_color(s: &str) -> Result<Rgba<u8>, String> {
+    let hex = s.trim().trim_start_matches('#');
+    let byte = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|h| u8::from_str_radix(h, 16).ok())
+            .ok_or_else(|| format!("invalid color {:?}, expected #rrggbb", s))
+    };
+    match hex.len() {
+        6 => Ok(Rgba([byte(0)?, byte(2)?, byte(4)?, 255])),
+        8 => Ok(Rgba([byte(0)?, byte(2)?, byte(4)?, byte(6)?])),
+        _ => Err(format!("invalid color {:?}, expected #rrggbb", s)),
+    }
+}
+
+fn hex(c: Rgba<u8>) -> String {
+    format!("#{:02x}{:02x}{:02x}", c[0], c[1], c[2])
+}
+
+/// Adds one input to `Branding::fingerprint`. Labelled and length-prefixed, so adjacent
+/// fields can't run together.
+pub(crate) fn theme_field(theme: &mut Sha256, label: &str, bytes: &[u8]) {
+    theme.update(label.as_bytes());
+    theme.update((bytes.len() as u64).to_le_bytes());
+    theme.update(bytes);
+}
+
+/// TTF embedded at compile time, used unless `IMAGE_FONT_PATH` is set
+const EMBEDDED_FONT: &[u8] = include_bytes!("../../assets/DejaVuSans.ttf");
+
+fn load_font() -> Result<FontArc, String> {
+    FontArc::try_from_slice(EMBEDDED_FONT).map_err(|_| "font load failed".to_string())
+}
+
+pub(crate) fn draw_logo(img: &mut Canvas, brand: &Branding) {
+    if let Some(logo) = &brand.logo {
+        let x = img.width().saturating_sub(logo.width() + 24) as i64;
+        image::imageops::overlay(img, logo.as_ref(), x, 24);
+    }
+}
+
+pub(crate) fn encode_png(img: &Canvas) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+    img.write_to(&mut Cursor::new(&mut buf), ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(buf)
+}
+
+/// What the summary's top list ranks countries by (`?rank_by=`, `SUMMARY_RANK_BY`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RankMetric {
+    #[default]
+    Gdp,
+    Population,
+    ExchangeRate,
+}
+
+impl RankMetric {
+    const CHOICES: [RankMetric; 3] = [RankMetric::Gdp, RankMetric::Population, RankMetric::ExchangeRate];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RankMetric::Gdp => "gdp",
+            RankMetric::Population => "population",
+            RankMetric::ExchangeRate => "exchange_rate",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::CHOICES.into_iter().find(|m| m.as_str() == s.trim()).ok_or_else(|| {
+            let allowed: Vec<&str> = Self::CHOICES.iter().map(|m| m.as_str()).collect();
+            format!("rank_by must be one of {}", allowed.join(", "))
+        })
+    }
+
+    fn column(self) -> &'static str {
+        match self {
+            RankMetric::Gdp => "estimated_gdp",
+            RankMetric::Population => "population",
+            RankMetric::ExchangeRate => "exchange_rate",
+        }
+    }
+
+    fn format(self, lang: Lang, v: f64) -> String {
+        match self {
+            RankMetric::Gdp => lang.format_num(v, 2),
+            RankMetric::Population => lang.format_int(v as i64),
+            // Rates span 0.3 (KWD) to tens of thousands per USD
+            RankMetric::ExchangeRate => lang.format_num(v, 4),
+        }
+    }
+}
+
+/// The summary's top list: the `top` highest countries by `metric`. `SUMMARY_TOP_N` /
+/// `SUMMARY_RANK_BY` set it for the saved image; `?top=` / `?rank_by=` override it per request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Ranking {
+    pub metric: RankMetric,
+    pub top: usize,
+}
+
+impl Default for Ranking {
+    fn default() -> Self {
+        Ranking { metric: RankMetric::Gdp, top: 5 }
+    }
+}
+
+impl Ranking {
+    /// More rows stop fitting the summary canvas legibly
+    pub const MAX_TOP: usize = 10;
+
+    pub fn new(metric: RankMetric, top: usize) -> Result<Self, String> {
+        if !(1..=Self::MAX_TOP).contains(&top) {
+            return Err(format!("top must be between 1 and {}", Self::MAX_TOP));
+        }
+        Ok(Ranking { metric, top })
+    }
+
+    /// `VariantKey` subject of summaries rendered with this ranking
+    pub fn subject(&self) -> String {
+        format!("summary-{}-{}", self.metric.as_str(), self.top)
+    }
+}
+
+/// What the summary image shows, also served as JSON by `GET /countries/image`.
+#[derive(serde::Serialize)]
+pub struct SummaryData {
+    pub total_countries: i64,
+    pub rank_by: &'static str,
+    /// Highest `rank_by` first; countries without a value are left out
+    pub top: Vec<RankEntry>,
+    /// `top` under its name from before `rank_by` existed; only when ranking by GDP
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_by_gdp: Option<Vec<GdpEntry>>,
+}
+
+#[derive(serde::Serialize)]
+pub struct RankEntry {
+    pub name: String,
+    pub value: f64,
+}
+
+#[derive(serde::Serialize)]
+pub struct GdpEntry {
+    pub name: String,
+    pub estimated_gdp: f64,
+}
+
+pub async fn summary_data(pool: &DbPool, ranking: Ranking) -> Result<SummaryData, String> {
     let total: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM countries")
         .fetch_one(pool)
         .await
         .map_err(|e| e.to_string())?;
 
-    let top5: Vec<MySqlRow> = sqlx::query(
-        "SELECT name, estimated_gdp FROM countries WHERE estimated_gdp IS NOT NULL ORDER BY estimated_gdp DESC LIMIT 5",
-    )
+    // The column comes from `RankMetric`, never from the request
+    let column = ranking.metric.column();
+    let rows: Vec<DbRow> = sqlx::query(&format!(
+        "SELECT name, {c} AS value FROM countries WHERE {c} IS NOT NULL ORDER BY {c} DESC, id ASC LIMIT ?",
+        c = column
+    ))
+    .bind(ranking.top as i64)
     .fetch_all(pool)
     .await
     .map_err(|e| e.to_string())?;
 
+    let top: Vec<RankEntry> = rows
+        .iter()
+        .map(|r| RankEntry {
+            name: r.try_get("name").unwrap_or_default(),
+            // population is an integer column
+            value: r
+                .try_get::<f64, _>("value")
+                .or_else(|_| r.try_get::<i64, _>("value").map(|v| v as f64))
+                .unwrap_or_default(),
+        })
+        .collect();
+    let top_by_gdp = (ranking.metric == RankMetric::Gdp).then(|| {
+        top.iter().map(|e| GdpEntry { name: e.name.clone(), estimated_gdp: e.value }).collect()
+    });
+    Ok(SummaryData { total_countries: total.0, rank_by: ranking.metric.as_str(), top, top_by_gdp })
+}
+
+/// Which data a summary image was drawn from: the latest succeeded refresh run and
+/// `app_meta.last_refreshed_at`. Embedded in the saved PNG as `tEXt` chunks.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct DataStamp {
+    pub refresh_run_id: Option<i64>,
+    pub last_refreshed_at: Option<String>,
+}
+
+impl DataStamp {
+    const RUN_ID: &'static str = "refresh_run_id";
+    const REFRESHED_AT: &'static str = "last_refreshed_at";
+
+    pub async fn current(pool: &DbPool) -> Result<Self, String> {
+        let (refresh_run_id, last_refreshed_at): (Option<i64>, Option<String>) = sqlx::query_as(
+            "SELECT (SELECT MAX(id) FROM refresh_runs WHERE status = 'succeeded'), \
+             (SELECT v FROM app_meta WHERE k = 'last_refreshed_at')",
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        Ok(DataStamp { refresh_run_id, last_refreshed_at })
+    }
+
+    /// The stamp in a PNG written by [`build_summary_image`]; `None` for files without one.
+    pub fn from_png(png: &[u8]) -> Option<Self> {
+        let text = png_text::read(png);
+        let get = |key: &str| text.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+        if get(Self::RUN_ID).is_none() && get(Self::REFRESHED_AT).is_none() {
+            return None;
+        }
+        Some(DataStamp {
+            refresh_run_id: get(Self::RUN_ID).and_then(|v| v.parse().ok()),
+            last_refreshed_at: get(Self::REFRESHED_AT),
+        })
+    }
+
+    fn embed(&self, png: &[u8]) -> Result<Vec<u8>, String> {
+        let run_id = self.refresh_run_id.map(|id| id.to_string());
+        let entries: Vec<(&str, &str)> = [
+            (Self::RUN_ID, run_id.as_deref()),
+            (Self::REFRESHED_AT, self.last_refreshed_at.as_deref()),
+        ]
+        .into_iter()
+        .filter_map(|(k, v)| Some((k, v?)))
+        .collect();
+        png_text::insert(png, &entries)
+    }
+}
+
+async fn summary_lines(pool: &DbPool, lang: Lang, ranking: Ranking) -> Result<Vec<String>, String> {
+    let data = summary_data(pool, ranking).await?;
+
     let mut lines: Vec<String> = vec![
-        format!("Total countries: {}", total.0),
-        "Top 5 by estimated GDP:".into(),
+        format!("{}: {}", lang.label(Label::TotalCountries), lang.format_int(data.total_countries)),
+        lang.top_heading(ranking.top, ranking.metric),
     ];
-    for (i, r) in top5.iter().enumerate() {
-        let name: String = r.try_get("name").unwrap_or_default();
-        let gdp: f64 = r.try_get("estimated_gdp").unwrap_or_default();
-        lines.push(format!("{}. {} — {:.2}", i + 1, name, gdp));
+    for (i, e) in data.top.iter().enumerate() {
+        lines.push(format!("{}. {} — {}", i + 1, e.name, ranking.metric.format(lang, e.value)));
+    }
+    lines.push(format!("{}: {}", lang.label(Label::Timestamp), Utc::now().to_rfc3339()));
+    Ok(lines)
+}
+
+/// The title and `lines` measured with `brand.font` and wrapped to [`SUMMARY_SIZE`]'s
+/// width, and the canvas size: taller when the top list or wrapped names need it. With
+/// `logo`, lines beside the logo end before it (the SVG has no logo).
+fn summary_layout(lines: &[String], brand: &Branding, logo: bool) -> (Layout, (u32, u32)) {
+    let width = SUMMARY_SIZE.0;
+    let logo = brand.logo.as_ref().filter(|_| logo);
+    let frame = Frame {
+        width,
+        margin: 40,
+        leading: 8,
+        keep_out: logo.map(|l| (width.saturating_sub(l.width() + 24), 24 + l.height())),
+        max_lines: 3,
+    };
+    let title = brand.title.as_deref().map(|text| Block { text, size: 36.0, space_after: 16 });
+    let blocks: Vec<Block> =
+        title.into_iter().chain(lines.iter().map(|text| Block { text, size: 28.0, space_after: 0 })).collect();
+    let layout = text_layout::layout(&brand.font, &frame, &blocks);
+    let height = SUMMARY_SIZE.1.max(layout.bottom + 20).max(frame.keep_out.map_or(0, |(_, bottom)| bottom + 24));
+    (layout, (width, height))
+}
+
+/// Whether a laid-out line is the title (drawn in the accent color)
+fn is_title(line: &Line, brand: &Branding) -> bool {
+    brand.title.is_some() && line.block == 0
+}
+
+fn draw_summary(lines: Vec<String>, brand: &Branding) -> Canvas {
+    let (layout, (width, height)) = summary_layout(&lines, brand, true);
+    let mut img: Canvas = ImageBuffer::from_pixel(width, height, brand.background);
+    draw_logo(&mut img, brand);
+
+    for line in &layout.lines {
+        let color = if is_title(line, brand) { brand.accent } else { brand.text };
+        draw_text_mut(&mut img, color, line.x, line.y, line.size, &brand.font, &line.text);
     }
-    lines.push(format!("Timestamp: {}", Utc::now().to_rfc3339()));
+    img
+}
+
+/// Draws and encodes a one-line summary with `brand`, without touching the DB or disk.
+/// Used by `--self-test`.
+pub fn render_probe(brand: &Branding) -> Result<Vec<u8>, String> {
+    encode_png(&draw_summary(vec!["Self-test".into()], brand))
+}
+
+/// Renders the default (English) summary and saves it to `path`; this is the
+/// file `GET /countries/image` serves. The PNG carries the [`DataStamp`] it was drawn from.
+pub async fn build_summary_image(
+    pool: &DbPool,
+    path: &Path,
+    brand: &Branding,
+    ranking: Ranking,
+) -> Result<(), String> {
+    // Read first: should a refresh commit meanwhile, the image claims older data, not newer
+    let stamp = DataStamp::current(pool).await?;
+    let lines = summary_lines(pool, Lang::En, ranking).await?;
 
     tokio::task::spawn_blocking({
-        let path = path.clone();
+        let path = path.to_path_buf();
+        let brand = brand.clone();
         move || {
-            // Canvas
-            let width = 1000u32;
-            let height = 600u32;
-            let mut img: ImageBuffer<Rgba<u8>, Vec<u8>> =
-                ImageBuffer::from_pixel(width, height, Rgba([245, 247, 250, 255]));
-
-            // Load TTF (embedded at compile-time)
-            let font_data: &[u8] = include_bytes!("../../assets/DejaVuSans.ttf");
-            let font = FontArc::try_from_slice(font_data)
-                .map_err(|_| "font load failed".to_string())?;
-
-            // ab_glyph uses a plain f32 for pixel scale
-            let scale: f32 = 28.0;
-
-            // Draw lines
-            let mut y = 40i32;
-            for line in lines {
-                draw_text_mut(&mut img, Rgba([20, 23, 26, 255]), 40, y, scale, &font, &line);
-                y += 40;
-            }
+            let bytes = stamp.embed(&encode_png(&draw_summary(lines, &brand))?)?;
 
-            img.save(&path).map_err(|e| e.to_string())?;
+            // Write a sibling temp file and rename over the old image, so a full disk or
+            // permission problem leaves the previous summary intact instead of truncated
+            let tmp = path.with_extension("png.tmp");
+            if let Err(e) = std::fs::write(&tmp, &bytes) {
+                std::fs::remove_file(&tmp).ok();
+                return Err(format!("write {} failed: {}", tmp.display(), e));
+            }
+            std::fs::rename(&tmp, &path).map_err(|e| {
+                std::fs::remove_file(&tmp).ok();
+                format!("rename to {} failed: {}", path.display(), e)
+            })?;
             Ok::<(), String>(())
         }
     })
@@ -64,3 +494,245 @@ pub async fn build_summary_image(pool: &Pool<MySql>, path: &PathBuf) -> Result<(
 
     Ok(())
 }
+
+/// Renders the summary on demand (e.g. for a non-default `lang`) and returns PNG bytes.
+pub async fn build_summary_png(
+    pool: &DbPool,
+    lang: Lang,
+    brand: &Branding,
+    ranking: Ranking,
+) -> Result<Vec<u8>, String> {
+    let lines = summary_lines(pool, lang, ranking).await?;
+    let brand = brand.clone();
+    tokio::task::spawn_blocking(move || encode_png(&draw_summary(lines, &brand)))
+        .await
+        .map_err(|e| format!("spawn failed: {:?}", e))?
+}
+
+fn card_lines(country: &Country, lang: Lang) -> Vec<String> {
+    let mut lines: Vec<String> = vec![
+        format!("{}: {}", lang.label(Label::Capital), country.capital.as_deref().unwrap_or("—")),
+        format!("{}: {}", lang.label(Label::Region), country.region.as_deref().unwrap_or("—")),
+        format!("{}: {}", lang.label(Label::Population), lang.format_int(country.population)),
+    ];
+    let currency = lang.label(Label::Currency);
+    match (country.currency_code.as_deref(), country.exchange_rate) {
+        (Some(code), Some(rate)) => {
+            lines.push(format!("{}: {} ({})", currency, code, lang.format_num(rate, 2)))
+        }
+        (Some(code), None) => lines.push(format!("{}: {}", currency, code)),
+        _ => lines.push(format!("{}: —", currency)),
+    }
+    lines
+}
+
+/// Renders a small per-country card (flag, key stats, GDP bar vs region average)
+/// and returns the encoded PNG bytes. `flag` is the raw flag download; formats the
+/// `image` crate can't decode (e.g. SVG) are skipped rather than failing the card.
+pub async fn build_country_card(
+    country: Country,
+    region_avg_gdp: Option<f64>,
+    flag: Option<Vec<u8>>,
+    lang: Lang,
+    brand: &Branding,
+) -> Result<Vec<u8>, String> {
+    let brand = brand.clone();
+    tokio::task::spawn_blocking(move || {
+        // Canvas
+        let (width, height) = CARD_SIZE;
+        let mut img: Canvas = ImageBuffer::from_pixel(width, height, brand.background);
+        let ink = brand.text;
+        let font = &brand.font;
+
+        // Flag (top-left), if we could decode it
+        let mut text_x = 24i32;
+        if let Some(flag_img) = flag.and_then(|b| image::load_from_memory(&b).ok()) {
+            let thumb = flag_img.resize(120, 80, FilterType::Triangle).to_rgba8();
+            image::imageops::overlay(&mut img, &thumb, 24, 24);
+            text_x = 24 + thumb.width() as i32 + 24;
+        }
+        draw_logo(&mut img, &brand);
+
+        draw_text_mut(&mut img, ink, text_x, 24, 32.0, font, &country.name);
+
+        let mut y = 68i32;
+        for line in card_lines(&country, lang) {
+            draw_text_mut(&mut img, ink, text_x, y, 20.0, font, &line);
+            y += 26;
+        }
+
+        // GDP bar vs region average
+        let gdp = country.estimated_gdp.unwrap_or(0.0);
+        let avg = region_avg_gdp.unwrap_or(0.0);
+        let max = gdp.max(avg);
+        let bar_max = (width - 48 - 160) as f64;
+        let bars = [
+            (lang.label(Label::EstimatedGdp), gdp, brand.accent),
+            (lang.label(Label::RegionAverage), avg, brand.muted),
+        ];
+        let mut y = 220i32;
+        for (label, value, color) in bars {
+            draw_text_mut(&mut img, ink, 24, y + 2, 18.0, font, label);
+            let len = if max > 0.0 { ((value / max) * bar_max).round() as u32 } else { 0 };
+            if len > 0 {
+                draw_filled_rect_mut(&mut img, Rect::at(160, y).of_size(len, 24), color);
+            }
+            draw_text_mut(&mut img, ink, 168, y + 30, 14.0, font, &lang.format_num(value, 2));
+            y += 48;
+        }
+
+        encode_png(&img)
+    })
+    .await
+    .map_err(|e| format!("spawn failed: {:?}", e))?
+}
+
+/// Flag cell size in the sprite sheet; flags are scaled to fit, keeping aspect ratio
+const SPRITE_CELL: (u32, u32) = (80, 60);
+const SPRITE_COLUMNS: u32 = 16;
+
+/// Packs flags (name, raw image bytes) into one grid PNG and returns it with a
+/// `{ width, height, cell, frames: { name: {x, y, w, h} } }` coordinate map.
+/// Undecodable flags are skipped.
+pub fn compose_flag_sprite(
+    flags: Vec<(String, Vec<u8>)>,
+) -> Result<(Vec<u8>, serde_json::Value), String> {
+    let decoded: Vec<(String, Canvas)> = flags
+        .into_iter()
+        .filter_map(|(name, bytes)| {
+            let img = image::load_from_memory(&bytes).ok()?;
+            Some((name, img.resize(SPRITE_CELL.0, SPRITE_CELL.1, FilterType::Triangle).to_rgba8()))
+        })
+        .collect();
+
+    let (cw, ch) = SPRITE_CELL;
+    let n = decoded.len() as u32;
+    let cols = n.clamp(1, SPRITE_COLUMNS);
+    let rows = n.div_ceil(cols).max(1);
+    let mut sheet: Canvas = ImageBuffer::from_pixel(cols * cw, rows * ch, Rgba([0, 0, 0, 0]));
+
+    let mut frames = serde_json::Map::new();
+    for (i, (name, flag)) in decoded.iter().enumerate() {
+        let (x, y) = ((i as u32 % cols) * cw, (i as u32 / cols) * ch);
+        image::imageops::overlay(&mut sheet, flag, x as i64, y as i64);
+        frames.insert(
+            name.clone(),
+            serde_json::json!({ "x": x, "y": y, "w": flag.width(), "h": flag.height() }),
+        );
+    }
+
+    let map = serde_json::json!({
+        "width": sheet.width(),
+        "height": sheet.height(),
+        "cell": { "w": cw, "h": ch },
+        "frames": frames,
+    });
+    Ok((encode_png(&sheet)?, map))
+}
+
+// --- SVG output: same layouts as the PNGs, but text stays text ---
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn svg_text(x: i32, y: i32, size: f32, text: &str) -> String {
+    // PNG text is positioned by its top edge; SVG by its baseline
+    format!(
+        r#"<text x="{}" y="{}" font-size="{}">{}</text>"#,
+        x,
+        y as f32 + size * 0.8,
+        size,
+        xml_escape(text)
+    )
+}
+
+fn svg_document((width, height): (u32, u32), brand: &Branding, body: &str) -> String {
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="DejaVu Sans, sans-serif" fill="{}"><rect width="100%" height="100%" fill="{}"/>{}</svg>"#,
+        hex(brand.text),
+        hex(brand.background),
+        body,
+        w = width,
+        h = height,
+    )
+}
+
+/// Renders the summary (total + top list) as a standalone SVG document.
+/// Colors and title follow [`Branding`]; the logo and custom font are PNG-only. Lines are
+/// wrapped as in the PNG, measured with the branding font, which viewers may substitute.
+pub async fn build_summary_svg(
+    pool: &DbPool,
+    lang: Lang,
+    brand: &Branding,
+    ranking: Ranking,
+) -> Result<String, String> {
+    let lines = summary_lines(pool, lang, ranking).await?;
+    let (layout, size) = summary_layout(&lines, brand, false);
+
+    let mut body = String::new();
+    for line in &layout.lines {
+        let text = svg_text(line.x, line.y, line.size, &line.text);
+        if is_title(line, brand) {
+            body.push_str(&format!(r#"<g fill="{}">{}</g>"#, hex(brand.accent), text));
+        } else {
+            body.push_str(&text);
+        }
+    }
+
+    Ok(svg_document(size, brand, &body))
+}
+
+/// SVG version of [`build_country_card`]. The flag is referenced by URL instead of
+/// being downloaded and rasterized.
+pub fn build_country_card_svg(
+    country: &Country,
+    region_avg_gdp: Option<f64>,
+    lang: Lang,
+    brand: &Branding,
+) -> String {
+    let mut body = String::new();
+
+    let mut text_x = 24i32;
+    if let Some(url) = country.flag_url.as_deref() {
+        body.push_str(&format!(
+            r#"<image href="{}" x="24" y="24" width="120" height="80" preserveAspectRatio="xMinYMin meet"/>"#,
+            xml_escape(url)
+        ));
+        text_x = 24 + 120 + 24;
+    }
+
+    body.push_str(&svg_text(text_x, 24, 32.0, &country.name));
+
+    let mut y = 68i32;
+    for line in &card_lines(country, lang) {
+        body.push_str(&svg_text(text_x, y, 20.0, line));
+        y += 26;
+    }
+
+    let gdp = country.estimated_gdp.unwrap_or(0.0);
+    let avg = region_avg_gdp.unwrap_or(0.0);
+    let max = gdp.max(avg);
+    let bar_max = (CARD_SIZE.0 - 48 - 160) as f64;
+    let bars = [
+        (lang.label(Label::EstimatedGdp), gdp, hex(brand.accent)),
+        (lang.label(Label::RegionAverage), avg, hex(brand.muted)),
+    ];
+    let mut y = 220i32;
+    for (label, value, color) in bars {
+        body.push_str(&svg_text(24, y + 2, 18.0, label));
+        let len = if max > 0.0 { ((value / max) * bar_max).round() } else { 0.0 };
+        body.push_str(&format!(
+            r#"<rect x="160" y="{}" width="{}" height="24" fill="{}"/>"#,
+            y, len, color
+        ));
+        body.push_str(&svg_text(168, y + 30, 14.0, &lang.format_num(value, 2)));
+        y += 48;
+    }
+
+    svg_document(CARD_SIZE, brand, &body)
+}
@@ -0,0 +1,277 @@
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::utils::error::ApiError;
+use crate::utils::i18n::Lang;
+use crate::utils::single_flight::SingleFlight;
+
+/// On-disk cache of rendered image variants (per-country cards, SVGs, localized summaries).
+///
+/// File names start with the data version (the last refresh timestamp), so a refresh
+/// implicitly invalidates every variant; [`ImageCache::gc`] then deletes the old files.
+#[derive(Clone)]
+pub struct ImageCache {
+    dir: PathBuf,
+    /// Prefix of `Branding::fingerprint`, so a restart with a new theme doesn't serve old renders
+    theme: String,
+    /// Concurrent misses on one variant render it once (e.g. right after a refresh)
+    renders: SingleFlight<PathBuf, Result<Vec<u8>, ApiError>>,
+    /// Lookups since startup, for `/admin/cache`
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+/// What `/admin/cache` reports.
+#[derive(Debug, serde::Serialize)]
+pub struct CacheStats {
+    pub dir: String,
+    pub entries: usize,
+    pub bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    /// Newest first, at most 100
+    pub files: Vec<CacheEntry>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct CacheEntry {
+    pub name: String,
+    pub bytes: u64,
+    pub modified_at: Option<String>,
+}
+
+pub struct VariantKey<'a> {
+    /// Last refresh timestamp (any string; sanitized for the file name)
+    pub version: &'a str,
+    /// What was rendered, e.g. "summary" or a country name
+    pub subject: &'a str,
+    pub width: u32,
+    pub height: u32,
+    pub lang: Lang,
+    /// File extension: "png" | "svg"
+    pub format: &'a str,
+}
+
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect()
+}
+
+/// First 4 bytes of the SHA-256, as hex: stable across builds, unlike `std`'s hasher,
+/// so cached file names survive an upgrade.
+fn short_hash(s: &str) -> String {
+    hex::encode(&Sha256::digest(s.as_bytes())[..4])
+}
+
+impl ImageCache {
+    /// `fingerprint` is the active `Branding::fingerprint`
+    pub fn new(dir: PathBuf, fingerprint: &str) -> Self {
+        Self {
+            dir,
+            theme: fingerprint.chars().take(8).collect(),
+            renders: SingleFlight::default(),
+            hits: Arc::default(),
+            misses: Arc::default(),
+        }
+    }
+
+    fn path(&self, key: &VariantKey<'_>) -> PathBuf {
+        // Subjects can be arbitrary country names: keep a readable slug, disambiguate by hash
+        let subject = key.subject.to_lowercase();
+        let file = format!(
+            "{}_{}-{}_{}x{}_{}_{}.{}",
+            sanitize(key.version),
+            sanitize(&subject).chars().take(40).collect::<String>(),
+            short_hash(&subject),
+            key.width,
+            key.height,
+            key.lang.code(),
+            self.theme,
+            key.format,
+        );
+        self.dir.join(file)
+    }
+
+    pub async fn get(&self, key: &VariantKey<'_>) -> Option<Vec<u8>> {
+        let bytes = tokio::fs::read(self.path(key)).await.ok();
+        let counter = if bytes.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        bytes
+    }
+
+    /// Cached bytes, or `render` them and cache the result. Concurrent callers for the
+    /// same variant share one render.
+    pub async fn get_or_render<F, Fut>(&self, key: &VariantKey<'_>, render: F) -> Result<Vec<u8>, ApiError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Vec<u8>, ApiError>>,
+    {
+        if let Some(bytes) = self.get(key).await {
+            return Ok(bytes);
+        }
+        self.renders
+            .run(self.path(key), || async {
+                let bytes = render().await?;
+                self.put(key, &bytes).await;
+                Ok(bytes)
+            })
+            .await
+    }
+
+    /// Best effort: a failed write only costs a re-render next time.
+    pub async fn put(&self, key: &VariantKey<'_>, bytes: &[u8]) {
+        let path = self.path(key);
+        if let Err(e) = tokio::fs::create_dir_all(&self.dir).await {
+            warn!("image cache dir {} unavailable: {}", self.dir.display(), e);
+            return;
+        }
+        // Write + rename so concurrent readers never see a partial file
+        let tmp = path.with_extension("tmp");
+        let res = match tokio::fs::write(&tmp, bytes).await {
+            Ok(()) => tokio::fs::rename(&tmp, &path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            warn!("image cache write {} failed: {}", path.display(), e);
+            tokio::fs::remove_file(&tmp).await.ok();
+        }
+    }
+
+    /// Lists the cached variants with hit/miss counters. A missing directory is an empty cache.
+    pub async fn stats(&self) -> CacheStats {
+        let mut files = Vec::new();
+        if let Ok(mut dir) = tokio::fs::read_dir(&self.dir).await {
+            while let Ok(Some(entry)) = dir.next_entry().await {
+                let Ok(meta) = entry.metadata().await else { continue };
+                if !meta.is_file() {
+                    continue;
+                }
+                let modified = meta.modified().ok();
+                files.push((modified, CacheEntry {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    bytes: meta.len(),
+                    modified_at: modified.map(|t| {
+                        chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+                    }),
+                }));
+            }
+        }
+        files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+        CacheStats {
+            dir: self.dir.display().to_string(),
+            entries: files.len(),
+            bytes: files.iter().map(|(_, f)| f.bytes).sum(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            files: files.into_iter().take(100).map(|(_, f)| f).collect(),
+        }
+    }
+
+    /// Deletes every cached variant; the next request for each re-renders it.
+    pub async fn clear(&self) -> usize {
+        let Ok(mut dir) = tokio::fs::read_dir(&self.dir).await else {
+            return 0;
+        };
+        let mut removed = 0;
+        while let Ok(Some(entry)) = dir.next_entry().await {
+            if tokio::fs::remove_file(entry.path()).await.is_ok() {
+                removed += 1;
+            }
+        }
+        info!("image cache: cleared {} variant(s)", removed);
+        removed
+    }
+
+    /// Deletes every cached variant that doesn't belong to `version`. Returns how many were removed.
+    pub async fn gc(&self, version: &str) -> usize {
+        let prefix = format!("{}_", sanitize(version));
+        let Ok(mut dir) = tokio::fs::read_dir(&self.dir).await else {
+            return 0;
+        };
+        let mut removed = 0;
+        while let Ok(Some(entry)) = dir.next_entry().await {
+            let name = entry.file_name();
+            if name.to_string_lossy().starts_with(&prefix) {
+                continue;
+            }
+            if tokio::fs::remove_file(entry.path()).await.is_ok() {
+                removed += 1;
+            }
+        }
+        if removed > 0 {
+            info!("image cache: removed {} stale variant(s)", removed);
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::image::theme_field;
+
+    fn key<'a>(subject: &'a str, format: &'a str) -> VariantKey<'a> {
+        VariantKey { version: "2024-01-01T00:00:00Z", subject, width: 1200, height: 630, lang: Lang::En, format }
+    }
+
+    #[test]
+    fn file_names_are_stable_and_theme_scoped() {
+        let cache = ImageCache::new(PathBuf::from("v"), "0123456789abcdef");
+        // Pinned: a changed name would orphan every cached variant after an upgrade
+        assert_eq!(short_hash("ghana"), "fc4f9c94");
+        assert_eq!(
+            cache.path(&key("Ghana", "png")),
+            PathBuf::from("v/2024-01-01t00-00-00z_ghana-fc4f9c94_1200x630_en_01234567.png")
+        );
+        // Case-insensitive subject, distinct format and theme
+        assert_eq!(cache.path(&key("GHANA", "png")), cache.path(&key("ghana", "png")));
+        assert_ne!(cache.path(&key("ghana", "png")), cache.path(&key("ghana", "svg")));
+        let other = ImageCache::new(PathBuf::from("v"), "fedcba9876543210");
+        assert_ne!(cache.path(&key("ghana", "png")), other.path(&key("ghana", "png")));
+    }
+
+    #[test]
+    fn theme_fields_are_unambiguous() {
+        let digest = |fields: &[(&str, &[u8])]| {
+            let mut h = Sha256::new();
+            for (label, bytes) in fields {
+                theme_field(&mut h, label, bytes);
+            }
+            hex::encode(h.finalize())
+        };
+        let fields: &[(&str, &[u8])] = &[("title", b"Report"), ("accent", &[37, 99, 235, 255])];
+        assert_eq!(digest(fields), digest(fields));
+        // The same bytes split differently between fields, or under another label, differ
+        assert_ne!(digest(&[("title", b"ab"), ("muted", b"c")]), digest(&[("title", b"a"), ("muted", b"bc")]));
+        assert_ne!(digest(&[("text", b"x")]), digest(&[("title", b"x")]));
+    }
+
+    #[tokio::test]
+    async fn renders_once_then_serves_from_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ImageCache::new(dir.path().join("variants"), "0123456789abcdef");
+        let renders = AtomicU64::new(0);
+        for _ in 0..3 {
+            let bytes = cache
+                .get_or_render(&key("ghana", "png"), || async {
+                    renders.fetch_add(1, Ordering::SeqCst);
+                    Ok(b"png".to_vec())
+                })
+                .await
+                .unwrap();
+            assert_eq!(bytes, b"png");
+        }
+        assert_eq!(renders.load(Ordering::SeqCst), 1);
+        let stats = cache.stats().await;
+        assert_eq!((stats.entries, stats.hits, stats.misses), (1, 2, 1));
+
+        assert_eq!(cache.gc("2024-01-01T00:00:00Z").await, 0);
+        assert_eq!(cache.gc("2024-02-01T00:00:00Z").await, 1);
+        assert!(cache.get(&key("ghana", "png")).await.is_none());
+    }
+}
@@ -0,0 +1,63 @@
+// Shared by the middlewares that reshape JSON responses on the way out (key casing,
+// envelope): buffer the body, parse, transform, re-encode.
+
+use axum::{
+    body::{to_bytes, Body},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+
+use crate::utils::error::ApiError;
+use crate::utils::server_timing::{self, Metric};
+
+pub fn is_json(res: &Response) -> bool {
+    res.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"))
+}
+
+/// Applies `f` to an `application/json` body. Other content types, and bodies that don't
+/// parse, pass through unchanged; the re-encoding counts as serialization time.
+pub async fn rewrite_json_body(res: Response, f: impl FnOnce(Value) -> Value) -> Response {
+    if !is_json(&res) {
+        return res;
+    }
+    let (mut parts, body) = res.into_parts();
+    let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+        return ApiError::Internal("could not buffer response body".into()).into_response();
+    };
+    let body = server_timing::measure(Metric::Serialize, || match serde_json::from_slice::<Value>(&bytes) {
+        Ok(v) => serde_json::to_vec(&f(v)).unwrap_or_else(|_| bytes.to_vec()),
+        Err(_) => bytes.to_vec(),
+    });
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn only_json_bodies_are_rewritten() {
+        let add_flag = |mut v: Value| {
+            v["seen"] = json!(true);
+            v
+        };
+        let res = rewrite_json_body(axum::Json(json!({ "a": 1 })).into_response(), add_flag).await;
+        assert!(res.headers().get(header::CONTENT_LENGTH).is_none());
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), json!({ "a": 1, "seen": true }));
+
+        let res = rewrite_json_body("plain".into_response(), add_flag).await;
+        assert_eq!(to_bytes(res.into_body(), usize::MAX).await.unwrap(), "plain");
+
+        // Labelled JSON but not parseable: sent as is
+        let res = ([(header::CONTENT_TYPE, "application/json")], "{oops").into_response();
+        let res = rewrite_json_body(res, add_flag).await;
+        assert_eq!(to_bytes(res.into_body(), usize::MAX).await.unwrap(), "{oops");
+    }
+}
@@ -0,0 +1,61 @@
+// JSON:API representation (https://jsonapi.org), chosen with `Accept: application/vnd.api+json`.
+// Only the country resources speak it; everything else stays plain JSON.
+
+use axum::{
+    http::{header, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+    Json,
+};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde_json::{json, Value};
+
+use crate::models::country::Country;
+use crate::routes::paths;
+
+pub const MEDIA_TYPE: &str = "application/vnd.api+json";
+
+pub fn wants_jsonapi(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|m| m.trim().starts_with(MEDIA_TYPE))
+}
+
+pub fn encode(s: &str) -> String {
+    utf8_percent_encode(s, NON_ALPHANUMERIC).to_string()
+}
+
+pub fn country_resource(c: &Country) -> Value {
+    let mut attributes = serde_json::to_value(c).unwrap_or_else(|_| json!({}));
+    if let Some(map) = attributes.as_object_mut() {
+        map.remove("id");
+    }
+    let currency = c
+        .currency_code
+        .as_ref()
+        .map(|code| json!({ "type": "currencies", "id": code }));
+    let region = c
+        .region
+        .as_ref()
+        .map(|r| json!({ "type": "regions", "id": r }));
+    json!({
+        "type": "countries",
+        "id": c.id.to_string(),
+        "attributes": attributes,
+        "relationships": {
+            "currency": { "data": currency },
+            "region": { "data": region },
+        },
+        "links": { "self": paths::link(paths::COUNTRY, &[("name", &c.name)]) },
+    })
+}
+
+/// Wraps a top-level document with the JSON:API media type.
+pub fn document(doc: Value) -> Response {
+    let mut res = Json(doc).into_response();
+    res.headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(MEDIA_TYPE));
+    res
+}
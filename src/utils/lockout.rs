@@ -0,0 +1,253 @@
+// Brute-force protection for `ADMIN_TOKEN` and `API_KEYS`. Failed authentications are
+// counted per client address (`401`s and `403`s) and per API key (`403`s: a valid key
+// calling routes outside its scopes). `AUTH_MAX_FAILURES` within
+// `AUTH_FAILURE_WINDOW_SECS` locks the address or key out for `AUTH_LOCKOUT_SECS`,
+// doubling with every further lockout up to `AUTH_LOCKOUT_MAX_SECS`. A locked-out
+// caller gets `429` even with the right token. Each lockout is written to `audit_log`.
+//
+// State is per process: with several replicas each one counts on its own.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{error, warn};
+
+use crate::config::AppState;
+
+/// Entries kept before idle ones are dropped
+const MAX_TRACKED: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockoutPolicy {
+    pub max_failures: u32,
+    pub window: Duration,
+    pub base: Duration,
+    pub max: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Subject {
+    Ip(IpAddr),
+    /// An API key, by name
+    Key(String),
+}
+
+impl Subject {
+    fn label(&self) -> String {
+        match self {
+            Subject::Ip(ip) => format!("ip:{}", ip),
+            Subject::Key(name) => format!("key:{}", name),
+        }
+    }
+}
+
+struct Entry {
+    failures: u32,
+    window_start: Instant,
+    locked_until: Option<Instant>,
+    /// Lockouts so far; the next one lasts `base * 2^level`
+    level: u32,
+    last_lockout: Option<Instant>,
+}
+
+/// A lockout that just started.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lockout {
+    pub subject: Subject,
+    pub failures: u32,
+    pub duration: Duration,
+    /// Start of the presented token, so an old or leaked key can be recognised
+    pub token_prefix: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct AuthLockout {
+    policy: LockoutPolicy,
+    entries: Arc<Mutex<HashMap<Subject, Entry>>>,
+}
+
+impl AuthLockout {
+    pub fn new(policy: LockoutPolicy) -> Self {
+        Self { policy, entries: Arc::default() }
+    }
+
+    /// Time left on the longest lockout among `subjects`, if any.
+    pub fn check(&self, subjects: &[Subject], now: Instant) -> Option<Duration> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        subjects
+            .iter()
+            .filter_map(|s| entries.get(s)?.locked_until)
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+            .max()
+    }
+
+    /// Counts one failure against each subject; returns the lockouts it started.
+    pub fn fail(&self, subjects: &[Subject], token: &str, now: Instant) -> Vec<Lockout> {
+        if self.policy.max_failures == 0 {
+            return Vec::new();
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= MAX_TRACKED {
+            let window = self.policy.window.max(self.policy.max);
+            entries.retain(|_, e| {
+                e.locked_until.is_some_and(|u| u > now) || now.duration_since(e.window_start) < window
+            });
+        }
+        let mut started = Vec::new();
+        for subject in subjects {
+            let e = entries.entry(subject.clone()).or_insert(Entry {
+                failures: 0,
+                window_start: now,
+                locked_until: None,
+                level: 0,
+                last_lockout: None,
+            });
+            if now.duration_since(e.window_start) >= self.policy.window {
+                e.failures = 0;
+                e.window_start = now;
+            }
+            // Escalation is forgotten after a quiet spell as long as the longest lockout
+            if e.last_lockout.is_some_and(|t| now.duration_since(t) >= self.policy.max * 2) {
+                e.level = 0;
+            }
+            e.failures += 1;
+            if e.failures >= self.policy.max_failures {
+                let duration = self.policy.base.saturating_mul(1u32 << e.level.min(16)).min(self.policy.max);
+                started.push(Lockout {
+                    subject: subject.clone(),
+                    failures: e.failures,
+                    duration,
+                    token_prefix: (!token.is_empty()).then(|| token.chars().take(6).collect()),
+                });
+                e.locked_until = Some(now + duration);
+                e.last_lockout = Some(now);
+                e.level += 1;
+                e.failures = 0;
+                e.window_start = now;
+            }
+        }
+        started
+    }
+
+    /// A successful authentication from `ip` clears its failure count.
+    pub fn succeed(&self, ip: IpAddr) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(e) = entries.get_mut(&Subject::Ip(ip)) {
+            e.failures = 0;
+        }
+    }
+}
+
+/// Counts a failed authentication and records any lockout it starts. Best effort: the
+/// audit row is written in the background.
+pub fn record_failure(state: &AppState, ip: Option<IpAddr>, key: Option<&str>, token: &str) {
+    let subjects: Vec<Subject> = ip.map(Subject::Ip).into_iter().chain(key.map(|k| Subject::Key(k.to_string()))).collect();
+    for lockout in state.auth_lockout.fail(&subjects, token, Instant::now()) {
+        warn!(
+            subject = %lockout.subject.label(),
+            failures = lockout.failures,
+            lockout_secs = lockout.duration.as_secs(),
+            "authentication locked out"
+        );
+        let pool = state.pool.clone();
+        let client_ip = ip.map(|ip| ip.to_string());
+        tokio::spawn(async move {
+            let snapshot = serde_json::json!({
+                "failures": lockout.failures,
+                "lockout_secs": lockout.duration.as_secs(),
+                "token_prefix": lockout.token_prefix,
+            });
+            let res = sqlx::query(
+                "INSERT INTO audit_log (action, subject, client_ip, snapshot) VALUES ('auth.locked_out', ?, ?, ?)",
+            )
+            .bind(lockout.subject.label())
+            .bind(client_ip)
+            .bind(snapshot.to_string())
+            .execute(&pool)
+            .await;
+            if let Err(e) = res {
+                error!("could not audit auth lockout: {}", e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lockout() -> AuthLockout {
+        AuthLockout::new(LockoutPolicy {
+            max_failures: 3,
+            window: Duration::from_secs(60),
+            base: Duration::from_secs(10),
+            max: Duration::from_secs(35),
+        })
+    }
+
+    fn ip(s: &str) -> Subject {
+        Subject::Ip(s.parse().unwrap())
+    }
+
+    #[test]
+    fn locks_after_max_failures_with_escalating_backoff() {
+        let l = lockout();
+        let a = [ip("203.0.113.1")];
+        let t = Instant::now();
+        assert!(l.fail(&a, "abcdefgh", t).is_empty());
+        assert!(l.fail(&a, "abcdefgh", t).is_empty());
+        let started = l.fail(&a, "abcdefgh", t);
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].duration, Duration::from_secs(10));
+        assert_eq!(started[0].token_prefix.as_deref(), Some("abcdef"));
+        assert_eq!(l.check(&a, t), Some(Duration::from_secs(10)));
+        assert_eq!(l.check(&[ip("203.0.113.2")], t), None);
+
+        let t = t + Duration::from_secs(11);
+        assert_eq!(l.check(&a, t), None);
+        l.fail(&a, "", t);
+        l.fail(&a, "", t);
+        assert_eq!(l.fail(&a, "", t)[0].duration, Duration::from_secs(20));
+        let t = t + Duration::from_secs(21);
+        l.fail(&a, "", t);
+        l.fail(&a, "", t);
+        assert_eq!(l.fail(&a, "", t)[0].duration, Duration::from_secs(35));
+
+        // Quiet for twice the longest lockout: back to the base duration
+        let t = t + Duration::from_secs(71);
+        l.fail(&a, "", t);
+        l.fail(&a, "", t);
+        assert_eq!(l.fail(&a, "", t)[0].duration, Duration::from_secs(10));
+    }
+
+    #[test]
+    fn failures_expire_and_success_resets() {
+        let l = lockout();
+        let a = [ip("203.0.113.1")];
+        let t = Instant::now();
+        l.fail(&a, "", t);
+        l.fail(&a, "", t);
+        assert!(l.fail(&a, "", t + Duration::from_secs(61)).is_empty());
+
+        l.fail(&a, "", t + Duration::from_secs(62));
+        l.succeed("203.0.113.1".parse().unwrap());
+        assert!(l.fail(&a, "", t + Duration::from_secs(63)).is_empty());
+    }
+
+    #[test]
+    fn ip_and_key_are_counted_apart() {
+        let l = lockout();
+        let t = Instant::now();
+        let key = Subject::Key("analytics".into());
+        for i in 1..=3 {
+            l.fail(&[ip(&format!("198.51.100.{}", i)), key.clone()], "", t);
+        }
+        assert!(l.check(&[key], t).is_some());
+        assert!(l.check(&[ip("198.51.100.1")], t).is_none());
+        let off = AuthLockout::new(LockoutPolicy { max_failures: 0, ..lockout().policy });
+        assert!((0..10).all(|_| off.fail(&[ip("198.51.100.1")], "", t).is_empty()));
+    }
+}
@@ -0,0 +1,429 @@
+use image::{ImageBuffer, Rgba};
+use imageproc::drawing::{draw_filled_rect_mut, draw_hollow_polygon_mut, draw_polygon_mut, draw_text_mut};
+use imageproc::point::Point;
+use imageproc::rect::Rect;
+use serde_json::Value;
+use sqlx::Row;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::db::{Db, DbPool};
+use crate::utils::i18n::Lang;
+use crate::utils::image::{draw_logo, encode_png, Branding, Canvas};
+
+// Choropleth map for `GET /map`, shaded by quantile of the metric.
+// No geometry ships with the binary. With `MAP_GEOJSON_PATH` set (a country
+// FeatureCollection such as Natural Earth's admin-0 file) countries are drawn as
+// their shapes, matched on ISO alpha-2 code. Without it, or when no country matches,
+// the map is a tile-grid cartogram: one tile per country, a column per region.
+
+pub const MAP_SIZE: (u32, u32) = (1200, 720);
+const TILE: (u32, u32) = (44, 28);
+const GAP: u32 = 4;
+const CLASSES: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MapMetric {
+    EstimatedGdp,
+    Population,
+}
+
+impl MapMetric {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "estimated_gdp" => Some(MapMetric::EstimatedGdp),
+            "population" => Some(MapMetric::Population),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MapMetric::EstimatedGdp => "estimated_gdp",
+            MapMetric::Population => "population",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            MapMetric::EstimatedGdp => "Estimated GDP",
+            MapMetric::Population => "Population",
+        }
+    }
+}
+
+struct Tile {
+    name: String,
+    iso_code: Option<String>,
+    value: Option<f64>,
+}
+
+/// A ring of (longitude, latitude) points
+type Ring = Vec<(f64, f64)>;
+
+/// Country outlines from `MAP_GEOJSON_PATH`, keyed by upper-case ISO alpha-2 code.
+/// Only outer rings are kept; holes (lakes, enclaves) are filled over.
+#[derive(Debug, Default)]
+pub struct MapShapes {
+    by_code: HashMap<String, Vec<Ring>>,
+}
+
+/// Feature properties tried in order for the ISO alpha-2 code. Natural Earth puts
+/// `-99` in `ISO_A2` for a few countries (France, Norway) and the real code in `ISO_A2_EH`.
+const CODE_PROPERTIES: [&str; 5] = ["ISO_A2_EH", "ISO_A2", "iso_a2", "iso_code", "cca2"];
+
+impl MapShapes {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let doc: Value = serde_json::from_str(raw).map_err(|e| e.to_string())?;
+        let features = doc
+            .get("features")
+            .and_then(Value::as_array)
+            .ok_or("expected a GeoJSON FeatureCollection")?;
+        let mut by_code: HashMap<String, Vec<Ring>> = HashMap::new();
+        for f in features {
+            let Some(code) = feature_code(f) else { continue };
+            let rings = outer_rings(f.get("geometry").unwrap_or(&Value::Null));
+            if !rings.is_empty() {
+                by_code.entry(code).or_default().extend(rings);
+            }
+        }
+        if by_code.is_empty() {
+            return Err("no feature has an ISO alpha-2 code and a (Multi)Polygon geometry".into());
+        }
+        Ok(Self { by_code })
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_code.len()
+    }
+
+    fn get(&self, iso_code: Option<&str>) -> Option<&[Ring]> {
+        self.by_code.get(&iso_code?.to_ascii_uppercase()).map(Vec::as_slice)
+    }
+}
+
+fn feature_code(f: &Value) -> Option<String> {
+    let props = f.get("properties")?;
+    CODE_PROPERTIES.iter().find_map(|k| {
+        let c = props.get(*k)?.as_str()?.trim();
+        (c.len() == 2 && c.chars().all(|ch| ch.is_ascii_alphabetic())).then(|| c.to_ascii_uppercase())
+    })
+}
+
+fn outer_rings(geometry: &Value) -> Vec<Ring> {
+    let ring = |v: &Value| -> Option<Ring> {
+        v.as_array()?
+            .iter()
+            .map(|p| Some((p.get(0)?.as_f64()?, p.get(1)?.as_f64()?)))
+            .collect()
+    };
+    let coords = geometry.get("coordinates");
+    let polygons: Vec<&Value> = match (geometry.get("type").and_then(Value::as_str), coords) {
+        (Some("Polygon"), Some(c)) => vec![c],
+        (Some("MultiPolygon"), Some(Value::Array(ps))) => ps.iter().collect(),
+        _ => return Vec::new(),
+    };
+    polygons.into_iter().filter_map(|p| ring(p.get(0)?)).filter(|r| r.len() >= 3).collect()
+}
+
+/// Equirectangular projection of `bbox` (min lon, min lat, max lon, max lat) into the
+/// pixel `frame` (x, y, w, h), keeping the aspect ratio and centring the result.
+struct Projection {
+    scale: f64,
+    x: f64,
+    y: f64,
+    max_lat: f64,
+    min_lon: f64,
+}
+
+impl Projection {
+    fn fit(bbox: (f64, f64, f64, f64), frame: (f64, f64, f64, f64)) -> Self {
+        let (min_lon, min_lat, max_lon, max_lat) = bbox;
+        let (fx, fy, fw, fh) = frame;
+        let (dlon, dlat) = ((max_lon - min_lon).max(1e-6), (max_lat - min_lat).max(1e-6));
+        let scale = (fw / dlon).min(fh / dlat);
+        Self {
+            scale,
+            x: fx + (fw - dlon * scale) / 2.0,
+            y: fy + (fh - dlat * scale) / 2.0,
+            max_lat,
+            min_lon,
+        }
+    }
+
+    fn project(&self, (lon, lat): (f64, f64)) -> (f64, f64) {
+        (self.x + (lon - self.min_lon) * self.scale, self.y + (self.max_lat - lat) * self.scale)
+    }
+
+    /// Pixel outline of a ring, or `None` when it collapses below a triangle at this scale
+    fn outline(&self, ring: &[(f64, f64)]) -> Option<Vec<Point<i32>>> {
+        let mut pts: Vec<Point<i32>> = Vec::with_capacity(ring.len());
+        for p in ring {
+            let (x, y) = self.project(*p);
+            let pt = Point::new(x.round() as i32, y.round() as i32);
+            if pts.last() != Some(&pt) {
+                pts.push(pt);
+            }
+        }
+        // GeoJSON rings are closed; imageproc wants them open
+        while pts.len() > 1 && pts.first() == pts.last() {
+            pts.pop();
+        }
+        (pts.len() >= 3).then_some(pts)
+    }
+}
+
+/// Drawn part of the world: Antarctica is left out, it would take a third of the height
+const WORLD: (f64, f64, f64, f64) = (-180.0, -60.0, 180.0, 85.0);
+
+fn bbox<'a>(rings: impl Iterator<Item = &'a Ring>) -> Option<(f64, f64, f64, f64)> {
+    let mut b: Option<(f64, f64, f64, f64)> = None;
+    for &(lon, lat) in rings.flatten() {
+        let (lon, lat) = (lon.clamp(WORLD.0, WORLD.2), lat.clamp(WORLD.1, WORLD.3));
+        b = Some(match b {
+            None => (lon, lat, lon, lat),
+            Some((a, c, d, e)) => (a.min(lon), c.min(lat), d.max(lon), e.max(lat)),
+        });
+    }
+    b
+}
+
+/// Blend from `from` to `to`; t in 0..=1
+fn mix(from: Rgba<u8>, to: Rgba<u8>, t: f64) -> Rgba<u8> {
+    let ch = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+    Rgba([ch(from[0], to[0]), ch(from[1], to[1]), ch(from[2], to[2]), 255])
+}
+
+/// Upper bounds of each quantile class over the non-null values (ascending).
+fn class_breaks(values: &mut [f64]) -> Vec<f64> {
+    values.sort_by(|a, b| a.total_cmp(b));
+    if values.is_empty() {
+        return Vec::new();
+    }
+    (1..=CLASSES)
+        .map(|k| values[(k * values.len()).div_ceil(CLASSES).saturating_sub(1)])
+        .collect()
+}
+
+fn class_of(v: f64, breaks: &[f64]) -> usize {
+    breaks.iter().position(|b| v <= *b).unwrap_or(CLASSES - 1)
+}
+
+pub async fn build_map_png(
+    pool: &DbPool,
+    metric: MapMetric,
+    region: Option<&str>,
+    brand: &Branding,
+) -> Result<Vec<u8>, String> {
+    // `metric` is a closed enum, so interpolating its column name is safe
+    let mut qb = sqlx::QueryBuilder::<Db>::new(format!(
+        "SELECT name, iso_code, COALESCE(region, 'Unknown') AS region, CAST({} AS DOUBLE) AS v \
+         FROM countries WHERE 1=1",
+        metric.as_str()
+    ));
+    if let Some(r) = region {
+        qb.push(" AND region = ").push_bind(r.to_string());
+    }
+    qb.push(" ORDER BY region ASC, name ASC");
+    let rows = qb.build().fetch_all(pool).await.map_err(|e| e.to_string())?;
+
+    let mut by_region: BTreeMap<String, Vec<Tile>> = BTreeMap::new();
+    for r in &rows {
+        by_region
+            .entry(r.try_get::<String, _>("region").unwrap_or_default())
+            .or_default()
+            .push(Tile {
+                name: r.try_get::<String, _>("name").unwrap_or_default(),
+                iso_code: r.try_get::<Option<String>, _>("iso_code").ok().flatten(),
+                value: r.try_get::<Option<f64>, _>("v").ok().flatten(),
+            });
+    }
+    let mut values: Vec<f64> = by_region
+        .values()
+        .flatten()
+        .filter_map(|t| t.value)
+        .collect();
+    let breaks = class_breaks(&mut values);
+
+    let brand = brand.clone();
+    tokio::task::spawn_blocking(move || {
+        let (width, height) = MAP_SIZE;
+        let mut img: Canvas = ImageBuffer::from_pixel(width, height, brand.background);
+        draw_logo(&mut img, &brand);
+        let font = &brand.font;
+        let lang = Lang::En;
+
+        let title = match &brand.title {
+            Some(t) => format!("{} — {}", t, metric.title()),
+            None => metric.title().to_string(),
+        };
+        draw_text_mut(&mut img, brand.text, 24, 20, 30.0, font, &title);
+
+        let shade = |v: Option<f64>| match v {
+            Some(v) => {
+                let k = class_of(v, &breaks);
+                mix(brand.background, brand.accent, (k + 1) as f64 / CLASSES as f64)
+            }
+            None => brand.muted,
+        };
+
+        let top = 72u32;
+        let legend_h = 64u32;
+        let missing = match brand.map_shapes.as_deref() {
+            Some(shapes) => draw_shapes(&mut img, shapes, &by_region, &shade, (top, legend_h), &brand),
+            None => None,
+        };
+        if missing.is_none() {
+            draw_tiles(&mut img, &by_region, &shade, (top, legend_h), &brand);
+        }
+
+        // Legend: class swatches with their upper bounds, then "no data"
+        let ly = (height - legend_h + 16) as i32;
+        let mut lx = 24i32;
+        for (k, b) in breaks.iter().enumerate() {
+            let c = mix(brand.background, brand.accent, (k + 1) as f64 / CLASSES as f64);
+            draw_filled_rect_mut(&mut img, Rect::at(lx, ly).of_size(24, 16), c);
+            draw_text_mut(&mut img, brand.text, lx + 30, ly, 14.0, font, &format!("≤ {}", lang.format_num(*b, 0)));
+            lx += 200;
+        }
+        draw_filled_rect_mut(&mut img, Rect::at(lx, ly).of_size(24, 16), brand.muted);
+        draw_text_mut(&mut img, brand.text, lx + 30, ly, 14.0, font, "no data");
+        if let Some(n) = missing.filter(|n| *n > 0) {
+            let note = format!("{} without geometry not shown", n);
+            draw_text_mut(&mut img, brand.muted, 24, ly + 24, 13.0, font, &note);
+        }
+
+        encode_png(&img)
+    })
+    .await
+    .map_err(|e| format!("spawn failed: {:?}", e))?
+}
+
+/// Fills each country with a shape, fitted to the countries drawn (so `?region=` zooms in).
+/// Returns how many countries had no shape, or `None` when none had one.
+fn draw_shapes(
+    img: &mut Canvas,
+    shapes: &MapShapes,
+    by_region: &BTreeMap<String, Vec<Tile>>,
+    shade: &dyn Fn(Option<f64>) -> Rgba<u8>,
+    (top, legend_h): (u32, u32),
+    brand: &Branding,
+) -> Option<usize> {
+    let tiles: Vec<&Tile> = by_region.values().flatten().collect();
+    let drawn: Vec<(&Tile, &[Ring])> =
+        tiles.iter().filter_map(|t| Some((*t, shapes.get(t.iso_code.as_deref())?))).collect();
+    let bbox = bbox(drawn.iter().flat_map(|(_, rings)| rings.iter()))?;
+
+    let (width, height) = MAP_SIZE;
+    let frame = (24.0, top as f64, (width - 48) as f64, (height - legend_h - top - 8) as f64);
+    let proj = Projection::fit(bbox, frame);
+    for (t, rings) in &drawn {
+        for ring in *rings {
+            if let Some(pts) = proj.outline(ring) {
+                draw_polygon_mut(img, &pts, shade(t.value));
+                let border: Vec<Point<f32>> = pts.iter().map(|p| Point::new(p.x as f32, p.y as f32)).collect();
+                draw_hollow_polygon_mut(img, &border, brand.background);
+            }
+        }
+    }
+    Some(tiles.len() - drawn.len())
+}
+
+/// Tile-grid fallback: one column per region; tiles wrap within the column
+fn draw_tiles(
+    img: &mut Canvas,
+    by_region: &BTreeMap<String, Vec<Tile>>,
+    shade: &dyn Fn(Option<f64>) -> Rgba<u8>,
+    (top, legend_h): (u32, u32),
+    brand: &Branding,
+) {
+    let (width, height) = MAP_SIZE;
+    let font = &brand.font;
+    let n = by_region.len().max(1) as u32;
+    let col_w = (width - 24) / n;
+    let per_row = ((col_w.saturating_sub(GAP)) / (TILE.0 + GAP)).max(1);
+    for (ci, (region, tiles)) in by_region.iter().enumerate() {
+        let x0 = 24 + ci as u32 * col_w;
+        draw_text_mut(img, brand.text, x0 as i32, top as i32, 18.0, font, region);
+        for (i, t) in tiles.iter().enumerate() {
+            let (col, row) = (i as u32 % per_row, i as u32 / per_row);
+            let x = x0 + col * (TILE.0 + GAP);
+            let y = top + 28 + row * (TILE.1 + GAP);
+            if y + TILE.1 > height - legend_h {
+                break; // column overflow: drop the rest rather than draw over the legend
+            }
+            draw_filled_rect_mut(img, Rect::at(x as i32, y as i32).of_size(TILE.0, TILE.1), shade(t.value));
+            let label: String = t.name.chars().take(3).collect::<String>().to_uppercase();
+            draw_text_mut(img, brand.text, x as i32 + 6, y as i32 + 7, 13.0, font, &label);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantile_breaks_split_values_into_five_classes() {
+        let mut values: Vec<f64> = (1..=10).rev().map(f64::from).collect();
+        let breaks = class_breaks(&mut values);
+        assert_eq!(breaks, [2.0, 4.0, 6.0, 8.0, 10.0]);
+        assert_eq!(class_of(1.0, &breaks), 0);
+        assert_eq!(class_of(4.0, &breaks), 1);
+        assert_eq!(class_of(4.5, &breaks), 2);
+        assert_eq!(class_of(10.0, &breaks), 4);
+        // Above every bound (can't happen for the values the breaks came from)
+        assert_eq!(class_of(11.0, &breaks), 4);
+
+        assert!(class_breaks(&mut []).is_empty());
+        // Fewer values than classes: bounds repeat, every value still gets a class
+        let breaks = class_breaks(&mut [3.0, 1.0]);
+        assert_eq!(breaks, [1.0, 1.0, 3.0, 3.0, 3.0]);
+        assert_eq!(class_of(3.0, &breaks), 2);
+    }
+
+    #[test]
+    fn parses_feature_collections_by_iso_code() {
+        let raw = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "properties": {"ISO_A2": "-99", "ISO_A2_EH": "FR"},
+             "geometry": {"type": "Polygon", "coordinates": [[[0,0],[2,0],[2,2],[0,0]], [[1,1],[1.5,1],[1,1.5],[1,1]]]}},
+            {"type": "Feature", "properties": {"iso_a2": "ng"},
+             "geometry": {"type": "MultiPolygon", "coordinates": [[[[0,0],[1,0],[1,1],[0,0]]], [[[5,5],[6,5],[6,6],[5,5]]]]}},
+            {"type": "Feature", "properties": {"ISO_A2": "-99"},
+             "geometry": {"type": "Polygon", "coordinates": [[[0,0],[1,0],[1,1],[0,0]]]}},
+            {"type": "Feature", "properties": {"ISO_A2": "GH"}, "geometry": {"type": "Point", "coordinates": [0,0]}}
+        ]}"#;
+        let shapes = MapShapes::parse(raw).unwrap();
+        assert_eq!(shapes.len(), 2);
+        // Outer ring only
+        assert_eq!(shapes.get(Some("fr")).unwrap().len(), 1);
+        assert_eq!(shapes.get(Some("NG")).unwrap().len(), 2);
+        assert!(shapes.get(Some("GH")).is_none());
+        assert!(shapes.get(None).is_none());
+
+        assert!(MapShapes::parse(r#"{"type": "Feature"}"#).is_err());
+        assert!(MapShapes::parse(r#"{"type": "FeatureCollection", "features": []}"#).is_err());
+    }
+
+    #[test]
+    fn projection_fits_the_bbox_into_the_frame() {
+        // 2:1 bbox in a 400x400 frame: full width, centred vertically
+        let proj = Projection::fit((-10.0, 0.0, 10.0, 10.0), (0.0, 0.0, 400.0, 400.0));
+        assert_eq!(proj.project((-10.0, 10.0)), (0.0, 100.0));
+        assert_eq!(proj.project((10.0, 0.0)), (400.0, 300.0));
+        assert_eq!(proj.project((0.0, 5.0)), (200.0, 200.0));
+
+        let square = [(-10.0, 0.0), (10.0, 0.0), (10.0, 10.0), (-10.0, 10.0), (-10.0, 0.0)];
+        let pts = proj.outline(&square).unwrap();
+        assert_eq!(pts.len(), 4, "closing point dropped");
+        assert_eq!(pts[0], Point::new(0, 300));
+        // A speck smaller than a pixel is skipped rather than drawn as a degenerate polygon
+        assert!(proj.outline(&[(0.0, 0.0), (0.001, 0.0), (0.0, 0.001), (0.0, 0.0)]).is_none());
+    }
+
+    #[test]
+    fn bbox_leaves_out_antarctica() {
+        let rings = [vec![(-20.0, -80.0), (30.0, 10.0)], vec![(100.0, 40.0)]];
+        assert_eq!(bbox(rings.iter()), Some((-20.0, -60.0, 100.0, 40.0)));
+        assert_eq!(bbox([].iter()), None);
+    }
+}
@@ -1,2 +1,26 @@
+pub mod auth;
+pub mod case;
+pub mod chaos;
+pub mod client_ip;
+pub mod currency;
+pub mod deadline;
+pub mod deprecation;
+pub mod envelope;
 pub mod error;
-pub mod image;
\ No newline at end of file
+pub mod error_report;
+pub mod explain;
+pub mod i18n;
+pub mod image;
+pub mod image_cache;
+pub mod json_body;
+pub mod jsonapi;
+pub mod lockout;
+pub mod map;
+pub mod png_text;
+pub mod server;
+pub mod server_timing;
+pub mod signed_url;
+pub mod single_flight;
+pub mod sitemap;
+pub mod telemetry;
+pub mod text_layout;
\ No newline at end of file
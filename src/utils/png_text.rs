@@ -0,0 +1,107 @@
+// `tEXt` chunks in rendered PNGs. The `image` crate writes none, so they are spliced in
+// after `IHDR` (PNG readers accept text chunks anywhere between `IHDR` and `IEND`).
+// The summary image carries the refresh it was drawn from; `GET /countries/image/meta`
+// reads it back to tell whether the file lags behind the data.
+
+use flate2::Crc;
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Signature + length, type, 13 bytes of data and CRC of `IHDR`
+const AFTER_IHDR: usize = 8 + 4 + 4 + 13 + 4;
+
+fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+    let mut out = Vec::with_capacity(data.len() + 12);
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&crc.sum().to_be_bytes());
+    out
+}
+
+/// `png` with one `tEXt` chunk per `(keyword, text)`. Keywords are 1–79 printable
+/// Latin-1 characters; keep both to ASCII.
+pub fn insert(png: &[u8], entries: &[(&str, &str)]) -> Result<Vec<u8>, String> {
+    if !png.starts_with(SIGNATURE) || png.get(12..16) != Some(&b"IHDR"[..]) || png.len() < AFTER_IHDR {
+        return Err("not a PNG".into());
+    }
+    let mut out = png[..AFTER_IHDR].to_vec();
+    for (key, text) in entries {
+        if key.is_empty() || key.len() > 79 || !key.bytes().all(|b| (b' '..=b'~').contains(&b)) {
+            return Err(format!("invalid PNG text keyword {:?}", key));
+        }
+        let data = [key.as_bytes(), b"\0", text.as_bytes()].concat();
+        out.extend(chunk(b"tEXt", &data));
+    }
+    out.extend_from_slice(&png[AFTER_IHDR..]);
+    Ok(out)
+}
+
+/// Every `tEXt` entry in `png`, in file order. Stops at `IEND` or the first truncated chunk.
+pub fn read(png: &[u8]) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    if !png.starts_with(SIGNATURE) {
+        return entries;
+    }
+    let mut pos = SIGNATURE.len();
+    while let Some(header) = png.get(pos..pos + 8) {
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let kind = &header[4..8];
+        let Some(data) = png.get(pos + 8..pos + 8 + len) else { break };
+        if kind == b"IEND" {
+            break;
+        }
+        if kind == b"tEXt" {
+            if let Some(nul) = data.iter().position(|&b| b == 0) {
+                // Latin-1: each byte is the code point
+                let latin1 = |b: &[u8]| b.iter().map(|&c| c as char).collect::<String>();
+                entries.push((latin1(&data[..nul]), latin1(&data[nul + 1..])));
+            }
+        }
+        pos += 8 + len + 4;
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, ImageFormat, Rgba};
+    use std::io::Cursor;
+
+    fn blank_png() -> Vec<u8> {
+        let img: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::from_pixel(4, 3, Rgba([1, 2, 3, 255]));
+        let mut buf = Vec::new();
+        img.write_to(&mut Cursor::new(&mut buf), ImageFormat::Png).unwrap();
+        buf
+    }
+
+    #[test]
+    fn round_trip_keeps_the_image_decodable() {
+        let png = blank_png();
+        assert!(read(&png).is_empty());
+        let tagged = insert(&png, &[("refresh_run_id", "42"), ("last_refreshed_at", "2026-01-01T00:00:00Z")]).unwrap();
+        assert_eq!(
+            read(&tagged),
+            vec![
+                ("refresh_run_id".to_string(), "42".to_string()),
+                ("last_refreshed_at".to_string(), "2026-01-01T00:00:00Z".to_string()),
+            ]
+        );
+        // The png decoder verifies chunk CRCs
+        let decoded = image::load_from_memory_with_format(&tagged, ImageFormat::Png).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (4, 3));
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(insert(b"GIF89a", &[]).is_err());
+        assert!(insert(&blank_png(), &[("", "x")]).is_err());
+        assert!(insert(&blank_png(), &[("bad\nkey", "x")]).is_err());
+        assert!(read(b"not a png").is_empty());
+        let tagged = insert(&blank_png(), &[("k", "v")]).unwrap();
+        assert_eq!(read(&tagged[..AFTER_IHDR + 16 + 6]), vec![("k".to_string(), "v".to_string())]);
+    }
+}
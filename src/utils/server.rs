@@ -0,0 +1,154 @@
+// Inbound connection handling. `axum::serve` hides hyper's connection builder, so the
+// accept loop lives here to expose keep-alive and HTTP/2 settings (`HTTP_*`).
+//
+// HTTP/2 is cleartext with prior knowledge (h2c): TLS ends at the load balancer, and
+// internal callers that speak HTTP/2 can multiplex requests over a few connections.
+// With `HTTP2=true` both protocols are served on the same port, detected from the
+// connection preface.
+
+use axum::{extract::ConnectInfo, Extension, Router};
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::{conn::auto::Builder, graceful::GracefulShutdown};
+use hyper_util::service::TowerToHyperService;
+use std::future::Future;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tower::Layer;
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerTuning {
+    /// Reuse HTTP/1.1 connections across requests
+    pub keep_alive: bool,
+    /// Closes an HTTP/1.1 connection that hasn't sent a full request head for this
+    /// long, whether idle between requests or slow mid-headers. `None` = no limit.
+    pub keep_alive_timeout: Option<Duration>,
+    pub http2: bool,
+    pub http2_max_concurrent_streams: u32,
+    /// PING interval on idle HTTP/2 connections; `None` = no pings
+    pub http2_keep_alive_interval: Option<Duration>,
+    /// A PING unanswered this long closes the connection
+    pub http2_keep_alive_timeout: Duration,
+}
+
+impl ServerTuning {
+    fn builder(&self) -> Builder<TokioExecutor> {
+        let mut builder = Builder::new(TokioExecutor::new());
+        builder
+            .http1()
+            .timer(TokioTimer::new())
+            .keep_alive(self.keep_alive)
+            .header_read_timeout(self.keep_alive_timeout);
+        builder
+            .http2()
+            .timer(TokioTimer::new())
+            .max_concurrent_streams(self.http2_max_concurrent_streams)
+            .keep_alive_interval(self.http2_keep_alive_interval)
+            .keep_alive_timeout(self.http2_keep_alive_timeout);
+        if self.http2 {
+            builder
+        } else {
+            builder.http1_only()
+        }
+    }
+}
+
+/// Serves `app` until `shutdown` resolves, then stops accepting and waits for open
+/// connections to finish their in-flight requests (like `axum::serve`'s graceful mode).
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    tuning: &ServerTuning,
+    shutdown: impl Future<Output = ()>,
+) -> std::io::Result<()> {
+    let builder = tuning.builder();
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(shutdown);
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(conn) => conn,
+                Err(e) => {
+                    // Out of file descriptors and the like: back off instead of spinning
+                    warn!("accept failed: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+        // The peer address, for `client_ip`
+        let with_peer = Extension(ConnectInfo(peer));
+        // No upgrades (nothing here speaks WebSocket); the upgrade-capable variant would
+        // also ignore `http1_only`
+        let conn = builder
+            .serve_connection(TokioIo::new(stream), TowerToHyperService::new(with_peer.layer(app.clone())))
+            .into_owned();
+        let conn = graceful.watch(conn);
+        tokio::spawn(async move {
+            if let Err(e) = conn.await {
+                debug!("connection from {} ended: {}", peer, e);
+            }
+        });
+    }
+    drop(listener);
+    info!("shutting down: waiting for {} open connection(s)", graceful.count());
+    graceful.shutdown().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use reqwest::Version;
+
+    fn tuning(http2: bool) -> ServerTuning {
+        ServerTuning {
+            keep_alive: true,
+            keep_alive_timeout: Some(Duration::from_secs(30)),
+            http2,
+            http2_max_concurrent_streams: 200,
+            http2_keep_alive_interval: None,
+            http2_keep_alive_timeout: Duration::from_secs(20),
+        }
+    }
+
+    async fn start(tuning: ServerTuning) -> (String, tokio::sync::oneshot::Sender<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let app = Router::new().route("/", get(|| async { "ok" }));
+        tokio::spawn(async move {
+            serve(listener, app, &tuning, async {
+                stopped.await.ok();
+            })
+            .await
+        });
+        (url, stop)
+    }
+
+    #[tokio::test]
+    async fn h2c_only_when_enabled() {
+        let h1 = reqwest::Client::new();
+        let h2 = reqwest::Client::builder().http2_prior_knowledge().build().unwrap();
+
+        let (url, _stop) = start(tuning(true)).await;
+        assert_eq!(h2.get(&url).send().await.unwrap().version(), Version::HTTP_2);
+        assert_eq!(h1.get(&url).send().await.unwrap().version(), Version::HTTP_11);
+
+        let (url, _stop) = start(tuning(false)).await;
+        assert!(h2.get(&url).send().await.is_err());
+        assert_eq!(h1.get(&url).send().await.unwrap().text().await.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn stops_accepting_on_shutdown() {
+        let (url, stop) = start(tuning(false)).await;
+        let client = reqwest::Client::builder().pool_max_idle_per_host(0).build().unwrap();
+        assert!(client.get(&url).send().await.is_ok());
+        stop.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(client.get(&url).send().await.is_err());
+    }
+}
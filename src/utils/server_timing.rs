@@ -0,0 +1,195 @@
+// `Server-Timing` response header (`SERVER_TIMING=true`), so clients can see where a slow
+// request spent its time without access to traces:
+//
+// - `db`: every SQLx query the request awaited. SQLx reports each statement's duration as a
+//   `sqlx::query` tracing event; `DbTimeLayer` adds it to the running request.
+// - `upstream`: provider fetches during `POST /countries/refresh`.
+// - `ser`: JSON encoding of responses built with this module's [`TimedJson`] (the data reads)
+//   and the `?case=` / envelope rewrites.
+// - `total`: the whole request, including the above.
+//
+// Like the request deadline (`utils::deadline`), the counters live in a task-local scope
+// opened by the middleware, so work moved to another task (image renders, background
+// jobs) is not counted.
+
+use axum::{
+    extract::{Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing_subscriber::layer::Context;
+
+use crate::config::AppState;
+
+#[derive(Clone, Copy)]
+pub enum Metric {
+    Db,
+    Upstream,
+    Serialize,
+}
+
+#[derive(Default)]
+struct Timings {
+    db: Cell<Duration>,
+    db_queries: Cell<u32>,
+    upstream: Cell<Duration>,
+    upstream_calls: Cell<u32>,
+    ser: Cell<Duration>,
+}
+
+tokio::task_local! {
+    static TIMINGS: Timings;
+}
+
+/// `SERVER_TIMING`, read once by `main` (for the tracing layer) and by `AppConfig`.
+pub fn enabled_from_env() -> bool {
+    matches!(std::env::var("SERVER_TIMING").as_deref(), Ok("1" | "true" | "yes"))
+}
+
+/// Adds `elapsed` to `metric` of the current request; a no-op outside one.
+pub fn record(metric: Metric, elapsed: Duration) {
+    let _ = TIMINGS.try_with(|t| {
+        let (total, count) = match metric {
+            Metric::Db => (&t.db, Some(&t.db_queries)),
+            Metric::Upstream => (&t.upstream, Some(&t.upstream_calls)),
+            Metric::Serialize => (&t.ser, None),
+        };
+        total.set(total.get() + elapsed);
+        if let Some(count) = count {
+            count.set(count.get() + 1);
+        }
+    });
+}
+
+/// Runs `f` and records how long it took under `metric`.
+pub fn measure<T>(metric: Metric, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let out = f();
+    record(metric, start.elapsed());
+    out
+}
+
+/// `axum::Json` for responses, counting its encoding as `ser`. Named apart from it so a
+/// handler's imports show which one it uses.
+pub struct TimedJson<T>(pub T);
+
+impl<T: Serialize> IntoResponse for TimedJson<T> {
+    fn into_response(self) -> Response {
+        measure(Metric::Serialize, || axum::Json(self.0).into_response())
+    }
+}
+
+fn ms(d: Duration) -> String {
+    format!("{:.1}", d.as_secs_f64() * 1000.0)
+}
+
+fn header_value(t: &Timings, total: Duration) -> String {
+    let mut parts = vec![format!("db;dur={};desc=\"queries: {}\"", ms(t.db.get()), t.db_queries.get())];
+    if t.upstream_calls.get() > 0 {
+        parts.push(format!("upstream;dur={};desc=\"calls: {}\"", ms(t.upstream.get()), t.upstream_calls.get()));
+    }
+    parts.push(format!("ser;dur={}", ms(t.ser.get())));
+    parts.push(format!("total;dur={}", ms(total)));
+    parts.join(", ")
+}
+
+/// Middleware: collects the request's timings and sends them as `Server-Timing`.
+pub async fn annotate(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    if !state.server_timing {
+        return next.run(req).await;
+    }
+    let start = Instant::now();
+    let (mut res, value) = TIMINGS
+        .scope(Timings::default(), async {
+            let res = next.run(req).await;
+            let value = TIMINGS.with(|t| header_value(t, start.elapsed()));
+            (res, value)
+        })
+        .await;
+    if let Ok(v) = HeaderValue::from_str(&value) {
+        res.headers_mut().insert("server-timing", v);
+    }
+    res
+}
+
+/// Tracing layer adding each `sqlx::query` event's `elapsed_secs` to `db`. Needs those
+/// events enabled for it (DEBUG on `sqlx::query`), see `main`.
+pub struct DbTimeLayer;
+
+struct ElapsedSecs(Option<f64>);
+
+impl Visit for ElapsedSecs {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "elapsed_secs" {
+            self.0 = Some(value);
+        }
+    }
+
+    fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+}
+
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for DbTimeLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, S>) {
+        if event.metadata().target() != "sqlx::query" {
+            return;
+        }
+        let mut elapsed = ElapsedSecs(None);
+        event.record(&mut elapsed);
+        if let Some(secs) = elapsed.0.filter(|s| s.is_finite() && *s >= 0.0) {
+            record(Metric::Db, Duration::from_secs_f64(secs));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn records_only_inside_a_request() {
+        record(Metric::Db, Duration::from_millis(5));
+        let value = TIMINGS
+            .scope(Timings::default(), async {
+                record(Metric::Db, Duration::from_millis(2));
+                record(Metric::Db, Duration::from_micros(1500));
+                measure(Metric::Serialize, || ());
+                TIMINGS.with(|t| {
+                    t.ser.set(Duration::from_millis(1));
+                    header_value(t, Duration::from_millis(10))
+                })
+            })
+            .await;
+        assert_eq!(value, "db;dur=3.5;desc=\"queries: 2\", ser;dur=1.0, total;dur=10.0");
+
+        let value = TIMINGS
+            .scope(Timings::default(), async {
+                record(Metric::Upstream, Duration::from_millis(250));
+                TIMINGS.with(|t| header_value(t, Duration::from_millis(300)))
+            })
+            .await;
+        assert!(value.contains("upstream;dur=250.0;desc=\"calls: 1\""));
+    }
+
+    #[test]
+    fn sqlx_statement_events_count_as_db_time() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let subscriber = tracing_subscriber::registry().with(DbTimeLayer);
+        let (db, queries) = TIMINGS.sync_scope(Timings::default(), || {
+            tracing::subscriber::with_default(subscriber, || {
+                // The fields SQLx logs for each statement
+                tracing::debug!(target: "sqlx::query", summary = "select 1", elapsed_secs = 0.004);
+                tracing::debug!(target: "sqlx::query", elapsed_secs = 0.001);
+                tracing::debug!(target: "other", elapsed_secs = 1.0);
+            });
+            TIMINGS.with(|t| (t.db.get(), t.db_queries.get()))
+        });
+        assert_eq!(queries, 2);
+        assert_eq!(db, Duration::from_secs_f64(0.004) + Duration::from_secs_f64(0.001));
+    }
+}
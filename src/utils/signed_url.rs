@@ -0,0 +1,120 @@
+// Temporary download links (`URL_SIGNING_KEY`). `POST /admin/signed-urls` appends
+// `expires` (unix seconds) and `signature` (hex HMAC-SHA256 over the expiry and the rest
+// of the URL) to a path; `auth::authorize` lets such a request through without an API
+// key until it expires. Only GET routes registered with `.signed()` in
+// `routes::registry`, and published files under `/static/exports/`, accept them.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::Arc;
+
+/// Links may be valid for a week at most
+pub const MAX_TTL_SECS: i64 = 7 * 24 * 3600;
+
+#[derive(Clone)]
+pub struct UrlSigner(Arc<[u8]>);
+
+impl UrlSigner {
+    pub fn new(key: &str) -> Result<Self, String> {
+        if key.len() < 32 {
+            return Err("URL_SIGNING_KEY must be at least 32 characters".into());
+        }
+        Ok(UrlSigner(key.as_bytes().into()))
+    }
+
+    fn mac(&self, expires: i64, path: &str, query: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.0).expect("HMAC accepts keys of any length");
+        mac.update(format!("{}\n{}?{}", expires, path, query).as_bytes());
+        mac
+    }
+
+    /// `path` with its query plus `expires` and `signature`.
+    pub fn sign(&self, path: &str, query: Option<&str>, expires: i64) -> String {
+        let query = unsigned_query(query.unwrap_or(""));
+        let signature = hex::encode(self.mac(expires, path, &query).finalize().into_bytes());
+        let sep = if query.is_empty() { "" } else { "&" };
+        format!("{}?{}{}expires={}&signature={}", path, query, sep, expires, signature)
+    }
+
+    /// `None` when the query carries no signature at all; otherwise whether it is valid at `now`.
+    pub fn verify(&self, path: &str, query: Option<&str>, now: i64) -> Option<Result<(), &'static str>> {
+        let query = query.unwrap_or("");
+        let signature = param(query, "signature")?;
+        let Some(expires) = param(query, "expires").and_then(|e| e.parse::<i64>().ok()) else {
+            return Some(Err("signed URL has no valid expires"));
+        };
+        let Ok(signature) = hex::decode(signature) else {
+            return Some(Err("invalid signature"));
+        };
+        if self.mac(expires, path, &unsigned_query(query)).verify_slice(&signature).is_err() {
+            return Some(Err("invalid signature"));
+        }
+        if expires <= now {
+            return Some(Err("signed URL expired"));
+        }
+        Some(Ok(()))
+    }
+}
+
+fn param<'a>(query: &'a str, key: &str) -> Option<&'a str> {
+    query.split('&').find_map(|pair| pair.strip_prefix(key)?.strip_prefix('='))
+}
+
+/// The query without `expires` / `signature`, other pairs as sent and in order.
+fn unsigned_query(query: &str) -> String {
+    query
+        .split('&')
+        .filter(|pair| {
+            let key = pair.split('=').next().unwrap_or("");
+            !pair.is_empty() && key != "expires" && key != "signature"
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer() -> UrlSigner {
+        UrlSigner::new("0123456789abcdef0123456789abcdef").unwrap()
+    }
+
+    fn split(url: &str) -> (&str, Option<&str>) {
+        match url.split_once('?') {
+            Some((p, q)) => (p, Some(q)),
+            None => (url, None),
+        }
+    }
+
+    #[test]
+    fn round_trip_until_expiry() {
+        let s = signer();
+        let url = s.sign("/countries/image", Some("format=svg&lang=fr"), 1_000);
+        assert!(url.starts_with("/countries/image?format=svg&lang=fr&expires=1000&signature="));
+        let (path, query) = split(&url);
+        assert_eq!(s.verify(path, query, 999), Some(Ok(())));
+        assert_eq!(s.verify(path, query, 1_000), Some(Err("signed URL expired")));
+
+        let bare = s.sign("/countries/bundle", None, 1_000);
+        let (path, query) = split(&bare);
+        assert!(query.unwrap().starts_with("expires=1000&"));
+        assert_eq!(s.verify(path, query, 0), Some(Ok(())));
+    }
+
+    #[test]
+    fn tampering_is_rejected() {
+        let s = signer();
+        let url = s.sign("/countries/image", Some("format=svg"), 1_000);
+        let (path, query) = split(&url);
+        let q = query.unwrap();
+        assert_eq!(s.verify("/countries/bundle", query, 0), Some(Err("invalid signature")));
+        assert_eq!(s.verify(path, Some(&q.replace("svg", "png")), 0), Some(Err("invalid signature")));
+        assert_eq!(s.verify(path, Some(&q.replace("expires=1000", "expires=9999")), 0), Some(Err("invalid signature")));
+        assert_eq!(s.verify(path, Some(&q.replace("expires=1000&", "")), 0), Some(Err("signed URL has no valid expires")));
+        let other = UrlSigner::new("fedcba9876543210fedcba9876543210").unwrap();
+        assert_eq!(other.verify(path, query, 0), Some(Err("invalid signature")));
+        assert_eq!(s.verify(path, Some("format=svg"), 0), None);
+        assert!(UrlSigner::new("short").is_err());
+    }
+}
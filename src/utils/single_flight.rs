@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+use crate::services::country_repository::Restriction;
+use crate::utils::error::ApiError;
+use crate::utils::image::Ranking;
+
+/// Coalesces concurrent calls with the same key: the first caller runs the work,
+/// everyone who arrives while it's in flight gets a clone of its result.
+///
+/// Once the flight lands the key is forgotten, so later calls run again. If the
+/// leading caller is cancelled (client went away), a waiter takes over the work.
+pub struct SingleFlight<K, V> {
+    inflight: Arc<Mutex<HashMap<K, Arc<OnceCell<V>>>>>,
+}
+
+impl<K, V> Clone for SingleFlight<K, V> {
+    fn clone(&self) -> Self {
+        Self { inflight: self.inflight.clone() }
+    }
+}
+
+impl<K, V> Default for SingleFlight<K, V> {
+    fn default() -> Self {
+        Self { inflight: Arc::default() }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> SingleFlight<K, V> {
+    pub async fn run<F, Fut>(&self, key: K, work: F) -> V
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V>,
+    {
+        let cell = {
+            let mut map = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+            map.entry(key.clone()).or_default().clone()
+        };
+        let value = cell.get_or_init(work).await.clone();
+
+        let mut map = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+        // Only drop our own flight; a newer one may already sit under this key
+        if map.get(&key).is_some_and(|c| Arc::ptr_eq(c, &cell)) {
+            map.remove(&key);
+        }
+        value
+    }
+}
+
+/// An aggregate read coalesced in [`AggregateFlights`]: the query with its parameters
+/// and the caller's key restriction, so differently limited keys never share a result.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AggregateKey {
+    Stats { top: u32, restriction: Option<Restriction> },
+    Regions { restriction: Option<Restriction> },
+    /// The summary as JSON (`GET /countries/image` with `Accept: application/json`), which
+    /// covers every country, so no restriction
+    Summary { ranking: Ranking },
+}
+
+/// `/stats`, `/regions` and the JSON summary scan every country; a burst of dashboard
+/// polls runs each once.
+pub type AggregateFlights = SingleFlight<AggregateKey, Result<serde_json::Value, ApiError>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn concurrent_callers_share_one_computation() {
+        let flights: AggregateFlights = SingleFlight::default();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let call = |key: AggregateKey| {
+            let (flights, runs) = (flights.clone(), runs.clone());
+            tokio::spawn(async move {
+                flights
+                    .run(key, || async move {
+                        let n = runs.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        Ok(serde_json::json!(n))
+                    })
+                    .await
+            })
+        };
+
+        let top5 = AggregateKey::Stats { top: 5, restriction: None };
+        let callers: Vec<_> = (0..16).map(|_| call(top5.clone())).collect();
+        for c in callers {
+            assert_eq!(c.await.unwrap().unwrap(), serde_json::json!(0));
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // Landed flights are forgotten; other parameters or restrictions are their own flight
+        let limited = Restriction { regions: vec!["Africa".into()], tags: vec![] };
+        let keys = [
+            top5,
+            AggregateKey::Stats { top: 3, restriction: None },
+            AggregateKey::Stats { top: 5, restriction: Some(limited.clone()) },
+            AggregateKey::Regions { restriction: Some(limited) },
+        ];
+        let callers: Vec<_> = keys.into_iter().map(call).collect();
+        for c in callers {
+            c.await.unwrap().unwrap();
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 5);
+    }
+}
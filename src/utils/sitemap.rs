@@ -0,0 +1,88 @@
+// `GET /sitemap.xml` and `GET /robots.txt` for public deployments. Crawlers need absolute
+// URLs, so the sitemap is only served once `PUBLIC_BASE_URL` says where the site lives.
+// Each country gets one `<url>` at `SITEMAP_COUNTRY_PATH` (a route template with `:name`,
+// by default this API's own `/countries/:name`), so a site rendering its own country pages
+// can point crawlers there instead.
+
+use crate::routes::paths;
+
+/// Sitemaps hold at most 50,000 URLs
+pub const MAX_URLS: usize = 50_000;
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// One country's page: stored name and when it was last refreshed (RFC 3339).
+pub struct Entry {
+    pub name: String,
+    pub last_refreshed_at: Option<String>,
+}
+
+/// `sitemaps.org` XML with a `<url>` per entry, `loc` built from `base` and `path`.
+pub fn sitemap_xml(base: &str, path: &str, entries: &[Entry]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for e in entries.iter().take(MAX_URLS) {
+        let loc = format!("{}{}", base, paths::link(path, &[("name", &e.name)]));
+        out.push_str(&format!("<url><loc>{}</loc>", escape(&loc)));
+        if let Some(at) = &e.last_refreshed_at {
+            out.push_str(&format!("<lastmod>{}</lastmod>", escape(at)));
+        }
+        out.push_str("</url>\n");
+    }
+    out.push_str("</urlset>\n");
+    out
+}
+
+/// Allows everything but the `disallow` prefixes; names the sitemap when there is one.
+pub fn robots_txt(disallow: &[String], base: Option<&str>) -> String {
+    let mut out = String::from("User-agent: *\n");
+    if disallow.is_empty() {
+        out.push_str("Disallow:\n");
+    }
+    for prefix in disallow {
+        out.push_str(&format!("Disallow: {}\n", prefix));
+    }
+    if let Some(base) = base {
+        out.push_str(&format!("\nSitemap: {}/sitemap.xml\n", base));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sitemap_has_encoded_absolute_urls() {
+        let entries = [
+            Entry { name: "Côte d'Ivoire".into(), last_refreshed_at: Some("2026-01-01T00:00:00Z".into()) },
+            Entry { name: "Ghana".into(), last_refreshed_at: None },
+        ];
+        let xml = sitemap_xml("https://countries.example.com", "/country/:name", &entries);
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset "));
+        assert!(xml.contains(
+            "<url><loc>https://countries.example.com/country/C%C3%B4te%20d%27Ivoire</loc>\
+             <lastmod>2026-01-01T00:00:00Z</lastmod></url>\n"
+        ));
+        assert!(xml.contains("<url><loc>https://countries.example.com/country/Ghana</loc></url>\n"));
+        assert!(xml.ends_with("</urlset>\n"));
+    }
+
+    #[test]
+    fn robots_lists_disallowed_prefixes_and_the_sitemap() {
+        let disallow = vec!["/admin/".to_string(), "/jobs".to_string()];
+        assert_eq!(
+            robots_txt(&disallow, Some("https://countries.example.com")),
+            "User-agent: *\nDisallow: /admin/\nDisallow: /jobs\n\nSitemap: https://countries.example.com/sitemap.xml\n"
+        );
+        assert_eq!(robots_txt(&[], None), "User-agent: *\nDisallow:\n");
+    }
+}
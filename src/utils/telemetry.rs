@@ -0,0 +1,15 @@
+// Business attributes for trace spans. They're plain `tracing` span fields, so any
+// subscriber layer that exports spans (e.g. tracing-opentelemetry) turns them into attributes.
+use sha2::{Digest, Sha256};
+
+/// Short, stable digest of a user-supplied value (filters, names). Traces can group
+/// requests by it without carrying the raw input.
+pub fn hash_attr(value: &str) -> String {
+    let digest = Sha256::digest(value.trim().to_lowercase().as_bytes());
+    hex::encode(&digest[..8])
+}
+
+/// Records `field` on the current span; a no-op if the span didn't declare it.
+pub fn record<V: tracing::Value>(field: &str, value: V) {
+    tracing::Span::current().record(field, value);
+}
@@ -0,0 +1,211 @@
+// Line placement for rendered images. Text is measured with the font that draws it
+// (ab_glyph advances and kerning, through imageproc's `text_size`). It wraps at ASCII spaces
+// to the width available (the no-break spaces inside French numbers hold) and breaks inside
+// a word only when that word can't fit on a line of its own. Line height comes from the
+// font's own metrics, so a larger font or a wrapped country name pushes the lines below it
+// down instead of overlapping them. The caller grows the canvas to the returned `bottom`.
+
+use ab_glyph::{Font, FontArc, ScaleFont};
+use std::collections::VecDeque;
+
+/// Text extents at a pixel size.
+pub trait Measure {
+    /// Advance width of `text`
+    fn width(&self, text: &str, size: f32) -> u32;
+    /// Distance from one line's top to the next
+    fn line_height(&self, size: f32) -> u32;
+}
+
+impl Measure for FontArc {
+    fn width(&self, text: &str, size: f32) -> u32 {
+        imageproc::drawing::text_size(size, self, text).0
+    }
+
+    fn line_height(&self, size: f32) -> u32 {
+        let scaled = self.as_scaled(size);
+        (scaled.height() + scaled.line_gap()).ceil().max(1.0) as u32
+    }
+}
+
+/// Where text may go. Lines start at `margin` and end `margin` before the right edge.
+pub struct Frame {
+    pub width: u32,
+    pub margin: u32,
+    /// Added to the font's line height
+    pub leading: u32,
+    /// A box in the top-right corner (the logo), as its left edge and bottom; lines that
+    /// overlap it vertically end `margin` before its left edge.
+    pub keep_out: Option<(u32, u32)>,
+    /// Lines per block; the last one ends in `…` when the text needs more
+    pub max_lines: usize,
+}
+
+/// One paragraph: a title or a summary line.
+pub struct Block<'a> {
+    pub text: &'a str,
+    pub size: f32,
+    /// Extra space below the block
+    pub space_after: u32,
+}
+
+/// A line ready to draw, positioned by its top-left corner.
+#[derive(Debug, PartialEq)]
+pub struct Line {
+    /// Index of the block it belongs to
+    pub block: usize,
+    pub text: String,
+    pub x: i32,
+    pub y: i32,
+    pub size: f32,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Layout {
+    pub lines: Vec<Line>,
+    /// Bottom of the last line
+    pub bottom: u32,
+}
+
+impl Frame {
+    fn right(&self, top: u32) -> u32 {
+        let edge = match self.keep_out {
+            Some((left, bottom)) if top < bottom => left.min(self.width),
+            _ => self.width,
+        };
+        edge.saturating_sub(self.margin).max(self.margin + 1)
+    }
+}
+
+/// Stacks `blocks` from `margin` down, each wrapped to the width left beside the keep-out box.
+pub fn layout(m: &impl Measure, frame: &Frame, blocks: &[Block]) -> Layout {
+    let max_lines = frame.max_lines.max(1);
+    let mut lines = Vec::new();
+    let mut y = frame.margin;
+    for (i, block) in blocks.iter().enumerate() {
+        let height = m.line_height(block.size) + frame.leading;
+        let mut words: VecDeque<&str> = block.text.split(' ').filter(|w| !w.is_empty()).collect();
+        for n in 1..=max_lines {
+            if words.is_empty() {
+                break;
+            }
+            let max = frame.right(y) - frame.margin;
+            let mut text = fill(m, block.size, max, &mut words);
+            if n == max_lines && !words.is_empty() {
+                text = ellipsize(m, block.size, max, &text);
+            }
+            lines.push(Line { block: i, text, x: frame.margin as i32, y: y as i32, size: block.size });
+            y += height;
+        }
+        y += block.space_after;
+    }
+    Layout { lines, bottom: y }
+}
+
+/// Takes as many words as fit in `max` pixels. A word too wide for any line is split,
+/// and what's left of it goes back to start the next line.
+fn fill(m: &impl Measure, size: f32, max: u32, words: &mut VecDeque<&str>) -> String {
+    let mut line = String::new();
+    while let Some(&word) = words.front() {
+        let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+        if m.width(&candidate, size) <= max {
+            line = candidate;
+            words.pop_front();
+            continue;
+        }
+        if line.is_empty() {
+            let (head, tail) = split_to_fit(m, size, max, word);
+            line = head.to_string();
+            words.pop_front();
+            if !tail.is_empty() {
+                words.push_front(tail);
+            }
+        }
+        break;
+    }
+    line
+}
+
+/// The longest prefix of `word` within `max` (at least one character) and the rest.
+fn split_to_fit<'a>(m: &impl Measure, size: f32, max: u32, word: &'a str) -> (&'a str, &'a str) {
+    let mut end = word.chars().next().map_or(0, char::len_utf8);
+    for (i, c) in word.char_indices().skip(1) {
+        if m.width(&word[..i + c.len_utf8()], size) > max {
+            break;
+        }
+        end = i + c.len_utf8();
+    }
+    word.split_at(end)
+}
+
+/// `text` shortened until it fits with a trailing `…`.
+fn ellipsize(m: &impl Measure, size: f32, max: u32, text: &str) -> String {
+    let mut kept: Vec<char> = text.chars().collect();
+    loop {
+        let candidate = format!("{}…", kept.iter().collect::<String>().trim_end());
+        if kept.is_empty() || m.width(&candidate, size) <= max {
+            return candidate;
+        }
+        kept.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Monospace: every character is half the size wide, lines are the size tall
+    struct Mono;
+
+    impl Measure for Mono {
+        fn width(&self, text: &str, size: f32) -> u32 {
+            (text.chars().count() as f32 * size / 2.0) as u32
+        }
+
+        fn line_height(&self, size: f32) -> u32 {
+            size as u32
+        }
+    }
+
+    fn frame(width: u32) -> Frame {
+        Frame { width, margin: 10, leading: 2, keep_out: None, max_lines: 3 }
+    }
+
+    fn texts(l: &Layout) -> Vec<&str> {
+        l.lines.iter().map(|l| l.text.as_str()).collect()
+    }
+
+    #[test]
+    fn wraps_at_spaces_and_stacks_by_line_height() {
+        // 100px wide with 10px margins leaves 80px: 16 characters at size 10
+        let blocks = [
+            Block { text: "Title", size: 20.0, space_after: 6 },
+            Block { text: "1. Saint Vincent and the Grenadines — 1,234", size: 10.0, space_after: 0 },
+        ];
+        let l = layout(&Mono, &frame(100), &blocks);
+        assert_eq!(texts(&l), ["Title", "1. Saint Vincent", "and the", "Grenadines —…"]);
+        let ys: Vec<i32> = l.lines.iter().map(|l| l.y).collect();
+        assert_eq!(ys, [10, 38, 50, 62]);
+        assert_eq!(l.bottom, 74);
+        assert!(l.lines.iter().all(|line| Mono.width(&line.text, line.size) <= 80));
+    }
+
+    #[test]
+    fn long_words_are_split_and_the_logo_is_avoided() {
+        let l = layout(&Mono, &frame(100), &[Block { text: "1234567890123456789012", size: 10.0, space_after: 0 }]);
+        assert_eq!(texts(&l), ["1234567890123456", "789012"]);
+
+        // A box from x=60 down to y=30 leaves 40px (8 characters) beside it for the first lines
+        let f = Frame { keep_out: Some((60, 30)), ..frame(100) };
+        let l = layout(&Mono, &f, &[Block { text: "aaaa bbbb cccc dddd eeee", size: 10.0, space_after: 0 }]);
+        assert_eq!(texts(&l), ["aaaa", "bbbb", "cccc dddd eeee"]);
+        assert_eq!(layout(&Mono, &f, &[Block { text: " ", size: 10.0, space_after: 0 }]).lines, []);
+    }
+
+    #[test]
+    fn no_break_spaces_keep_a_number_together() {
+        // The number as French formats it moves to the next line whole
+        let text = format!("Population : {}", crate::utils::i18n::Lang::Fr.format_int(1_234_567));
+        let l = layout(&Mono, &frame(100), &[Block { text: &text, size: 10.0, space_after: 0 }]);
+        assert_eq!(texts(&l), ["Population :", "1\u{202F}234\u{202F}567"]);
+    }
+}